@@ -75,8 +75,8 @@ export class RuggyCollection {
     /**
      * Finds documents using advanced operators
      * @param field - Field name to search
-     * @param value - Value to match
-     * @param operator - Operator to use (=, ==, eq, like, LIKE, contains, starts_with, ends_with)
+     * @param value - Value to match (an array for `in`)
+     * @param operator - Operator to use (=, ==, eq, like, LIKE, contains, starts_with, ends_with, ieq, ilike, regex, in)
      * @returns Array of matching documents
      */
     findWithOperator(field: string, value: any, operator: string): Array<Record<string, any>>;
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::Value;
+use parking_lot::RwLock;
+use crate::collection::Collection;
+use crate::options::CollectionOptions;
+use crate::storage::StorageBackend;
+
+/// Qué documentos se consideran fríos y se mueven al archivo comprimido
+#[derive(Clone)]
+pub enum ArchivePolicy {
+    /// Fecha en `field` (epoch en ms o ISO-8601) más antigua que `age`
+    OlderThan { field: String, age: Duration },
+    /// Documentos para los que el filtro devuelve `true`
+    Matching(Arc<dyn Fn(&Value) -> bool + Send + Sync>),
+}
+
+impl ArchivePolicy {
+    pub fn older_than(field: &str, age: Duration) -> Self {
+        ArchivePolicy::OlderThan { field: field.to_string(), age }
+    }
+
+    pub fn matching<F: Fn(&Value) -> bool + Send + Sync + 'static>(filter: F) -> Self {
+        ArchivePolicy::Matching(Arc::new(filter))
+    }
+}
+
+/// Archivo comprimido de documentos fríos (`<name>.archive.gz`). Cada pasada de archivado
+/// añade un miembro gzip nuevo, así no hace falta reescribir lo ya archivado. El cifrado y la
+/// normalización se toman de las opciones actuales de la colección (p. ej. tras `rotate_cipher`).
+pub struct Archive {
+    storage: Arc<dyn StorageBackend>,
+    name: String,
+    options: Arc<RwLock<CollectionOptions>>,
+}
+
+impl Archive {
+    pub(crate) fn new(storage: Arc<dyn StorageBackend>, name: String, options: Arc<RwLock<CollectionOptions>>) -> Self {
+        Self { storage, name, options }
+    }
+
+    /// Nombre del archivo en el almacenamiento de la base de datos
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Añade documentos al archivo y sincroniza a disco antes de volver
+    pub(crate) fn append(&self, docs: &[Value]) -> io::Result<()> {
+        let encryption = self.options.read().encryption.clone();
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for doc in docs {
+            let line = match &encryption {
+                Some(enc) => serde_json::to_string(&enc.seal(doc)?)?,
+                None => serde_json::to_string(doc)?,
+            };
+            writeln!(encoder, "{}", line)?;
+        }
+        self.storage.append(&self.name, &encoder.finish()?)?;
+        self.storage.sync(&self.name)
+    }
+
+    /// Todos los documentos archivados. Si un `_id` aparece varias veces
+    /// (p. ej. tras una interrupción a mitad de archivado) gana la última copia.
+    pub fn find_all(&self) -> io::Result<Vec<Value>> {
+        let Some(file) = self.storage.open(&self.name, 0)? else { return Ok(Vec::new()); };
+        let encryption = self.options.read().encryption.clone();
+        let reader = BufReader::new(MultiGzDecoder::new(file));
+        let mut docs: Vec<Value> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut doc: Value = match serde_json::from_str(&line) {
+                Ok(doc) => doc,
+                Err(_) => continue,
+            };
+            if let Some(enc) = &encryption {
+                enc.open(&mut doc)?;
+            }
+            match doc.get("_id").and_then(|v| v.as_str()).map(|s| s.to_string()) {
+                Some(id) => match positions.get(&id) {
+                    Some(&pos) => docs[pos] = doc,
+                    None => {
+                        positions.insert(id, docs.len());
+                        docs.push(doc);
+                    }
+                },
+                None => docs.push(doc),
+            }
+        }
+        Ok(docs)
+    }
+
+    pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> io::Result<Vec<Value>> {
+        let normalization = self.options.read().normalization;
+        Collection::scan_operator(&self.find_all()?, field, value, operator, normalization.as_ref())
+    }
+
+    pub fn find_where<F: Fn(&Value) -> bool>(&self, filter: F) -> io::Result<Vec<Value>> {
+        Ok(self.find_all()?.into_iter().filter(|doc| filter(doc)).collect())
+    }
+}
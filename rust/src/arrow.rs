@@ -0,0 +1,107 @@
+//! Conversión de resultados a `RecordBatch` de Apache Arrow (feature `arrow`). El esquema se
+//! deduce de los campos de primer nivel de los documentos.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, NullArray, RecordBatch, RecordBatchOptions, StringArray};
+use arrow_schema::{DataType, Field, Schema};
+use serde_json::Value;
+use crate::collection::Collection;
+
+/// Tipo de columna que admite todos los valores vistos de un campo
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ColumnType {
+    Null,
+    Boolean,
+    Int64,
+    Float64,
+    Utf8,
+    /// Tipos mezclados, objetos o arrays: el valor serializado como JSON
+    Json,
+}
+
+impl ColumnType {
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => ColumnType::Null,
+            Value::Bool(_) => ColumnType::Boolean,
+            Value::Number(n) if n.is_i64() => ColumnType::Int64,
+            Value::Number(_) => ColumnType::Float64,
+            Value::String(_) => ColumnType::Utf8,
+            _ => ColumnType::Json,
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Null, t) | (t, ColumnType::Null) => t,
+            (ColumnType::Int64, ColumnType::Float64) | (ColumnType::Float64, ColumnType::Int64) => ColumnType::Float64,
+            _ => ColumnType::Json,
+        }
+    }
+
+    fn data_type(self) -> DataType {
+        match self {
+            ColumnType::Null => DataType::Null,
+            ColumnType::Boolean => DataType::Boolean,
+            ColumnType::Int64 => DataType::Int64,
+            ColumnType::Float64 => DataType::Float64,
+            ColumnType::Utf8 | ColumnType::Json => DataType::Utf8,
+        }
+    }
+}
+
+/// Un campo por cada clave de primer nivel (en orden alfabético), todos admiten nulos. Los
+/// enteros mezclados con decimales son `Float64`; cualquier otra mezcla, los objetos y los
+/// arrays son `Utf8` con el JSON del valor.
+pub fn infer_schema(docs: &[Value]) -> Schema {
+    schema(&column_types(docs))
+}
+
+fn schema(columns: &[(String, ColumnType)]) -> Schema {
+    Schema::new(columns.iter()
+        .map(|(name, column)| Field::new(name, column.data_type(), true))
+        .collect::<Vec<_>>())
+}
+
+fn column_types(docs: &[Value]) -> Vec<(String, ColumnType)> {
+    let mut columns: BTreeMap<String, ColumnType> = BTreeMap::new();
+    for obj in docs.iter().filter_map(|doc| doc.as_object()) {
+        for (name, value) in obj {
+            let column = columns.entry(name.clone()).or_insert(ColumnType::Null);
+            *column = column.merge(ColumnType::of(value));
+        }
+    }
+    columns.into_iter().collect()
+}
+
+/// Documentos como un `RecordBatch` con el esquema de `infer_schema`. Los campos ausentes son nulos.
+pub fn to_record_batch(docs: &[Value]) -> io::Result<RecordBatch> {
+    let columns = column_types(docs);
+
+    let arrays: Vec<ArrayRef> = columns.iter().map(|(name, column)| {
+        let values = docs.iter().map(|doc| doc.get(name).filter(|v| !v.is_null()));
+        let array: ArrayRef = match column {
+            ColumnType::Null => Arc::new(NullArray::new(docs.len())),
+            ColumnType::Boolean => Arc::new(values.map(|v| v.and_then(Value::as_bool)).collect::<BooleanArray>()),
+            ColumnType::Int64 => Arc::new(values.map(|v| v.and_then(Value::as_i64)).collect::<Int64Array>()),
+            ColumnType::Float64 => Arc::new(values.map(|v| v.and_then(Value::as_f64)).collect::<Float64Array>()),
+            ColumnType::Utf8 => Arc::new(values.map(|v| v.and_then(Value::as_str)).collect::<StringArray>()),
+            ColumnType::Json => Arc::new(values.map(|v| v.map(|v| v.to_string())).collect::<StringArray>()),
+        };
+        array
+    }).collect();
+
+    let options = RecordBatchOptions::new().with_row_count(Some(docs.len()));
+    RecordBatch::try_new_with_options(Arc::new(schema(&columns)), arrays, &options).map_err(io::Error::other)
+}
+
+impl Collection {
+    /// Documentos que cumplen `filter` como un `RecordBatch` de Arrow
+    pub fn find_arrow<F: Fn(&Value) -> bool>(&self, filter: F) -> io::Result<RecordBatch> {
+        let docs: Vec<Value> = self.snapshot().iter().filter(|doc| filter(doc)).cloned().collect();
+        to_record_batch(&docs)
+    }
+}
@@ -0,0 +1,379 @@
+//! Carga de trabajo simulada para medir Ruggy en el hardware del host: genera documentos,
+//! ejecuta una mezcla de lecturas y escrituras (en uno o varios hilos) y devuelve el
+//! rendimiento y los percentiles de latencia de cada operación. Sirve para elegir la caché,
+//! la estrategia de persistencia o si hace falta un índice.
+//!
+//! ```json
+//! {"collection": "bench", "documents": 10000, "operations": 50000, "threads": 4,
+//!  "mix": {"reads": 80, "inserts": 10, "updates": 10, "deletes": 0}, "indexed": true}
+//! ```
+//!
+//! Usa la colección indicada tal como esté, así que conviene una base de datos de pruebas.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use crate::bulk::ImportResult;
+use crate::collection::Collection;
+use crate::db::Database;
+
+/// Pesos relativos de cada operación. En JSON los pesos que falten valen 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mix {
+    /// `find` por el campo `key`
+    #[serde(default)]
+    pub reads: u32,
+    #[serde(default)]
+    pub inserts: u32,
+    /// `update_field` de un documento al azar
+    #[serde(default)]
+    pub updates: u32,
+    #[serde(default)]
+    pub deletes: u32,
+}
+
+impl Mix {
+    pub fn read_heavy() -> Self {
+        Self { reads: 90, inserts: 5, updates: 5, deletes: 0 }
+    }
+
+    pub fn balanced() -> Self {
+        Self { reads: 50, inserts: 20, updates: 20, deletes: 10 }
+    }
+
+    pub fn write_heavy() -> Self {
+        Self { reads: 10, inserts: 50, updates: 30, deletes: 10 }
+    }
+
+    fn total(&self) -> u32 {
+        self.reads + self.inserts + self.updates + self.deletes
+    }
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self::read_heavy()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Workload {
+    pub collection: String,
+    /// Documentos que se cargan antes de medir
+    pub documents: usize,
+    /// Campos de texto de cada documento, además de `key` y `n`
+    pub fields: usize,
+    /// Longitud de cada campo de texto
+    pub value_len: usize,
+    /// Operaciones medidas, repartidas entre los hilos
+    pub operations: usize,
+    pub threads: usize,
+    pub mix: Mix,
+    /// Crea un índice de igualdad sobre `key` antes de empezar
+    pub indexed: bool,
+    /// Semilla del generador: la misma semilla repite la misma secuencia de operaciones
+    pub seed: u64,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            collection: "bench".to_string(),
+            documents: 1000,
+            fields: 4,
+            value_len: 16,
+            operations: 10_000,
+            threads: 1,
+            mix: Mix::default(),
+            indexed: false,
+            seed: 42,
+        }
+    }
+}
+
+/// Latencias de un tipo de operación, en microsegundos
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Latency {
+    pub count: u64,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p90_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+impl Latency {
+    fn from_samples(samples: &mut [Duration]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_unstable();
+        let us = |d: Duration| d.as_secs_f64() * 1e6;
+        let at = |q: f64| us(samples[((samples.len() - 1) as f64 * q).round() as usize]);
+        let total: Duration = samples.iter().sum();
+        Self {
+            count: samples.len() as u64,
+            mean_us: us(total) / samples.len() as f64,
+            p50_us: at(0.50),
+            p90_us: at(0.90),
+            p99_us: at(0.99),
+            max_us: us(samples[samples.len() - 1]),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct WorkloadReport {
+    pub operations: u64,
+    /// Operaciones que devolvieron error (no cuentan en las latencias)
+    pub errors: u64,
+    /// Tiempo de la carga inicial de documentos, en milisegundos
+    pub load_ms: f64,
+    pub elapsed_ms: f64,
+    pub ops_per_sec: f64,
+    /// Por operación: `read`, `insert`, `update` y `delete`
+    pub latency: BTreeMap<String, Latency>,
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Read,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Op {
+    fn name(self) -> &'static str {
+        match self {
+            Op::Read => "read",
+            Op::Insert => "insert",
+            Op::Update => "update",
+            Op::Delete => "delete",
+        }
+    }
+}
+
+/// xorshift64*: suficiente para generar datos y no añade dependencias
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545f4914f6cdd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 { 0 } else { self.next() % n }
+    }
+
+    fn text(&mut self, len: usize) -> String {
+        (0..len).map(|_| (b'a' + self.below(26) as u8) as char).collect()
+    }
+}
+
+/// Estado compartido por los hilos: claves generadas e `_id` vivos
+struct Shared {
+    next_key: AtomicU64,
+    ids: Mutex<Vec<String>>,
+}
+
+impl Workload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lee la especificación JSON del ejemplo del módulo; lo que falte toma el valor por defecto
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        Ok(Self::deserialize(spec)?)
+    }
+
+    pub fn collection(mut self, name: &str) -> Self {
+        self.collection = name.to_string();
+        self
+    }
+
+    pub fn documents(mut self, documents: usize) -> Self {
+        self.documents = documents;
+        self
+    }
+
+    pub fn document_shape(mut self, fields: usize, value_len: usize) -> Self {
+        self.fields = fields;
+        self.value_len = value_len;
+        self
+    }
+
+    pub fn operations(mut self, operations: usize) -> Self {
+        self.operations = operations;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    pub fn mix(mut self, mix: Mix) -> Self {
+        self.mix = mix;
+        self
+    }
+
+    pub fn indexed(mut self, indexed: bool) -> Self {
+        self.indexed = indexed;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    fn document(&self, rng: &mut Rng, key: u64) -> Value {
+        let mut doc = Map::new();
+        doc.insert("key".to_string(), Value::String(format!("k{}", key)));
+        doc.insert("n".to_string(), Value::from(rng.below(1000)));
+        for i in 0..self.fields {
+            doc.insert(format!("f{}", i), Value::String(rng.text(self.value_len)));
+        }
+        Value::Object(doc)
+    }
+
+    /// Carga los documentos iniciales y ejecuta las operaciones sobre `db`
+    pub fn run(&self, db: &Database) -> io::Result<WorkloadReport> {
+        if self.mix.total() == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Workload mix has no operations"));
+        }
+        let col = db.collection(&self.collection)?;
+        if self.indexed && !col.indexes().iter().any(|index| index.field == "key") {
+            col.create_index("bench_key", "key")?;
+        }
+
+        let start = Instant::now();
+        let mut rng = Rng::new(self.seed);
+        let docs: Vec<Value> = (0..self.documents as u64).map(|key| self.document(&mut rng, key)).collect();
+        let ids = col.import(docs, None)?
+            .into_iter()
+            .filter_map(|result| match result {
+                ImportResult::Inserted { id } => Some(id),
+                _ => None,
+            })
+            .collect();
+        let load_ms = start.elapsed().as_secs_f64() * 1e3;
+
+        let shared = Shared { next_key: AtomicU64::new(self.documents as u64), ids: Mutex::new(ids) };
+        let threads = self.threads.max(1);
+        let start = Instant::now();
+        let results: Vec<(BTreeMap<&'static str, Vec<Duration>>, u64)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|t| {
+                    let operations = self.operations / threads + usize::from(t < self.operations % threads);
+                    let (col, shared) = (&col, &shared);
+                    scope.spawn(move || self.run_thread(col, shared, t as u64, operations))
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().expect("benchmark thread panicked")).collect()
+        });
+        let elapsed = start.elapsed();
+
+        let mut report = WorkloadReport { load_ms, elapsed_ms: elapsed.as_secs_f64() * 1e3, ..Default::default() };
+        let mut samples: BTreeMap<&'static str, Vec<Duration>> = BTreeMap::new();
+        for (thread_samples, errors) in results {
+            report.errors += errors;
+            for (op, durations) in thread_samples {
+                samples.entry(op).or_default().extend(durations);
+            }
+        }
+        for (op, mut durations) in samples {
+            report.operations += durations.len() as u64;
+            report.latency.insert(op.to_string(), Latency::from_samples(&mut durations));
+        }
+        report.operations += report.errors;
+        if elapsed > Duration::ZERO {
+            report.ops_per_sec = report.operations as f64 / elapsed.as_secs_f64();
+        }
+        Ok(report)
+    }
+
+    fn run_thread(&self, col: &Arc<Collection>, shared: &Shared, thread: u64, operations: usize)
+        -> (BTreeMap<&'static str, Vec<Duration>>, u64)
+    {
+        let mut rng = Rng::new(self.seed.wrapping_add(thread + 1));
+        let mut samples: BTreeMap<&'static str, Vec<Duration>> = BTreeMap::new();
+        let mut errors = 0;
+        let mix = self.mix;
+        for _ in 0..operations {
+            let mut pick = rng.below(mix.total() as u64) as u32;
+            let op = [(Op::Read, mix.reads), (Op::Insert, mix.inserts), (Op::Update, mix.updates), (Op::Delete, mix.deletes)]
+                .into_iter()
+                .find(|(_, weight)| {
+                    let hit = pick < *weight;
+                    pick = pick.saturating_sub(*weight);
+                    hit
+                })
+                .map_or(Op::Read, |(op, _)| op);
+
+            // Los datos de cada operación se preparan fuera de la medida
+            let result = match op {
+                Op::Read => {
+                    let key = format!("k{}", rng.below(shared.next_key.load(Ordering::Relaxed)));
+                    let start = Instant::now();
+                    col.find("key", &key);
+                    Ok(start.elapsed())
+                }
+                Op::Insert => {
+                    let doc = self.document(&mut rng, shared.next_key.fetch_add(1, Ordering::Relaxed));
+                    let start = Instant::now();
+                    col.insert(doc).map(|id| {
+                        let elapsed = start.elapsed();
+                        shared.ids.lock().push(id);
+                        elapsed
+                    })
+                }
+                Op::Update => {
+                    let id = {
+                        let ids = shared.ids.lock();
+                        ids.get(rng.below(ids.len() as u64) as usize).cloned()
+                    };
+                    let value = Value::from(rng.below(1000));
+                    let start = Instant::now();
+                    match id {
+                        Some(id) => col.update_field(&id, "n", value).map(|_| start.elapsed()),
+                        None => Ok(start.elapsed()),
+                    }
+                }
+                Op::Delete => {
+                    let id = {
+                        let mut ids = shared.ids.lock();
+                        let len = ids.len() as u64;
+                        (len > 0).then(|| ids.swap_remove(rng.below(len) as usize))
+                    };
+                    let start = Instant::now();
+                    match id {
+                        Some(id) => col.delete_by_id(&id).map(|_| start.elapsed()),
+                        None => Ok(start.elapsed()),
+                    }
+                }
+            };
+            match result {
+                Ok(elapsed) => samples.entry(op.name()).or_default().push(elapsed),
+                Err(_) => errors += 1,
+            }
+        }
+        (samples, errors)
+    }
+}
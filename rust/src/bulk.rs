@@ -0,0 +1,160 @@
+//! Escrituras por lotes: se aplican con un solo lock y una sola reescritura del archivo.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::Arc;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::cdc::ChangeOp;
+use crate::collection::{check_reserved, doc_id, Collection};
+use crate::limits;
+use crate::types;
+
+/// Resultado de `Collection::upsert_many`
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct UpsertReport {
+    pub inserted: usize,
+    pub updated: usize,
+}
+
+/// Resultado de cada documento de `Collection::import`, en el mismo orden que la entrada
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ImportResult {
+    Inserted { id: String },
+    Updated { id: String },
+    Failed { reason: String },
+}
+
+impl Collection {
+    /// Sincroniza `documents` usando `key_field` como clave única: los que coinciden con un
+    /// documento existente actualizan sus campos (conservando `_id`) y el resto se insertan.
+    /// Si la clave se repite en el lote gana el último. Falla sin escribir nada si algún
+    /// documento no es válido, también por las reglas o los tipos propios; el error dice cuál.
+    pub fn upsert_many(&self, key_field: &str, documents: Vec<Value>) -> io::Result<UpsertReport> {
+        self.ensure_writable()?;
+        let mut report = UpsertReport::default();
+        for result in self.apply_batch(Some(key_field), documents, true)? {
+            match result {
+                ImportResult::Inserted { .. } => report.inserted += 1,
+                ImportResult::Updated { .. } => report.updated += 1,
+                // `apply_batch` ya devuelve el error sin escribir nada
+                ImportResult::Failed { reason } => return Err(io::Error::new(io::ErrorKind::InvalidInput, reason)),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Importa un lote sin detenerse en los documentos inválidos: cada uno obtiene su
+    /// resultado (insertado, actualizado o fallido con el motivo). Con `key_field` se hace
+    /// upsert como en `upsert_many`; sin él todos se insertan.
+    pub fn import(&self, documents: Vec<Value>, key_field: Option<&str>) -> io::Result<Vec<ImportResult>> {
+        self.ensure_writable()?;
+        self.apply_batch(key_field, documents, false)
+    }
+
+    /// Motivo por el que un documento no se puede guardar
+    fn check_item(&self, doc: &Value, key_field: Option<&str>) -> Result<(), String> {
+        if !doc.is_object() {
+            return Err("Document is not a JSON object".to_string());
+        }
+        if let Some(key_field) = key_field {
+            if doc.get(key_field).is_none_or(|key| key.is_null()) {
+                return Err(format!("Missing key '{}'", key_field));
+            }
+        }
+        limits::check_depth(doc).map_err(|e| e.to_string())?;
+        check_reserved(doc).map_err(|e| e.to_string())?;
+        // Lo mismo que se escribirá en disco (p. ej. falla si un campo cifrado no se puede sellar)
+        let line = self.encode_line(doc).map_err(|e| e.to_string())?;
+        limits::check_size(line.len()).map_err(|e| e.to_string())
+    }
+
+    /// Aplica el lote con un solo lock. Los cambios se preparan antes de tocar los datos: con
+    /// `all_or_nothing` el primer documento que falla devuelve el error sin cambiar nada.
+    fn apply_batch(&self, key_field: Option<&str>, documents: Vec<Value>, all_or_nothing: bool) -> io::Result<Vec<ImportResult>> {
+        let mut results = Vec::with_capacity(documents.len());
+        let mut guard = self.data_write()?;
+        let len = guard.len();
+        // Primera aparición de cada clave; las siguientes se ignoran igual que en un índice único
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        if let Some(key_field) = key_field {
+            for (pos, doc) in guard.iter().enumerate() {
+                if let Some(key) = doc.get(key_field) {
+                    positions.entry(key.to_string()).or_insert(pos);
+                }
+            }
+        }
+        // Documento final de cada posición que cambia (las nuevas, a partir de `len`) y los
+        // eventos en el orden del lote
+        let mut changed: BTreeMap<usize, Value> = BTreeMap::new();
+        let mut events: Vec<(ChangeOp, String, Value)> = Vec::new();
+        let mut next_pos = len;
+
+        for (n, mut doc) in documents.into_iter().enumerate() {
+            let checked = self.check_item(&doc, key_field)
+                .and_then(|_| types::canonicalize(&mut doc).map_err(|e| e.to_string()));
+            let key = key_field.map(|field| doc[field].to_string());
+            let staged = checked.and_then(|_| match key.as_ref().and_then(|key| positions.get(key)) {
+                Some(&pos) => {
+                    let mut merged = changed.get(&pos).unwrap_or_else(|| &guard[pos]).clone();
+                    if let (Some(target), Value::Object(fields)) = (merged.as_object_mut(), doc) {
+                        for (field, value) in fields {
+                            if field != "_id" {
+                                target.insert(field, value);
+                            }
+                        }
+                    }
+                    self.check_rules(&merged).map_err(|e| e.to_string())?;
+                    let id = doc_id(&merged).to_string();
+                    events.push((ChangeOp::Update, id.clone(), merged.clone()));
+                    changed.insert(pos, merged);
+                    Ok(ImportResult::Updated { id })
+                }
+                None => {
+                    self.check_rules(&doc).map_err(|e| e.to_string())?;
+                    let id = Uuid::new_v4().to_string();
+                    if let Some(obj) = doc.as_object_mut() {
+                        obj.insert("_id".to_string(), Value::String(id.clone()));
+                    }
+                    let pos = next_pos;
+                    next_pos += 1;
+                    if let Some(key) = key {
+                        positions.insert(key, pos);
+                    }
+                    events.push((ChangeOp::Insert, id.clone(), doc.clone()));
+                    changed.insert(pos, doc);
+                    Ok(ImportResult::Inserted { id })
+                }
+            });
+            match staged {
+                Ok(result) => results.push(result),
+                Err(reason) if all_or_nothing => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Document {}: {}", n, reason)));
+                }
+                Err(reason) => results.push(ImportResult::Failed { reason }),
+            }
+        }
+
+        if !changed.is_empty() {
+            let data = Arc::make_mut(&mut guard);
+            // En orden de posición: las nuevas se añaden detrás en el orden del lote
+            for (pos, doc) in changed {
+                match data.get_mut(pos) {
+                    Some(existing) => *existing = doc,
+                    None => data.push(doc),
+                }
+            }
+            for (op, id, doc) in &events {
+                self.emit(*op, id, Some(doc));
+            }
+            self.apply_order(data);
+            self.rebuild_indexes(data);
+            self.touch();
+            drop(guard);
+            self.persist_on_write()?;
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+use crate::memory::{string_size, values_size, MAP_ENTRY_OVERHEAD};
+
+/// Contadores de la caché de resultados
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
+struct CacheInner {
+    entries: HashMap<String, Arc<Vec<Value>>>,
+    order: VecDeque<String>,
+    generation: u64,
+    stats: CacheStats,
+}
+
+/// Caché de resultados por colección, indexada por la consulta normalizada.
+/// Cualquier escritura la invalida entera; las entradas más antiguas salen primero al llenarse.
+pub struct ResultCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+impl ResultCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            inner: Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                order: VecDeque::new(),
+                generation: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// Clave normalizada de una consulta: operación y argumentos separados por `\0`
+    pub fn key(parts: &[&str]) -> String {
+        parts.join("\0")
+    }
+
+    /// Devuelve el resultado cacheado o la generación actual para guardarlo después con `put`
+    pub fn get(&self, key: &str) -> Result<Arc<Vec<Value>>, u64> {
+        let mut inner = self.inner.lock();
+        match inner.entries.get(key).cloned() {
+            Some(hit) => {
+                inner.stats.hits += 1;
+                Ok(hit)
+            }
+            None => {
+                inner.stats.misses += 1;
+                Err(inner.generation)
+            }
+        }
+    }
+
+    /// Guarda un resultado solo si no hubo escrituras desde que se leyó `generation`
+    pub fn put(&self, key: String, generation: u64, result: Arc<Vec<Value>>) {
+        let mut inner = self.inner.lock();
+        if inner.generation != generation || inner.entries.contains_key(&key) {
+            return;
+        }
+        if inner.entries.len() >= self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.entries.remove(&oldest);
+            }
+        }
+        inner.order.push_back(key.clone());
+        inner.entries.insert(key, result);
+    }
+
+    pub fn invalidate(&self) {
+        let mut inner = self.inner.lock();
+        inner.generation += 1;
+        inner.entries.clear();
+        inner.order.clear();
+        inner.stats.invalidations += 1;
+    }
+
+    /// Bytes aproximados de los resultados guardados
+    pub fn memory_usage(&self) -> usize {
+        let inner = self.inner.lock();
+        inner.entries.iter()
+            .map(|(key, result)| 2 * string_size(key) + values_size(result) + MAP_ENTRY_OVERHEAD)
+            .sum()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock();
+        CacheStats { entries: inner.entries.len(), ..inner.stats }
+    }
+}
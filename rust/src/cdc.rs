@@ -0,0 +1,66 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// Mutación confirmada de un documento
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeEvent {
+    /// Secuencia creciente por base de datos
+    pub seq: u64,
+    pub collection: String,
+    pub op: ChangeOp,
+    pub id: String,
+    /// Documento completo tras la operación (`None` en los deletes)
+    pub document: Option<Value>,
+}
+
+pub type CdcSink = Box<dyn Fn(ChangeEvent) + Send + Sync>;
+
+/// Destino de los eventos de cambio, compartido entre la base de datos y sus colecciones
+#[derive(Default)]
+pub struct ChangeFeed {
+    seq: AtomicU64,
+    sink: RwLock<Option<Arc<CdcSink>>>,
+}
+
+impl ChangeFeed {
+    pub fn set_sink(&self, sink: Option<CdcSink>) {
+        *self.sink.write() = sink.map(Arc::new);
+    }
+
+    pub fn has_sink(&self) -> bool {
+        self.sink.read().is_some()
+    }
+
+    /// Última secuencia emitida
+    pub fn last_seq(&self) -> u64 {
+        self.seq.load(Ordering::SeqCst)
+    }
+
+    /// Se llama con el lock de escritura de la colección tomado, así el orden de `seq`
+    /// coincide con el de las escrituras. Sin sink no se asigna secuencia.
+    pub(crate) fn emit(&self, collection: &str, op: ChangeOp, id: &str, document: Option<&Value>) {
+        let sink = match self.sink.read().clone() {
+            Some(sink) => sink,
+            None => return,
+        };
+        let event = ChangeEvent {
+            seq: self.seq.fetch_add(1, Ordering::SeqCst) + 1,
+            collection: collection.to_string(),
+            op,
+            id: id.to_string(),
+            document: document.cloned(),
+        };
+        sink(event);
+    }
+}
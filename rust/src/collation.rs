@@ -0,0 +1,79 @@
+//! Ordenación de strings según idioma (una versión reducida del algoritmo de colación de
+//! Unicode). Se compara primero la letra base, luego los acentos y por último
+//! mayúsculas/minúsculas, así "Álvarez" queda junto a "Alvarez" y no después de "Zapata".
+
+use std::cmp::Ordering;
+use serde_json::Value;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use crate::collection::doc_id;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Collation {
+    /// Orden de los puntos de código (el de `str::cmp`)
+    #[default]
+    Binary,
+    /// Acentos y mayúsculas solo desempatan
+    Unicode,
+    /// Como `Unicode`, con la ñ como letra propia entre la n y la o
+    Spanish,
+}
+
+const COMBINING_TILDE: char = '\u{0303}';
+
+impl Collation {
+    /// Clave de ordenación: comparar claves da el mismo resultado que `compare`,
+    /// útil para calcularla una sola vez por documento
+    pub fn sort_key(&self, s: &str) -> Vec<u32> {
+        if *self == Collation::Binary {
+            return s.chars().map(|c| c as u32 + 1).collect();
+        }
+        let mut primary = Vec::new();
+        let mut secondary = Vec::new();
+        let mut tertiary = Vec::new();
+
+        let mut chars = s.nfd().peekable();
+        while let Some(c) = chars.next() {
+            let mut marks = 0u32;
+            let mut weight = None;
+            while let Some(&mark) = chars.peek().filter(|m| is_combining_mark(**m)) {
+                chars.next();
+                if *self == Collation::Spanish && mark == COMBINING_TILDE && matches!(c, 'n' | 'N') {
+                    weight = Some('n' as u32 * 2 + 3);
+                } else {
+                    marks = marks.wrapping_mul(31).wrapping_add(mark as u32);
+                }
+            }
+            for lower in c.to_lowercase() {
+                primary.push(weight.unwrap_or(lower as u32 * 2 + 2));
+                secondary.push(marks + 1);
+                tertiary.push(if c.is_uppercase() { 2 } else { 1 });
+            }
+        }
+
+        primary.push(0);
+        primary.extend(secondary);
+        primary.push(0);
+        primary.extend(tertiary);
+        primary
+    }
+
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Collation::Binary => a.cmp(b),
+            _ => self.sort_key(a).cmp(&self.sort_key(b)).then_with(|| a.cmp(b)),
+        }
+    }
+
+    /// Ordena documentos por un campo de texto. Los que no tienen el campo como string van
+    /// al final; los empates se ordenan por `_id`.
+    pub fn sort_by_field(&self, docs: &mut [Value], field: &str) {
+        docs.sort_by_cached_key(|doc| {
+            let id = doc_id(doc).to_string();
+            match doc.get(field) {
+                Some(Value::String(s)) => (false, self.sort_key(s), id),
+                _ => (true, Vec::new(), id),
+            }
+        });
+    }
+}
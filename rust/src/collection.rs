@@ -1,132 +1,845 @@
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufRead, BufReader, BufWriter, Write};
-use std::path::PathBuf;
-use std::sync::Arc;
-use parking_lot::{Mutex, RwLock};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use regex::Regex;
 use serde_json::Value;
 use uuid::Uuid;
+use crate::archive::{Archive, ArchivePolicy};
+use crate::cache::{CacheStats, ResultCache};
+use crate::cdc::{ChangeFeed, ChangeOp};
+use crate::compression;
+use crate::contention;
+use crate::datetime;
+use crate::dedupe;
+use crate::encryption::{self, FieldCipher};
+use crate::expr;
+use crate::limits;
+use crate::load::{self, LoadMode, LoadReport};
+use crate::lock_stats::{LockCounters, LockWaitCallback};
+use crate::index::{self, Index, IndexInfo, IndexKind, IndexSpec, QueryOptions, ReadConsistency};
+use crate::index_build::IndexBuilds;
+use crate::io_stats::{CompactionRecord, IoCounters, IoStats};
+use crate::memory::{values_size, MemoryUsage};
+use crate::normalize::{normalized, Normalization};
+use crate::page;
+use crate::options::{CollectionOptions, DocumentOrder, PersistStrategy};
+use crate::replica::{self, FollowState};
+use crate::stats::CollectionStats;
+use crate::storage::{self, MemoryStorage, StorageBackend};
+use crate::types;
+use crate::text::{Analyzer, Highlight, SearchHit};
 
 pub struct Collection {
-    #[allow(dead_code)]
     name: String,
-    #[allow(dead_code)]
-    file_path: PathBuf,
-    pub(crate) data: RwLock<Vec<Value>>,
-    pub(crate) writer: Mutex<BufWriter<File>>,
+    /// Dónde está el archivo de la colección; cambia a memoria con `detach`
+    storage: RwLock<Arc<dyn StorageBackend>>,
+    /// Nombre del archivo en `storage` (`<name>.col`)
+    pub(crate) file_name: String,
+    /// Versión actual de los datos. Lectores y `persist()` trabajan sobre una copia del `Arc`
+    /// y las escrituras clonan el `Vec` solo si hay alguna copia viva (copy-on-write).
+    pub(crate) data: RwLock<Arc<Vec<Value>>>,
+    /// `false` tras `unload()`: `data` está vacío y se vuelve a leer del archivo al usarse.
+    /// Solo cambia con el lock de `data` tomado.
+    loaded: AtomicBool,
+    /// Documentos que había al descargar, para `count` sin recargar
+    unloaded_len: AtomicUsize,
+    /// Índices sobre la versión actual de `data`; solo cambian con el lock de `data` tomado
+    pub(crate) indexes: RwLock<Arc<Vec<Index>>>,
+    /// Versión de `data` e `indexes` al empezar la última escritura, para las lecturas
+    /// `ReadConsistency::Snapshot`. Referencias débiles: no obligan a copiar los datos.
+    committed: Mutex<WeakView>,
+    /// Serializa la IO de escritura; ver `writer()`
+    pub(crate) writer: Mutex<()>,
+    pub(crate) options: Arc<RwLock<CollectionOptions>>,
+    pub(crate) stats: RwLock<Option<CollectionStats>>,
+    pub(crate) cache: Option<ResultCache>,
+    /// Hay inserts añadidos al final desde la última reescritura completa
+    pub(crate) dirty: AtomicBool,
+    pub(crate) last_write_ms: AtomicU64,
+    /// Época del archivo: impar mientras se reescribe, ver `replica`
+    pub(crate) epoch: AtomicU64,
+    pub(crate) follow: Option<Mutex<FollowState>>,
+    pub(crate) changes: Arc<ChangeFeed>,
+    pub(crate) io: IoCounters,
+    pub(crate) index_builds: IndexBuilds,
+    pub(crate) lock_counters: LockCounters,
+    pub(crate) lock_wait_callback: RwLock<Option<Arc<LockWaitCallback>>>,
+    /// Resultado de la última lectura completa del archivo
+    pub(crate) load_report: RwLock<LoadReport>,
+    expiry_callback: RwLock<Option<Arc<ExpiryCallback>>>,
+    /// Hay escrituras en memoria que no están en el archivo (estrategias distintas de `OnEveryWrite`)
+    pub(crate) unsaved: AtomicBool,
+    pub(crate) last_persist_ms: AtomicU64,
 }
 
+/// Recibe los documentos eliminados por una pasada de expiración
+pub type ExpiryCallback = Box<dyn Fn(&[Value]) + Send + Sync>;
+
+/// Una versión de los documentos con sus índices
+pub(crate) type DataView = (Arc<Vec<Value>>, Arc<Vec<Index>>);
+type WeakView = (Weak<Vec<Value>>, Weak<Vec<Index>>);
+
 impl Collection {
     pub fn new(name: &str, file_path: PathBuf) -> io::Result<Self> {
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .read(true)
-            .open(&file_path)?;
-            
-        let mut data = Vec::new();
-        let reader = BufReader::new(&file);
-        for line in reader.lines() {
-            let line = line?;
-            if !line.trim().is_empty() {
-                if let Ok(value) = serde_json::from_str::<Value>(&line) {
-                    data.push(value);
-                }
+        Self::with_options(name, file_path, CollectionOptions::default())
+    }
+
+    pub fn with_options(name: &str, file_path: PathBuf, options: CollectionOptions) -> io::Result<Self> {
+        let (storage, file_name) = storage::local_file(&file_path)?;
+        Self::open_in(name, Arc::new(storage), file_name, options)
+    }
+
+    /// Abre (o crea) la colección `<name>.col` de `storage`
+    pub fn with_storage(name: &str, storage: Arc<dyn StorageBackend>, options: CollectionOptions) -> io::Result<Self> {
+        Self::open_in(name, storage, format!("{}.col", name), options)
+    }
+
+    pub(crate) fn open_in(name: &str, storage: Arc<dyn StorageBackend>, file_name: String, options: CollectionOptions) -> io::Result<Self> {
+        let (data, _, report) = match storage.open(&file_name, 0)? {
+            Some(reader) => read_records(BufReader::new(reader), &options, 0, false)?,
+            None => {
+                // Una colección abierta tiene archivo aunque esté vacía
+                storage.append(&file_name, b"")?;
+                Default::default()
             }
-        }
-        let write_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(&file_path)?;
-            
-        Ok(Self {
+        };
+        let epoch = replica::read_epoch(storage.as_ref(), &file_name)?;
+        Ok(Self::from_parts(name, storage, file_name, data, options, epoch, None)
+            .with_load_report(report))
+    }
+
+    pub(crate) fn from_parts(
+        name: &str,
+        storage: Arc<dyn StorageBackend>,
+        file_name: String,
+        data: Vec<Value>,
+        options: CollectionOptions,
+        epoch: u64,
+        follow: Option<FollowState>,
+    ) -> Self {
+        Self {
             name: name.to_string(),
-            file_path,
-            data: RwLock::new(data),
-            writer: Mutex::new(BufWriter::new(write_file)),
-        })
+            storage: RwLock::new(storage),
+            file_name,
+            indexes: RwLock::new(Arc::new(build_indexes(&options, &data))),
+            data: RwLock::new(Arc::new(data)),
+            committed: Mutex::new((Weak::new(), Weak::new())),
+            loaded: AtomicBool::new(true),
+            unloaded_len: AtomicUsize::new(0),
+            writer: Mutex::new(()),
+            cache: options.result_cache.map(ResultCache::new),
+            options: Arc::new(RwLock::new(options)),
+            stats: RwLock::new(None),
+            dirty: AtomicBool::new(false),
+            last_write_ms: AtomicU64::new(now_millis()),
+            epoch: AtomicU64::new(epoch),
+            follow: follow.map(Mutex::new),
+            changes: Arc::new(ChangeFeed::default()),
+            io: IoCounters::default(),
+            index_builds: IndexBuilds::default(),
+            lock_counters: LockCounters::default(),
+            lock_wait_callback: RwLock::new(None),
+            load_report: RwLock::new(LoadReport::default()),
+            expiry_callback: RwLock::new(None),
+            unsaved: AtomicBool::new(false),
+            last_persist_ms: AtomicU64::new(now_millis()),
+        }
+    }
+
+    pub(crate) fn with_load_report(self, report: LoadReport) -> Self {
+        *self.load_report.write() = report;
+        self
+    }
+
+    /// Envía los eventos de cambio de esta colección al feed de la base de datos
+    pub(crate) fn with_change_feed(mut self, changes: Arc<ChangeFeed>) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// Registra una mutación en las estadísticas de IO y la envía al feed de cambios. Se
+    /// llama con el lock de escritura de los datos tomado, ver `ChangeFeed::emit`.
+    pub(crate) fn emit(&self, op: ChangeOp, id: &str, document: Option<&Value>) {
+        self.io.change(id, document);
+        self.changes.emit(&self.name, op, id, document);
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.follow.is_some()
+    }
+
+    pub(crate) fn ensure_writable(&self) -> io::Result<()> {
+        if self.is_read_only() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Collection is a read-only follower"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn storage(&self) -> Arc<dyn StorageBackend> {
+        self.storage.read().clone()
+    }
+
+    /// Deja de usar el archivo (p. ej. porque se ha borrado): a partir de ahora los datos solo
+    /// se guardan en memoria
+    pub(crate) fn detach(&self) {
+        let _writer = self.writer.lock();
+        *self.storage.write() = Arc::new(MemoryStorage::new());
+        self.unsaved.store(true, Ordering::Release);
+    }
+
+    /// Lock de la IO de escritura: mientras se tiene, nadie más escribe en el archivo. Espera
+    /// según `CollectionOptions::contention`.
+    pub(crate) fn writer(&self) -> io::Result<MutexGuard<'_, ()>> {
+        self.ensure_writable()?;
+        self.file_lock()
+    }
+
+    /// El mismo lock que `writer` también en un seguidor de solo lectura, para leer el archivo
+    /// sin que esta colección lo cambie mientras tanto
+    pub(crate) fn file_lock(&self) -> io::Result<MutexGuard<'_, ()>> {
+        let policy = self.options.read().contention;
+        let try_for = |timeout| match timeout {
+            Some(timeout) => self.writer.try_lock_for(timeout),
+            None => Some(self.writer.lock()),
+        };
+        contention::acquire(&policy, &self.name, "writer", try_for, |waited| self.lock_waited("io", waited))
+    }
+
+    /// Lock de escritura sobre los datos, recargándolos si la colección estaba descargada.
+    /// Espera según `CollectionOptions::contention`.
+    pub(crate) fn data_write(&self) -> io::Result<RwLockWriteGuard<'_, Arc<Vec<Value>>>> {
+        let policy = self.options.read().contention;
+        let try_for = |timeout| match timeout {
+            Some(timeout) => self.data.try_write_for(timeout),
+            None => Some(self.data.write()),
+        };
+        let mut guard = contention::acquire(&policy, &self.name, "data", try_for, |waited| self.lock_waited("write", waited))?;
+        if !self.loaded.load(Ordering::Acquire) {
+            let options = self.options.read().clone();
+            let (data, _, report) = match self.storage().open(&self.file_name, 0)? {
+                Some(reader) => read_records(BufReader::new(reader), &options, 0, false)?,
+                None => Default::default(),
+            };
+            *self.load_report.write() = report;
+            self.rebuild_indexes(&data);
+            *guard = Arc::new(data);
+            self.loaded.store(true, Ordering::Release);
+        }
+        *self.committed.lock() = (Arc::downgrade(&guard), Arc::downgrade(&self.indexes.read()));
+        Ok(guard)
+    }
+
+    /// Datos e índices para una lectura `Snapshot`: los actuales si nadie está escribiendo y
+    /// si no los de antes de la escritura en curso, mientras sigan en memoria (una escritura
+    /// que modifica los documentos sin copiarlos los libera). `None` si hay que esperar.
+    pub(crate) fn snapshot_view(&self) -> Option<DataView> {
+        if let Some(guard) = self.data.try_read() {
+            return self.is_loaded().then(|| (guard.clone(), self.indexes.read().clone()));
+        }
+        let committed = self.committed.lock();
+        Some((committed.0.upgrade()?, committed.1.upgrade()?))
+    }
+
+    pub(crate) fn data_read(&self) -> io::Result<RwLockReadGuard<'_, Arc<Vec<Value>>>> {
+        {
+            let guard = self.data.try_read().unwrap_or_else(|| {
+                let start = Instant::now();
+                let guard = self.data.read();
+                self.lock_waited("read", start.elapsed());
+                guard
+            });
+            if self.loaded.load(Ordering::Acquire) {
+                return Ok(guard);
+            }
+        }
+        Ok(RwLockWriteGuard::downgrade(self.data_write()?))
+    }
+
+    /// Libera los documentos en memoria. El handle sigue siendo válido y la colección
+    /// se vuelve a leer del archivo en el siguiente acceso.
+    pub fn unload(&self) -> io::Result<()> {
+        if self.is_read_only() {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Cannot unload a read-only follower"));
+        }
+        // Con el writer tomado no puede haber un insert a medias
+        let _writer = self.writer()?;
+        let mut guard = self.data.write();
+        // Lo que no se ha guardado se perdería al recargar
+        if self.unsaved.swap(false, Ordering::AcqRel) {
+            if let Err(e) = self.rewrite(&guard) {
+                self.unsaved.store(true, Ordering::Release);
+                return Err(e);
+            }
+        }
+        self.unloaded_len.store(guard.len(), Ordering::Relaxed);
+        *guard = Arc::new(Vec::new());
+        self.release_indexes();
+        self.loaded.store(false, Ordering::Release);
+        drop(guard);
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.load(Ordering::Acquire)
+    }
+
+    /// Documentos leídos y registros saltados en la última carga completa del archivo
+    pub fn load_report(&self) -> LoadReport {
+        self.load_report.read().clone()
+    }
+
+    /// Ordena `data` según `CollectionOptions::order` tras añadir documentos. Llamar con el
+    /// lock de escritura de `data` tomado y antes de reconstruir los índices.
+    pub(crate) fn apply_order(&self, data: &mut [Value]) {
+        sort_documents(data, self.options.read().order);
+    }
+
+    /// Reconstruye los índices sobre `data`. Llamar con el lock de escritura de `data` tomado.
+    pub(crate) fn rebuild_indexes(&self, data: &[Value]) {
+        let mut indexes = self.indexes.write();
+        if indexes.is_empty() {
+            return;
+        }
+        *indexes = Arc::new(indexes.iter().map(|index| index.rebuilt(data)).collect());
+    }
+
+    /// Vacía los índices al descargar los datos salvo los que cubren consultas, que siguen
+    /// valiendo porque no se escribe sin recargar (y la recarga los reconstruye)
+    fn release_indexes(&self) {
+        let mut indexes = self.indexes.write();
+        if indexes.is_empty() {
+            return;
+        }
+        *indexes = Arc::new(indexes.iter()
+            .map(|index| if index.is_covering() { index.clone() } else { index.rebuilt(&[]) })
+            .collect());
+    }
+
+    /// Crea un índice de igualdad sobre un campo de primer nivel. Los índices viven en
+    /// memoria: para que existan al reabrir, declararlos con `CollectionOptions::index`. Con
+    /// colecciones grandes en uso, `create_index_background` no bloquea las escrituras.
+    pub fn create_index(&self, name: &str, field: &str) -> io::Result<()> {
+        self.add_index(IndexSpec::new(name, field, IndexKind::Equality))
+    }
+
+    /// Índice de igualdad que además guarda `include` para responder consultas sin leer los
+    /// documentos (ver `covered`). Se conserva al descargar la colección con `unload`.
+    pub fn create_covering_index(&self, name: &str, field: &str, include: &[&str]) -> io::Result<()> {
+        self.add_index(IndexSpec::new(name, field, IndexKind::Equality).include(include))
+    }
+
+    /// Crea un índice de texto completo sobre un campo de texto, para `search`
+    pub fn create_text_index(&self, name: &str, field: &str, analyzer: Analyzer) -> io::Result<()> {
+        self.add_index(IndexSpec::new(name, field, IndexKind::Text(analyzer)))
+    }
+
+    /// Crea un índice de prefijos sobre un campo de texto, para `autocomplete`
+    pub fn create_prefix_index(&self, name: &str, field: &str) -> io::Result<()> {
+        self.add_index(IndexSpec::new(name, field, IndexKind::Prefix))
+    }
+
+    fn add_index(&self, spec: IndexSpec) -> io::Result<()> {
+        let guard = self.data_write()?;
+        let mut indexes = self.indexes.write();
+        if indexes.iter().any(|i| i.name() == spec.name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Index '{}' already exists", spec.name)));
+        }
+        let normalization = self.options.read().normalization;
+        Arc::make_mut(&mut indexes).push(Index::build(spec, normalization, Arc::default(), &guard));
+        drop(indexes);
+        drop(guard);
+        self.invalidate_cache();
+        Ok(())
+    }
+
+    /// Elimina un índice. Devuelve `false` si no existía.
+    pub fn drop_index(&self, name: &str) -> io::Result<bool> {
+        let guard = self.data_write()?;
+        let mut indexes = self.indexes.write();
+        let before = indexes.len();
+        Arc::make_mut(&mut indexes).retain(|index| index.name() != name);
+        let dropped = indexes.len() < before;
+        drop(indexes);
+        drop(guard);
+        if dropped {
+            self.invalidate_cache();
+        }
+        Ok(dropped)
+    }
+
+    /// Reconstruye todos los índices a partir de los datos. Devuelve cuántos se reconstruyeron.
+    pub fn reindex(&self) -> io::Result<usize> {
+        self.reindex_with_progress(|_, _, _| {})
+    }
+
+    /// Como `reindex`, llamando a `progress(índice, hechos, total)` al terminar cada índice
+    pub fn reindex_with_progress<F: FnMut(&str, usize, usize)>(&self, mut progress: F) -> io::Result<usize> {
+        let guard = self.data_write()?;
+        let mut indexes = self.indexes.write();
+        let total = indexes.len();
+        let mut rebuilt = Vec::with_capacity(total);
+        for (i, index) in indexes.iter().enumerate() {
+            rebuilt.push(index.rebuilt(&guard));
+            progress(index.name(), i + 1, total);
+        }
+        *indexes = Arc::new(rebuilt);
+        drop(indexes);
+        drop(guard);
+        self.invalidate_cache();
+        Ok(total)
+    }
+
+    /// Índices de la colección con sus contadores de uso
+    pub fn indexes(&self) -> Vec<IndexInfo> {
+        self.indexes.read().iter().map(|index| index.info()).collect()
+    }
+
+    /// Serializa un documento tal como se guarda en disco
+    pub(crate) fn encode_line(&self, doc: &Value) -> io::Result<String> {
+        let options = self.options.read();
+        let sealed = match &options.encryption {
+            Some(enc) => Cow::Owned(enc.seal(doc)?),
+            None => Cow::Borrowed(doc),
+        };
+        let line = serde_json::to_string(&sealed)?;
+        match options.compress_above {
+            Some(limit) if line.len() > limit => compression::compress_line(&sealed, line),
+            _ => Ok(line),
+        }
     }
 
     pub fn insert(&self, mut document: Value) -> io::Result<String> {
-        use std::io::{Seek, SeekFrom};
+        self.ensure_writable()?;
+        limits::check_depth(&document)?;
+        check_reserved(&document)?;
         let id = Uuid::new_v4().to_string();
         if let Some(obj) = document.as_object_mut() {
             obj.insert("_id".to_string(), Value::String(id.clone()));
         } else {
             return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not an object"));
         }
-        let json_line = serde_json::to_string(&document)?;
+        types::canonicalize(&mut document)?;
+        self.check_rules(&document)?;
+        let json_line = self.encode_line(&document)?;
+        limits::check_size(json_line.len())?;
+        // Con otras estrategias la línea no se añade: se guarda con el resto en el próximo persist
+        let append = self.options.read().persist_strategy == PersistStrategy::OnEveryWrite;
         {
-            let mut writer = self.writer.lock();
-            // Asegurarse de estar al final para el insert
-            writer.flush()?;
-            let file = writer.get_mut();
-            file.seek(SeekFrom::End(0))?;
-            
-            writeln!(writer, "{}", json_line)?;
-            writer.flush()?;
+            let _writer = self.writer()?;
+            // Recargar antes de añadir la línea, si no se leería dos veces. Con el writer
+            // tomado no se puede descargar otra vez mientras tanto.
+            if !self.is_loaded() {
+                drop(self.data_write()?);
+            }
+            if append {
+                let line = format!("{}\n", json_line);
+                self.storage().append(&self.file_name, line.as_bytes())?;
+                self.io.append(line.len());
+            }
+
+            // Con el writer aún tomado, para que un persist concurrente no pierda la línea
+            let mut guard = self.data_write()?;
+            self.emit(ChangeOp::Insert, &id, Some(&document));
+            if self.options.read().order == DocumentOrder::ById {
+                let data = Arc::make_mut(&mut guard);
+                let pos = data.partition_point(|doc| doc_id(doc) < id.as_str());
+                data.insert(pos, document);
+                self.rebuild_indexes(data);
+            } else {
+                {
+                    let mut indexes = self.indexes.write();
+                    if !indexes.is_empty() {
+                        for index in Arc::make_mut(&mut indexes).iter_mut() {
+                            index.add(guard.len(), &document);
+                        }
+                    }
+                }
+                Arc::make_mut(&mut guard).push(document);
+            }
+            self.touch();
         }
-        {
-            let mut data = self.data.write();
-            data.push(document);
+        if append {
+            self.dirty.store(true, Ordering::Relaxed);
+        } else {
+            self.persist_on_write()?;
         }
         Ok(id)
     }
 
+    /// Añade documentos que ya tienen `_id` (p. ej. movidos desde otra colección) al final del
+    /// archivo, sincronizado en disco. Un `_id` que ya existía se reemplaza.
+    pub(crate) fn append_documents(&self, docs: &[Value]) -> io::Result<()> {
+        self.ensure_writable()?;
+        let mut lines = Vec::new();
+        for doc in docs {
+            writeln!(lines, "{}", self.encode_line(doc)?)?;
+        }
+        let replaced = {
+            let _writer = self.writer()?;
+            if !self.is_loaded() {
+                drop(self.data_write()?);
+            }
+            let storage = self.storage();
+            storage.append(&self.file_name, &lines)?;
+            storage.sync(&self.file_name)?;
+            self.io.append(lines.len());
+
+            let mut guard = self.data_write()?;
+            let data = Arc::make_mut(&mut guard);
+            let mut positions: HashMap<String, usize> = data.iter().enumerate()
+                .map(|(pos, doc)| (doc_id(doc).to_string(), pos))
+                .collect();
+            let mut replaced = false;
+            for doc in docs {
+                let id = doc_id(doc).to_string();
+                self.emit(ChangeOp::Insert, &id, Some(doc));
+                match positions.get(&id) {
+                    Some(&pos) => {
+                        data[pos] = doc.clone();
+                        replaced = true;
+                    }
+                    None => {
+                        positions.insert(id, data.len());
+                        data.push(doc.clone());
+                    }
+                }
+            }
+            self.apply_order(data);
+            self.rebuild_indexes(data);
+            self.touch();
+            replaced
+        };
+        self.dirty.store(true, Ordering::Relaxed);
+        if replaced {
+            // Sin las líneas repetidas en el archivo
+            self.persist()?;
+        }
+        Ok(())
+    }
+
+    /// Ejecuta `scan` sobre los datos y sus índices, pasando por la caché de resultados si
+    /// está activada
+    pub(crate) fn cached<F>(&self, key: &[&str], scan: F) -> io::Result<Vec<Value>>
+    where
+        F: FnOnce(&[Value], &[Index]) -> io::Result<Vec<Value>>,
+    {
+        self.cached_with(key, ReadConsistency::Latest, scan)
+    }
+
+    /// Como `cached`, leyendo con `consistency`. Una lectura `Snapshot` servida con la versión
+    /// anterior a una escritura en curso no pasa por la caché.
+    pub(crate) fn cached_with<F>(&self, key: &[&str], consistency: ReadConsistency, scan: F) -> io::Result<Vec<Value>>
+    where
+        F: FnOnce(&[Value], &[Index]) -> io::Result<Vec<Value>>,
+    {
+        let guard = match consistency {
+            ReadConsistency::Latest => self.data_read()?,
+            ReadConsistency::Snapshot => match self.data.try_read() {
+                Some(guard) if self.is_loaded() => guard,
+                _ => match self.snapshot_view() {
+                    Some((data, indexes)) => return scan(&data, &indexes),
+                    None => self.data_read()?,
+                },
+            },
+        };
+        let indexes = self.indexes.read().clone();
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => {
+                let data = guard.clone();
+                drop(guard);
+                return scan(&data, &indexes);
+            }
+        };
+        let key = ResultCache::key(key);
+        // La generación se lee con el lock tomado para que corresponda a esta versión
+        let lookup = cache.get(&key);
+        let data = guard.clone();
+        drop(guard);
+        match lookup {
+            Ok(hit) => Ok(hit.as_ref().clone()),
+            Err(generation) => {
+                let result = Arc::new(scan(&data, &indexes)?);
+                cache.put(key, generation, result.clone());
+                Ok(Arc::try_unwrap(result).unwrap_or_else(|shared| shared.as_ref().clone()))
+            }
+        }
+    }
+
+    /// Las consultas sin `Result` informan del error y devuelven una lista vacía
+    fn or_report(&self, result: io::Result<Vec<Value>>) -> Vec<Value> {
+        result.unwrap_or_else(|e| {
+            eprintln!("Ruggy Error: Query on '{}' failed: {}", self.name, e);
+            Vec::new()
+        })
+    }
+
+    /// Versión actual de los datos, sin bloquear a los escritores mientras se recorre
+    pub fn snapshot(&self) -> Arc<Vec<Value>> {
+        match self.data_read() {
+            Ok(guard) => guard.clone(),
+            Err(e) => {
+                eprintln!("Ruggy Error: Reload of '{}' failed: {}", self.name, e);
+                Arc::new(Vec::new())
+            }
+        }
+    }
+
+    /// Documentos e índices de la misma versión: los índices se leen con el lock de los datos
+    /// tomado, para que sus posiciones correspondan a esos documentos
+    pub(crate) fn data_view(&self) -> io::Result<DataView> {
+        let guard = self.data_read()?;
+        let indexes = self.indexes.read().clone();
+        Ok((guard.clone(), indexes))
+    }
+
+    /// Como `data_view` para las consultas sin `Result`: si no se puede recargar lo informa y
+    /// devuelve una colección vacía
+    fn view_or_report(&self) -> DataView {
+        self.data_view().unwrap_or_else(|e| {
+            eprintln!("Ruggy Error: Reload of '{}' failed: {}", self.name, e);
+            Default::default()
+        })
+    }
+
+    /// Contadores de la caché de resultados, si está activada
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache.as_ref().map(|cache| cache.stats())
+    }
+
+    pub(crate) fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate();
+        }
+    }
+
+    /// Marca una escritura: invalida la caché y actualiza la hora de la última escritura
+    pub(crate) fn touch(&self) {
+        self.invalidate_cache();
+        self.last_write_ms.store(now_millis(), Ordering::Relaxed);
+    }
+
+    /// Milisegundos desde la última escritura
+    pub fn idle_for(&self) -> Duration {
+        Duration::from_millis(now_millis().saturating_sub(self.last_write_ms.load(Ordering::Relaxed)))
+    }
+
     pub fn find_all(&self) -> Vec<Value> {
-        let data = self.data.read();
-        data.clone()
+        self.or_report(self.cached(&["all"], |data, _| Ok(data.to_vec())))
     }
 
+    /// Documentos con `field == value`. `field` puede ser una ruta a un campo anidado
+    /// (`address.city`), igual que en `find_with_operator`, `update_field` y los índices.
     pub fn find(&self, field: &str, value: &str) -> Vec<Value> {
-        let data = self.data.read();
-        data.iter()
-            .filter(|doc| {
-                match doc.get(field) {
-                    Some(Value::String(s)) => s == value,
-                    _ => false,
-                }
+        self.or_report(self.find_with(field, value, &QueryOptions::default()))
+    }
+
+    /// El documento con `_id == id`, sin recorrer el resto: con un índice sobre `_id` o
+    /// `DocumentOrder::ById` se busca directamente y si no se para en el primero que coincide
+    pub fn find_by_id(&self, id: &str) -> Option<Value> {
+        let (data, indexes) = self.view_or_report();
+        if let Ok(Some(index)) = index::choose(&indexes, "_id", true, &QueryOptions::default()) {
+            return index.lookup(id, false).first().map(|&pos| data[pos].clone());
+        }
+        if self.options.read().order == DocumentOrder::ById {
+            let pos = data.partition_point(|doc| doc_id(doc) < id);
+            return data.get(pos).filter(|doc| doc_id(doc) == id).cloned();
+        }
+        data.iter().find(|doc| doc_id(doc) == id).cloned()
+    }
+
+    /// Como `find`, saltando `skip` resultados y devolviendo como mucho `limit` (orden de inserción)
+    pub fn find_range(&self, field: &str, value: &str, skip: usize, limit: Option<usize>) -> Vec<Value> {
+        page::skip_limit(self.find(field, value), skip, limit)
+    }
+
+    /// Como `find`, con preferencias de índice
+    pub fn find_with(&self, field: &str, value: &str, options: &QueryOptions) -> io::Result<Vec<Value>> {
+        let normalization = self.options.read().normalization;
+        self.cached_with(&["find", field, value], options.consistency, |data, indexes| {
+            if let Some(index) = index::choose(indexes, field, true, options)? {
+                return Ok(index.lookup(value, false).into_iter().map(|pos| data[pos].clone()).collect());
+            }
+            let text = value;
+            let value = normalized(normalization.as_ref(), value);
+            let equals = |field_value: &Value| match field_value {
+                Value::String(s) => normalized(normalization.as_ref(), s) == value,
+                other => types::equals_text(other, text),
+            };
+            Ok(data.iter()
+                .filter(|doc| {
+                    match expr::field_value(doc, field) {
+                        // Un array coincide si alguno de sus elementos coincide
+                        Some(Value::Array(items)) => items.iter().any(equals),
+                        Some(other) => equals(other),
+                        None => false,
+                    }
+                })
+                .cloned()
+                .collect())
+        })
+    }
+
+    /// Búsqueda de texto completo con el índice `index`: documentos que contienen todos los
+    /// términos de `query`, los más relevantes primero
+    pub fn search(&self, index: &str, query: &str) -> io::Result<Vec<Value>> {
+        self.cached(&["search", index, query], |data, indexes| {
+            let text_index = text_index(indexes, index)?;
+            Ok(text_index.search(query).into_iter().map(|(pos, _)| data[pos].clone()).collect())
+        })
+    }
+
+    /// Como `search`, devolviendo también las posiciones de las coincidencias en el campo
+    /// indexado y un fragmento resaltado, para que la interfaz muestre por qué coincidió
+    pub fn search_highlighted(&self, index: &str, query: &str, highlight: &Highlight) -> io::Result<Vec<SearchHit>> {
+        let (data, indexes) = self.data_view()?;
+        let text_index = text_index(&indexes, index)?;
+        let analyzer = text_index.analyzer().cloned().unwrap_or_default();
+        let terms: HashSet<String> = analyzer.analyze(query).into_iter().map(|t| t.term).collect();
+
+        Ok(text_index.search(query).into_iter()
+            .map(|(pos, score)| {
+                let document = data[pos].clone();
+                let text = expr::field_value(&document, text_index.field()).and_then(|v| v.as_str()).unwrap_or("");
+                let matches: Vec<(usize, usize)> = analyzer.analyze(text).into_iter()
+                    .filter(|token| terms.contains(&token.term))
+                    .map(|token| (token.start, token.end))
+                    .collect();
+                let snippet = highlight.snippet(text, &matches);
+                SearchHit { document, score, matches, snippet }
             })
-            .cloned()
-            .collect()
+            .collect())
+    }
+
+    /// Hasta `limit` valores distintos de `field` que empiezan por `prefix`, sin distinguir
+    /// mayúsculas y en orden alfabético. Usa un índice de prefijos si hay uno sobre el campo.
+    pub fn autocomplete(&self, field: &str, prefix: &str, limit: usize) -> Vec<String> {
+        let (data, indexes) = self.view_or_report();
+        let value_at = |pos: usize| expr::field_value(&data[pos], field).and_then(|v| v.as_str()).unwrap_or("").to_string();
+        if let Some(index) = indexes.iter().find(|i| i.field() == field && matches!(i.kind(), IndexKind::Prefix)) {
+            return index.complete(prefix, limit).into_iter().map(value_at).collect();
+        }
+
+        let normalization = self.options.read().normalization;
+        let key = |s: &str| normalized(normalization.as_ref(), s).to_lowercase();
+        let prefix = key(prefix);
+        let mut matches: BTreeMap<String, &str> = BTreeMap::new();
+        for doc in data.iter() {
+            if let Some(Value::String(s)) = expr::field_value(doc, field) {
+                let k = key(s);
+                if k.starts_with(&prefix) {
+                    matches.entry(k).or_insert(s);
+                }
+            }
+        }
+        matches.into_values().take(limit).map(|s| s.to_string()).collect()
     }
 
     pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> Vec<Value> {
+        self.or_report(self.find_with_operator_options(field, value, operator, &QueryOptions::default()))
+    }
+
+    /// Como `find_with_operator`, con preferencias de índice y de consistencia de lectura
+    pub fn find_with_operator_options(&self, field: &str, value: &str, operator: &str, options: &QueryOptions) -> io::Result<Vec<Value>> {
+        let normalization = self.options.read().normalization;
+        self.cached_with(&["op", field, value, operator], options.consistency, |data, indexes| {
+            match index::choose(indexes, field, index::is_eq(operator), options)? {
+                Some(index) => Ok(index.lookup(value, true).into_iter().map(|pos| data[pos].clone()).collect()),
+                None => Self::scan_operator(data, field, value, operator, normalization.as_ref()),
+            }
+        })
+    }
+
+    pub(crate) fn scan_operator(data: &[Value], field: &str, value: &str, operator: &str, normalization: Option<&Normalization>) -> io::Result<Vec<Value>> {
+        let condition = OperatorMatch::new(value, operator, normalization)?;
+        Ok(data.iter().filter(|doc| condition.matches(doc, field)).cloned().collect())
+    }
+
+    /// Número de documentos. Con la colección descargada no la vuelve a leer.
+    pub fn count(&self) -> usize {
         let data = self.data.read();
-        data.iter()
-            .filter(|doc| {
-                match doc.get(field) {
-                    Some(Value::String(s)) => {
-                        match operator {
-                            "=" | "==" | "eq" => s == value,
-                            "like" | "LIKE" | "contains" => s.contains(value),
-                            "starts_with" => s.starts_with(value),
-                            "ends_with" => s.ends_with(value),
-                            _ => false,
-                        }
-                    },
-                    Some(Value::Number(n)) => {
-                        if operator == "=" || operator == "==" || operator == "eq" {
-                            n.to_string() == value
-                        } else {
-                            false
-                        }
-                    },
-                    _ => false,
-                }
-            })
-            .cloned()
-            .collect()
+        if self.is_loaded() { data.len() } else { self.unloaded_len.load(Ordering::Relaxed) }
+    }
+
+    /// Cuántos documentos devolvería `find_with_operator`, sin copiarlos. Las igualdades con
+    /// índice se cuentan en el índice; con la colección descargada, si el índice la cubre
+    /// (ver `create_covering_index`), sin recargarla.
+    pub fn count_where(&self, field: &str, value: &str, operator: &str) -> io::Result<usize> {
+        if let Some(count) = self.count_unloaded(field, value, operator) {
+            return Ok(count);
+        }
+        let data = self.data_read()?;
+        let indexes = self.indexes.read().clone();
+        if let Some(index) = index::choose(&indexes, field, index::is_eq(operator), &QueryOptions::default())? {
+            return Ok(index.count(value, true));
+        }
+        let normalization = self.options.read().normalization;
+        let condition = OperatorMatch::new(value, operator, normalization.as_ref())?;
+        Ok(data.iter().filter(|doc| condition.matches(doc, field)).count())
+    }
+
+    /// Si algún documento cumple `field operator value`; como `count_where`, pero sin índice
+    /// para en el primero
+    pub fn exists(&self, field: &str, value: &str, operator: &str) -> io::Result<bool> {
+        if let Some(count) = self.count_unloaded(field, value, operator) {
+            return Ok(count > 0);
+        }
+        let data = self.data_read()?;
+        let indexes = self.indexes.read().clone();
+        if let Some(index) = index::choose(&indexes, field, index::is_eq(operator), &QueryOptions::default())? {
+            return Ok(index.count(value, true) > 0);
+        }
+        let normalization = self.options.read().normalization;
+        let condition = OperatorMatch::new(value, operator, normalization.as_ref())?;
+        Ok(data.iter().any(|doc| condition.matches(doc, field)))
+    }
+
+    /// Igualdad contada con un índice que se conserva descargado; `None` si la colección está
+    /// cargada o no hay tal índice
+    fn count_unloaded(&self, field: &str, value: &str, operator: &str) -> Option<usize> {
+        let _data = self.data.read();
+        if self.is_loaded() || !index::is_eq(operator) {
+            return None;
+        }
+        let indexes = self.indexes.read().clone();
+        let index = indexes.iter().find(|index| index.field() == field && index.is_covering())?;
+        Some(index.count(value, true))
     }
 
-    pub fn update_field(&self, id: &str, field: &str, value: Value) -> io::Result<bool> {
-        let mut data = self.data.write();
+    /// Cambia un campo del documento `id`. Con una ruta (`profile.settings.theme`) se crean los
+    /// objetos intermedios que falten.
+    pub fn update_field(&self, id: &str, field: &str, mut value: Value) -> io::Result<bool> {
+        self.ensure_writable()?;
+        check_reserved_field(field)?;
+        types::canonicalize(&mut value)?;
+        let mut guard = self.data_write()?;
+        let data = Arc::make_mut(&mut guard);
         let mut updated = false;
 
         for doc in data.iter_mut() {
             if let Some(doc_id) = doc.get("_id").and_then(|v| v.as_str()) {
                 if doc_id == id {
                     if let Some(obj) = doc.as_object_mut() {
-                        obj.insert(field.to_string(), value);
+                        // El documento resultante se valida antes de cambiar nada
+                        let mut candidate = obj.clone();
+                        expr::set_field(&mut candidate, field, value.clone())?;
+                        let candidate = Value::Object(candidate);
+                        limits::check_depth(&candidate)?;
+                        self.check_rules(&candidate)?;
+                        limits::check_size(self.encode_line(&candidate)?.len())?;
+                        expr::set_field(obj, field, value)?;
+                        self.emit(ChangeOp::Update, id, Some(doc));
                         updated = true;
                         break;
                     }
@@ -135,8 +848,10 @@ impl Collection {
         }
 
         if updated {
-            drop(data);
-            self.persist()?;
+            self.rebuild_indexes(data);
+            self.touch();
+            drop(guard);
+            self.persist_on_write()?;
             Ok(true)
         } else {
             Ok(false)
@@ -144,7 +859,9 @@ impl Collection {
     }
 
     pub fn delete_by_id(&self, id: &str) -> io::Result<bool> {
-        let mut data = self.data.write();
+        self.ensure_writable()?;
+        let mut guard = self.data_write()?;
+        let data = Arc::make_mut(&mut guard);
         let mut index_to_remove = None;
 
         for (i, doc) in data.iter().enumerate() {
@@ -158,32 +875,604 @@ impl Collection {
 
         if let Some(index) = index_to_remove {
             data.remove(index);
-            drop(data);
-            self.persist()?;
+            self.emit(ChangeOp::Delete, id, None);
+            self.rebuild_indexes(data);
+            self.touch();
+            drop(guard);
+            self.persist_on_write()?;
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
+    /// Elimina los documentos cuyo campo TTL (epoch en milisegundos) es más antiguo que `max_age`.
+    /// Los documentos sin el campo no expiran.
+    pub fn expire_now(&self) -> io::Result<usize> {
+        self.ensure_writable()?;
+        let ttl = match self.options.read().ttl.clone() {
+            Some(ttl) => ttl,
+            None => return Ok(0),
+        };
+        let cutoff = now_millis().saturating_sub(ttl.max_age.as_millis() as u64);
+
+        let is_expired = |doc: &Value| doc.get(&ttl.field)
+            .and_then(|v| v.as_u64())
+            .is_some_and(|ts| ts < cutoff);
+
+        let mut guard = self.data_write()?;
+        if !guard.iter().any(is_expired) {
+            return Ok(0);
+        }
+        let data = Arc::make_mut(&mut guard);
+        let (expired, kept): (Vec<Value>, Vec<Value>) = std::mem::take(data).into_iter().partition(is_expired);
+        *data = kept;
+        for doc in &expired {
+            self.emit(ChangeOp::Delete, doc_id(doc), None);
+        }
+        self.rebuild_indexes(data);
+
+        self.touch();
+        drop(guard);
+        self.persist_on_write()?;
+        // Sin locks: el callback puede volver a usar la colección
+        let callback = self.expiry_callback.read().clone();
+        if let Some(callback) = callback {
+            callback(&expired);
+        }
+        Ok(expired.len())
+    }
+
+    /// Registra la función que recibe los documentos eliminados por expiración TTL, una vez
+    /// guardado el cambio y sin locks tomados. Con `None` se elimina.
+    pub fn set_expiry_callback(&self, callback: Option<ExpiryCallback>) {
+        *self.expiry_callback.write() = callback.map(Arc::new);
+    }
+
+    /// Archivo comprimido con los documentos fríos de esta colección
+    pub fn archive(&self) -> Archive {
+        Archive::new(self.storage(), storage::sibling(&self.file_name, "archive.gz"), self.options.clone())
+    }
+
+    /// Mueve al archivo los documentos que cumplen la política configurada en las opciones
+    pub fn archive_now(&self) -> io::Result<usize> {
+        match self.options.read().archive.clone() {
+            Some(policy) => self.archive_where(&policy),
+            None => Ok(0),
+        }
+    }
+
+    /// Mueve al archivo comprimido los documentos que cumplen `policy`. Primero se escriben
+    /// (y sincronizan) en el archivo y después se quitan de la colección, que se guarda según
+    /// `CollectionOptions::persist_strategy`.
+    pub fn archive_where(&self, policy: &ArchivePolicy) -> io::Result<usize> {
+        self.ensure_writable()?;
+        let is_cold: Box<dyn Fn(&Value) -> bool> = match policy {
+            ArchivePolicy::OlderThan { field, age } => {
+                let cutoff = now_millis() as i64 - age.as_millis() as i64;
+                let field = field.clone();
+                Box::new(move |doc: &Value| {
+                    doc.get(&field)
+                        .and_then(datetime::value_to_millis)
+                        .is_some_and(|ms| ms < cutoff)
+                })
+            }
+            ArchivePolicy::Matching(filter) => {
+                let filter = filter.clone();
+                Box::new(move |doc: &Value| filter(doc))
+            }
+        };
+
+        // El archivo se escribe sin bloquear a los lectores; luego solo se quitan los
+        // documentos que no cambiaron mientras tanto
+        let current = self.data_read()?.clone();
+        let cold: Vec<Value> = current.iter().filter(|doc| is_cold(doc)).cloned().collect();
+        if cold.is_empty() {
+            return Ok(0);
+        }
+        self.archive().append(&cold)?;
+        let archived: HashMap<&str, &Value> = cold.iter().map(|doc| (doc_id(doc), doc)).collect();
+
+        let mut guard = self.data_write()?;
+        let mut removed = Vec::new();
+        Arc::make_mut(&mut guard).retain(|doc| {
+            if archived.get(doc_id(doc)) == Some(&doc) {
+                removed.push(doc_id(doc).to_string());
+                return false;
+            }
+            true
+        });
+        for id in &removed {
+            self.emit(ChangeOp::Delete, id, None);
+        }
+        self.rebuild_indexes(&guard);
+        self.touch();
+        drop(guard);
+        self.persist_on_write()?;
+        Ok(removed.len())
+    }
+
+    /// Reescribe el archivo si hubo inserts desde la última reescritura o quedan escrituras sin
+    /// guardar, sin parar las lecturas ni las escrituras mientras tanto (ver `compaction`). Con
+    /// `PersistStrategy::Manual` no guarda escrituras pendientes: eso solo lo hace
+    /// `persist_now`. Devuelve `true` si se reescribió.
+    pub fn compact(&self) -> io::Result<bool> {
+        self.ensure_writable()?;
+        let unsaved = self.has_unsaved_changes();
+        if unsaved && self.options.read().persist_strategy == PersistStrategy::Manual {
+            return Ok(false);
+        }
+        if !unsaved && !self.dirty.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+        let storage = self.storage();
+        let bytes_before = storage.len(&self.file_name)?.unwrap_or(0);
+        if !self.rewrite_online()? {
+            return Ok(false);
+        }
+        self.io.compaction(CompactionRecord {
+            at: now_millis(),
+            bytes_before,
+            bytes_after: storage.len(&self.file_name)?.unwrap_or(0),
+            documents: self.data.read().len(),
+        });
+        Ok(true)
+    }
+
+    /// Bytes escritos frente a bytes cambiados, reescrituras y compactaciones desde que se abrió
+    /// la colección o desde el último `reset_io_stats`
+    pub fn io_stats(&self) -> IoStats {
+        self.io.snapshot()
+    }
+
+    pub fn reset_io_stats(&self) {
+        self.io.reset();
+    }
+
+    /// Copia el archivo de la colección a `dest` sin que se escriba mientras tanto
+    pub fn backup_to(&self, dest: &Path) -> io::Result<()> {
+        let _writer = self.writer()?;
+        let bytes = storage::read_all(self.storage().as_ref(), &self.file_name)?.unwrap_or_default();
+        fs::write(dest, bytes)
+    }
+
+    /// Recalcula las estadísticas por campo (distintos, min/max, histograma) y las guarda
+    pub fn analyze(&self) -> CollectionStats {
+        let stats = CollectionStats::compute(&self.snapshot());
+        *self.stats.write() = Some(stats.clone());
+        stats
+    }
+
+    /// Estadísticas del último `analyze()`, si se ha llamado
+    pub fn stats(&self) -> Option<CollectionStats> {
+        self.stats.read().clone()
+    }
+
+    /// Memoria estimada de documentos, índices, caché y estadísticas, para decidir qué descargar
+    pub fn memory_usage(&self) -> MemoryUsage {
+        // Sin recargar: una colección descargada no ocupa memoria de documentos
+        let documents = values_size(&self.data.read());
+        let indexes = self.indexes.read().iter().map(|index| index.memory_usage()).sum();
+        let cache = self.cache.as_ref().map_or(0, |cache| cache.memory_usage());
+        let stats = self.stats.read().as_ref().map_or(0, |stats| stats.memory_usage());
+        MemoryUsage::new(documents, indexes, cache, stats)
+    }
+
+    /// Cambia el cifrador de los campos cifrados y reescribe el archivo con la nueva clave,
+    /// y también el de documentos fríos (`archive`). Devuelve `false` si no había nada cifrado
+    /// con `old` (p. ej. ya estaba rotada).
+    pub fn rotate_cipher(&self, old: &dyn FieldCipher, new: Arc<dyn FieldCipher>) -> io::Result<bool> {
+        self.ensure_writable()?;
+        // Con el writer tomado nadie añade líneas con la clave nueva antes de reescribir el archivo
+        let _writer = self.writer()?;
+        // Si está descargada, se lee todavía con la clave anterior
+        drop(self.data_read()?);
+        let previous = {
+            let mut options = self.options.write();
+            match options.encryption.as_mut() {
+                Some(enc) if enc.cipher.key_id() == old.key_id() => Some(std::mem::replace(&mut enc.cipher, new.clone())),
+                Some(_) => None,
+                None => return Ok(false),
+            }
+        };
+        let rotated = previous.is_some();
+        // Si no se ha podido reescribir, el archivo sigue con la clave anterior
+        if let Some(previous) = previous {
+            if let Err(e) = self.persist_locked() {
+                if let Some(enc) = self.options.write().encryption.as_mut() {
+                    enc.cipher = previous;
+                }
+                return Err(e);
+            }
+        }
+        // Los documentos fríos, aunque la colección ya estuviera rotada: si una llamada anterior
+        // se interrumpió aquí, esta la termina
+        let archive = storage::sibling(&self.file_name, "archive.gz");
+        let archived = encryption::rotate_archive(self.storage().as_ref(), &archive, old, new.as_ref())?;
+        Ok(rotated || archived)
+    }
+
+    /// Sustituye todos los documentos por `docs` y reescribe el archivo (emite un borrado por
+    /// cada documento anterior y un insert por cada nuevo)
+    pub(crate) fn replace_documents(&self, docs: Vec<Value>) -> io::Result<()> {
+        self.ensure_writable()?;
+        let _writer = self.writer()?;
+        let mut guard = self.data_write()?;
+        for doc in guard.iter() {
+            self.emit(ChangeOp::Delete, doc_id(doc), None);
+        }
+        for doc in &docs {
+            self.emit(ChangeOp::Insert, doc_id(doc), Some(doc));
+        }
+        self.rebuild_indexes(&docs);
+        *guard = Arc::new(docs);
+        self.touch();
+        // La reescritura, con el writer aún tomado pero sin bloquear a los lectores
+        let data = guard.clone();
+        drop(guard);
+        let result = self.rewrite(&data);
+        self.unsaved.store(result.is_err(), Ordering::Release);
+        result
+    }
+
     pub fn persist(&self) -> io::Result<()> {
-        use std::io::{Seek, SeekFrom};
-        let data = self.data.read();
-        let mut writer = self.writer.lock();
-        
-        // Limpiar el buffer actual y truncar el archivo usando el mismo handle
-        writer.flush()?;
-        let file = writer.get_mut();
-        file.set_len(0)?;
-        file.seek(SeekFrom::Start(0))?;
-        
-        // Re-escribir todos los documentos
+        // Primero el writer y después la copia de los datos: dos persists seguidos escriben
+        // siempre en el orden de sus versiones, y la IO no bloquea a nadie que lea o escriba datos
+        let _writer = self.writer()?;
+        self.persist_locked()
+    }
+
+    /// `persist` con el writer ya tomado
+    fn persist_locked(&self) -> io::Result<()> {
+        // Antes de copiar los datos: una escritura posterior a la copia vuelve a marcarlo
+        let unsaved = self.unsaved.swap(false, Ordering::AcqRel);
+        // Si la recarga falla no se puede reescribir el archivo con datos vacíos
+        let data = match self.data_read() {
+            Ok(data) => data.clone(),
+            Err(e) => {
+                self.unsaved.fetch_or(unsaved, Ordering::AcqRel);
+                return Err(e);
+            }
+        };
+        let result = self.rewrite(&data);
+        if result.is_err() {
+            self.unsaved.fetch_or(unsaved, Ordering::AcqRel);
+        }
+        result
+    }
+
+    /// Guarda ya los cambios pendientes, sea cual sea la estrategia de persistencia
+    pub fn persist_now(&self) -> io::Result<()> {
+        self.persist()
+    }
+
+    /// Hay escrituras en memoria que aún no están en el archivo (ver `PersistStrategy`)
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.unsaved.load(Ordering::Acquire)
+    }
+
+    /// Guarda una escritura según la estrategia de persistencia de la colección
+    pub(crate) fn persist_on_write(&self) -> io::Result<()> {
+        let strategy = self.options.read().persist_strategy;
+        match strategy {
+            PersistStrategy::OnEveryWrite => self.persist(),
+            PersistStrategy::Manual => {
+                self.unsaved.store(true, Ordering::Release);
+                Ok(())
+            }
+            PersistStrategy::Debounced(_) => {
+                self.unsaved.store(true, Ordering::Release);
+                self.flush_if_due().map(|_| ())
+            }
+        }
+    }
+
+    /// Con `PersistStrategy::Debounced`, guarda los cambios pendientes si ya pasó el intervalo
+    /// desde el último guardado. Devuelve `true` si se guardó.
+    pub fn flush_if_due(&self) -> io::Result<bool> {
+        let interval = match self.options.read().persist_strategy {
+            PersistStrategy::Debounced(interval) => interval,
+            _ => return Ok(false),
+        };
+        let since = now_millis().saturating_sub(self.last_persist_ms.load(Ordering::Relaxed));
+        if !self.has_unsaved_changes() || since < interval.as_millis() as u64 {
+            return Ok(false);
+        }
+        self.persist()?;
+        Ok(true)
+    }
+
+    /// Contenido del archivo con `data`, como lo escribe `rewrite`
+    pub(crate) fn encode_file(&self, data: &[Value]) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut bodies = self.options.read().dedupe.then(dedupe::Bodies::default);
         for doc in data.iter() {
-            let json_line = serde_json::to_string(doc)?;
-            writeln!(writer, "{}", json_line)?;
+            let json_line = match bodies.as_mut().and_then(|bodies| bodies.reference(doc)) {
+                Some(reference) => reference,
+                None => self.encode_line(doc)?,
+            };
+            writeln!(bytes, "{}", json_line)?;
         }
-        writer.flush()?;
+        Ok(bytes)
+    }
+
+    /// Reescribe el archivo con `data`. Llamar con el writer tomado.
+    fn rewrite(&self, data: &[Value]) -> io::Result<()> {
+        let bytes = self.encode_file(data)?;
+
+        // Época impar durante la reescritura para que los seguidores no lean a medias
+        let storage = self.storage();
+        let epoch = self.epoch.load(Ordering::Relaxed);
+        let epoch = if epoch.is_multiple_of(2) { epoch + 1 } else { epoch + 2 };
+        replica::write_epoch(storage.as_ref(), &self.file_name, epoch)?;
+        storage.replace(&self.file_name, &bytes)?;
+        replica::write_epoch(storage.as_ref(), &self.file_name, epoch + 1)?;
+        self.io.rewrite(bytes.len() + epoch.to_string().len() + (epoch + 1).to_string().len());
+        self.epoch.store(epoch + 1, Ordering::Relaxed);
+        self.dirty.store(false, Ordering::Relaxed);
+        self.last_persist_ms.store(now_millis(), Ordering::Relaxed);
         
         Ok(())
     }
 }
+
+impl Drop for Collection {
+    fn drop(&mut self) {
+        let debounced = matches!(self.options.read().persist_strategy, PersistStrategy::Debounced(_));
+        if debounced && self.has_unsaved_changes() {
+            if let Err(e) = self.persist() {
+                eprintln!("Ruggy Error: Saving '{}' on close failed: {}", self.name, e);
+            }
+        }
+    }
+}
+
+/// Comparación de `find_with_operator`, preparada una vez por consulta: el valor normalizado
+/// (en minúsculas para `ieq` e `ilike`), con `regex` la expresión ya compilada y con `in` los
+/// valores de la lista. Un campo array cumple si lo cumple alguno de sus elementos.
+struct OperatorMatch<'a> {
+    value: Cow<'a, str>,
+    operator: &'a str,
+    regex: Option<Regex>,
+    /// Valores de `in` como texto normalizado
+    list: Vec<String>,
+    normalization: Option<&'a Normalization>,
+}
+
+impl<'a> OperatorMatch<'a> {
+    fn new(value: &'a str, operator: &'a str, normalization: Option<&'a Normalization>) -> io::Result<Self> {
+        let regex = match operator {
+            "regex" => Some(Regex::new(value).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid regex '{}': {}", value, e))
+            })?),
+            _ => None,
+        };
+        let list = match operator {
+            "in" => Self::parse_list(value)?.iter().map(|item| normalized(normalization, item).into_owned()).collect(),
+            _ => Vec::new(),
+        };
+        let value = match operator {
+            "ieq" | "ilike" => Cow::Owned(normalized(normalization, value).to_lowercase()),
+            _ => normalized(normalization, value),
+        };
+        Ok(Self { value, operator, regex, list, normalization })
+    }
+
+    /// Valores de `in`: un array JSON de strings o números (`["rust", "go"]`)
+    fn parse_list(value: &str) -> io::Result<Vec<String>> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("'in' needs a JSON array of strings or numbers: {}", value));
+        let Ok(Value::Array(items)) = serde_json::from_str(value) else { return Err(invalid()); };
+        items.into_iter()
+            .map(|item| match item {
+                Value::String(s) => Ok(s),
+                Value::Number(n) => Ok(n.to_string()),
+                _ => Err(invalid()),
+            })
+            .collect()
+    }
+
+    fn matches(&self, doc: &Value, field: &str) -> bool {
+        match expr::field_value(doc, field) {
+            Some(Value::Array(items)) => items.iter().any(|item| self.matches_value(item)),
+            Some(value) => self.matches_value(value),
+            None => false,
+        }
+    }
+
+    fn matches_value(&self, field_value: &Value) -> bool {
+        let (value, operator) = (self.value.as_ref(), self.operator);
+        if operator == "in" {
+            return self.list.iter().any(|item| self.equals(field_value, item));
+        }
+        match field_value {
+            // La expresión se aplica al texto sin normalizar; `(?i)` no distingue mayúsculas
+            Value::String(s) if operator == "regex" => self.regex.as_ref().is_some_and(|regex| regex.is_match(s)),
+            Value::String(s) => {
+                let s = normalized(self.normalization, s);
+                match operator {
+                    "=" | "==" | "eq" => s == value,
+                    "like" | "LIKE" | "contains" => s.contains(value),
+                    "starts_with" => s.starts_with(value),
+                    "ends_with" => s.ends_with(value),
+                    "ieq" => s.to_lowercase() == value,
+                    "ilike" => s.to_lowercase().contains(value),
+                    _ => false,
+                }
+            },
+            other if index::is_eq(operator) => self.equals(other, value),
+            other if operator == "starts_with" => types::bytes_prefix(other, value),
+            _ => false,
+        }
+    }
+
+    /// Igualdad de `=` con un texto ya normalizado
+    fn equals(&self, field_value: &Value, text: &str) -> bool {
+        match field_value {
+            Value::String(s) => normalized(self.normalization, s) == text,
+            Value::Number(n) => n.to_string() == text,
+            other => types::equals_text(other, text),
+        }
+    }
+}
+
+/// Lee documentos línea a línea según `options.load_mode` y `options.duplicate_ids`. Con
+/// `complete_only` se ignora una última línea sin `\n` (todavía a medio escribir); `offset` es
+/// la posición de `reader` en el archivo, para ubicar los registros corruptos. Devuelve los
+/// documentos, los bytes consumidos y el resumen de la lectura.
+pub(crate) fn read_records<R: BufRead>(reader: R, options: &CollectionOptions, offset: u64, complete_only: bool) -> io::Result<(Vec<Value>, u64, LoadReport)> {
+    read_records_with(reader, options, offset, complete_only, |line| serde_json::from_str(line))
+}
+
+/// Línea leída del archivo, antes de resolver las referencias de `dedupe`
+enum Record {
+    Blank,
+    Document(Value),
+    Corrupt(String),
+}
+
+/// Interpreta una línea: parser, descompresión y descifrado. No depende de las demás líneas,
+/// así que se puede hacer en paralelo.
+fn parse_record<P>(line: &[u8], parse: &P, options: &CollectionOptions) -> io::Result<Record>
+where
+    P: Fn(&str) -> serde_json::Result<Value>,
+{
+    let line = match std::str::from_utf8(line) {
+        Ok(line) if line.trim().is_empty() => return Ok(Record::Blank),
+        Ok(line) => line,
+        Err(e) => return Ok(Record::Corrupt(e.to_string())),
+    };
+    let mut value = match parse(line) {
+        Ok(value) => value,
+        Err(e) => return Ok(Record::Corrupt(e.to_string())),
+    };
+    if options.compress_above.is_some() {
+        if let Err(e) = compression::expand(&mut value) {
+            return Ok(Record::Corrupt(e.to_string()));
+        }
+    }
+    // Las referencias toman el contenido ya descifrado de su destino
+    let reference = options.dedupe && dedupe::reference_target(&value).is_some();
+    if let (false, Some(enc)) = (reference, options.encryption.as_ref()) {
+        enc.open(&mut value)?;
+    }
+    Ok(Record::Document(value))
+}
+
+/// `read_records` con otro parser para cada línea (p. ej. uno que solo extrae algunos campos).
+/// Los archivos grandes (`load::PARALLEL_BYTES`) se interpretan en paralelo por trozos.
+pub(crate) fn read_records_with<R, P>(mut reader: R, options: &CollectionOptions, offset: u64, complete_only: bool, parse: P) -> io::Result<(Vec<Value>, u64, LoadReport)>
+where
+    R: BufRead,
+    P: Fn(&str) -> serde_json::Result<Value> + Sync,
+{
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if complete_only {
+        // Sin la última línea si no tiene `\n` (todavía a medio escribir)
+        bytes.truncate(bytes.iter().rposition(|&b| b == b'\n').map_or(0, |end| end + 1));
+    }
+    let consumed = bytes.len() as u64;
+    // Cada línea con su `\n` y su posición en `bytes`
+    let mut start = 0;
+    let lines: Vec<(usize, &[u8])> = bytes.split_inclusive(|&b| b == b'\n')
+        .map(|line| {
+            start += line.len();
+            (start - line.len(), line)
+        })
+        .collect();
+    let records = if bytes.len() >= load::PARALLEL_BYTES {
+        load::parallel_map(&lines, |(_, line)| parse_record(line, &parse, options))
+    } else {
+        lines.iter().map(|(_, line)| parse_record(line, &parse, options)).collect()
+    };
+
+    let mut data = Vec::new();
+    let mut line_numbers = Vec::new();
+    // Última posición de cada `_id`, para resolver las referencias de `dedupe`
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut report = LoadReport::default();
+    for (i, ((start, _), record)) in lines.iter().zip(records).enumerate() {
+        let (line_number, start) = (i + 1, offset + *start as u64);
+        let mut value = match record? {
+            Record::Blank => continue,
+            Record::Document(value) => value,
+            Record::Corrupt(e) => {
+                report.skip(line_number, start, e);
+                continue;
+            }
+        };
+        if let Some(target) = options.dedupe.then(|| dedupe::reference_target(&value)).flatten() {
+            match positions.get(target) {
+                Some(&pos) => value = dedupe::resolve(&value, &data[pos]),
+                None => {
+                    report.skip(line_number, start, format!("Reference to unknown _id '{}'", target));
+                    continue;
+                }
+            }
+        }
+        positions.insert(doc_id(&value).to_string(), data.len());
+        data.push(value);
+        line_numbers.push(line_number);
+    }
+    if options.load_mode == LoadMode::Strict && !report.corrupt.is_empty() {
+        return Err(report.corruption_error());
+    }
+    load::resolve_duplicates(&mut data, &line_numbers, options.duplicate_ids, &mut report)?;
+    sort_documents(&mut data, options.order);
+    report.loaded = data.len();
+    Ok((data, consumed, report))
+}
+
+pub(crate) fn text_index<'a>(indexes: &'a [Index], name: &str) -> io::Result<&'a Index> {
+    match indexes.iter().find(|i| i.name() == name) {
+        Some(index) if index.analyzer().is_some() => Ok(index),
+        Some(_) => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Index '{}' is not a text index", name))),
+        None => Err(io::Error::new(io::ErrorKind::NotFound, format!("Unknown index '{}'", name))),
+    }
+}
+
+/// Índices de `options` sobre `data`, cada uno en su hilo si hay muchos documentos
+fn build_indexes(options: &CollectionOptions, data: &[Value]) -> Vec<Index> {
+    let build = |spec: &IndexSpec| Index::build(spec.clone(), options.normalization, Arc::default(), data);
+    if data.len() >= load::PARALLEL_DOCS {
+        load::parallel_map(&options.indexes, build)
+    } else {
+        options.indexes.iter().map(build).collect()
+    }
+}
+
+/// Con `DocumentOrder::ById` ordena por `_id` (estable); con `Insertion` no hace nada
+pub(crate) fn sort_documents(data: &mut [Value], order: DocumentOrder) {
+    if order == DocumentOrder::ById {
+        data.sort_by(|a, b| doc_id(a).cmp(doc_id(b)));
+    }
+}
+
+/// Claves de primer nivel que el archivo usa para sus líneas especiales. Un documento con una
+/// de ellas se leería como esa línea al recargar.
+const RESERVED_KEYS: &[&str] = &[dedupe::SAME_AS, compression::COMPRESSED];
+
+/// Error si `field` (o el primer tramo de una ruta) es una clave reservada
+pub(crate) fn check_reserved_field(field: &str) -> io::Result<()> {
+    let top = field.split('.').next().unwrap_or(field);
+    match RESERVED_KEYS.contains(&top) {
+        true => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Field '{}' is reserved", top))),
+        false => Ok(()),
+    }
+}
+
+/// Error si el documento tiene alguna clave reservada en el primer nivel
+pub(crate) fn check_reserved(doc: &Value) -> io::Result<()> {
+    doc.as_object().map_or(Ok(()), |obj| obj.keys().try_for_each(|key| check_reserved_field(key)))
+}
+
+pub(crate) fn doc_id(doc: &Value) -> &str {
+    doc.get("_id").and_then(|v| v.as_str()).unwrap_or("")
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
@@ -0,0 +1,92 @@
+//! Compactación sin parar la colección (`Collection::compact`). Los documentos se serializan y
+//! se escriben en `<nombre>.compact` sin ningún lock, con lecturas y escrituras en curso; solo
+//! al final, con el writer tomado, se añaden al archivo nuevo las líneas que llegaron
+//! entretanto y se sustituye el archivo con `StorageBackend::rename`.
+//!
+//! Si mientras tanto alguien reescribió el archivo entero (update, delete, persist), esa
+//! reescritura ya lo dejó compacto y el archivo nuevo se descarta.
+
+use std::io::{self, Read};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use serde_json::Value;
+use crate::collection::{now_millis, Collection};
+use crate::replica;
+use crate::storage::{self, StorageBackend};
+
+/// Tamaño de cada escritura del archivo nuevo, para no acaparar el backend
+const CHUNK: usize = 1 << 20;
+
+impl Collection {
+    /// Reescribe el archivo con los documentos actuales bloqueando las escrituras solo para
+    /// copiar las líneas nuevas y cambiar de archivo. `false` si otra reescritura se adelantó.
+    pub(crate) fn rewrite_online(&self) -> io::Result<bool> {
+        let storage = self.storage();
+        let tmp = storage::sibling(&self.file_name, "compact");
+
+        // Documentos y tamaño del archivo en el mismo instante: lo que se añada después del
+        // tamaño son escrituras posteriores a los documentos
+        let (data, start, epoch, unsaved) = {
+            let _writer = self.writer()?;
+            let unsaved = self.unsaved.swap(false, Ordering::AcqRel);
+            let snapshot = self.data_read()
+                .map(|data| data.clone())
+                .and_then(|data| Ok((data, storage.len(&self.file_name)?.unwrap_or(0))));
+            match snapshot {
+                Ok((data, start)) => (data, start, self.epoch.load(Ordering::Relaxed), unsaved),
+                Err(e) => {
+                    self.unsaved.fetch_or(unsaved, Ordering::AcqRel);
+                    return Err(e);
+                }
+            }
+        };
+        let result = self.swap_compacted(&storage, &tmp, &data, start, epoch);
+        if result.is_err() {
+            self.unsaved.fetch_or(unsaved, Ordering::AcqRel);
+            let _ = storage.remove(&tmp);
+        }
+        result
+    }
+
+    fn swap_compacted(
+        &self,
+        storage: &Arc<dyn StorageBackend>,
+        tmp: &str,
+        data: &[Value],
+        start: u64,
+        epoch: u64,
+    ) -> io::Result<bool> {
+        let bytes = self.encode_file(data)?;
+        storage.remove(tmp)?;
+        for chunk in bytes.chunks(CHUNK) {
+            storage.append(tmp, chunk)?;
+        }
+
+        let _writer = self.writer()?;
+        let replaced = !Arc::ptr_eq(storage, &self.storage())
+            || self.epoch.load(Ordering::Relaxed) != epoch
+            || storage.len(&self.file_name)?.unwrap_or(0) < start;
+        if replaced {
+            storage.remove(tmp)?;
+            return Ok(false);
+        }
+        let mut tail = Vec::new();
+        if let Some(mut reader) = storage.open(&self.file_name, start)? {
+            reader.read_to_end(&mut tail)?;
+        }
+        storage.append(tmp, &tail)?;
+        storage.sync(tmp)?;
+
+        // Época impar durante el cambio, como en `rewrite`
+        let epoch = if epoch.is_multiple_of(2) { epoch + 1 } else { epoch + 2 };
+        replica::write_epoch(storage.as_ref(), &self.file_name, epoch)?;
+        storage.rename(tmp, &self.file_name)?;
+        replica::write_epoch(storage.as_ref(), &self.file_name, epoch + 1)?;
+        self.io.rewrite(bytes.len() + tail.len() + epoch.to_string().len() + (epoch + 1).to_string().len());
+        self.epoch.store(epoch + 1, Ordering::Relaxed);
+        // Las líneas copiadas siguen siendo inserts añadidos al final
+        self.dirty.store(!tail.is_empty(), Ordering::Relaxed);
+        self.last_persist_ms.store(now_millis(), Ordering::Relaxed);
+        Ok(true)
+    }
+}
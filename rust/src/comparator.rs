@@ -0,0 +1,240 @@
+//! Comparadores con nombre para los `sortBy` de ventanas y páginas. Se indican detrás del
+//! campo con `:` (`"-version:semver"`, `"file:natural"`). Vienen incluidos `natural` (los
+//! números dentro del texto se comparan por valor: `file2` < `file10`) y `semver`; se pueden
+//! añadir otros con `register_comparator`.
+//!
+//! Los comparadores solo ven valores presentes y no nulos: los ausentes y `null` van siempre
+//! primero, como en el orden normal.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde_json::Value;
+use crate::expr::get_path;
+use crate::window::sort_order;
+
+pub type CompareFn = Arc<dyn Fn(&Value, &Value) -> Ordering + Send + Sync>;
+
+static COMPARATORS: RwLock<Option<HashMap<String, CompareFn>>> = parking_lot::const_rwlock(None);
+
+/// Registra (o sustituye) el comparador `name` para todo el proceso
+pub fn register_comparator<F>(name: &str, compare: F) -> io::Result<()>
+where
+    F: Fn(&Value, &Value) -> Ordering + Send + Sync + 'static,
+{
+    if name.is_empty() || name.contains(':') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid comparator name '{}'", name)));
+    }
+    COMPARATORS.write().get_or_insert_with(HashMap::new).insert(name.to_string(), Arc::new(compare));
+    Ok(())
+}
+
+/// Comparador `name`: primero los registrados y después los incluidos
+pub fn comparator(name: &str) -> Option<CompareFn> {
+    if let Some(compare) = COMPARATORS.read().as_ref().and_then(|all| all.get(name)) {
+        return Some(compare.clone());
+    }
+    match name {
+        "natural" => Some(Arc::new(natural)),
+        "semver" => Some(Arc::new(semver)),
+        _ => None,
+    }
+}
+
+/// Comparador resuelto junto con su nombre
+#[derive(Clone)]
+struct Named {
+    name: String,
+    compare: CompareFn,
+}
+
+impl fmt::Debug for Named {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl PartialEq for Named {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// Un criterio de orden: campo, sentido y comparador opcional
+#[derive(Clone, Debug, PartialEq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+    comparator: Option<Named>,
+}
+
+impl SortKey {
+    pub fn new(field: &str, descending: bool) -> Self {
+        Self { field: field.to_string(), descending, comparator: None }
+    }
+
+    /// Usa el comparador registrado `name`; error si no existe
+    pub fn using(mut self, name: &str) -> io::Result<Self> {
+        let compare = comparator(name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown comparator '{}'", name)))?;
+        self.comparator = Some(Named { name: name.to_string(), compare });
+        Ok(self)
+    }
+
+    /// `campo`, `-campo` (descendente) y cualquiera de los dos con `:comparador`
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        let (field, descending) = match spec.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (spec, false),
+        };
+        match field.split_once(':') {
+            Some((field, name)) => Self::new(field, descending).using(name),
+            None => Ok(Self::new(field, descending)),
+        }
+    }
+
+    /// Lista JSON de especificaciones como las de `parse`: `["-score", "name:natural"]`
+    pub fn parse_list(spec: &Value) -> io::Result<Vec<Self>> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+        let keys = spec.as_array().ok_or_else(|| invalid("'sortBy' must be an array"))?;
+        keys.iter()
+            .map(|key| Self::parse(key.as_str().ok_or_else(|| invalid("Sort keys must be strings"))?))
+            .collect()
+    }
+
+    pub fn comparator_name(&self) -> Option<&str> {
+        self.comparator.as_ref().map(|named| named.name.as_str())
+    }
+
+    /// Compara los valores del campo, ya con el sentido aplicado
+    pub(crate) fn compare_values(&self, a: Option<&Value>, b: Option<&Value>) -> Ordering {
+        let ordering = match (&self.comparator, a, b) {
+            (Some(named), Some(x), Some(y)) if !x.is_null() && !y.is_null() => (named.compare)(x, y),
+            _ => sort_order(a, b),
+        };
+        if self.descending { ordering.reverse() } else { ordering }
+    }
+
+    pub(crate) fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        self.compare_values(get_path(a, &self.field), get_path(b, &self.field))
+    }
+}
+
+/// Ordena `docs` por `keys` en orden de prioridad. Es estable: los empates conservan el orden
+/// que tenían (el de inserción en los resultados de las consultas).
+pub fn sort_documents<T: Borrow<Value>>(docs: &mut [T], keys: &[SortKey]) {
+    if !keys.is_empty() {
+        docs.sort_by(|a, b| {
+            keys.iter().map(|key| key.compare(a.borrow(), b.borrow())).find(|o| o.is_ne()).unwrap_or(Ordering::Equal)
+        });
+    }
+}
+
+/// Texto con los números comparados por valor (`img2` < `img10`). Para valores que no son
+/// strings se usa el orden normal.
+pub fn natural(a: &Value, b: &Value) -> Ordering {
+    match (a.as_str(), b.as_str()) {
+        (Some(a), Some(b)) => natural_str(a, b),
+        _ => sort_order(Some(a), Some(b)),
+    }
+}
+
+fn natural_str(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (da, ra) = split_digits(a);
+            let (db, rb) = split_digits(b);
+            a = ra;
+            b = rb;
+            // Sin ceros a la izquierda, más cifras es mayor; con las mismas, orden de texto.
+            // Si el valor es igual, menos ceros a la izquierda va antes.
+            let (ta, tb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+            ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb)).then_with(|| da.len().cmp(&db.len()))
+        } else {
+            a = &a[x.len_utf8()..];
+            b = &b[y.len_utf8()..];
+            x.cmp(&y)
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()))
+}
+
+/// Versiones semánticas (`1.10.0` > `1.9.2`, `1.0.0-rc.1` < `1.0.0`; se admite una `v`
+/// delante). Lo que no es una versión va después de las versiones, en orden natural.
+pub fn semver(a: &Value, b: &Value) -> Ordering {
+    let (Some(a), Some(b)) = (a.as_str(), b.as_str()) else {
+        return sort_order(Some(a), Some(b));
+    };
+    match (Version::parse(a), Version::parse(b)) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => natural_str(a, b),
+    }
+}
+
+struct Version<'a> {
+    core: [u64; 3],
+    pre: Option<&'a str>,
+}
+
+impl<'a> Version<'a> {
+    fn parse(s: &'a str) -> Option<Self> {
+        let s = s.strip_prefix('v').unwrap_or(s);
+        // Los metadatos de compilación (`+...`) no cuentan para el orden
+        let s = s.split_once('+').map_or(s, |(version, _)| version);
+        let (core, pre) = match s.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, Some(pre)),
+            Some(_) => return None,
+            None => (s, None),
+        };
+        let mut parts = core.split('.').map(|part| part.parse::<u64>().ok());
+        let version = Self { core: [parts.next()??, parts.next()??, parts.next()??], pre };
+        parts.next().is_none().then_some(version)
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.core.cmp(&other.core).then_with(|| match (self.pre, other.pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => compare_prerelease(a, b),
+        })
+    }
+}
+
+/// Identificadores separados por `.`: los numéricos por valor y antes que los de texto
+fn compare_prerelease(a: &str, b: &str) -> Ordering {
+    let mut a = a.split('.');
+    let mut b = b.split('.');
+    loop {
+        let ordering = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.parse::<u64>(), y.parse::<u64>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => x.cmp(y),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Compresión de documentos grandes en disco. Con `CollectionOptions::compress_above`, un
+//! documento cuya línea supera ese tamaño se guarda como `{"_id", "$z": <cuerpo>}`, con el
+//! cuerpo (todo menos `_id`) comprimido con deflate y en base64. Los pequeños se quedan en
+//! JSON normal, que se lee más rápido. En memoria cada documento sigue completo. Las líneas
+//! comprimidas solo se expanden al leer con la opción activada, y `$z` es una clave
+//! reservada: no se acepta en los documentos.
+
+use std::io::{self, Read};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde_json::{json, Map, Value};
+use crate::collection::doc_id;
+
+pub(crate) const COMPRESSED: &str = "$z";
+
+/// Línea comprimida de `doc` si ocupa menos que `line`; si no, `line` tal cual
+pub(crate) fn compress_line(doc: &Value, line: String) -> io::Result<String> {
+    let Some(obj) = doc.as_object() else { return Ok(line); };
+    let body: Map<String, Value> = obj.iter()
+        .filter(|(key, _)| *key != "_id")
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, &body)?;
+    let packed = base64_encode(&encoder.finish()?);
+    let compressed = json!({"_id": doc_id(doc), COMPRESSED: packed}).to_string();
+    Ok(if compressed.len() < line.len() { compressed } else { line })
+}
+
+pub(crate) fn is_compressed(line: &Value) -> bool {
+    matches!(line.as_object(), Some(obj) if obj.len() == 2 && obj.contains_key("_id") && obj.get(COMPRESSED).is_some_and(Value::is_string))
+}
+
+/// Sustituye una línea comprimida por el documento completo; las demás no cambian
+pub(crate) fn expand(line: &mut Value) -> io::Result<()> {
+    if !is_compressed(line) {
+        return Ok(());
+    }
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let packed = line.get(COMPRESSED).and_then(Value::as_str).unwrap_or_default();
+    let mut text = Vec::new();
+    DeflateDecoder::new(base64_decode(packed).ok_or_else(|| invalid("Invalid compressed document"))?.as_slice())
+        .read_to_end(&mut text)?;
+    let Value::Object(mut doc) = serde_json::from_slice(&text)? else {
+        return Err(invalid("Compressed document is not an object"));
+    };
+    if let Some(id) = line.get("_id") {
+        doc.insert("_id".to_string(), id.clone());
+    }
+    *line = Value::Object(doc);
+    Ok(())
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            out.push(if i <= chunk.len() { ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] } else { b'=' });
+        }
+    }
+    // Solo caracteres de `ALPHABET` y `=`
+    String::from_utf8(out).unwrap_or_default()
+}
+
+pub(crate) fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let digit = ALPHABET.iter().position(|&a| a == c)? as u32;
+            n |= digit << (18 - 6 * i);
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(out)
+}
@@ -0,0 +1,223 @@
+//! Configuración de la base de datos desde un archivo TOML o JSON, con las variables de
+//! entorno `RUGGY_*` por encima del archivo. Permite ajustar el motor en cada despliegue sin
+//! recompilar la aplicación.
+//!
+//! - `root` / `RUGGY_ROOT`: directorio de la base de datos
+//! - `follower` / `RUGGY_FOLLOWER`: `true` abre como seguidor de solo lectura
+//! - `durability` / `RUGGY_DURABILITY`: `every_write`, `manual` o `debounced`
+//! - `debounce_ms` / `RUGGY_DEBOUNCE_MS`: intervalo de `debounced` (1000 por defecto)
+//! - `result_cache` / `RUGGY_RESULT_CACHE`: capacidad de la caché de resultados
+//! - `load_mode` / `RUGGY_LOAD_MODE`: `lossy` o `strict`
+//! - `duplicate_ids` / `RUGGY_DUPLICATE_IDS`: `keep_last`, `keep_first` o `fail`
+//! - `compress_above` / `RUGGY_COMPRESS_ABOVE`: bytes a partir de los que se comprime cada
+//!   documento en disco
+//! - `lock_timeout_ms` / `RUGGY_LOCK_TIMEOUT_MS`: espera máxima de las escrituras por los locks
+//!   de una colección antes de fallar con `Busy` (ver `contention`)
+//! - `[maintenance]` / `RUGGY_MAINTENANCE_INTERVAL_MS`: activa el mantenimiento en segundo plano
+//! - `[s3]` (feature `s3`): guarda los datos en un bucket (ver `S3Options`); `root` es entonces
+//!   el directorio de la caché local
+//!
+//! El archivo se toma del argumento o, si no hay, de `RUGGY_CONFIG`; sin ninguno de los dos
+//! solo cuentan las variables.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use crate::contention::ContentionPolicy;
+use crate::db::Database;
+use crate::load::{DuplicatePolicy, LoadMode};
+use crate::maintenance::MaintenanceConfig;
+use crate::options::{CollectionOptions, PersistStrategy};
+use crate::storage::StorageBackend;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    #[default]
+    EveryWrite,
+    Manual,
+    Debounced,
+}
+
+/// Mantenimiento en segundo plano; los campos sin valor toman los de `MaintenanceConfig`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaintenanceSettings {
+    pub interval_ms: Option<u64>,
+    pub idle_after_ms: Option<u64>,
+    pub compact: Option<bool>,
+    pub expire: Option<bool>,
+    pub archive: Option<bool>,
+    pub backup_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub root: Option<PathBuf>,
+    pub follower: bool,
+    /// Estrategia de persistencia por defecto de las colecciones
+    pub durability: Durability,
+    pub debounce_ms: Option<u64>,
+    /// Capacidad de la caché de resultados por defecto (`None` = sin caché)
+    pub result_cache: Option<usize>,
+    pub load_mode: LoadMode,
+    pub duplicate_ids: DuplicatePolicy,
+    /// Ver `CollectionOptions::compress_above`
+    pub compress_above: Option<usize>,
+    pub lock_timeout_ms: Option<u64>,
+    pub maintenance: Option<MaintenanceSettings>,
+    #[cfg(feature = "s3")]
+    pub s3: Option<crate::s3::S3Options>,
+}
+
+impl DatabaseConfig {
+    /// Lee el archivo (`path` o `RUGGY_CONFIG`, TOML si termina en `.toml` y JSON si no) y
+    /// aplica encima las variables de entorno
+    pub fn load(path: Option<&Path>) -> io::Result<Self> {
+        let path = path.map(Path::to_path_buf).or_else(|| env::var_os("RUGGY_CONFIG").map(PathBuf::from));
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env()?;
+        Ok(config)
+    }
+
+    /// Solo el archivo, sin variables de entorno
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let parsed = if path.extension().and_then(|e| e.to_str()) == Some("toml") {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|e| invalid(format!("Invalid config '{}': {}", path.display(), e)))
+    }
+
+    fn apply_env(&mut self) -> io::Result<()> {
+        if let Some(root) = env::var_os("RUGGY_ROOT") {
+            self.root = Some(PathBuf::from(root));
+        }
+        if let Some(follower) = env_value("RUGGY_FOLLOWER")? {
+            self.follower = follower;
+        }
+        if let Some(durability) = env_value("RUGGY_DURABILITY")? {
+            self.durability = durability;
+        }
+        if let Some(ms) = env_value("RUGGY_DEBOUNCE_MS")? {
+            self.debounce_ms = Some(ms);
+        }
+        if let Some(capacity) = env_value("RUGGY_RESULT_CACHE")? {
+            self.result_cache = Some(capacity);
+        }
+        if let Some(mode) = env_value("RUGGY_LOAD_MODE")? {
+            self.load_mode = mode;
+        }
+        if let Some(policy) = env_value("RUGGY_DUPLICATE_IDS")? {
+            self.duplicate_ids = policy;
+        }
+        if let Some(bytes) = env_value("RUGGY_COMPRESS_ABOVE")? {
+            self.compress_above = Some(bytes);
+        }
+        if let Some(ms) = env_value("RUGGY_LOCK_TIMEOUT_MS")? {
+            self.lock_timeout_ms = Some(ms);
+        }
+        if let Some(ms) = env_value("RUGGY_MAINTENANCE_INTERVAL_MS")? {
+            self.maintenance.get_or_insert_with(MaintenanceSettings::default).interval_ms = Some(ms);
+        }
+        Ok(())
+    }
+
+    /// Opciones que reciben las colecciones abiertas con `Database::collection`
+    pub fn collection_options(&self) -> CollectionOptions {
+        let strategy = match self.durability {
+            Durability::EveryWrite => PersistStrategy::OnEveryWrite,
+            Durability::Manual => PersistStrategy::Manual,
+            Durability::Debounced => PersistStrategy::Debounced(Duration::from_millis(self.debounce_ms.unwrap_or(1000))),
+        };
+        let mut options = CollectionOptions::new()
+            .persist_strategy(strategy)
+            .load_mode(self.load_mode)
+            .duplicate_ids(self.duplicate_ids);
+        options.result_cache = self.result_cache;
+        options.compress_above = self.compress_above;
+        if let Some(ms) = self.lock_timeout_ms {
+            options.contention = ContentionPolicy::new().lock_timeout(Duration::from_millis(ms));
+        }
+        options
+    }
+
+    /// Almacenamiento que no es el directorio `root` (`None`: el directorio)
+    #[cfg_attr(not(feature = "s3"), allow(unused_variables))]
+    fn storage(&self, root: &Path) -> io::Result<Option<Arc<dyn StorageBackend>>> {
+        #[cfg(feature = "s3")]
+        if let Some(s3) = &self.s3 {
+            return Ok(Some(Arc::new(crate::s3::S3Storage::new(s3.clone(), root)?)));
+        }
+        Ok(None)
+    }
+
+    pub fn maintenance_config(&self) -> Option<MaintenanceConfig> {
+        let settings = self.maintenance.as_ref()?;
+        let defaults = MaintenanceConfig::default();
+        Some(MaintenanceConfig {
+            interval: settings.interval_ms.map_or(defaults.interval, Duration::from_millis),
+            idle_after: settings.idle_after_ms.map(Duration::from_millis),
+            compact: settings.compact.unwrap_or(defaults.compact),
+            expire: settings.expire.unwrap_or(defaults.expire),
+            archive: settings.archive.unwrap_or(defaults.archive),
+            backup_dir: settings.backup_dir.clone(),
+        })
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Valor de una variable de entorno: primero como JSON (números, `true`) y si no como string
+/// (`manual`, `keep_first`)
+fn env_value<T: DeserializeOwned>(name: &str) -> io::Result<Option<T>> {
+    let raw = match env::var(name) {
+        Ok(raw) => raw,
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(e) => return Err(invalid(format!("{}: {}", name, e))),
+    };
+    let value = serde_json::from_str(&raw).unwrap_or(Value::String(raw.clone()));
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|e| invalid(format!("Invalid value '{}' for {}: {}", raw, name, e)))
+}
+
+impl Database {
+    /// Abre la base de datos según el archivo de configuración (`path` o `RUGGY_CONFIG`) y las
+    /// variables `RUGGY_*`. Si la configuración lo pide, el mantenimiento queda en marcha
+    /// hasta soltar la base de datos.
+    pub fn from_config(path: Option<&Path>) -> io::Result<Self> {
+        Self::with_config(&DatabaseConfig::load(path)?)
+    }
+
+    pub fn with_config(config: &DatabaseConfig) -> io::Result<Self> {
+        let root = config.root.as_ref()
+            .ok_or_else(|| invalid("Config has no root path (set `root` or RUGGY_ROOT)".to_string()))?;
+        let mut db = match config.storage(root)? {
+            Some(storage) if config.follower => Self::follower_with_storage(storage),
+            Some(storage) => Self::with_storage(storage)?,
+            None if config.follower => Self::open_follower(root)?,
+            None => Self::new(root)?,
+        };
+        db.defaults = config.collection_options();
+        if let Some(maintenance) = config.maintenance_config() {
+            let handle = db.start_maintenance(maintenance);
+            *db.maintenance.lock() = Some(handle);
+        }
+        Ok(db)
+    }
+}
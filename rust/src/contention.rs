@@ -0,0 +1,121 @@
+//! Esperas acotadas por los locks de escritura. Por defecto una escritura espera sin límite a
+//! que terminen las demás; con `CollectionOptions::contention` cada intento espera como mucho
+//! `lock_timeout` y, tras los reintentos, la escritura falla con un error `Busy`
+//! (`io::ErrorKind::ResourceBusy`) en lugar de bloquear al host.
+//!
+//! ```ignore
+//! let options = CollectionOptions::new()
+//!     .contention(ContentionPolicy::new().lock_timeout(Duration::from_millis(20)).retry(3, Duration::from_millis(5)));
+//! if let Err(e) = col.insert(doc) {
+//!     if contention::is_busy(&e) { /* reintentar en el siguiente frame */ }
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::collection::Collection;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ContentionPolicy {
+    /// Espera máxima de cada intento; `None` espera sin límite
+    pub lock_timeout: Option<Duration>,
+    /// Intentos extra después del primero
+    pub retries: u32,
+    /// Pausa antes del primer reintento; se duplica en cada uno
+    pub backoff: Duration,
+}
+
+impl ContentionPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// Una escritura no consiguió un lock de la colección a tiempo
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Busy {
+    pub collection: String,
+    /// `"writer"` (IO del archivo) o `"data"` (documentos en memoria)
+    pub lock: &'static str,
+    pub attempts: u32,
+    pub waited: Duration,
+}
+
+impl fmt::Display for Busy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Collection '{}' is busy: {} lock not acquired after {} attempts ({:?})",
+            self.collection, self.lock, self.attempts, self.waited)
+    }
+}
+
+impl std::error::Error for Busy {}
+
+/// El error viene de un lock que no se consiguió a tiempo (el detalle es un `Busy`)
+pub fn is_busy(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::ResourceBusy
+}
+
+thread_local! {
+    static LAST_BUSY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Si el último lock de escritura pedido en este hilo no se consiguió a tiempo
+pub(crate) fn last_busy() -> bool {
+    LAST_BUSY.with(Cell::get)
+}
+
+/// Toma un lock con `try_for(Some(espera))`, o con `try_for(None)` (que bloquea) si la
+/// política no tiene límite. Si estaba ocupado, llama a `waited` con lo que se esperó.
+pub(crate) fn acquire<T>(
+    policy: &ContentionPolicy,
+    collection: &str,
+    lock: &'static str,
+    mut try_for: impl FnMut(Option<Duration>) -> Option<T>,
+    waited: impl FnOnce(Duration),
+) -> io::Result<T> {
+    if let Some(guard) = try_for(Some(Duration::ZERO)) {
+        LAST_BUSY.with(|busy| busy.set(false));
+        return Ok(guard);
+    }
+    let start = Instant::now();
+    let mut backoff = policy.backoff;
+    let mut attempts = 0;
+    let acquired = loop {
+        attempts += 1;
+        if let Some(guard) = try_for(policy.lock_timeout) {
+            break Some(guard);
+        }
+        if attempts > policy.retries {
+            break None;
+        }
+        thread::sleep(backoff);
+        backoff = backoff.saturating_mul(2);
+    };
+    LAST_BUSY.with(|busy| busy.set(acquired.is_none()));
+    waited(start.elapsed());
+    acquired.ok_or_else(|| {
+        let busy = Busy { collection: collection.to_string(), lock, attempts, waited: start.elapsed() };
+        io::Error::new(io::ErrorKind::ResourceBusy, busy)
+    })
+}
+
+impl Collection {
+    /// Cambia la política de espera de las escrituras siguientes
+    pub fn set_contention(&self, policy: ContentionPolicy) {
+        self.options.write().contention = policy;
+    }
+}
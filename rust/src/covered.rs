@@ -0,0 +1,160 @@
+//! Consultas de igualdad con orden y proyección. Si un índice con campos incluidos
+//! (`IndexSpec::include`) tiene todo lo que usan el orden y la proyección, la consulta se
+//! responde con las filas del índice sin tocar los documentos; así funciona también con la
+//! colección descargada (`unload`), sin volver a leer el archivo.
+//!
+//! ```ignore
+//! col.create_covering_index("by_team", "team", &["name", "score"])?;
+//! let top = col.find_eq(&EqQuery::new("team", "red").sort_by("score", true).project(Projection::parse(&json!({"name": 1}))?))?;
+//! assert!(top.covered);
+//! ```
+
+use std::io;
+use serde::Serialize;
+use serde_json::Value;
+use crate::collection::Collection;
+use crate::comparator::{self, SortKey};
+use crate::expr::Projection;
+use crate::index::{self, QueryOptions, ReadConsistency};
+use crate::page;
+
+#[derive(Clone, Debug)]
+pub struct EqQuery {
+    field: String,
+    value: String,
+    sort_by: Vec<SortKey>,
+    projection: Option<Projection>,
+    skip: usize,
+    limit: Option<usize>,
+    options: QueryOptions,
+}
+
+/// Resultado de `find_eq`. `covered` indica si se respondió solo con el índice.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EqResult {
+    pub documents: Vec<Value>,
+    pub covered: bool,
+}
+
+impl EqQuery {
+    /// Documentos con `field == value`, como `Collection::find`
+    pub fn new(field: &str, value: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            value: value.to_string(),
+            sort_by: Vec::new(),
+            projection: None,
+            skip: 0,
+            limit: None,
+            options: QueryOptions::default(),
+        }
+    }
+
+    pub fn sort_by(self, field: &str, descending: bool) -> Self {
+        self.sort_by_key(SortKey::new(field, descending))
+    }
+
+    pub fn sort_by_key(mut self, key: SortKey) -> Self {
+        self.sort_by.push(key);
+        self
+    }
+
+    /// Sin proyección se devuelven los documentos completos y la consulta nunca está cubierta
+    pub fn project(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    /// Salta los `n` primeros resultados (después de ordenar)
+    pub fn skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    pub fn options(mut self, options: QueryOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// `{"field": ..., "value": ..., "sortBy": ["-score"], "projection": {...}, "skip": 20,
+    /// "limit": 10, "hint": ..., "consistency": "snapshot"}`
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+        let field = spec.get("field").and_then(|v| v.as_str()).ok_or_else(|| invalid("Query needs a 'field'"))?;
+        let value = spec.get("value").and_then(|v| v.as_str()).ok_or_else(|| invalid("Query needs a string 'value'"))?;
+        let mut query = Self::new(field, value);
+        if let Some(keys) = spec.get("sortBy") {
+            query.sort_by = SortKey::parse_list(keys)?;
+        }
+        if let Some(skip) = spec.get("skip") {
+            query.skip = skip.as_u64().ok_or_else(|| invalid("'skip' must be a non-negative integer"))? as usize;
+        }
+        if let Some(limit) = spec.get("limit") {
+            query.limit = Some(limit.as_u64().ok_or_else(|| invalid("'limit' must be a non-negative integer"))? as usize);
+        }
+        if let Some(projection) = spec.get("projection") {
+            query = query.project(Projection::parse(projection)?);
+        }
+        if let Some(hint) = spec.get("hint").and_then(|v| v.as_str()) {
+            query.options = query.options.hint(hint);
+        }
+        if let Some(consistency) = spec.get("consistency") {
+            let name = consistency.as_str().ok_or_else(|| invalid("'consistency' must be a string"))?;
+            query.options = query.options.consistency(ReadConsistency::parse(name)?);
+        }
+        Ok(query)
+    }
+
+    /// Campos de primer nivel que necesitan el orden y la proyección; `None` sin proyección
+    fn needed_fields(&self) -> Option<Vec<String>> {
+        let mut fields = self.projection.as_ref()?.source_fields();
+        fields.push("_id".to_string());
+        for key in &self.sort_by {
+            let field = key.field.split('.').next().unwrap_or(&key.field);
+            if !fields.iter().any(|f| f == field) {
+                fields.push(field.to_string());
+            }
+        }
+        Some(fields)
+    }
+
+    fn finish(&self, mut docs: Vec<Value>) -> Vec<Value> {
+        comparator::sort_documents(&mut docs, &self.sort_by);
+        let docs = page::skip_limit(docs, self.skip, self.limit);
+        match &self.projection {
+            Some(projection) => docs.iter().map(|doc| projection.apply(doc)).collect(),
+            None => docs,
+        }
+    }
+}
+
+impl Collection {
+    /// Igualdad con orden y proyección, con el índice que la cubra si lo hay
+    pub fn find_eq(&self, query: &EqQuery) -> io::Result<EqResult> {
+        if let Some(fields) = query.needed_fields() {
+            let rows = {
+                let snapshot = match query.options.consistency {
+                    ReadConsistency::Snapshot => self.snapshot_view().map(|(_, indexes)| indexes),
+                    ReadConsistency::Latest => None,
+                };
+                // Sin recargar: los índices que cubren se conservan al descargar
+                let indexes = snapshot.unwrap_or_else(|| {
+                    let _data = self.data.read();
+                    self.indexes.read().clone()
+                });
+                index::choose_covering(&indexes, &query.field, &fields, &query.options)?
+                    .map(|index| index.covered_rows(&query.value))
+            };
+            if let Some(rows) = rows {
+                return Ok(EqResult { documents: query.finish(rows), covered: true });
+            }
+        }
+        let docs = self.find_with(&query.field, &query.value, &query.options)?;
+        Ok(EqResult { documents: query.finish(docs), covered: false })
+    }
+}
@@ -0,0 +1,184 @@
+//! Importación de CSV con inferencia de tipos y reconstrucción de campos anidados a partir
+//! de cabeceras con puntos (`address.city`).
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use serde_json::{Map, Number, Value};
+use crate::bulk::ImportResult;
+use crate::collection::Collection;
+use crate::datetime;
+use crate::expr;
+
+/// Cómo convertir cada celda del CSV
+#[derive(Clone, Debug)]
+pub struct CsvOptions {
+    pub delimiter: u8,
+    /// Convertir a número las celdas numéricas
+    pub numbers: bool,
+    /// Convertir `true`/`false` a booleanos
+    pub booleans: bool,
+    /// Convertir las fechas ISO-8601 a milisegundos desde epoch (el formato que usan TTL y archivado)
+    pub dates: bool,
+    /// Reconstruir objetos a partir de cabeceras con puntos
+    pub nested: bool,
+    /// Celdas que se guardan como `null`
+    pub null_values: Vec<String>,
+    /// Omitir los campos nulos en lugar de guardarlos como `null`
+    pub skip_nulls: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            numbers: true,
+            booleans: true,
+            dates: false,
+            nested: true,
+            null_values: vec![String::new(), "null".to_string(), "NULL".to_string()],
+            skip_nulls: false,
+        }
+    }
+}
+
+impl CsvOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn numbers(mut self, enabled: bool) -> Self {
+        self.numbers = enabled;
+        self
+    }
+
+    pub fn booleans(mut self, enabled: bool) -> Self {
+        self.booleans = enabled;
+        self
+    }
+
+    pub fn dates(mut self, enabled: bool) -> Self {
+        self.dates = enabled;
+        self
+    }
+
+    pub fn nested(mut self, enabled: bool) -> Self {
+        self.nested = enabled;
+        self
+    }
+
+    pub fn null_values(mut self, values: &[&str]) -> Self {
+        self.null_values = values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+
+    pub fn skip_nulls(mut self, enabled: bool) -> Self {
+        self.skip_nulls = enabled;
+        self
+    }
+
+    /// Valor JSON de una celda según las opciones
+    pub fn convert(&self, cell: &str) -> Value {
+        if self.null_values.iter().any(|v| v == cell) {
+            return Value::Null;
+        }
+        let trimmed = cell.trim();
+        if self.booleans {
+            match trimmed {
+                "true" | "TRUE" | "True" => return Value::Bool(true),
+                "false" | "FALSE" | "False" => return Value::Bool(false),
+                _ => {}
+            }
+        }
+        if self.numbers {
+            if let Some(n) = parse_number(trimmed) {
+                return Value::Number(n);
+            }
+        }
+        if self.dates && (trimmed.len() == 10 || trimmed.len() >= 19) {
+            if let Some(ms) = datetime::parse_iso(trimmed) {
+                return Value::from(ms);
+            }
+        }
+        Value::String(cell.to_string())
+    }
+
+    /// Documento a partir de una fila y sus cabeceras
+    fn row_to_document(&self, headers: &[String], row: &csv::StringRecord) -> Result<Value, String> {
+        if row.len() != headers.len() {
+            return Err(format!("Expected {} columns, found {}", headers.len(), row.len()));
+        }
+        let mut doc = Map::new();
+        for (header, cell) in headers.iter().zip(row.iter()) {
+            let value = self.convert(cell);
+            if value.is_null() && self.skip_nulls {
+                continue;
+            }
+            if self.nested && header.contains('.') {
+                expr::set_field(&mut doc, header, value).map_err(|e| e.to_string())?;
+            } else {
+                doc.insert(header.clone(), value);
+            }
+        }
+        Ok(Value::Object(doc))
+    }
+}
+
+/// Solo números con la forma de JSON: "007" o "1e" se quedan como texto
+fn parse_number(s: &str) -> Option<Number> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.")) {
+        return None;
+    }
+    if let Ok(n) = s.parse::<i64>() {
+        return Some(n.into());
+    }
+    s.parse::<f64>().ok().filter(|f| f.is_finite()).and_then(Number::from_f64)
+}
+
+impl Collection {
+    /// Importa un CSV con cabecera. Cada fila da un `ImportResult` en orden; las filas mal
+    /// formadas fallan sin detener la importación. Con `key_field` se hace upsert.
+    pub fn import_csv<R: Read>(&self, reader: R, options: &CsvOptions, key_field: Option<&str>) -> io::Result<Vec<ImportResult>> {
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(options.delimiter)
+            .flexible(true)
+            .from_reader(reader);
+        let headers: Vec<String> = csv_reader.headers()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            .iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+
+        // Un `Some(motivo)` por cada fila inválida, `None` por cada documento a importar
+        let mut failures = Vec::new();
+        let mut valid = Vec::new();
+        for row in csv_reader.records() {
+            match row.map_err(|e| e.to_string()).and_then(|row| options.row_to_document(&headers, &row)) {
+                Ok(doc) => {
+                    valid.push(doc);
+                    failures.push(None);
+                }
+                Err(reason) => failures.push(Some(reason)),
+            }
+        }
+
+        // `import` devuelve un resultado por documento, en orden
+        let mut imported = self.import(valid, key_field)?.into_iter();
+        Ok(failures.into_iter()
+            .filter_map(|failure| match failure {
+                Some(reason) => Some(ImportResult::Failed { reason }),
+                None => imported.next(),
+            })
+            .collect())
+    }
+
+    pub fn import_csv_file(&self, path: &Path, options: &CsvOptions, key_field: Option<&str>) -> io::Result<Vec<ImportResult>> {
+        self.import_csv(File::open(path)?, options, key_field)
+    }
+}
@@ -0,0 +1,109 @@
+use serde_json::Value;
+
+const MS_PER_DAY: i64 = 86_400_000;
+
+/// Fecha civil (UTC) de un instante en milisegundos desde epoch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Civil {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    pub millis: u32,
+}
+
+impl Civil {
+    pub fn from_millis(ms: i64) -> Self {
+        let days = ms.div_euclid(MS_PER_DAY);
+        let rem = ms.rem_euclid(MS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: (rem / 3_600_000) as u32,
+            minute: (rem / 60_000 % 60) as u32,
+            second: (rem / 1000 % 60) as u32,
+            millis: (rem % 1000) as u32,
+        }
+    }
+
+    pub fn to_millis(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * MS_PER_DAY
+            + self.hour as i64 * 3_600_000
+            + self.minute as i64 * 60_000
+            + self.second as i64 * 1000
+            + self.millis as i64
+    }
+
+    /// 0 = domingo ... 6 = sábado
+    pub fn weekday(&self) -> u32 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        (days + 4).rem_euclid(7) as u32
+    }
+}
+
+/// Instante de un valor: número de milisegundos desde epoch o string ISO-8601
+/// (`YYYY-MM-DD`, `YYYY-MM-DDTHH:MM:SS[.mmm][Z]`, siempre tratado como UTC)
+pub fn value_to_millis(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Value::String(s) => parse_iso(s),
+        _ => None,
+    }
+}
+
+pub fn parse_iso(s: &str) -> Option<i64> {
+    let bytes = s.as_bytes();
+    if bytes.len() < 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| s.get(range)?.parse::<u32>().ok();
+    let mut civil = Civil {
+        year: num(0..4)? as i64,
+        month: num(5..7)?,
+        day: num(8..10)?,
+        hour: 0,
+        minute: 0,
+        second: 0,
+        millis: 0,
+    };
+    if !(1..=12).contains(&civil.month) || !(1..=31).contains(&civil.day) {
+        return None;
+    }
+    if bytes.len() >= 19 && (bytes[10] == b'T' || bytes[10] == b' ') {
+        civil.hour = num(11..13)?;
+        civil.minute = num(14..16)?;
+        civil.second = num(17..19)?;
+        if bytes.len() >= 23 && bytes[19] == b'.' {
+            civil.millis = num(20..23)?;
+        }
+    }
+    Some(civil.to_millis())
+}
+
+/// Algoritmo de Howard Hinnant: días desde 1970-01-01 a fecha civil
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -1,29 +1,170 @@
 use std::collections::HashMap;
-use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use parking_lot::RwLock;
+use std::sync::{Arc, Weak};
+use parking_lot::{Mutex, RwLock};
+use crate::cdc::{CdcSink, ChangeFeed};
 use crate::collection::Collection;
+use crate::encryption::{self, FieldCipher};
+use crate::io_stats::IoStats;
+use crate::maintenance::{self, MaintenanceConfig, MaintenanceHandle, MaintenanceReport};
+use crate::memory::MemoryUsage;
+use crate::options::CollectionOptions;
+use crate::partition::{PartitionBy, PartitionedCollection};
+use crate::sharding::{ShardBy, ShardedCollection};
+use crate::storage::{self, LocalStorage, StorageBackend};
+use crate::transfer;
 
 pub struct Database {
-    pub(crate) root_path: PathBuf,
-    pub(crate) collections: RwLock<HashMap<String, Arc<Collection>>>,
+    pub(crate) storage: Arc<dyn StorageBackend>,
+    pub(crate) collections: Arc<RwLock<HashMap<String, Arc<Collection>>>>,
+    /// Abierta con `open_follower`: las colecciones son seguidores de solo lectura
+    pub(crate) read_only: bool,
+    pub(crate) changes: Arc<ChangeFeed>,
+    /// Serializa `move_documents`
+    pub(crate) moves: Mutex<()>,
+    /// Opciones de las colecciones abiertas con `collection` (ver `DatabaseConfig`)
+    pub(crate) defaults: CollectionOptions,
+    /// Mantenimiento arrancado por la configuración; se detiene al soltar la base de datos
+    pub(crate) maintenance: Mutex<Option<MaintenanceHandle>>,
 }
 
+/// Instancias de `open_shared` por ruta canónica
+static SHARED: Mutex<Option<HashMap<PathBuf, Weak<Database>>>> = parking_lot::const_mutex(None);
+
 impl Database {
+    /// Abre una instancia independiente. Dos instancias sobre el mismo directorio no ven los
+    /// cambios de la otra y se sobrescriben al guardar: para compartirla usar `open_shared`.
     pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
-        let root_path = path.as_ref().to_path_buf();
-        if !root_path.exists() {
-            fs::create_dir_all(&root_path)?;
+        Self::with_storage(Arc::new(LocalStorage::new(path)?))
+    }
+
+    /// Devuelve la instancia ya abierta con `open_shared` para el mismo directorio (comparando
+    /// rutas canónicas, así `./data` y `data/` son la misma) o abre una nueva. Se cierra al
+    /// soltar la última referencia.
+    pub fn open_shared<P: AsRef<Path>>(path: P) -> io::Result<Arc<Self>> {
+        let root = storage::canonical_dir(path.as_ref(), true)?;
+        let mut shared = SHARED.lock();
+        let open = shared.get_or_insert_with(HashMap::new);
+        if let Some(db) = open.get(&root).and_then(Weak::upgrade) {
+            return Ok(db);
         }
+        open.retain(|_, db| db.strong_count() > 0);
+        let db = Arc::new(Self::new(&root)?);
+        open.insert(root, Arc::downgrade(&db));
+        Ok(db)
+    }
+
+    /// Base de datos sobre otro almacenamiento (en memoria, de la plataforma...), ver `storage`
+    pub fn with_storage(storage: Arc<dyn StorageBackend>) -> io::Result<Self> {
+        transfer::recover(storage.as_ref())?;
         Ok(Self {
-            root_path,
-            collections: RwLock::new(HashMap::new()),
+            storage,
+            collections: Arc::new(RwLock::new(HashMap::new())),
+            read_only: false,
+            changes: Arc::new(ChangeFeed::default()),
+            moves: Mutex::new(()),
+            defaults: CollectionOptions::default(),
+            maintenance: Mutex::new(None),
         })
     }
 
+    /// Abre como seguidor de solo lectura el directorio de otra instancia, que puede estar en
+    /// otro proceso. Las colecciones se actualizan con `refresh()`.
+    pub fn open_follower<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let root = storage::canonical_dir(path.as_ref(), false)?;
+        Ok(Self::follower_with_storage(Arc::new(LocalStorage::existing(root))))
+    }
+
+    /// Como `open_follower`, siguiendo las colecciones de `storage`
+    pub fn follower_with_storage(storage: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            storage,
+            collections: Arc::new(RwLock::new(HashMap::new())),
+            read_only: true,
+            changes: Arc::new(ChangeFeed::default()),
+            moves: Mutex::new(()),
+            defaults: CollectionOptions::default(),
+            maintenance: Mutex::new(None),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Aplica en todas las colecciones abiertas los cambios del líder.
+    /// Devuelve cuántas colecciones cambiaron.
+    pub fn refresh(&self) -> io::Result<usize> {
+        let open: Vec<Arc<Collection>> = self.collections.read().values().cloned().collect();
+        let mut changed = 0;
+        for col in open {
+            if col.refresh()? {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Libera la memoria de una colección abierta sin invalidar sus handles; se recarga
+    /// sola en el siguiente acceso. Devuelve `false` si no estaba abierta.
+    pub fn unload_collection(&self, name: &str) -> io::Result<bool> {
+        let col = match self.collections.read().get(name) {
+            Some(col) => col.clone(),
+            None => return Ok(false),
+        };
+        col.unload()?;
+        Ok(true)
+    }
+
+    /// Memoria estimada de cada colección abierta
+    pub fn memory_usage(&self) -> HashMap<String, MemoryUsage> {
+        self.collections.read()
+            .iter()
+            .map(|(name, col)| (name.clone(), col.memory_usage()))
+            .collect()
+    }
+
+    /// Estadísticas de IO de cada colección abierta
+    pub fn io_stats(&self) -> HashMap<String, IoStats> {
+        self.collections.read()
+            .iter()
+            .map(|(name, col)| (name.clone(), col.io_stats()))
+            .collect()
+    }
+
+    fn load_collection(&self, name: &str, options: CollectionOptions) -> io::Result<Collection> {
+        let file_name = format!("{}.col", name);
+        let collection = if self.read_only {
+            Collection::follow_in(name, self.storage.clone(), file_name, options)?
+        } else {
+            Collection::open_in(name, self.storage.clone(), file_name, options)?
+        };
+        Ok(collection.with_change_feed(self.changes.clone()))
+    }
+
+    /// Registra la función que recibe cada mutación confirmada (insert, update, delete,
+    /// expiración o archivado) con su número de secuencia. Se llama de forma síncrona con la
+    /// colección bloqueada para escritura: no debe escribir en la misma colección.
+    pub fn set_cdc_sink(&self, sink: CdcSink) {
+        self.changes.set_sink(Some(sink));
+    }
+
+    pub fn clear_cdc_sink(&self) {
+        self.changes.set_sink(None);
+    }
+
+    /// Secuencia del último evento de cambio emitido
+    pub fn last_change_seq(&self) -> u64 {
+        self.changes.last_seq()
+    }
+
     pub fn collection(&self, name: &str) -> io::Result<Arc<Collection>> {
+        self.collection_with_options(name, self.defaults.clone())
+    }
+
+    /// Igual que `collection`, pero las opciones solo se aplican si la colección aún no estaba abierta
+    pub fn collection_with_options(&self, name: &str, options: CollectionOptions) -> io::Result<Arc<Collection>> {
         {
             let cols = self.collections.read();
             if let Some(col) = cols.get(name) {
@@ -34,9 +175,95 @@ impl Database {
         if let Some(col) = cols.get(name) {
             return Ok(col.clone());
         }
-        let col_path = self.root_path.join(format!("{}.col", name));
-        let collection = Arc::new(Collection::new(name, col_path)?);
+        let collection = Arc::new(self.load_collection(name, options)?);
         cols.insert(name.to_string(), collection.clone());
         Ok(collection)
     }
+
+    /// Quita la colección de la caché y borra su archivo. Los handles que sigan vivos
+    /// mantienen los datos en memoria pero ya no están respaldados en disco.
+    /// Devuelve `false` si no existía.
+    pub fn drop_collection(&self, name: &str) -> io::Result<bool> {
+        let mut cols = self.collections.write();
+        let open = cols.remove(name);
+        if let Some(col) = &open {
+            col.detach();
+        }
+        Ok(self.storage.remove(&format!("{}.col", name))? || open.is_some())
+    }
+
+    /// Abre una colección particionada por fecha (`<name>.p-<día o mes>.col`)
+    pub fn partitioned_collection(&self, name: &str, field: &str, by: PartitionBy, options: CollectionOptions) -> io::Result<PartitionedCollection<'_>> {
+        PartitionedCollection::open(self, name, field, by, options)
+    }
+
+    /// Abre (o crea) una colección repartida en varios archivos `<name>.shard-<n>.col`.
+    /// Los shards que aún no estaban abiertos se cargan en paralelo.
+    pub fn sharded_collection(&self, name: &str, spec: ShardBy, options: CollectionOptions) -> io::Result<ShardedCollection> {
+        let names: Vec<String> = (0..spec.shard_count())
+            .map(|i| ShardBy::shard_name(name, i))
+            .collect();
+        let shards = self.open_many(&names, &options)?;
+        Ok(ShardedCollection::new(name, spec, shards))
+    }
+
+    /// Abre varias colecciones, cargando en paralelo las que no estaban en caché
+    pub(crate) fn open_many(&self, names: &[String], options: &CollectionOptions) -> io::Result<Vec<Arc<Collection>>> {
+        let mut cols = self.collections.write();
+        let missing: Vec<&String> = names.iter().filter(|n| !cols.contains_key(n.as_str())).collect();
+
+        let loaded: Vec<io::Result<Collection>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = missing.iter()
+                .map(|name| {
+                    let options = options.clone();
+                    scope.spawn(move || self.load_collection(name, options))
+                })
+                .collect();
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| Err(io::Error::other("Collection load panicked"))))
+                .collect()
+        });
+
+        for (name, collection) in missing.into_iter().zip(loaded) {
+            cols.insert(name.clone(), Arc::new(collection?));
+        }
+        Ok(names.iter().map(|n| cols[n.as_str()].clone()).collect())
+    }
+
+    /// Re-cifra con `new_key` todos los campos cifrados con `old_key`, tanto en las colecciones
+    /// abiertas como en los archivos `.col` que aún no se han abierto, junto con sus archivos
+    /// de documentos fríos (`.archive.gz`). Es reanudable: si se
+    /// interrumpe, basta con volver a llamarla con las mismas claves.
+    /// Devuelve el número de colecciones re-cifradas.
+    pub fn rotate_key(&self, old_key: Arc<dyn FieldCipher>, new_key: Arc<dyn FieldCipher>) -> io::Result<usize> {
+        let cols = self.collections.write();
+        let mut rotated = 0;
+
+        for file_name in self.storage.list()? {
+            let Some(name) = file_name.strip_suffix(".col") else { continue; };
+            let changed = match cols.get(name) {
+                Some(col) => col.rotate_cipher(old_key.as_ref(), new_key.clone())?,
+                None => {
+                    let archive = storage::sibling(&file_name, "archive.gz");
+                    let stored = encryption::rotate_stored(self.storage.as_ref(), &file_name, old_key.as_ref(), new_key.as_ref())?;
+                    encryption::rotate_archive(self.storage.as_ref(), &archive, old_key.as_ref(), new_key.as_ref())? || stored
+                }
+            };
+            if changed {
+                rotated += 1;
+            }
+        }
+        Ok(rotated)
+    }
+
+    /// Arranca un hilo que ejecuta periódicamente expiración TTL, compactación y copias de
+    /// seguridad sobre las colecciones abiertas. Se detiene al soltar el handle.
+    pub fn start_maintenance(&self, config: MaintenanceConfig) -> MaintenanceHandle {
+        MaintenanceHandle::spawn(self.collections.clone(), config)
+    }
+
+    /// Ejecuta una sola pasada de mantenimiento en el hilo actual
+    pub fn run_maintenance(&self, config: &MaintenanceConfig) -> MaintenanceReport {
+        maintenance::run_once(&self.collections, config)
+    }
 }
@@ -0,0 +1,89 @@
+//! Hash de contenido y deduplicación en disco. Con `CollectionOptions::dedupe`, al reescribir
+//! el archivo un documento cuyo cuerpo (todo menos `_id`) es igual al de otro anterior se
+//! guarda como referencia `{"_id", "_same_as": <_id del primero>}`. En memoria cada documento
+//! sigue completo. Las referencias solo se resuelven al leer con la opción activada, y
+//! `_same_as` es una clave reservada: no se acepta en los documentos.
+
+use std::collections::HashMap;
+use serde_json::{json, Value};
+use crate::collection::doc_id;
+
+pub(crate) const SAME_AS: &str = "_same_as";
+
+/// Hash estable del cuerpo del documento (sin `_id`), en hexadecimal. Dos documentos con el
+/// mismo contenido tienen el mismo hash aunque sus campos estén en otro orden.
+pub fn content_hash(doc: &Value) -> String {
+    format!("{:016x}", body_hash(doc))
+}
+
+fn body_hash(doc: &Value) -> u64 {
+    // FNV-1a: estable entre ejecuciones. Los objetos de serde_json tienen las claves ordenadas.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes: &[u8]| {
+        for b in bytes {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    match doc.as_object() {
+        Some(obj) => {
+            for (key, value) in obj.iter().filter(|(key, _)| *key != "_id") {
+                feed(key.as_bytes());
+                feed(b"\0");
+                feed(value.to_string().as_bytes());
+                feed(b"\0");
+            }
+        }
+        None => feed(doc.to_string().as_bytes()),
+    }
+    hash
+}
+
+fn same_body(a: &Value, b: &Value) -> bool {
+    match (a.as_object(), b.as_object()) {
+        (Some(a), Some(b)) => {
+            let body = |obj: &'_ serde_json::Map<String, Value>| obj.len() - obj.contains_key("_id") as usize;
+            body(a) == body(b) && a.iter().filter(|(key, _)| *key != "_id").all(|(key, value)| b.get(key) == Some(value))
+        }
+        _ => a == b,
+    }
+}
+
+/// Cuerpos ya escritos durante una reescritura
+#[derive(Default)]
+pub(crate) struct Bodies<'a> {
+    seen: HashMap<u64, Vec<&'a Value>>,
+}
+
+impl<'a> Bodies<'a> {
+    /// Línea de referencia si ya se escribió un documento con el mismo cuerpo; si no, anota
+    /// este y devuelve `None` (hay que escribirlo completo)
+    pub(crate) fn reference(&mut self, doc: &'a Value) -> Option<String> {
+        let candidates = self.seen.entry(body_hash(doc)).or_default();
+        match candidates.iter().find(|first| same_body(first, doc)) {
+            Some(first) => Some(json!({"_id": doc_id(doc), SAME_AS: doc_id(first)}).to_string()),
+            None => {
+                candidates.push(doc);
+                None
+            }
+        }
+    }
+}
+
+/// `_id` al que apunta una línea de referencia
+pub(crate) fn reference_target(line: &Value) -> Option<&str> {
+    let obj = line.as_object()?;
+    if obj.len() != 2 || !obj.contains_key("_id") {
+        return None;
+    }
+    obj.get(SAME_AS)?.as_str()
+}
+
+/// Documento completo de una referencia a partir del documento al que apunta
+pub(crate) fn resolve(line: &Value, target: &Value) -> Value {
+    let mut doc = target.clone();
+    if let (Some(obj), Some(id)) = (doc.as_object_mut(), line.get("_id")) {
+        obj.insert("_id".to_string(), id.clone());
+    }
+    doc
+}
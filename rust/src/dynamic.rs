@@ -0,0 +1,118 @@
+//! Perfil de C ABI para lenguajes dinámicos (Ruby, PHP, Perl...): una sola función,
+//! `ruggy_dyn_call`, que recibe un comando JSON y devuelve la respuesta en JSON. No se pasan
+//! structs ni punteros a Ruggy: las bases de datos se identifican con un número de la tabla
+//! de handles (o con un alias de `registry`) y las colecciones por nombre.
+//!
+//! ```json
+//! {"cmd": "open", "path": "data"}                          -> {"ok": true, "result": 1}
+//! {"cmd": "insert", "db": 1, "collection": "users", "doc": {"name": "ana"}}
+//! {"cmd": "find", "db": 1, "collection": "users", "field": "name", "value": "ana"}
+//! {"cmd": "close", "db": 1}
+//! ```
+//!
+//! Las respuestas son `{"ok": true, "result": ...}` o `{"ok": false, "error": "..."}`.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use crate::collection::Collection;
+use crate::db::Database;
+use crate::limits;
+use crate::registry;
+
+static HANDLES: Mutex<Option<HashMap<u64, Arc<Database>>>> = parking_lot::const_mutex(None);
+static NEXT_HANDLE: AtomicU64 = AtomicU64::new(1);
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn str_arg<'a>(request: &'a Value, name: &str) -> io::Result<&'a str> {
+    request.get(name)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| invalid(format!("Missing string argument '{}'", name)))
+}
+
+fn arg<'a>(request: &'a Value, name: &str) -> io::Result<&'a Value> {
+    request.get(name).ok_or_else(|| invalid(format!("Missing argument '{}'", name)))
+}
+
+/// Base de datos de `"db"`: número de la tabla de handles o alias de `registry`
+fn database(request: &Value) -> io::Result<Arc<Database>> {
+    let found = match arg(request, "db")? {
+        Value::Number(n) => n.as_u64().and_then(|h| HANDLES.lock().as_ref()?.get(&h).cloned()),
+        Value::String(alias) => registry::named(alias),
+        _ => None,
+    };
+    found.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown database {}", request["db"])))
+}
+
+fn collection(request: &Value) -> io::Result<Arc<Collection>> {
+    database(request)?.collection(str_arg(request, "collection")?)
+}
+
+/// Valores de consulta como texto, igual que en el resto de la FFI
+fn query_value(request: &Value) -> io::Result<String> {
+    Ok(match arg(request, "value")? {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
+
+fn run(request: &Value) -> io::Result<Value> {
+    match str_arg(request, "cmd")? {
+        "open" => {
+            let db = Database::open_shared(str_arg(request, "path")?)?;
+            let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+            HANDLES.lock().get_or_insert_with(HashMap::new).insert(handle, db);
+            Ok(json!(handle))
+        }
+        "close" => {
+            let handle = arg(request, "db")?.as_u64().ok_or_else(|| invalid("'db' must be a handle number".to_string()))?;
+            Ok(json!(HANDLES.lock().as_mut().is_some_and(|handles| handles.remove(&handle).is_some())))
+        }
+        "insert" => Ok(json!(collection(request)?.insert(arg(request, "doc")?.clone())?)),
+        "upsert_many" => {
+            let docs: Vec<Value> = serde_json::from_value(arg(request, "docs")?.clone())?;
+            Ok(serde_json::to_value(collection(request)?.upsert_many(str_arg(request, "key")?, docs)?)?)
+        }
+        "find_all" => Ok(json!(collection(request)?.find_all())),
+        "find" => Ok(json!(collection(request)?.find(str_arg(request, "field")?, &query_value(request)?))),
+        "find_op" => {
+            let col = collection(request)?;
+            Ok(json!(col.find_with_operator(str_arg(request, "field")?, &query_value(request)?, str_arg(request, "op")?)))
+        }
+        "update" => {
+            let col = collection(request)?;
+            Ok(json!(col.update_field(str_arg(request, "id")?, str_arg(request, "field")?, arg(request, "value")?.clone())?))
+        }
+        "delete" => Ok(json!(collection(request)?.delete_by_id(str_arg(request, "id")?)?)),
+        "persist" => {
+            collection(request)?.persist_now()?;
+            Ok(Value::Null)
+        }
+        "drop_collection" => Ok(json!(database(request)?.drop_collection(str_arg(request, "collection")?)?)),
+        "kv_get" => Ok(database(request)?.kv()?.get_value(str_arg(request, "key")?).unwrap_or(Value::Null)),
+        "kv_set" => {
+            database(request)?.kv()?.set(str_arg(request, "key")?, arg(request, "value")?)?;
+            Ok(Value::Null)
+        }
+        other => Err(invalid(format!("Unknown command '{}'", other))),
+    }
+}
+
+/// Ejecuta un comando JSON y devuelve la respuesta JSON. Nunca falla: los errores van en la
+/// respuesta.
+pub fn dispatch(request: &str) -> String {
+    let result = limits::parse_json::<Value>(request)
+        .map_err(|e| if limits::is_limit_exceeded(&e) { e } else { invalid(format!("Invalid request JSON: {}", e)) })
+        .and_then(|request| run(&request));
+    match result {
+        Ok(result) => json!({"ok": true, "result": result}),
+        Err(e) => json!({"ok": false, "error": e.to_string()}),
+    }
+    .to_string()
+}
@@ -0,0 +1,240 @@
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde_json::{Map, Value};
+use crate::compression;
+use crate::storage::{self, StorageBackend};
+
+/// Clave del sobre que marca un valor cifrado en disco
+pub const ENC_KEY: &str = "$enc";
+/// Clave del sobre con el identificador de la clave usada
+pub const KID_KEY: &str = "$kid";
+
+/// Cifrador de campos. Se puede implementar para usar un KMS o HSM propio.
+pub trait FieldCipher: Send + Sync {
+    /// Identificador de la clave, se guarda junto a cada valor cifrado
+    fn key_id(&self) -> &str;
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>>;
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// Cifrador por defecto: XChaCha20-Poly1305 con nonce aleatorio por valor
+pub struct FieldKey {
+    id: String,
+    cipher: XChaCha20Poly1305,
+}
+
+impl FieldKey {
+    pub fn new(id: &str, key: [u8; 32]) -> Self {
+        Self {
+            id: id.to_string(),
+            cipher: XChaCha20Poly1305::new(&key.into()),
+        }
+    }
+
+    /// Crea la clave a partir de 64 caracteres hexadecimales
+    pub fn from_hex(id: &str, key_hex: &str) -> io::Result<Self> {
+        let bytes = hex_decode(key_hex)?;
+        let key: [u8; 32] = bytes.try_into().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Field key must be 32 bytes")
+        })?;
+        Ok(Self::new(id, key))
+    }
+}
+
+impl FieldCipher for FieldKey {
+    fn key_id(&self) -> &str {
+        &self.id
+    }
+
+    fn encrypt(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut out = nonce.to_vec();
+        let sealed = self.cipher.encrypt(&nonce, plaintext)
+            .map_err(|_| io::Error::other("Field encryption failed"))?;
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> io::Result<Vec<u8>> {
+        if ciphertext.len() < 24 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Encrypted value too short"));
+        }
+        let (nonce, sealed) = ciphertext.split_at(24);
+        self.cipher.decrypt(XNonce::from_slice(nonce), sealed)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Field decryption failed (wrong key?)"))
+    }
+}
+
+/// Campos a cifrar en una colección y el cifrador a usar
+#[derive(Clone)]
+pub struct FieldEncryption {
+    pub fields: Vec<String>,
+    pub cipher: Arc<dyn FieldCipher>,
+}
+
+impl FieldEncryption {
+    pub fn new(fields: &[&str], cipher: Arc<dyn FieldCipher>) -> Self {
+        Self {
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+            cipher,
+        }
+    }
+
+    /// Devuelve una copia del documento con los campos marcados cifrados
+    pub fn seal(&self, doc: &Value) -> io::Result<Value> {
+        let mut out = doc.clone();
+        if let Some(obj) = out.as_object_mut() {
+            for field in &self.fields {
+                if let Some(value) = obj.get_mut(field.as_str()) {
+                    if !is_envelope(value) {
+                        *value = seal_value(self.cipher.as_ref(), value)?;
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    /// Descifra en el sitio los sobres de los campos marcados
+    pub fn open(&self, doc: &mut Value) -> io::Result<()> {
+        if let Some(obj) = doc.as_object_mut() {
+            for field in &self.fields {
+                if let Some(value) = obj.get_mut(field.as_str()) {
+                    if is_envelope(value) {
+                        *value = open_value(self.cipher.as_ref(), value)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn is_envelope(value: &Value) -> bool {
+    matches!(value, Value::Object(obj) if obj.contains_key(ENC_KEY))
+}
+
+/// Identificador de clave de un sobre cifrado
+pub fn envelope_key_id(value: &Value) -> Option<&str> {
+    value.get(KID_KEY).and_then(|v| v.as_str())
+}
+
+pub fn seal_value(cipher: &dyn FieldCipher, value: &Value) -> io::Result<Value> {
+    let plain = serde_json::to_vec(value)?;
+    let sealed = cipher.encrypt(&plain)?;
+    let mut env = Map::new();
+    env.insert(ENC_KEY.to_string(), Value::String(hex_encode(&sealed)));
+    env.insert(KID_KEY.to_string(), Value::String(cipher.key_id().to_string()));
+    Ok(Value::Object(env))
+}
+
+pub fn open_value(cipher: &dyn FieldCipher, envelope: &Value) -> io::Result<Value> {
+    if let Some(kid) = envelope_key_id(envelope) {
+        if kid != cipher.key_id() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Value encrypted with key '{}', expected '{}'", kid, cipher.key_id()),
+            ));
+        }
+    }
+    let hex = envelope.get(ENC_KEY).and_then(|v| v.as_str()).unwrap_or("");
+    let plain = cipher.decrypt(&hex_decode(hex)?)?;
+    Ok(serde_json::from_slice(&plain)?)
+}
+
+/// Re-cifra con `new` todos los sobres de `old` de un archivo de colección cerrado.
+/// El archivo se sustituye entero, así una interrupción deja el original intacto y volver a
+/// llamar continúa con los sobres que aún usan `old`.
+pub fn rotate_file(path: &Path, old: &dyn FieldCipher, new: &dyn FieldCipher) -> io::Result<bool> {
+    let (storage, name) = storage::local_file(path)?;
+    rotate_stored(&storage, &name, old, new)
+}
+
+/// `rotate_file` sobre el archivo `name` de `storage`
+pub(crate) fn rotate_stored(storage: &dyn StorageBackend, name: &str, old: &dyn FieldCipher, new: &dyn FieldCipher) -> io::Result<bool> {
+    let bytes = storage::read_all(storage, name)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("File '{}' not found", name)))?;
+    match rotate_lines(&bytes, old, new)? {
+        Some(writer) => storage.replace(name, &writer).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// `rotate_stored` sobre un archivo de documentos fríos (`Archive`), que se reescribe como un
+/// solo miembro gzip. `false` también si no existe.
+pub(crate) fn rotate_archive(storage: &dyn StorageBackend, name: &str, old: &dyn FieldCipher, new: &dyn FieldCipher) -> io::Result<bool> {
+    let Some(packed) = storage::read_all(storage, name)? else { return Ok(false); };
+    let mut bytes = Vec::new();
+    MultiGzDecoder::new(packed.as_slice()).read_to_end(&mut bytes)?;
+    let Some(lines) = rotate_lines(&bytes, old, new)? else { return Ok(false); };
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&lines)?;
+    storage.replace(name, &encoder.finish()?)?;
+    storage.sync(name)?;
+    Ok(true)
+}
+
+/// Líneas con los sobres de `old` re-cifrados con `new`; `None` si no había ninguno
+fn rotate_lines(bytes: &[u8], old: &dyn FieldCipher, new: &dyn FieldCipher) -> io::Result<Option<Vec<u8>>> {
+    let mut writer = Vec::new();
+    let mut changed = false;
+
+    for line in bytes.lines() {
+        let line = line?;
+        let mut doc = match serde_json::from_str::<Value>(&line) {
+            Ok(doc) => doc,
+            Err(_) => {
+                writeln!(writer, "{}", line)?;
+                continue;
+            }
+        };
+        let compressed = compression::is_compressed(&doc);
+        compression::expand(&mut doc)?;
+        if let Some(obj) = doc.as_object_mut() {
+            for value in obj.values_mut() {
+                if is_envelope(value) && envelope_key_id(value) == Some(old.key_id()) {
+                    let plain = open_value(old, value)?;
+                    *value = seal_value(new, &plain)?;
+                    changed = true;
+                }
+            }
+        }
+        let line = serde_json::to_string(&doc)?;
+        match compressed {
+            true => writeln!(writer, "{}", compression::compress_line(&doc, line)?)?,
+            false => writeln!(writer, "{}", line)?,
+        }
+    }
+    Ok(changed.then_some(writer))
+}
+
+pub fn hex_encode(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        out.push(DIGITS[(b >> 4) as usize] as char);
+        out.push(DIGITS[(b & 0x0f) as usize] as char);
+    }
+    out
+}
+
+pub fn hex_decode(s: &str) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid hex string");
+    if !s.len().is_multiple_of(2) {
+        return Err(invalid());
+    }
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let hi = (pair[0] as char).to_digit(16).ok_or_else(invalid)?;
+            let lo = (pair[1] as char).to_digit(16).ok_or_else(invalid)?;
+            Ok((hi * 16 + lo) as u8)
+        })
+        .collect()
+}
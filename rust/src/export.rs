@@ -0,0 +1,100 @@
+//! Exportación de documentos a JSON por líneas, con filtro, proyección y formato de salida.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use serde_json::{Map, Value};
+use crate::collection::Collection;
+use crate::expr;
+use crate::format::JsonFormat;
+
+pub type DocFilter = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+/// Qué documentos y campos exportar
+#[derive(Clone, Default)]
+pub struct ExportOptions {
+    pub filter: Option<DocFilter>,
+    /// Solo estos campos (y `_id`). Admite rutas con puntos (`address.city`).
+    pub include: Option<Vec<String>>,
+    /// Campos que se quitan, p. ej. para exportaciones anonimizadas
+    pub exclude: Vec<String>,
+    /// Con `pretty` cada documento ocupa varias líneas (sigue siendo un flujo JSON válido)
+    pub format: JsonFormat,
+}
+
+impl ExportOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter<F: Fn(&Value) -> bool + Send + Sync + 'static>(mut self, filter: F) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+
+    pub fn include(mut self, fields: &[&str]) -> Self {
+        self.include = Some(fields.iter().map(|f| f.to_string()).collect());
+        self
+    }
+
+    pub fn exclude(mut self, fields: &[&str]) -> Self {
+        self.exclude.extend(fields.iter().map(|f| f.to_string()));
+        self
+    }
+
+    pub fn format(mut self, format: JsonFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Aplica la proyección a un documento
+    pub fn project(&self, doc: &Value) -> Value {
+        let mut out = match &self.include {
+            Some(fields) => {
+                let mut out = Map::new();
+                if let Some(id) = doc.get("_id") {
+                    out.insert("_id".to_string(), id.clone());
+                }
+                for path in fields {
+                    // Una clave con puntos literal se copia tal cual, como la lee `field_value`
+                    if let Some(value) = doc.get(path.as_str()) {
+                        out.insert(path.clone(), value.clone());
+                    } else if let Some(value) = expr::field_value(doc, path) {
+                        // Solo falla si otro campo incluido ya puso ahí un valor que no es objeto
+                        let _ = expr::set_field(&mut out, path, value.clone());
+                    }
+                }
+                Value::Object(out)
+            }
+            None => doc.clone(),
+        };
+        if let Some(obj) = out.as_object_mut() {
+            for path in &self.exclude {
+                expr::remove_field(obj, path);
+            }
+        }
+        out
+    }
+}
+
+impl Collection {
+    /// Escribe un documento JSON por línea. Devuelve cuántos se exportaron.
+    pub fn export<W: Write>(&self, writer: W, options: &ExportOptions) -> io::Result<usize> {
+        let mut writer = BufWriter::new(writer);
+        let mut count = 0;
+        for doc in self.snapshot().iter() {
+            if options.filter.as_ref().is_some_and(|filter| !filter(doc)) {
+                continue;
+            }
+            writeln!(writer, "{}", options.format.to_string(&options.project(doc))?)?;
+            count += 1;
+        }
+        writer.flush()?;
+        Ok(count)
+    }
+
+    pub fn export_to_file(&self, path: &Path, options: &ExportOptions) -> io::Result<usize> {
+        self.export(File::create(path)?, options)
+    }
+}
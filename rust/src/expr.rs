@@ -0,0 +1,468 @@
+//! Expresiones sobre documentos para proyecciones calculadas. Sintaxis JSON al estilo de
+//! MongoDB: `"$campo"` (admite rutas con puntos) referencia un campo, un objeto con una sola
+//! clave `$operador` es una operación y cualquier otro valor es un literal.
+//!
+//! ```json
+//! {"nombre": {"$concat": ["$first", " ", "$last"]},
+//!  "total": {"$multiply": ["$price", "$qty"]},
+//!  "tipo": {"$cond": [{"$gte": ["$qty", 10]}, "mayorista", "minorista"]},
+//!  "año": {"$year": "$created_at"}}
+//! ```
+//!
+//! `{"$fn": "nombre", "field": "campo"}` llama a un predicado registrado (ver `functions`).
+
+use std::cmp::Ordering;
+use std::io;
+use serde_json::{Map, Number, Value};
+use crate::collection::Collection;
+use crate::datetime::{self, Civil};
+use crate::functions::Predicate;
+use crate::types;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArithOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatePart {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+    Second,
+    /// 0 = domingo ... 6 = sábado
+    DayOfWeek,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Field(String),
+    /// Concatena strings; números y booleanos se convierten a texto y un `null` da `null`
+    Concat(Vec<Expr>),
+    /// Operación numérica de izquierda a derecha; cualquier operando no numérico da `null`
+    Arith(ArithOp, Vec<Expr>),
+    Compare(CmpOp, Box<Expr>, Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Cond {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    /// Primer valor no nulo
+    IfNull(Vec<Expr>),
+    /// Parte de una fecha (epoch en ms o ISO-8601, en UTC)
+    Date(DatePart, Box<Expr>),
+    /// Predicado registrado aplicado al valor de la expresión
+    Call(Predicate, Box<Expr>),
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl Expr {
+    pub fn field(path: &str) -> Self {
+        Expr::Field(path.to_string())
+    }
+
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        match spec {
+            Value::String(s) if s.starts_with('$') && s.len() > 1 => Ok(Expr::Field(s[1..].to_string())),
+            Value::Object(obj) if obj.contains_key("$fn") => Self::parse_call(obj),
+            Value::Object(obj) if obj.len() == 1 && obj.keys().all(|k| k.starts_with('$')) => {
+                let (op, args) = obj.iter().next().expect("one entry");
+                Self::parse_op(op, args)
+            }
+            other => Ok(Expr::Literal(other.clone())),
+        }
+    }
+
+    /// `{"$fn": nombre, "field": ruta}` o `{"$fn": nombre, "arg": expresión}`
+    fn parse_call(obj: &Map<String, Value>) -> io::Result<Self> {
+        let name = obj["$fn"].as_str().ok_or_else(|| invalid("'$fn' must be a function name".to_string()))?;
+        let arg = match (obj.get("field"), obj.get("arg"), obj.len()) {
+            (Some(Value::String(path)), None, 2) => Expr::field(path),
+            (None, Some(arg), 2) => Expr::parse(arg)?,
+            _ => return Err(invalid(format!("'$fn' needs exactly one of 'field' or 'arg': {}", Value::Object(obj.clone())))),
+        };
+        Ok(Expr::Call(Predicate::resolve(name)?, Box::new(arg)))
+    }
+
+    fn parse_op(op: &str, args: &Value) -> io::Result<Self> {
+        let list = |args: &Value| -> io::Result<Vec<Expr>> {
+            match args {
+                Value::Array(items) => items.iter().map(Expr::parse).collect(),
+                single => Ok(vec![Expr::parse(single)?]),
+            }
+        };
+        let pair = |args: &Value| -> io::Result<(Box<Expr>, Box<Expr>)> {
+            match list(args)?.as_slice() {
+                [a, b] => Ok((Box::new(a.clone()), Box::new(b.clone()))),
+                _ => Err(invalid(format!("'{}' needs two arguments", op))),
+            }
+        };
+        let date = |part: DatePart| -> io::Result<Expr> { Ok(Expr::Date(part, Box::new(Expr::parse(args)?))) };
+
+        match op {
+            "$literal" => Ok(Expr::Literal(args.clone())),
+            "$concat" => Ok(Expr::Concat(list(args)?)),
+            "$add" => Ok(Expr::Arith(ArithOp::Add, list(args)?)),
+            "$subtract" => Ok(Expr::Arith(ArithOp::Subtract, list(args)?)),
+            "$multiply" => Ok(Expr::Arith(ArithOp::Multiply, list(args)?)),
+            "$divide" => Ok(Expr::Arith(ArithOp::Divide, list(args)?)),
+            "$eq" | "$ne" | "$gt" | "$gte" | "$lt" | "$lte" => {
+                let cmp = match op {
+                    "$eq" => CmpOp::Eq,
+                    "$ne" => CmpOp::Ne,
+                    "$gt" => CmpOp::Gt,
+                    "$gte" => CmpOp::Gte,
+                    "$lt" => CmpOp::Lt,
+                    _ => CmpOp::Lte,
+                };
+                let (a, b) = pair(args)?;
+                Ok(Expr::Compare(cmp, a, b))
+            }
+            "$and" => Ok(Expr::And(list(args)?)),
+            "$or" => Ok(Expr::Or(list(args)?)),
+            "$not" => Ok(Expr::Not(Box::new(Expr::parse(args)?))),
+            "$ifNull" => Ok(Expr::IfNull(list(args)?)),
+            "$cond" => {
+                let (condition, then, otherwise) = match args {
+                    Value::Array(items) if items.len() == 3 => (&items[0], &items[1], &items[2]),
+                    Value::Object(obj) => match (obj.get("if"), obj.get("then"), obj.get("else")) {
+                        (Some(c), Some(t), Some(e)) => (c, t, e),
+                        _ => return Err(invalid("'$cond' needs 'if', 'then' and 'else'".to_string())),
+                    },
+                    _ => return Err(invalid("'$cond' needs [if, then, else]".to_string())),
+                };
+                Ok(Expr::Cond {
+                    condition: Box::new(Expr::parse(condition)?),
+                    then: Box::new(Expr::parse(then)?),
+                    otherwise: Box::new(Expr::parse(otherwise)?),
+                })
+            }
+            "$year" => date(DatePart::Year),
+            "$month" => date(DatePart::Month),
+            "$dayOfMonth" => date(DatePart::Day),
+            "$hour" => date(DatePart::Hour),
+            "$minute" => date(DatePart::Minute),
+            "$second" => date(DatePart::Second),
+            "$dayOfWeek" => date(DatePart::DayOfWeek),
+            _ => Err(invalid(format!("Unknown operator '{}'", op))),
+        }
+    }
+
+    /// Campos de primer nivel que lee la expresión
+    pub fn top_level_fields(&self, out: &mut Vec<String>) {
+        let mut add = |expr: &Expr| expr.top_level_fields(out);
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Field(path) => {
+                let field = path.split('.').next().unwrap_or(path);
+                if !out.iter().any(|f| f == field) {
+                    out.push(field.to_string());
+                }
+            }
+            Expr::Concat(args) | Expr::Arith(_, args) | Expr::And(args) | Expr::Or(args) | Expr::IfNull(args) => {
+                args.iter().for_each(add);
+            }
+            Expr::Compare(_, a, b) => {
+                add(a);
+                add(b);
+            }
+            Expr::Not(expr) | Expr::Date(_, expr) | Expr::Call(_, expr) => add(expr),
+            Expr::Cond { condition, then, otherwise } => {
+                add(condition);
+                add(then);
+                add(otherwise);
+            }
+        }
+    }
+
+    pub fn eval(&self, doc: &Value) -> Value {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(path) => get_path(doc, path).cloned().unwrap_or(Value::Null),
+            Expr::Concat(parts) => {
+                let mut out = String::new();
+                for part in parts {
+                    match part.eval(doc) {
+                        Value::String(s) => out.push_str(&s),
+                        Value::Null => return Value::Null,
+                        Value::Number(n) => out.push_str(&n.to_string()),
+                        Value::Bool(b) => out.push_str(&b.to_string()),
+                        other => out.push_str(&other.to_string()),
+                    }
+                }
+                Value::String(out)
+            }
+            Expr::Arith(op, args) => {
+                let mut numbers = Vec::with_capacity(args.len());
+                for arg in args {
+                    match arg.eval(doc) {
+                        Value::Number(n) => numbers.push(n),
+                        _ => return Value::Null,
+                    }
+                }
+                if let Some(result) = integer_arith(*op, &numbers) {
+                    return Value::Number(result);
+                }
+                let Some(values) = numbers.iter().map(Number::as_f64).collect::<Option<Vec<f64>>>() else {
+                    return Value::Null;
+                };
+                let mut iter = values.into_iter();
+                let first = match iter.next() {
+                    Some(first) => first,
+                    None => return Value::Null,
+                };
+                let result = iter.try_fold(first, |acc, n| match op {
+                    ArithOp::Add => Some(acc + n),
+                    ArithOp::Subtract => Some(acc - n),
+                    ArithOp::Multiply => Some(acc * n),
+                    ArithOp::Divide if n == 0.0 => None,
+                    ArithOp::Divide => Some(acc / n),
+                });
+                result.map_or(Value::Null, number)
+            }
+            Expr::Compare(op, a, b) => {
+                let ordering = compare_values(&a.eval(doc), &b.eval(doc));
+                Value::Bool(match op {
+                    CmpOp::Eq => ordering == Some(Ordering::Equal),
+                    CmpOp::Ne => ordering != Some(Ordering::Equal),
+                    CmpOp::Gt => ordering == Some(Ordering::Greater),
+                    CmpOp::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    CmpOp::Lt => ordering == Some(Ordering::Less),
+                    CmpOp::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                })
+            }
+            Expr::And(args) => Value::Bool(args.iter().all(|a| truthy(&a.eval(doc)))),
+            Expr::Or(args) => Value::Bool(args.iter().any(|a| truthy(&a.eval(doc)))),
+            Expr::Not(arg) => Value::Bool(!truthy(&arg.eval(doc))),
+            Expr::Cond { condition, then, otherwise } => {
+                if truthy(&condition.eval(doc)) { then.eval(doc) } else { otherwise.eval(doc) }
+            }
+            Expr::IfNull(args) => args.iter()
+                .map(|a| a.eval(doc))
+                .find(|v| !v.is_null())
+                .unwrap_or(Value::Null),
+            Expr::Date(part, arg) => match datetime::value_to_millis(&arg.eval(doc)) {
+                Some(ms) => {
+                    let c = Civil::from_millis(ms);
+                    Value::from(match part {
+                        DatePart::Year => c.year,
+                        DatePart::Month => c.month as i64,
+                        DatePart::Day => c.day as i64,
+                        DatePart::Hour => c.hour as i64,
+                        DatePart::Minute => c.minute as i64,
+                        DatePart::Second => c.second as i64,
+                        DatePart::DayOfWeek => c.weekday() as i64,
+                    })
+                }
+                None => Value::Null,
+            },
+            Expr::Call(predicate, arg) => Value::Bool(predicate.call(&arg.eval(doc))),
+        }
+    }
+
+    /// Como filtro: el documento pasa si el resultado es verdadero (ver `truthy`)
+    pub fn matches(&self, doc: &Value) -> bool {
+        truthy(&self.eval(doc))
+    }
+}
+
+/// Suma, resta y producto exactos de enteros (sin pasar por f64, que pierde precisión por
+/// encima de 2^53). `None` si hay decimales, es una división o el resultado no cabe en un
+/// `Number` (más allá de 64 bits solo con la feature `arbitrary-precision`).
+fn integer_arith(op: ArithOp, numbers: &[Number]) -> Option<Number> {
+    let mut values = numbers.iter().map(Number::as_i128);
+    let first = values.next()??;
+    let result = values.try_fold(first, |acc, n| match op {
+        ArithOp::Add => acc.checked_add(n?),
+        ArithOp::Subtract => acc.checked_sub(n?),
+        ArithOp::Multiply => acc.checked_mul(n?),
+        ArithOp::Divide => None,
+    })?;
+    Number::from_i128(result)
+}
+
+/// Enteros cuando el resultado no tiene decimales, para que `2 + 3` dé `5` y no `5.0`
+pub(crate) fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9.0e15 {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// Falsos: `null`, `false`, `0` y el campo ausente
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        _ => true,
+    }
+}
+
+/// Orden entre valores del mismo tipo (números, strings, booleanos); `None` si no son comparables
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (x, y) if types::is_typed(x) || types::is_typed(y) => types::compare(x, y),
+        (Value::Number(x), Value::Number(y)) => compare_numbers(x, y),
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (x, y) if x == y => Some(Ordering::Equal),
+        _ => None,
+    }
+}
+
+/// Orden exacto entre números: los enteros como enteros y, con la feature
+/// `arbitrary-precision`, los decimales por su texto; el resto como f64
+pub fn compare_numbers(a: &Number, b: &Number) -> Option<Ordering> {
+    if let (Some(x), Some(y)) = (a.as_i128(), b.as_i128()) {
+        return Some(x.cmp(&y));
+    }
+    #[cfg(feature = "arbitrary-precision")]
+    if let Some(ordering) = types::compare_decimal_text(a.as_str(), b.as_str()) {
+        return Some(ordering);
+    }
+    a.as_f64()?.partial_cmp(&b.as_f64()?)
+}
+
+pub(crate) fn get_path<'a>(doc: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(doc, |current, part| current.get(part))
+}
+
+/// Campo de una consulta: la clave tal cual si existe (`"a.b"` literal) y si no, la ruta
+/// con puntos (`address.city`)
+pub(crate) fn field_value<'a>(doc: &'a Value, field: &str) -> Option<&'a Value> {
+    match doc.get(field) {
+        Some(value) => Some(value),
+        None if field.contains('.') => get_path(doc, field),
+        None => None,
+    }
+}
+
+/// Escribe `value` en `field` con el mismo criterio que `field_value`, creando los objetos
+/// intermedios que falten. Error si la ruta pasa por un valor que no es un objeto.
+pub(crate) fn set_field(obj: &mut Map<String, Value>, field: &str, value: Value) -> io::Result<()> {
+    if !field.contains('.') || obj.contains_key(field) {
+        obj.insert(field.to_string(), value);
+        return Ok(());
+    }
+    let (parents, last) = field.rsplit_once('.').unwrap_or(("", field));
+    let mut current = obj;
+    for part in parents.split('.') {
+        current = current.entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Field '{}' is not an object in '{}'", part, field)))?;
+    }
+    current.insert(last.to_string(), value);
+    Ok(())
+}
+
+/// Quita `field` con el mismo criterio que `field_value`. Devuelve el valor quitado.
+pub(crate) fn remove_field(obj: &mut Map<String, Value>, field: &str) -> Option<Value> {
+    if let Some(value) = obj.remove(field) {
+        return Some(value);
+    }
+    let (parents, last) = field.rsplit_once('.')?;
+    let mut current = obj;
+    for part in parents.split('.') {
+        current = current.get_mut(part)?.as_object_mut()?;
+    }
+    current.remove(last)
+}
+
+/// Proyección: campo de salida -> expresión. En la especificación `1`/`true` copia el campo
+/// con el mismo nombre y cualquier otro valor es una expresión. `_id` se incluye siempre
+/// salvo `"_id": 0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Projection {
+    fields: Vec<(String, Expr)>,
+    include_id: bool,
+}
+
+impl Projection {
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Projection must be an object".to_string()))?;
+        let mut fields = Vec::new();
+        let mut include_id = true;
+        for (name, value) in obj {
+            match value {
+                Value::Bool(false) if name == "_id" => include_id = false,
+                Value::Number(n) if name == "_id" && n.as_i64() == Some(0) => include_id = false,
+                Value::Bool(true) => fields.push((name.clone(), Expr::Field(name.clone()))),
+                Value::Number(n) if n.as_i64() == Some(1) => fields.push((name.clone(), Expr::Field(name.clone()))),
+                other => fields.push((name.clone(), Expr::parse(other)?)),
+            }
+        }
+        Ok(Self { fields, include_id })
+    }
+
+    /// Copia los campos `fields` (admiten rutas con puntos) y `_id`
+    pub fn fields(fields: &[&str]) -> Self {
+        Self {
+            fields: fields.iter().map(|field| (field.to_string(), Expr::Field(field.to_string()))).collect(),
+            include_id: true,
+        }
+    }
+
+    /// Campos de primer nivel que hacen falta para aplicar la proyección (sin contar `_id`)
+    pub fn source_fields(&self) -> Vec<String> {
+        let mut fields = Vec::new();
+        for (_, expr) in &self.fields {
+            expr.top_level_fields(&mut fields);
+        }
+        fields
+    }
+
+    pub fn apply(&self, doc: &Value) -> Value {
+        let mut out = Map::new();
+        if self.include_id {
+            if let Some(id) = doc.get("_id") {
+                out.insert("_id".to_string(), id.clone());
+            }
+        }
+        for (name, expr) in &self.fields {
+            out.insert(name.clone(), expr.eval(doc));
+        }
+        Value::Object(out)
+    }
+}
+
+impl Collection {
+    /// Documentos que cumplen `filter`, transformados por la proyección
+    pub fn find_projected<F: Fn(&Value) -> bool>(&self, filter: F, projection: &Projection) -> Vec<Value> {
+        self.snapshot().iter()
+            .filter(|doc| filter(doc))
+            .map(|doc| projection.apply(doc))
+            .collect()
+    }
+
+    /// Documentos para los que la expresión es verdadera, p. ej.
+    /// `{"$and": [{"$fn": "is_weekend", "field": "created_at"}, {"$gt": ["$total", 100]}]}`
+    pub fn find_expr(&self, filter: &Expr) -> Vec<Value> {
+        self.snapshot().iter().filter(|doc| filter.matches(doc)).cloned().collect()
+    }
+}
@@ -0,0 +1,87 @@
+//! Conteo de valores por campo (facetas) calculado en la misma pasada que el filtro, y valores
+//! distintos de un campo.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::collection::Collection;
+use crate::expr;
+use crate::filter::Filter;
+use crate::window::sort_order;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacetCount {
+    pub value: Value,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacetedResult {
+    pub documents: Vec<Value>,
+    /// Por campo, los valores de los documentos filtrados, los más frecuentes primero
+    pub facets: HashMap<String, Vec<FacetCount>>,
+}
+
+impl Collection {
+    /// Documentos que cumplen `filter` y, para cada campo de `fields`, cuántos de ellos tienen
+    /// cada valor. En los arrays (p. ej. etiquetas) cuenta cada elemento; los campos ausentes
+    /// o nulos no cuentan.
+    pub fn find_with_facets<F: Fn(&Value) -> bool>(&self, filter: F, fields: &[&str]) -> FacetedResult {
+        let mut documents = Vec::new();
+        // Clave: el valor serializado, para agrupar cualquier tipo JSON
+        let mut counts: Vec<HashMap<String, FacetCount>> = vec![HashMap::new(); fields.len()];
+
+        for doc in self.snapshot().iter().filter(|doc| filter(doc)) {
+            for (field, field_counts) in fields.iter().zip(counts.iter_mut()) {
+                let values: &[Value] = match doc.get(*field) {
+                    Some(Value::Array(items)) => items,
+                    Some(Value::Null) | None => &[],
+                    Some(value) => std::slice::from_ref(value),
+                };
+                for value in values.iter().filter(|v| !v.is_null()) {
+                    field_counts.entry(value.to_string())
+                        .or_insert_with(|| FacetCount { value: value.clone(), count: 0 })
+                        .count += 1;
+                }
+            }
+            documents.push(doc.clone());
+        }
+
+        let facets = fields.iter().zip(counts)
+            .map(|(field, field_counts)| {
+                let mut sorted: Vec<(String, FacetCount)> = field_counts.into_iter().collect();
+                sorted.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(&b.0)));
+                (field.to_string(), sorted.into_iter().map(|(_, count)| count).collect())
+            })
+            .collect();
+
+        FacetedResult { documents, facets }
+    }
+
+    /// Valores distintos de `field` (admite rutas con puntos) en los documentos que cumplen
+    /// `filter` (ver `filter`; todos sin filtro), en el orden de `sort_documents`. Como en las
+    /// facetas, de los arrays cuenta cada elemento y los campos ausentes o nulos no cuentan.
+    pub fn distinct(&self, field: &str, filter: Option<Value>) -> io::Result<Vec<Value>> {
+        let filter = filter.unwrap_or_else(|| json!({}));
+        let parsed = Filter::parse(&filter)?;
+        self.cached(&["distinct", field, &filter.to_string()], |data, indexes| {
+            let mut seen = HashSet::new();
+            let mut values = Vec::new();
+            for doc in parsed.select(data, indexes, self) {
+                let found: &[Value] = match expr::field_value(doc, field) {
+                    Some(Value::Array(items)) => items,
+                    Some(Value::Null) | None => &[],
+                    Some(value) => std::slice::from_ref(value),
+                };
+                for value in found.iter().filter(|v| !v.is_null()) {
+                    if seen.insert(value.to_string()) {
+                        values.push(value.clone());
+                    }
+                }
+            }
+            values.sort_by(|a, b| sort_order(Some(a), Some(b)));
+            Ok(values)
+        })
+    }
+}
@@ -0,0 +1,140 @@
+//! Inyección de fallos para los tests de integración del host (feature `fault-injection`):
+//! hacer fallar la escritura número N, simular un disco lleno o devolver lecturas cortas, y
+//! comprobar así que la aplicación se recupera de fallos reales de Ruggy.
+//!
+//! Los fallos se inyectan en `LocalStorage`: cuenta como escritura cada `append` o `replace`
+//! de un archivo de la base de datos (un insert, la reescritura completa de un `.col`, cada
+//! cambio de época, una pasada de archivado...). Los fallos son para todo el proceso; con
+//! `only_under` se limitan a los archivos de un directorio, para que los tests que se
+//! ejecutan en paralelo no se afecten entre sí. Desde el host, con `ruggy_fault_*` en la FFI.
+//!
+//! ```ignore
+//! faults::only_under(&dir);
+//! faults::fail_nth_write(3);
+//! assert!(col.insert(doc).is_err());
+//! faults::reset();
+//! ```
+
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+#[derive(Debug, Default)]
+struct State {
+    scope: Option<PathBuf>,
+    /// Escrituras que faltan hasta la que falla (la siguiente es 1)
+    fail_in: Option<u64>,
+    /// Bytes que quedan antes de que el disco se llene
+    space_left: Option<u64>,
+    short_reads: Option<usize>,
+    stats: FaultStats,
+}
+
+/// Contadores desde el último `reset`, solo de los archivos dentro del ámbito
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FaultStats {
+    pub writes: u64,
+    pub bytes_written: u64,
+    /// Escrituras que se han hecho fallar
+    pub failed_writes: u64,
+    /// Lecturas que han devuelto menos bytes de los pedidos por la inyección
+    pub short_reads: u64,
+}
+
+static STATE: Mutex<Option<State>> = parking_lot::const_mutex(None);
+
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    f(STATE.lock().get_or_insert_with(State::default))
+}
+
+/// Quita todos los fallos y el ámbito y pone los contadores a cero
+pub fn reset() {
+    *STATE.lock() = None;
+}
+
+/// Solo se inyectan fallos (y se cuentan escrituras) en archivos dentro de `dir`
+pub fn only_under(dir: &Path) {
+    with_state(|state| state.scope = Some(dir.to_path_buf()));
+}
+
+/// La `n`-ésima escritura a partir de ahora (1 = la siguiente) falla con un error de IO;
+/// las demás funcionan
+pub fn fail_nth_write(n: u64) {
+    with_state(|state| state.fail_in = Some(n.max(1)));
+}
+
+/// Después de escribir `bytes` bytes más, todas las escrituras fallan como con el disco lleno
+/// hasta `reset`
+pub fn disk_full_after(bytes: u64) {
+    with_state(|state| state.space_left = Some(bytes));
+}
+
+/// Las lecturas de los archivos de datos devuelven como mucho `max_bytes` por llamada
+pub fn short_reads(max_bytes: usize) {
+    with_state(|state| state.short_reads = Some(max_bytes.max(1)));
+}
+
+pub fn stats() -> FaultStats {
+    with_state(|state| state.stats)
+}
+
+fn in_scope(state: &State, path: &Path) -> bool {
+    state.scope.as_ref().is_none_or(|scope| path.starts_with(scope))
+}
+
+/// Se llama antes de escribir `len` bytes en `path`
+pub(crate) fn before_write(path: &Path, len: usize) -> io::Result<()> {
+    let mut guard = STATE.lock();
+    let Some(state) = guard.as_mut() else { return Ok(()); };
+    if !in_scope(state, path) {
+        return Ok(());
+    }
+    state.stats.writes += 1;
+    if let Some(fail_in) = state.fail_in {
+        if fail_in <= 1 {
+            state.fail_in = None;
+            state.stats.failed_writes += 1;
+            return Err(io::Error::other(format!("Injected write failure on '{}'", path.display())));
+        }
+        state.fail_in = Some(fail_in - 1);
+    }
+    if let Some(space_left) = state.space_left {
+        if len as u64 > space_left {
+            state.space_left = Some(0);
+            state.stats.failed_writes += 1;
+            return Err(io::Error::new(io::ErrorKind::StorageFull, "No space left on device (injected)"));
+        }
+        state.space_left = Some(space_left - len as u64);
+    }
+    state.stats.bytes_written += len as u64;
+    Ok(())
+}
+
+/// Lector de un archivo de datos que aplica `short_reads`
+pub(crate) struct FaultyReader<R> {
+    inner: R,
+    path: PathBuf,
+}
+
+pub(crate) fn reader<R: Read>(inner: R, path: &Path) -> FaultyReader<R> {
+    FaultyReader { inner, path: path.to_path_buf() }
+}
+
+impl<R: Read> Read for FaultyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let limit = {
+            let mut guard = STATE.lock();
+            match guard.as_mut() {
+                Some(state) if in_scope(state, &self.path) => state.short_reads.filter(|max| *max < buf.len()).inspect(|_| {
+                    state.stats.short_reads += 1;
+                }),
+                _ => None,
+            }
+        };
+        match limit {
+            Some(max) => self.inner.read(&mut buf[..max]),
+            None => self.inner.read(buf),
+        }
+    }
+}
@@ -1,18 +1,186 @@
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
-use std::sync::Arc;
+use std::io;
+use std::os::raw::{c_char, c_void};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
+use crate::bench::Workload;
 use crate::db::Database;
 use crate::collection::Collection;
+use crate::csv_import::CsvOptions;
+use crate::dynamic;
+use crate::expr::{get_path, Expr, Projection};
+use crate::functions::{register_function, unregister_function};
+use crate::format::JsonFormat;
+use crate::handles::{self, HandleKind};
+use crate::pack::PackBuilder;
+use crate::covered::EqQuery;
+use crate::comparator::SortKey;
+use crate::contention::{self, ContentionPolicy};
+use crate::limits::{self, ParseLimits};
+use crate::msgpack;
+use crate::page::PageQuery;
+use crate::pivot::Pivot;
+use crate::registry;
+use crate::rules::{self, Rule, UpdateExpr};
+use crate::storage::MemoryStorage;
+use crate::window::Window;
+use crate::text::Highlight;
 
-/// Helper para convertir puntero genérico C a referencia Rust
-unsafe fn from_ptr<'a, T>(ptr: *mut T) -> &'a T {
-    &*ptr
+/// Helper para validar un handle de base de datos (ver `handles`) y convertirlo a referencia
+unsafe fn db_ref<'a>(db: *mut Database) -> Option<&'a Arc<Database>> {
+    match handles::check(db, HandleKind::Database) {
+        Ok(()) => Some(&*(db as *mut Arc<Database>)),
+        Err(e) => {
+            eprintln!("Ruggy Error: {}", e);
+            None
+        },
+    }
+}
+
+/// Helper para validar un handle de colección (ver `handles`) y convertirlo a referencia
+unsafe fn col_ref<'a>(col: *mut Collection) -> Option<&'a Arc<Collection>> {
+    match handles::check(col, HandleKind::Collection) {
+        Ok(()) => Some(&*(col as *mut Arc<Collection>)),
+        Err(e) => {
+            eprintln!("Ruggy Error: {}", e);
+            None
+        },
+    }
+}
+
+/// Helper para las llamadas por alias: colección `name` de la base de datos `alias`
+unsafe fn named_col(alias: *const c_char, name: *const c_char) -> Option<Arc<Collection>> {
+    if let Err(e) = handles::check_not_in_callback() {
+        eprintln!("Ruggy Error: {}", e);
+        return None;
+    }
+    match registry::named_collection(to_str(alias), to_str(name)) {
+        Ok(col) => Some(col),
+        Err(e) => {
+            eprintln!("Ruggy Error: {}", e);
+            None
+        },
+    }
+}
+
+/// Los handles de base de datos son `Box<Arc<Database>>`, como los de colección: un mismo
+/// directorio abierto dos veces (`open_shared`) comparte la instancia
+fn return_db(db: Arc<Database>) -> *mut Database {
+    let ptr = Box::into_raw(Box::new(db)) as *mut Database;
+    handles::register(ptr, HandleKind::Database);
+    ptr
 }
 
 /// Helper para devolver un puntero CString (el que llama debe liberarlo)
 fn return_string(s: String) -> *mut c_char {
-    CString::new(s).unwrap().into_raw()
+    let s = CString::new(s).unwrap();
+    RETURNED.store(true, Ordering::Relaxed);
+    match HOST_ALLOCATOR.get() {
+        Some(host) => host.copy(s.as_bytes_with_nul()) as *mut c_char,
+        None => s.into_raw(),
+    }
+}
+
+/// Formato de los resultados de lectura (bits de `JsonFormat`), común a todo el proceso
+static OUTPUT_FORMAT: AtomicU32 = AtomicU32::new(0);
+
+/// Serializa documentos devueltos al host según `ruggy_set_output_format`
+fn documents_json(docs: &[Value]) -> String {
+    JsonFormat::from_flags(OUTPUT_FORMAT.load(Ordering::Relaxed))
+        .to_string(docs)
+        .unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Como `documents_json` con un solo documento (o `null`)
+fn document_json(doc: Option<&Value>) -> String {
+    JsonFormat::from_flags(OUTPUT_FORMAT.load(Ordering::Relaxed))
+        .to_string(&doc)
+        .unwrap_or_else(|_| "null".to_string())
+}
+
+/// Helper para devolver un buffer MessagePack: escribe su longitud en `out_len` (el que llama
+/// debe liberarlo con `ruggy_buf_free`)
+fn return_buffer(bytes: Vec<u8>, out_len: *mut usize) -> *mut u8 {
+    if out_len.is_null() {
+        return std::ptr::null_mut();
+    }
+    unsafe { *out_len = bytes.len() };
+    RETURNED.store(true, Ordering::Relaxed);
+    match HOST_ALLOCATOR.get() {
+        Some(host) => host.copy(&bytes),
+        None => Box::into_raw(bytes.into_boxed_slice()) as *mut u8,
+    }
+}
+
+fn documents_msgpack(docs: &[Value], out_len: *mut usize) -> *mut u8 {
+    match msgpack::encode(docs) {
+        Ok(bytes) => return_buffer(bytes, out_len),
+        Err(e) => {
+            eprintln!("Ruggy Error: MessagePack encoding failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Helper para decodificar un buffer MessagePack del host
+unsafe fn from_msgpack(data: *const u8, len: usize) -> Option<Value> {
+    if data.is_null() { return None; }
+    limits::check_size(len)
+        .and_then(|_| msgpack::decode(std::slice::from_raw_parts(data, len)))
+        .inspect_err(report_limit)
+        .ok()
+}
+
+/// Helper para interpretar JSON del host con los `limits::ParseLimits`
+fn parse_json<T: DeserializeOwned>(text: &str) -> io::Result<T> {
+    limits::parse_json(text).inspect_err(report_limit)
+}
+
+/// Los errores de límites se avisan siempre, aunque el que llama no diga por qué falló
+fn report_limit(err: &io::Error) {
+    if limits::is_limit_exceeded(err) {
+        eprintln!("Ruggy Error: {}", err);
+    }
+}
+
+thread_local! {
+    /// Mensaje y regla incumplida (si la hay) del último error de escritura de este hilo
+    static LAST_ERROR: RefCell<Option<(String, Option<String>)>> = const { RefCell::new(None) };
+}
+
+/// Guarda el resultado de una escritura para `ruggy_last_error` (o lo borra si fue bien)
+fn record_error<T>(result: &io::Result<T>) {
+    let last = result.as_ref().err()
+        .map(|e| (e.to_string(), rules::violated_rule(e).map(str::to_string)));
+    LAST_ERROR.with(|cell| *cell.borrow_mut() = last);
+}
+
+/// Helper para leer un filtro JSON campo -> valor exigido (vacío o null: todos); None si no es válido
+unsafe fn parse_filter(ptr: *const c_char) -> Option<serde_json::Map<String, Value>> {
+    let filter_str = to_str(ptr);
+    if filter_str.is_empty() {
+        return Some(serde_json::Map::new());
+    }
+    match parse_json(filter_str) {
+        Ok(Value::Null) => Some(serde_json::Map::new()),
+        Ok(Value::Object(obj)) => Some(obj),
+        Ok(_) => {
+            eprintln!("Ruggy Error: Query filter must be an object");
+            None
+        },
+        Err(_) => {
+            eprintln!("Ruggy Error: Failed to parse query filter");
+            None
+        },
+    }
+}
+
+fn matches_filter(doc: &Value, conditions: &serde_json::Map<String, Value>) -> bool {
+    conditions.iter().all(|(field, value)| get_path(doc, field) == Some(value))
 }
 
 /// Helper para parsear string C a &str de Rust
@@ -22,160 +190,1814 @@ unsafe fn to_str<'a>(ptr: *const c_char) -> &'a str {
 }
 
 #[no_mangle]
+// SAFETY: `path` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn ruggy_open(path: *const c_char) -> *mut Database {
     let path_str = unsafe { to_str(path) };
-    match Database::new(path_str) {
-        Ok(db) => Box::into_raw(Box::new(db)),
+    match Database::open_shared(path_str) {
+        Ok(db) => return_db(db),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Abre según el archivo de configuración `path` (o `RUGGY_CONFIG` si es null) y las
+/// variables `RUGGY_*`
+#[no_mangle]
+// SAFETY: `path` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_open_config(path: *const c_char) -> *mut Database {
+    let path_str = unsafe { to_str(path) };
+    let path = (!path_str.is_empty()).then(|| std::path::Path::new(path_str));
+    match Database::from_config(path) {
+        Ok(db) => return_db(Arc::new(db)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Open from config failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `path` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_open_follower(path: *const c_char) -> *mut Database {
+    let path_str = unsafe { to_str(path) };
+    match Database::open_follower(path_str) {
+        Ok(db) => return_db(Arc::new(db)),
         Err(_) => std::ptr::null_mut(),
     }
 }
 
+/// Base de datos que no toca el sistema de archivos; se pierde al cerrarla
 #[no_mangle]
+pub extern "C" fn ruggy_open_memory() -> *mut Database {
+    match Database::with_storage(Arc::new(MemoryStorage::new())) {
+        Ok(db) => return_db(Arc::new(db)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Abre un archivo de `ruggy_build_pack` con colecciones de solo lectura
+#[no_mangle]
+// SAFETY: `path` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_open_pack(path: *const c_char) -> *mut Database {
+    let path_str = unsafe { to_str(path) };
+    match Database::open_pack(path_str) {
+        Ok(db) => return_db(Arc::new(db)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Open pack failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Devuelve cuántas colecciones cambiaron, o -1 si hubo error
+#[no_mangle]
+// SAFETY: `db` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_refresh(db: *mut Database) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return -1; };
+    match db.refresh() {
+        Ok(changed) => changed as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Refresh failed: {}", e);
+            -1
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `name` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn ruggy_get_collection(db: *mut Database, name: *const c_char) -> *mut Collection {
-    if db.is_null() { return std::ptr::null_mut(); }
-    let db = unsafe { from_ptr(db) };
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
     let name_str = unsafe { to_str(name) };
     match db.collection(name_str) {
         Ok(col) => {
-            Box::into_raw(Box::new(col.clone())) as *mut Collection
+            let ptr = Box::into_raw(Box::new(col.clone())) as *mut Collection;
+            handles::register(ptr, HandleKind::Collection);
+            ptr
         },
         Err(_) => std::ptr::null_mut(),
     }
 }
 
+/// Abre la base de datos de `path` con el alias `alias` para usarla con `ruggy_named_*`.
+/// 1 si quedó abierta (también si ya lo estaba con esa ruta), 0 si hubo error.
 #[no_mangle]
-pub extern "C" fn ruggy_insert(col: *mut Collection, json: *const c_char) -> *mut c_char {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
+// SAFETY: `alias` y `path` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_open_named(alias: *const c_char, path: *const c_char) -> i32 {
+    if let Err(e) = handles::check_not_in_callback() {
+        eprintln!("Ruggy Error: {}", e);
+        return 0;
+    }
+    let alias_str = unsafe { to_str(alias) };
+    let path_str = unsafe { to_str(path) };
+    match registry::open_named(alias_str, path_str) {
+        Ok(_) => 1,
+        Err(e) => {
+            eprintln!("Ruggy Error: Open '{}' failed: {}", alias_str, e);
+            0
+        },
+    }
+}
 
-    let json_str = unsafe { to_str(json) };
-    let json_val: Value = match serde_json::from_str(json_str) {
+/// 1 si se quitó el alias, 0 si no existía
+#[no_mangle]
+// SAFETY: `alias` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_close_named(alias: *const c_char) -> i32 {
+    if let Err(e) = handles::check_not_in_callback() {
+        eprintln!("Ruggy Error: {}", e);
+        return 0;
+    }
+    registry::close_named(unsafe { to_str(alias) }) as i32
+}
+
+/// Handle de colección de una base de datos con alias, para usarlo con el resto de la API.
+/// Se libera con `ruggy_col_free` como cualquier otro.
+#[no_mangle]
+// SAFETY: `alias` y `name` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_collection(alias: *const c_char, name: *const c_char) -> *mut Collection {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return std::ptr::null_mut(); };
+    let ptr = Box::into_raw(Box::new(col)) as *mut Collection;
+    handles::register(ptr, HandleKind::Collection);
+    ptr
+}
+
+/// Como `ruggy_insert` sobre la colección `name` de la base de datos `alias`
+#[no_mangle]
+// SAFETY: `alias`, `name` y `json` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_insert(alias: *const c_char, name: *const c_char, json: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return std::ptr::null_mut(); };
+    let json_val: Value = match parse_json(unsafe { to_str(json) }) {
         Ok(v) => v,
         Err(_) => return std::ptr::null_mut(),
     };
-
     match col.insert(json_val) {
         Ok(id) => return_string(id),
-        Err(_) => std::ptr::null_mut(),
+        Err(e) => {
+            eprintln!("Ruggy Error: Insert failed: {}", e);
+            std::ptr::null_mut()
+        },
     }
 }
 
 #[no_mangle]
-pub extern "C" fn ruggy_find_all(col: *mut Collection) -> *mut c_char {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
+// SAFETY: `alias` y `name` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_find_all(alias: *const c_char, name: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return std::ptr::null_mut(); };
+    return_string(documents_json(&col.find_all()))
+}
 
-    let docs = col.find_all();
-    let json_out = serde_json::to_string(&docs).unwrap_or_else(|_| "[]".to_string());
-    return_string(json_out)
+#[no_mangle]
+// SAFETY: `alias`, `name`, `field` y `value` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_find(alias: *const c_char, name: *const c_char, field: *const c_char, value: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return std::ptr::null_mut(); };
+    let docs = col.find(unsafe { to_str(field) }, unsafe { to_str(value) });
+    return_string(documents_json(&docs))
 }
 
 #[no_mangle]
-pub extern "C" fn ruggy_find(col: *mut Collection, field: *const c_char, value: *const c_char) -> *mut c_char {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
-    
-    let f_str = unsafe { to_str(field) };
-    let v_str = unsafe { to_str(value) };
+// SAFETY: `alias`, `name`, `id`, `field` y `value_json` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_update_field(
+    alias: *const c_char,
+    name: *const c_char,
+    id: *const c_char,
+    field: *const c_char,
+    value_json: *const c_char
+) -> i32 {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return 0; };
+    let val: Value = match parse_json(unsafe { to_str(value_json) }) {
+        Ok(v) => v,
+        Err(_) => {
+            eprintln!("Ruggy Error: Failed to parse update JSON");
+            return 0;
+        },
+    };
+    match col.update_field(unsafe { to_str(id) }, unsafe { to_str(field) }, val) {
+        Ok(success) => success as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Update failed: {}", e);
+            0
+        },
+    }
+}
 
-    let docs = col.find(f_str, v_str);
-    let json_out = serde_json::to_string(&docs).unwrap_or_else(|_| "[]".to_string());
-    return_string(json_out)
+#[no_mangle]
+// SAFETY: `alias`, `name` y `id` son C strings terminados en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_named_delete(alias: *const c_char, name: *const c_char, id: *const c_char) -> i32 {
+    let Some(col) = (unsafe { named_col(alias, name) }) else { return 0; };
+    match col.delete_by_id(unsafe { to_str(id) }) {
+        Ok(success) => success as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Delete failed: {}", e);
+            0
+        },
+    }
 }
 
+/// Mueve de `src` a `dst` los documentos que coinciden con `filter` (mismo formato que en
+/// `ruggy_query_into`). Devuelve cuántos se movieron o -1 si hubo error.
 #[no_mangle]
-pub extern "C" fn ruggy_find_op(
-    col: *mut Collection, 
-    field: *const c_char, 
-    value: *const c_char,
-    operator: *const c_char
-) -> *mut c_char {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
-    
-    let f_str = unsafe { to_str(field) };
-    let v_str = unsafe { to_str(value) };
-    let op_str = unsafe { to_str(operator) };
+// SAFETY: `src`, `dst` y `filter` son C strings terminados en NUL o null; `db` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_move_documents(db: *mut Database, src: *const c_char, dst: *const c_char, filter: *const c_char) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return -1; };
+    let Some(conditions) = (unsafe { parse_filter(filter) }) else { return -1; };
+    let src_str = unsafe { to_str(src) };
+    let dst_str = unsafe { to_str(dst) };
 
-    let docs = col.find_with_operator(f_str, v_str, op_str);
-    let json_out = serde_json::to_string(&docs).unwrap_or_else(|_| "[]".to_string());
-    return_string(json_out)
+    match db.move_documents(src_str, dst_str, |doc| matches_filter(doc, &conditions)) {
+        Ok(moved) => moved as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Move failed: {}", e);
+            -1
+        },
+    }
 }
 
+/// Exporta la base de datos a un archivo `.ruggy`. Devuelve el manifiesto en JSON o null si
+/// hubo error.
 #[no_mangle]
-pub extern "C" fn ruggy_update_field(
-    col: *mut Collection,
-    id: *const c_char,
-    field: *const c_char,
-    value_json: *const c_char
-) -> i32 {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
+// SAFETY: `path` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_export_snapshot(db: *mut Database, path: *const c_char) -> *mut c_char {
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
+    let path_str = unsafe { to_str(path) };
+    match db.export_snapshot(path_str) {
+        Ok(manifest) => return_string(serde_json::to_string(&manifest).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Snapshot export failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
 
-    let id_str = unsafe { to_str(id) };
-    let field_str = unsafe { to_str(field) };
-    let val_json_str = unsafe { to_str(value_json) };
+/// Importa un archivo de `ruggy_export_snapshot`. Devuelve su manifiesto en JSON o null si
+/// hubo error.
+#[no_mangle]
+// SAFETY: `path` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_import_snapshot(db: *mut Database, path: *const c_char) -> *mut c_char {
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
+    let path_str = unsafe { to_str(path) };
+    match db.import_snapshot(path_str) {
+        Ok(manifest) => return_string(serde_json::to_string(&manifest).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Snapshot import failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Empaqueta en `path` las colecciones `names_json` (array de nombres) de la base de datos.
+/// Devuelve el manifiesto en JSON o null si hubo error.
+#[no_mangle]
+// SAFETY: `names_json` y `path` son C strings terminados en NUL o null; `db` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_build_pack(db: *mut Database, names_json: *const c_char, path: *const c_char) -> *mut c_char {
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
+    let path_str = unsafe { to_str(path) };
+    let names: Vec<String> = match parse_json(unsafe { to_str(names_json) }) {
+        Ok(names) => names,
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid collection names: {}", e);
+            return std::ptr::null_mut();
+        },
+    };
+    let built = names.iter()
+        .try_fold(PackBuilder::new(), |pack, name| pack.collection(db.collection(name)?.as_ref()))
+        .and_then(|pack| pack.write(path_str));
+    match built {
+        Ok(manifest) => return_string(serde_json::to_string(&manifest).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Pack build failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
 
-    let val: Value = match serde_json::from_str(val_json_str) {
+/// Guarda `value_json` en la clave; 1 si se guardó, 0 si hubo error
+#[no_mangle]
+// SAFETY: `key` y `value_json` son C strings terminados en NUL o null; `db` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_kv_set(db: *mut Database, key: *const c_char, value_json: *const c_char) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return 0; };
+    let key_str = unsafe { to_str(key) };
+    let value: Value = match parse_json(unsafe { to_str(value_json) }) {
         Ok(v) => v,
         Err(_) => {
-            eprintln!("Ruggy Error: Failed to parse update JSON");
+            eprintln!("Ruggy Error: Failed to parse value JSON");
             return 0;
         },
     };
-
-    match col.update_field(id_str, field_str, val) {
-        Ok(success) => {
-            if success { 1 } else { 0 }
-        },
+    match db.kv().and_then(|kv| kv.set(key_str, &value)) {
+        Ok(()) => 1,
         Err(e) => {
-            eprintln!("Ruggy Error: Update failed: {}", e);
+            eprintln!("Ruggy Error: KV set failed: {}", e);
             0
         },
     }
 }
 
+/// Valor de la clave como JSON; null si no existe
 #[no_mangle]
-pub extern "C" fn ruggy_delete(
-    col: *mut Collection,
-    id: *const c_char
-) -> i32 {
-    let col_arc_ptr = col as *mut Arc<Collection>;
-    let col = unsafe { &*col_arc_ptr };
+// SAFETY: `key` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_kv_get(db: *mut Database, key: *const c_char) -> *mut c_char {
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
+    let key_str = unsafe { to_str(key) };
+    match db.kv().map(|kv| kv.get_value(key_str)) {
+        Ok(Some(value)) => return_string(value.to_string()),
+        Ok(None) => std::ptr::null_mut(),
+        Err(e) => {
+            eprintln!("Ruggy Error: KV get failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
 
-    let id_str = unsafe { to_str(id) };
+/// 1 si se borró, 0 si no existía o hubo error
+#[no_mangle]
+// SAFETY: `key` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_kv_remove(db: *mut Database, key: *const c_char) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return 0; };
+    let key_str = unsafe { to_str(key) };
+    match db.kv().and_then(|kv| kv.remove(key_str)) {
+        Ok(removed) => removed as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: KV remove failed: {}", e);
+            0
+        },
+    }
+}
 
-    match col.delete_by_id(id_str) {
-        Ok(success) => {
-            if success { 1 } else { 0 }
+/// Suma `by` al contador y escribe el resultado en `out`; 1 si se hizo, 0 si hubo error
+#[no_mangle]
+// SAFETY: `key` es un C string terminado en NUL o null; `out` apunta a un `i64` escribible o es
+// null; `db` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_kv_incr(db: *mut Database, key: *const c_char, by: i64, out: *mut i64) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return 0; };
+    let key_str = unsafe { to_str(key) };
+    match db.kv().and_then(|kv| kv.incr(key_str, by)) {
+        Ok(value) => {
+            if !out.is_null() {
+                unsafe { *out = value };
+            }
+            1
         },
         Err(e) => {
-            eprintln!("Ruggy Error: Delete failed: {}", e);
+            eprintln!("Ruggy Error: KV incr failed: {}", e);
             0
         },
     }
 }
 
-// --- Destructores ---
+/// 1 si se descargó, 0 si no estaba abierta o hubo error
+#[no_mangle]
+// SAFETY: `name` es un C string terminado en NUL o null; `db` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_unload_collection(db: *mut Database, name: *const c_char) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return 0; };
+    let name_str = unsafe { to_str(name) };
+    match db.unload_collection(name_str) {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(e) => {
+            eprintln!("Ruggy Error: Unload failed: {}", e);
+            0
+        },
+    }
+}
 
 #[no_mangle]
-pub extern "C" fn ruggy_db_free(db: *mut Database) {
-    if !db.is_null() {
-        unsafe { let _ = Box::from_raw(db); }
+// SAFETY: `json` es un C string terminado en NUL o null; `col` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_insert(col: *mut Collection, json: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let json_str = unsafe { to_str(json) };
+    let result = parse_json(json_str).and_then(|json_val| col.insert(json_val));
+    record_error(&result);
+    match result {
+        Ok(id) => return_string(id),
+        Err(_) => std::ptr::null_mut(),
     }
 }
 
+/// `json` es un array de documentos; devuelve el `UpsertReport` como JSON o null si hubo error
 #[no_mangle]
-pub extern "C" fn ruggy_col_free(col: *mut Collection) {
-    if !col.is_null() {
-        let col_arc_ptr = col as *mut Arc<Collection>;
-        unsafe { let _ = Box::from_raw(col_arc_ptr); }
+// SAFETY: `key_field` y `json` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_upsert_many(col: *mut Collection, key_field: *const c_char, json: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let key_str = unsafe { to_str(key_field) };
+    let json_str = unsafe { to_str(json) };
+    let result = parse_json(json_str).and_then(|docs| col.upsert_many(key_str, docs));
+    record_error(&result);
+    match result {
+        Ok(report) => return_string(serde_json::to_string(&report).unwrap_or_else(|_| "{}".to_string())),
+        Err(e) => {
+            eprintln!("Ruggy Error: Upsert failed: {}", e);
+            std::ptr::null_mut()
+        },
     }
 }
 
+/// Importa un array de documentos sin abortar en los inválidos. `key_field` puede ser nulo.
+/// Devuelve el array de resultados por documento como JSON, o null si hubo error.
 #[no_mangle]
-pub extern "C" fn ruggy_str_free(s: *mut c_char) {
-    if !s.is_null() {
-        unsafe { let _ = CString::from_raw(s); }
+// SAFETY: `json` y `key_field` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_import(col: *mut Collection, json: *const c_char, key_field: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let json_str = unsafe { to_str(json) };
+    let key = if key_field.is_null() { None } else { Some(unsafe { to_str(key_field) }) };
+    let docs: Vec<Value> = match parse_json(json_str) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+
+    match col.import(docs, key) {
+        Ok(results) => return_string(serde_json::to_string(&results).unwrap_or_else(|_| "[]".to_string())),
+        Err(e) => {
+            eprintln!("Ruggy Error: Import failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Importa un archivo CSV con las opciones por defecto. `key_field` puede ser nulo.
+#[no_mangle]
+// SAFETY: `path` y `key_field` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_import_csv(col: *mut Collection, path: *const c_char, key_field: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let path_str = unsafe { to_str(path) };
+    let key = if key_field.is_null() { None } else { Some(unsafe { to_str(key_field) }) };
+
+    match col.import_csv_file(std::path::Path::new(path_str), &CsvOptions::default(), key) {
+        Ok(results) => return_string(serde_json::to_string(&results).unwrap_or_else(|_| "[]".to_string())),
+        Err(e) => {
+            eprintln!("Ruggy Error: CSV import failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// `flags`: 1 = indentado, 2 = claves ordenadas, 4 = solo ASCII. Afecta a `ruggy_find*`.
+#[no_mangle]
+pub extern "C" fn ruggy_set_output_format(flags: u32) {
+    OUTPUT_FORMAT.store(flags, Ordering::Relaxed);
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_all(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let docs = col.find_all();
+    let json_out = documents_json(&docs);
+    return_string(json_out)
+}
+
+#[no_mangle]
+// SAFETY: `field` y `value` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find(col: *mut Collection, field: *const c_char, value: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+    
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+
+    let docs = col.find(f_str, v_str);
+    let json_out = documents_json(&docs);
+    return_string(json_out)
+}
+
+/// El documento con ese `_id` como JSON, o null si no existe
+#[no_mangle]
+// SAFETY: `id` es un C string terminado en NUL o null; `col` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_by_id(col: *mut Collection, id: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    match col.find_by_id(unsafe { to_str(id) }) {
+        Some(doc) => return_string(document_json(Some(&doc))),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// El primer documento que cumple un filtro JSON al estilo de MongoDB (ver `filter`), `null`
+/// (el texto) si ninguno lo cumple o null si hubo error
+#[no_mangle]
+// SAFETY: `filter` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_one(col: *mut Collection, filter: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(filter) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match col.find_one(spec) {
+        Ok(doc) => return_string(document_json(doc.as_ref())),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `field`, `value` y `operator` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_op(
+    col: *mut Collection, 
+    field: *const c_char, 
+    value: *const c_char,
+    operator: *const c_char
+) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+    
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+    let op_str = unsafe { to_str(operator) };
+
+    let docs = col.find_with_operator(f_str, v_str, op_str);
+    let json_out = documents_json(&docs);
+    return_string(json_out)
+}
+
+/// Número de documentos
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_count(col: *mut Collection) -> i64 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+    col.count() as i64
+}
+
+/// Documentos que devolvería `ruggy_find_op`, o -1 si hubo error
+#[no_mangle]
+// SAFETY: `field`, `value` y `operator` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_count_where(
+    col: *mut Collection,
+    field: *const c_char,
+    value: *const c_char,
+    operator: *const c_char
+) -> i64 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+    let op_str = unsafe { to_str(operator) };
+
+    match col.count_where(f_str, v_str, op_str) {
+        Ok(n) => n as i64,
+        Err(e) => {
+            eprintln!("Ruggy Error: Count failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Documentos que devolvería `ruggy_query` con ese filtro JSON, o -1 si hubo error
+#[no_mangle]
+// SAFETY: `filter` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_count_matching(col: *mut Collection, filter: *const c_char) -> i64 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+
+    let spec: Value = match parse_json(unsafe { to_str(filter) }) {
+        Ok(v) => v,
+        Err(_) => return -1,
+    };
+    match col.count_matching(spec) {
+        Ok(n) => n as i64,
+        Err(e) => {
+            eprintln!("Ruggy Error: Count failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Valores distintos de `field` como array JSON, en los documentos que cumplen `filter` (un
+/// filtro como el de `ruggy_query`, o null para todos). Null si hubo error.
+#[no_mangle]
+// SAFETY: `field` y `filter` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_distinct(col: *mut Collection, field: *const c_char, filter: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec = if filter.is_null() {
+        None
+    } else {
+        match parse_json(unsafe { to_str(filter) }) {
+            Ok(v) => Some(v),
+            Err(_) => return std::ptr::null_mut(),
+        }
+    };
+    match col.distinct(unsafe { to_str(field) }, spec) {
+        Ok(values) => return_string(documents_json(&values)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Distinct failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// 1 si algún documento cumple la condición de `ruggy_find_op`, 0 si no, -1 si hubo error
+#[no_mangle]
+// SAFETY: `field`, `value` y `operator` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_exists(
+    col: *mut Collection,
+    field: *const c_char,
+    value: *const c_char,
+    operator: *const c_char
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+    let op_str = unsafe { to_str(operator) };
+
+    match col.exists(f_str, v_str, op_str) {
+        Ok(found) => found as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Exists check failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Escribe en `buf` (sin NUL final) los documentos que coinciden con `filter`, un objeto JSON
+/// campo -> valor exigido (vacío o null: todos), y su longitud en `out_written`. Devuelve 1 si
+/// se escribió, -1 si `buf` es demasiado pequeño (en `out_written` queda el tamaño necesario
+/// y el contenido de `buf` no sirve) y 0 si hubo error. Los documentos se serializan
+/// directamente en `buf`, sin copiarlos (salvo con `ruggy_set_output_format` canónico o ASCII).
+#[no_mangle]
+// SAFETY: `filter` es un C string terminado en NUL o null; `buf` tiene `buf_len` bytes escribibles
+// o es null; `out_written` apunta a un `usize` escribible o es null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_query_into(
+    col: *mut Collection,
+    filter: *const c_char,
+    buf: *mut u8,
+    buf_len: usize,
+    out_written: *mut usize
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    if out_written.is_null() {
+        return 0;
+    }
+    let Some(conditions) = (unsafe { parse_filter(filter) }) else { return 0; };
+
+    let snapshot = col.snapshot();
+    let docs: Vec<&Value> = snapshot.iter().filter(|doc| matches_filter(doc, &conditions)).collect();
+    let buf: &mut [u8] = match buf.is_null() {
+        true => &mut [],
+        false => unsafe { std::slice::from_raw_parts_mut(buf, buf_len) },
+    };
+    let mut out = CallerBuffer { buf, len: 0 };
+    if let Err(e) = JsonFormat::from_flags(OUTPUT_FORMAT.load(Ordering::Relaxed)).to_writer(&mut out, &docs) {
+        eprintln!("Ruggy Error: Query failed: {}", e);
+        return 0;
+    }
+    unsafe { *out_written = out.len };
+    if out.len > out.buf.len() { -1 } else { 1 }
+}
+
+/// Buffer del que llama: se escribe lo que cabe y se cuenta todo, así el tamaño necesario se
+/// sabe sin serializar dos veces
+struct CallerBuffer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl io::Write for CallerBuffer<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        if let Some(room) = self.buf.get_mut(self.len..) {
+            let n = room.len().min(bytes.len());
+            room[..n].copy_from_slice(&bytes[..n]);
+        }
+        self.len += bytes.len();
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Búsqueda de texto completo en un índice de texto; null si el índice no existe
+#[no_mangle]
+// SAFETY: `index` y `query` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_search(col: *mut Collection, index: *const c_char, query: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let index_str = unsafe { to_str(index) };
+    let query_str = unsafe { to_str(query) };
+    match col.search(index_str, query_str) {
+        Ok(docs) => return_string(documents_json(&docs)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Search failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Como `ruggy_search`, devolviendo por documento las coincidencias y un fragmento resaltado
+#[no_mangle]
+// SAFETY: `index` y `query` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_search_highlighted(col: *mut Collection, index: *const c_char, query: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let index_str = unsafe { to_str(index) };
+    let query_str = unsafe { to_str(query) };
+    match col.search_highlighted(index_str, query_str, &Highlight::default()) {
+        Ok(hits) => return_string(serde_json::to_string(&hits).unwrap_or_else(|_| "[]".to_string())),
+        Err(e) => {
+            eprintln!("Ruggy Error: Search failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Sugerencias de autocompletado como array JSON de strings
+#[no_mangle]
+// SAFETY: `field` y `prefix` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_autocomplete(col: *mut Collection, field: *const c_char, prefix: *const c_char, limit: u32) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let field_str = unsafe { to_str(field) };
+    let prefix_str = unsafe { to_str(prefix) };
+    let suggestions = col.autocomplete(field_str, prefix_str, limit as usize);
+    return_string(serde_json::to_string(&suggestions).unwrap_or_else(|_| "[]".to_string()))
+}
+
+/// Todos los documentos transformados por una proyección JSON (ver `expr::Projection`)
+#[no_mangle]
+// SAFETY: `projection` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_projected(col: *mut Collection, projection: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(projection) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match Projection::parse(&spec) {
+        Ok(projection) => return_string(documents_json(&col.find_projected(|_| true, &projection))),
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid projection: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Documentos para los que la expresión JSON `filter` es verdadera (ver `expr::Expr`)
+#[no_mangle]
+// SAFETY: `filter` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_expr(col: *mut Collection, filter: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(filter) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match Expr::parse(&spec) {
+        Ok(filter) => return_string(documents_json(&col.find_expr(&filter))),
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid filter: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Documentos que cumplen un filtro JSON al estilo de MongoDB (ver `filter`)
+#[no_mangle]
+// SAFETY: `filter` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_query(col: *mut Collection, filter: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(filter) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match col.query(spec) {
+        Ok(docs) => return_string(documents_json(&docs)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Como `ruggy_query`, ordenado por una lista JSON de campos: `["-age", "name"]`
+#[no_mangle]
+// SAFETY: `filter` y `sort_by` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_query_sorted(col: *mut Collection, filter: *const c_char, sort_by: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let (spec, sort): (Value, Value) = match (parse_json(unsafe { to_str(filter) }), parse_json(unsafe { to_str(sort_by) })) {
+        (Ok(spec), Ok(sort)) => (spec, sort),
+        _ => return std::ptr::null_mut(),
+    };
+    match SortKey::parse_list(&sort).and_then(|keys| col.query_sorted(spec, &keys)) {
+        Ok(docs) => return_string(documents_json(&docs)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Página de `ruggy_find`: salta `skip` resultados y devuelve como mucho `limit` (0 = sin límite)
+#[no_mangle]
+// SAFETY: `field` y `value` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_range(col: *mut Collection, field: *const c_char, value: *const c_char, skip: u32, limit: u32) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let limit = (limit > 0).then_some(limit as usize);
+    let docs = col.find_range(unsafe { to_str(field) }, unsafe { to_str(value) }, skip as usize, limit);
+    return_string(documents_json(&docs))
+}
+
+/// Como `ruggy_query` con opciones JSON de `QueryBuilder::options`: `{"sortBy": ["-age"],
+/// "skip": 20, "limit": 10, "projection": {"name": 1}, "consistency": "snapshot"}`, todo opcional
+#[no_mangle]
+// SAFETY: `filter` y `options` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_query_range(col: *mut Collection, filter: *const c_char, options: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let (spec, options): (Value, Value) = match (parse_json(unsafe { to_str(filter) }), parse_json(unsafe { to_str(options) })) {
+        (Ok(spec), Ok(options)) => (spec, options),
+        _ => return std::ptr::null_mut(),
+    };
+    match col.query_builder().matching(spec).options(&options).and_then(|query| query.execute()) {
+        Ok(docs) => return_string(documents_json(&docs)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Solo los campos de la lista JSON `fields` (`["name", "email"]`) y `_id` de los documentos
+/// que cumplen `filter`
+#[no_mangle]
+// SAFETY: `filter` y `fields` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_query_projected(col: *mut Collection, filter: *const c_char, fields: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let (spec, fields): (Value, Vec<String>) = match (parse_json(unsafe { to_str(filter) }), parse_json(unsafe { to_str(fields) })) {
+        (Ok(spec), Ok(fields)) => (spec, fields),
+        _ => return std::ptr::null_mut(),
+    };
+    let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+    match col.query_projected(spec, &fields) {
+        Ok(docs) => return_string(documents_json(&docs)),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Todos los documentos con los campos de una ventana JSON (ver `window::Window`)
+#[no_mangle]
+// SAFETY: `window` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_window(col: *mut Collection, window: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(window) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match Window::parse(&spec) {
+        Ok(window) => return_string(documents_json(&col.find_window(|_| true, &window))),
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid window: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Página de todos los documentos según `query` (ver `page::PageQuery`). Devuelve
+/// `{"documents": [...], "next": token o null}` o null si hubo error.
+#[no_mangle]
+// SAFETY: `query` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_page(col: *mut Collection, query: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(query) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match PageQuery::parse(&spec).and_then(|query| col.find_page(|_| true, &query)) {
+        Ok(page) => return_string(serde_json::to_string(&page).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Page query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Igualdad con orden y proyección (ver `covered::EqQuery::parse`). Devuelve
+/// `{"documents": [...], "covered": bool}` o null si hubo error.
+#[no_mangle]
+// SAFETY: `query` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_eq(col: *mut Collection, query: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(query) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match EqQuery::parse(&spec).and_then(|query| col.find_eq(&query)) {
+        Ok(result) => return_string(serde_json::to_string(&result).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Query failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Tabla dinámica JSON (ver `pivot::Pivot`) sobre todos los documentos
+#[no_mangle]
+// SAFETY: `pivot` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_pivot(col: *mut Collection, pivot: *const c_char) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let spec: Value = match parse_json(unsafe { to_str(pivot) }) {
+        Ok(v) => v,
+        Err(_) => return std::ptr::null_mut(),
+    };
+    match Pivot::parse(&spec) {
+        Ok(pivot) => return_string(documents_json(&col.pivot(|_| true, &pivot))),
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid pivot: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Aplica un update con expresiones (ver `rules::UpdateExpr`) al documento `id`. 1 si se ha
+/// actualizado, 0 si no existe o hubo error (p. ej. el resultado no cumple las reglas).
+#[no_mangle]
+// SAFETY: `id` y `update_json` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_update_expr(col: *mut Collection, id: *const c_char, update_json: *const c_char) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let id_str = unsafe { to_str(id) };
+    let update = parse_json(unsafe { to_str(update_json) })
+        .and_then(|spec| UpdateExpr::parse(&spec));
+    let result = update.and_then(|update| col.update_with(id_str, &update));
+    record_error(&result);
+    match result {
+        Ok(success) => success as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Update failed: {}", e);
+            0
+        },
+    }
+}
+
+/// Aplica un update con expresiones a los documentos para los que `filter` es verdadera.
+/// Devuelve cuántos se han actualizado o -1 si hubo error (y entonces no cambia ninguno).
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_update_where(col: *mut Collection, filter: *const c_char, update_json: *const c_char) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+
+    let parse = |json: *const c_char| parse_json::<Value>(unsafe { to_str(json) });
+    let result = parse(filter).and_then(|spec| Expr::parse(&spec)).and_then(|filter| {
+        let update = UpdateExpr::parse(&parse(update_json)?)?;
+        col.update_where(&filter, &update)
+    });
+    record_error(&result);
+    match result {
+        Ok(n) => n as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Update failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Espera máxima de las escrituras por los locks de la colección (0 = sin límite), con
+/// `retries` reintentos separados por `backoff_ms` (que se duplica en cada uno). Ver `contention`.
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_set_contention(col: *mut Collection, timeout_ms: u32, retries: u32, backoff_ms: u32) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let mut policy = ContentionPolicy::new().retry(retries, Duration::from_millis(backoff_ms as u64));
+    if timeout_ms > 0 {
+        policy = policy.lock_timeout(Duration::from_millis(timeout_ms as u64));
+    }
+    col.set_contention(policy);
+    1
+}
+
+/// 1 si la última escritura de este hilo falló porque la colección estaba ocupada (ver
+/// `ruggy_set_contention`): se puede reintentar más tarde
+#[no_mangle]
+pub extern "C" fn ruggy_last_error_busy() -> i32 {
+    contention::last_busy() as i32
+}
+
+/// Límites al interpretar JSON y MessagePack en todo el proceso (ver `limits`): profundidad
+/// máxima (como mucho 128) y tamaño máximo en bytes; 0 deja el valor por defecto
+#[no_mangle]
+pub extern "C" fn ruggy_set_parse_limits(max_depth: u32, max_bytes: u64) {
+    let defaults = ParseLimits::default();
+    limits::set_parse_limits(ParseLimits {
+        max_depth: if max_depth == 0 { defaults.max_depth } else { max_depth as usize },
+        max_bytes: if max_bytes == 0 { defaults.max_bytes } else { max_bytes.min(usize::MAX as u64) as usize },
+    });
+}
+
+/// 1 si la última entrada de este hilo se rechazó por superar los límites de
+/// `ruggy_set_parse_limits`
+#[no_mangle]
+pub extern "C" fn ruggy_last_error_limit() -> i32 {
+    limits::last_exceeded() as i32
+}
+
+/// Mensaje del último error de `ruggy_insert`, `ruggy_update_field`, `ruggy_upsert_many`,
+/// `ruggy_update_expr`, `ruggy_update_where` o `ruggy_set_rules` en este hilo; null si la
+/// última de ellas fue bien. El que llama debe liberarlo con `ruggy_str_free`.
+#[no_mangle]
+pub extern "C" fn ruggy_last_error() -> *mut c_char {
+    match LAST_ERROR.with(|cell| cell.borrow().as_ref().map(|(message, _)| message.clone())) {
+        Some(message) => return_string(message),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Nombre de la regla de validación que no cumplía el documento en el error de
+/// `ruggy_last_error`, o null si el error fue otro
+#[no_mangle]
+pub extern "C" fn ruggy_last_error_rule() -> *mut c_char {
+    match LAST_ERROR.with(|cell| cell.borrow().as_ref().and_then(|(_, rule)| rule.clone())) {
+        Some(rule) => return_string(rule),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Sustituye las reglas de validación de la colección por las de `rules_json` (objeto
+/// nombre -> expresión, ver `rules`). 1 si se han cambiado, 0 si no son válidas.
+#[no_mangle]
+// SAFETY: `rules_json` es un C string terminado en NUL o null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_set_rules(col: *mut Collection, rules_json: *const c_char) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let rules = parse_json(unsafe { to_str(rules_json) })
+        .and_then(|spec| Rule::parse_all(&spec));
+    record_error(&rules);
+    match rules {
+        Ok(rules) => {
+            col.set_rules(rules);
+            1
+        },
+        Err(e) => {
+            eprintln!("Ruggy Error: Invalid rules: {}", e);
+            0
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `id`, `field` y `value_json` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_update_field(
+    col: *mut Collection,
+    id: *const c_char,
+    field: *const c_char,
+    value_json: *const c_char
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let id_str = unsafe { to_str(id) };
+    let field_str = unsafe { to_str(field) };
+    let val_json_str = unsafe { to_str(value_json) };
+
+    let val: Value = match parse_json(val_json_str) {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("Ruggy Error: Failed to parse update JSON");
+            record_error::<()>(&Err(e));
+            return 0;
+        },
+    };
+
+    let result = col.update_field(id_str, field_str, val);
+    record_error(&result);
+    match result {
+        Ok(success) => {
+            if success { 1 } else { 0 }
+        },
+        Err(e) => {
+            eprintln!("Ruggy Error: Update failed: {}", e);
+            0
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `id` es un C string terminado en NUL o null; `col` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_delete(
+    col: *mut Collection,
+    id: *const c_char
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let id_str = unsafe { to_str(id) };
+
+    match col.delete_by_id(id_str) {
+        Ok(success) => {
+            if success { 1 } else { 0 }
+        },
+        Err(e) => {
+            eprintln!("Ruggy Error: Delete failed: {}", e);
+            0
+        },
+    }
+}
+
+/// Guarda ya los cambios pendientes; 1 si se guardó, 0 si hubo error
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_persist_now(col: *mut Collection) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    match col.persist_now() {
+        Ok(()) => 1,
+        Err(e) => {
+            eprintln!("Ruggy Error: Persist failed: {}", e);
+            0
+        },
+    }
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_analyze(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let stats = col.analyze();
+    let json_out = serde_json::to_string(&stats).unwrap_or_else(|_| "{}".to_string());
+    return_string(json_out)
+}
+
+/// `LoadReport` de la última carga como JSON
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_load_report(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let json_out = serde_json::to_string(&col.load_report()).unwrap_or_else(|_| "{}".to_string());
+    return_string(json_out)
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_memory_usage(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let usage = col.memory_usage();
+    let json_out = serde_json::to_string(&usage).unwrap_or_else(|_| "{}".to_string());
+    return_string(json_out)
+}
+
+/// `IoStats` como JSON, con `write_amplification` calculada (`null` sin cambios)
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_io_stats(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let stats = col.io_stats();
+    let mut json = serde_json::to_value(&stats).unwrap_or_default();
+    if let Some(obj) = json.as_object_mut() {
+        obj.insert("bytes_written".to_string(), serde_json::json!(stats.bytes_written()));
+        obj.insert("write_amplification".to_string(), serde_json::json!(stats.write_amplification()));
+    }
+    return_string(json.to_string())
+}
+
+/// `LockStats` como JSON
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_lock_stats(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+    return_string(serde_json::to_string(&col.lock_stats()).unwrap_or_else(|_| "{}".to_string()))
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_reset_lock_stats(col: *mut Collection) {
+    let Some(col) = (unsafe { col_ref(col) }) else { return; };
+    col.reset_lock_stats();
+}
+
+/// Pone a cero las estadísticas de IO
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_reset_io_stats(col: *mut Collection) {
+    let Some(col) = (unsafe { col_ref(col) }) else { return; };
+    col.reset_io_stats();
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_list_indexes(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let json_out = serde_json::to_string(&col.indexes()).unwrap_or_else(|_| "[]".to_string());
+    return_string(json_out)
+}
+
+/// 1 si se eliminó, 0 si no existía, -1 si hubo error
+#[no_mangle]
+// SAFETY: `name` es un C string terminado en NUL o null; `col` se valida con `handles::check` antes
+// de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_drop_index(col: *mut Collection, name: *const c_char) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+    let name_str = unsafe { to_str(name) };
+    match col.drop_index(name_str) {
+        Ok(dropped) => dropped as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Drop index failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Empieza a construir un índice de igualdad en segundo plano. 1 si empezó, 0 si hubo error.
+#[no_mangle]
+// SAFETY: `name` y `field` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_create_index_background(col: *mut Collection, name: *const c_char, field: *const c_char) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+    let (name_str, field_str) = unsafe { (to_str(name), to_str(field)) };
+    match col.create_index_background(name_str, field_str) {
+        Ok(()) => 1,
+        Err(e) => {
+            eprintln!("Ruggy Error: Index build failed: {}", e);
+            0
+        },
+    }
+}
+
+/// Progreso JSON de las construcciones en segundo plano (ver `IndexBuildProgress`)
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_index_builds(col: *mut Collection) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let json_out = serde_json::to_string(&col.index_builds()).unwrap_or_else(|_| "[]".to_string());
+    return_string(json_out)
+}
+
+/// Espera a las construcciones en segundo plano. 1 si todas terminaron bien, 0 si no.
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_await_indexes(col: *mut Collection) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+    match col.await_indexes() {
+        Ok(()) => 1,
+        Err(e) => {
+            eprintln!("Ruggy Error: {}", e);
+            0
+        },
+    }
+}
+
+/// Devuelve cuántos índices se reconstruyeron, o -1 si hubo error
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_reindex(col: *mut Collection) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+    match col.reindex() {
+        Ok(n) => n as i32,
+        Err(e) => {
+            eprintln!("Ruggy Error: Reindex failed: {}", e);
+            -1
+        },
+    }
+}
+
+/// Ejecuta una carga de trabajo simulada (ver `bench::Workload`) y devuelve el informe JSON
+/// o null si hubo error
+#[no_mangle]
+// SAFETY: `workload_json` es un C string terminado en NUL o null; `db` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_bench(db: *mut Database, workload_json: *const c_char) -> *mut c_char {
+    let Some(db) = (unsafe { db_ref(db) }) else { return std::ptr::null_mut(); };
+
+    let workload = parse_json(unsafe { to_str(workload_json) })
+        .and_then(|spec| Workload::parse(&spec));
+    match workload.and_then(|workload| workload.run(db)) {
+        Ok(report) => return_string(serde_json::to_string(&report).unwrap()),
+        Err(e) => {
+            eprintln!("Ruggy Error: Benchmark failed: {}", e);
+            std::ptr::null_mut()
+        },
+    }
+}
+
+/// Callback de CDC: recibe el evento como JSON (válido solo durante la llamada) y `user_data`
+pub type RuggyCdcCallback = extern "C" fn(event_json: *const c_char, user_data: *mut c_void);
+
+struct UserData(*mut c_void);
+// El host es responsable de que `user_data` se pueda usar desde cualquier hilo
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Registra el callback de CDC. Con `callback` nulo se elimina.
+#[no_mangle]
+// SAFETY: `db` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_set_cdc_sink(db: *mut Database, callback: Option<RuggyCdcCallback>, user_data: *mut c_void) -> i32 {
+    let Some(db) = (unsafe { db_ref(db) }) else { return 0; };
+    match callback {
+        Some(cb) => {
+            let user_data = UserData(user_data);
+            db.set_cdc_sink(Box::new(move |event| {
+                let json = serde_json::to_string(&event).unwrap_or_else(|_| "{}".to_string());
+                if let Ok(c_json) = CString::new(json) {
+                    handles::in_callback(|| cb(c_json.as_ptr(), user_data.ptr()));
+                }
+            }));
+        },
+        None => db.clear_cdc_sink(),
+    }
+    1
+}
+
+/// Predicado para `$fn`: recibe el valor como JSON (válido solo durante la llamada) y
+/// `user_data`; distinto de 0 si se cumple
+pub type RuggyPredicateCallback = extern "C" fn(value_json: *const c_char, user_data: *mut c_void) -> i32;
+
+/// Registra el predicado `name` para las expresiones `{"$fn": name, ...}`. Con `callback`
+/// nulo se elimina. Se llama durante las consultas, así que no puede volver a llamar a Ruggy.
+#[no_mangle]
+// SAFETY: `name` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_register_function(name: *const c_char, callback: Option<RuggyPredicateCallback>, user_data: *mut c_void) -> i32 {
+    let name = unsafe { to_str(name) };
+    let Some(cb) = callback else { return unregister_function(name) as i32; };
+    let user_data = UserData(user_data);
+    let result = register_function(name, move |value| {
+        let Ok(c_json) = CString::new(value.to_string()) else { return false; };
+        handles::in_callback(|| cb(c_json.as_ptr(), user_data.ptr()) != 0)
+    });
+    match result {
+        Ok(()) => 1,
+        Err(e) => {
+            eprintln!("Ruggy Error: Cannot register function: {}", e);
+            0
+        },
+    }
+}
+
+/// Reserva de memoria del host: devuelve `size` bytes o null
+pub type RuggyAllocFn = extern "C" fn(size: usize, user_data: *mut c_void) -> *mut c_void;
+/// Liberación de memoria reservada con la `RuggyAllocFn` correspondiente
+pub type RuggyFreeFn = extern "C" fn(ptr: *mut c_void, user_data: *mut c_void);
+
+struct HostAllocator {
+    alloc: RuggyAllocFn,
+    free: RuggyFreeFn,
+    user_data: UserData,
+}
+
+impl HostAllocator {
+    /// Copia `bytes` a memoria del host; null si el host no pudo reservarla
+    fn copy(&self, bytes: &[u8]) -> *mut u8 {
+        let ptr = (self.alloc)(bytes.len().max(1), self.user_data.ptr()) as *mut u8;
+        if ptr.is_null() {
+            eprintln!("Ruggy Error: Host allocator failed to allocate {} bytes", bytes.len());
+            return ptr;
+        }
+        unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len()) };
+        ptr
+    }
+}
+
+static HOST_ALLOCATOR: OnceLock<HostAllocator> = OnceLock::new();
+/// Ya se ha devuelto memoria al host: a partir de aquí no se puede cambiar de asignador
+static RETURNED: AtomicBool = AtomicBool::new(false);
+
+/// Registra las funciones con las que se reserva toda la memoria devuelta por la FFI (strings
+/// y buffers); `ruggy_str_free` y `ruggy_buf_free` la liberan con `free`. Debe llamarse una
+/// sola vez y antes de recibir cualquier resultado; si no, devuelve 0 y no cambia nada.
+#[no_mangle]
+pub extern "C" fn ruggy_set_allocator(alloc: Option<RuggyAllocFn>, free: Option<RuggyFreeFn>, user_data: *mut c_void) -> i32 {
+    let (alloc, free) = match (alloc, free) {
+        (Some(alloc), Some(free)) => (alloc, free),
+        _ => return 0,
+    };
+    if RETURNED.load(Ordering::Relaxed) {
+        eprintln!("Ruggy Error: Allocator must be set before any result is returned");
+        return 0;
+    }
+    match HOST_ALLOCATOR.set(HostAllocator { alloc, free, user_data: UserData(user_data) }) {
+        Ok(()) => 1,
+        Err(_) => 0,
+    }
+}
+
+/// Callback de expiración: recibe el array JSON de documentos expirados (válido solo durante
+/// la llamada) y `user_data`. Se ejecuta sin locks, así que puede volver a llamar a Ruggy.
+pub type RuggyExpiryCallback = extern "C" fn(documents_json: *const c_char, user_data: *mut c_void);
+
+/// Registra el callback de expiración TTL de la colección. Con `callback` nulo se elimina.
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_set_expiry_callback(col: *mut Collection, callback: Option<RuggyExpiryCallback>, user_data: *mut c_void) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+    match callback {
+        Some(cb) => {
+            let user_data = UserData(user_data);
+            col.set_expiry_callback(Some(Box::new(move |docs| {
+                let json = serde_json::to_string(docs).unwrap_or_else(|_| "[]".to_string());
+                if let Ok(c_json) = CString::new(json) {
+                    cb(c_json.as_ptr(), user_data.ptr());
+                }
+            })));
+        },
+        None => col.set_expiry_callback(None),
+    }
+    1
+}
+
+/// Callback de esperas por locks: recibe el `LockWait` como JSON (válido solo durante la
+/// llamada) y `user_data`. Se ejecuta con el lock tomado y no puede volver a llamar a Ruggy.
+pub type RuggyLockWaitCallback = extern "C" fn(event_json: *const c_char, user_data: *mut c_void);
+
+/// Avisa de las esperas por los locks de la colección de `threshold_ms` o más (0 = ninguna).
+/// Con `callback` nulo solo se guardan en `ruggy_lock_stats`.
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_set_lock_wait_callback(
+    col: *mut Collection,
+    threshold_ms: u32,
+    callback: Option<RuggyLockWaitCallback>,
+    user_data: *mut c_void
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+    col.set_lock_wait_threshold((threshold_ms > 0).then(|| Duration::from_millis(threshold_ms as u64)));
+    match callback {
+        Some(cb) => {
+            let user_data = UserData(user_data);
+            col.set_lock_wait_callback(Some(Box::new(move |event| {
+                let json = serde_json::to_string(event).unwrap_or_else(|_| "{}".to_string());
+                if let Ok(c_json) = CString::new(json) {
+                    handles::in_callback(|| cb(c_json.as_ptr(), user_data.ptr()));
+                }
+            })));
+        },
+        None => col.set_lock_wait_callback(None),
+    }
+    1
+}
+
+/// Callback de `ruggy_raw_lines`: recibe cada línea (válida solo durante la llamada) y
+/// `user_data`; 0 para dejar de leer. No puede volver a llamar a Ruggy.
+pub type RuggyLineCallback = extern "C" fn(line: *const c_char, user_data: *mut c_void) -> i32;
+
+/// Pasa a `callback` las líneas del archivo tal como están guardadas (ver `raw`), con las
+/// escrituras de la colección en espera hasta terminar. Devuelve las líneas entregadas o -1
+/// si hubo error.
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_raw_lines(col: *mut Collection, callback: Option<RuggyLineCallback>, user_data: *mut c_void) -> i64 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return -1; };
+    let Some(cb) = callback else { return -1; };
+
+    let result = col.raw_lines().and_then(|lines| {
+        let mut delivered = 0;
+        for line in lines {
+            let c_line = CString::new(line?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            delivered += 1;
+            if handles::in_callback(|| cb(c_line.as_ptr(), user_data)) == 0 {
+                break;
+            }
+        }
+        Ok(delivered)
+    });
+    match result {
+        Ok(delivered) => delivered,
+        Err(e) => {
+            eprintln!("Ruggy Error: Cannot read lines: {}", e);
+            -1
+        },
+    }
+}
+
+// --- Variantes MessagePack (`_mp`): mismos argumentos, con buffers en lugar de JSON ---
+
+#[no_mangle]
+// SAFETY: `data` apunta a `len` bytes legibles o es null; `col` se valida con `handles::check`
+// antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_insert_mp(col: *mut Collection, data: *const u8, len: usize) -> *mut c_char {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let doc = match unsafe { from_msgpack(data, len) } {
+        Some(v) => v,
+        None => return std::ptr::null_mut(),
+    };
+
+    match col.insert(doc) {
+        Ok(id) => return_string(id),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+// SAFETY: `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_all_mp(col: *mut Collection, out_len: *mut usize) -> *mut u8 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    documents_msgpack(&col.find_all(), out_len)
+}
+
+#[no_mangle]
+// SAFETY: `field` y `value` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_mp(col: *mut Collection, field: *const c_char, value: *const c_char, out_len: *mut usize) -> *mut u8 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+    documents_msgpack(&col.find(f_str, v_str), out_len)
+}
+
+#[no_mangle]
+// SAFETY: `field`, `value` y `operator` son C strings terminados en NUL o null; `col` se valida con
+// `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_find_op_mp(
+    col: *mut Collection,
+    field: *const c_char,
+    value: *const c_char,
+    operator: *const c_char,
+    out_len: *mut usize
+) -> *mut u8 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return std::ptr::null_mut(); };
+
+    let f_str = unsafe { to_str(field) };
+    let v_str = unsafe { to_str(value) };
+    let op_str = unsafe { to_str(operator) };
+    documents_msgpack(&col.find_with_operator(f_str, v_str, op_str), out_len)
+}
+
+#[no_mangle]
+// SAFETY: `id` y `field` son C strings terminados en NUL o null; `value` apunta a `len` bytes
+// legibles o es null; `col` se valida con `handles::check` antes de usarlo
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_update_field_mp(
+    col: *mut Collection,
+    id: *const c_char,
+    field: *const c_char,
+    value: *const u8,
+    len: usize
+) -> i32 {
+    let Some(col) = (unsafe { col_ref(col) }) else { return 0; };
+
+    let id_str = unsafe { to_str(id) };
+    let field_str = unsafe { to_str(field) };
+    let val = match unsafe { from_msgpack(value, len) } {
+        Some(v) => v,
+        None => {
+            eprintln!("Ruggy Error: Failed to decode update MessagePack");
+            return 0;
+        },
+    };
+
+    match col.update_field(id_str, field_str, val) {
+        Ok(success) => {
+            if success { 1 } else { 0 }
+        },
+        Err(e) => {
+            eprintln!("Ruggy Error: Update failed: {}", e);
+            0
+        },
+    }
+}
+
+// --- Destructores ---
+
+#[no_mangle]
+pub extern "C" fn ruggy_db_free(db: *mut Database) {
+    if db.is_null() { return; }
+    match handles::release(db, HandleKind::Database) {
+        Ok(()) => unsafe { let _ = Box::from_raw(db as *mut Arc<Database>); },
+        Err(e) => eprintln!("Ruggy Error: ruggy_db_free: {}", e),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ruggy_col_free(col: *mut Collection) {
+    if col.is_null() { return; }
+    match handles::release(col, HandleKind::Collection) {
+        Ok(()) => unsafe { let _ = Box::from_raw(col as *mut Arc<Collection>); },
+        Err(e) => eprintln!("Ruggy Error: ruggy_col_free: {}", e),
+    }
+}
+
+/// Perfil para lenguajes dinámicos (ver `dynamic`): ejecuta el comando JSON `request` y
+/// devuelve siempre una respuesta JSON, que se libera con `ruggy_dyn_free`. `request` debe
+/// ser UTF-8 válido.
+#[no_mangle]
+// SAFETY: `request` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_dyn_call(request: *const c_char) -> *mut c_char {
+    let response = if request.is_null() {
+        serde_json::json!({"ok": false, "error": "Null request"}).to_string()
+    } else if let Err(e) = handles::check_not_in_callback() {
+        serde_json::json!({"ok": false, "error": e}).to_string()
+    } else {
+        match unsafe { CStr::from_ptr(request) }.to_str() {
+            Ok(request) => dynamic::dispatch(request),
+            Err(e) => serde_json::json!({"ok": false, "error": format!("Request is not valid UTF-8: {}", e)}).to_string(),
+        }
+    };
+    return_string(response)
+}
+
+/// Solo se inyectan fallos en los archivos dentro de `dir` (feature `fault-injection`, ver
+/// `faults`)
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+// SAFETY: `dir` es un C string terminado en NUL o null
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_fault_only_under(dir: *const c_char) {
+    crate::faults::only_under(std::path::Path::new(unsafe { to_str(dir) }));
+}
+
+/// La `n`-ésima escritura a partir de ahora falla (1 = la siguiente)
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+pub extern "C" fn ruggy_fault_fail_nth_write(n: u64) {
+    crate::faults::fail_nth_write(n);
+}
+
+/// Después de `bytes` bytes más, las escrituras fallan como con el disco lleno
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+pub extern "C" fn ruggy_fault_disk_full_after(bytes: u64) {
+    crate::faults::disk_full_after(bytes);
+}
+
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+pub extern "C" fn ruggy_fault_short_reads(max_bytes: usize) {
+    crate::faults::short_reads(max_bytes);
+}
+
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+pub extern "C" fn ruggy_fault_reset() {
+    crate::faults::reset();
+}
+
+/// Contadores de `faults::stats` en JSON
+#[cfg(feature = "fault-injection")]
+#[no_mangle]
+pub extern "C" fn ruggy_fault_stats() -> *mut c_char {
+    return_string(serde_json::to_string(&crate::faults::stats()).unwrap())
+}
+
+#[no_mangle]
+pub extern "C" fn ruggy_dyn_free(response: *mut c_char) {
+    ruggy_str_free(response);
+}
+
+#[no_mangle]
+// SAFETY: `s` es null o un string devuelto por Ruggy sin liberar
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn ruggy_str_free(s: *mut c_char) {
+    if !s.is_null() {
+        match HOST_ALLOCATOR.get() {
+            Some(host) => (host.free)(s as *mut c_void, host.user_data.ptr()),
+            None => unsafe { let _ = CString::from_raw(s); },
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ruggy_buf_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        match HOST_ALLOCATOR.get() {
+            Some(host) => (host.free)(buf as *mut c_void, host.user_data.ptr()),
+            None => unsafe { let _ = Box::from_raw(std::ptr::slice_from_raw_parts_mut(buf, len)); },
+        }
     }
 }
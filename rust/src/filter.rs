@@ -0,0 +1,325 @@
+//! Filtros en documentos JSON al estilo de MongoDB para `Collection::query`:
+//!
+//! ```json
+//! {"age": {"$gt": 30}, "name": {"$regex": "^a", "$options": "i"},
+//!  "$or": [{"status": "active"}, {"address.city": {"$in": ["Lima", "Quito"]}}]}
+//! ```
+//!
+//! Las claves de un objeto se combinan con AND. Un campo (admite rutas con puntos) con un valor
+//! que no es un objeto de operadores se compara por igualdad. Operadores de campo: `$eq`,
+//! `$ne`, `$gt`, `$gte`, `$lt`, `$lte`, `$in`, `$nin`, `$regex` (con `$options` `i`, `m`, `s`
+//! o `x`), `$ieq` y `$ilike` (igualdad y contiene sin distinguir mayúsculas, solo en strings),
+//! `$exists` y `$not`; de documento: `$and`, `$or`, `$nor` y `$not`. Las comparaciones
+//! son las de `expr::compare_values` (valores con tipo incluidos). Como en MongoDB, un campo
+//! array cumple una condición si la cumple el array entero o alguno de sus elementos
+//! (`{"tags": "rust"}` encuentra `{"tags": ["go", "rust"]}`), y `$ne` y `$nin` piden que no
+//! la cumpla ninguno. En una colección con `CollectionOptions::normalization`, las igualdades,
+//! `$in`, `$nin`, `$ieq` y `$ilike` comparan los strings normalizados, igual que `find`.
+
+use std::cmp::Ordering;
+use std::io;
+use regex::{Regex, RegexBuilder};
+use serde_json::{Map, Value};
+use crate::collection::Collection;
+use crate::comparator::SortKey;
+use crate::expr::{self, compare_values, CmpOp};
+use crate::index::{self, Index, QueryOptions, ReadConsistency};
+use crate::query::QueryBuilder;
+use crate::normalize::{normalized, Normalization};
+use crate::stats::CollectionStats;
+
+#[derive(Clone, Debug)]
+pub enum Filter {
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Not(Box<Filter>),
+    Field(String, Condition),
+}
+
+/// Condición sobre el valor de un campo
+#[derive(Clone, Debug)]
+pub enum Condition {
+    Compare(CmpOp, Value),
+    In(Vec<Value>),
+    Nin(Vec<Value>),
+    Regex(Regex),
+    /// `$ieq` y `$ilike`, con el texto ya en minúsculas
+    IEq(String),
+    ILike(String),
+    Exists(bool),
+    Not(Box<Condition>),
+    All(Vec<Condition>),
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn is_operators(obj: &Map<String, Value>) -> bool {
+    !obj.is_empty() && obj.keys().all(|k| k.starts_with('$'))
+}
+
+impl Filter {
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid(format!("Filter must be an object: {}", spec)))?;
+        let mut all = Vec::with_capacity(obj.len());
+        for (key, value) in obj {
+            all.push(match key.as_str() {
+                "$and" => Filter::And(Self::parse_list(key, value)?),
+                "$or" => Filter::Or(Self::parse_list(key, value)?),
+                "$nor" => Filter::Not(Box::new(Filter::Or(Self::parse_list(key, value)?))),
+                "$not" => Filter::Not(Box::new(Self::parse(value)?)),
+                op if op.starts_with('$') => return Err(invalid(format!("Unknown filter operator '{}'", op))),
+                field => Filter::Field(field.to_string(), Condition::parse(value)?),
+            });
+        }
+        Ok(match all.len() {
+            1 => all.remove(0),
+            _ => Filter::And(all),
+        })
+    }
+
+    fn parse_list(op: &str, value: &Value) -> io::Result<Vec<Filter>> {
+        match value {
+            Value::Array(items) if !items.is_empty() => items.iter().map(Self::parse).collect(),
+            _ => Err(invalid(format!("'{}' needs a non-empty array of filters", op))),
+        }
+    }
+
+    pub fn matches(&self, doc: &Value) -> bool {
+        self.matches_with(doc, None)
+    }
+
+    /// Como `matches`, normalizando los dos lados de las igualdades, `$in`, `$nin`, `$ieq` y
+    /// `$ilike` como en `find` (ver `CollectionOptions::normalization`)
+    pub fn matches_with(&self, doc: &Value, normalization: Option<&Normalization>) -> bool {
+        match self {
+            Filter::And(all) => all.iter().all(|f| f.matches_with(doc, normalization)),
+            Filter::Or(any) => any.iter().any(|f| f.matches_with(doc, normalization)),
+            Filter::Not(filter) => !filter.matches_with(doc, normalization),
+            Filter::Field(field, condition) => condition.matches_with(expr::field_value(doc, field), normalization),
+        }
+    }
+
+    /// Documentos de `data` que cumplen el filtro, en orden de inserción. Si una igualdad con un
+    /// string de primer nivel tiene índice, solo se revisan sus candidatos. Con varias, y con
+    /// estadísticas (`Collection::analyze`), se usa la que se estima más selectiva. Los strings
+    /// se comparan con la normalización de la colección.
+    pub(crate) fn select<'d>(&self, data: &'d [Value], indexes: &[Index], col: &Collection) -> Vec<&'d Value> {
+        let normalization = col.options.read().normalization;
+        let normalization = normalization.as_ref();
+        match self.candidates(indexes, col.stats.read().as_ref()) {
+            Some(positions) => positions.into_iter().map(|pos| &data[pos]).filter(|doc| self.matches_with(doc, normalization)).collect(),
+            None => data.iter().filter(|doc| self.matches_with(doc, normalization)).collect(),
+        }
+    }
+
+    /// El primero de `select`, sin revisar los siguientes
+    pub(crate) fn first<'d>(&self, data: &'d [Value], indexes: &[Index], col: &Collection) -> Option<&'d Value> {
+        let normalization = col.options.read().normalization;
+        let normalization = normalization.as_ref();
+        match self.candidates(indexes, col.stats.read().as_ref()) {
+            Some(positions) => positions.into_iter().map(|pos| &data[pos]).find(|doc| self.matches_with(doc, normalization)),
+            None => data.iter().find(|doc| self.matches_with(doc, normalization)),
+        }
+    }
+
+    /// Cuántos devolvería `select`, sin reunirlos
+    pub(crate) fn count(&self, data: &[Value], indexes: &[Index], col: &Collection) -> usize {
+        let normalization = col.options.read().normalization;
+        let normalization = normalization.as_ref();
+        match self.candidates(indexes, col.stats.read().as_ref()) {
+            Some(positions) => positions.into_iter().filter(|&pos| self.matches_with(&data[pos], normalization)).count(),
+            None => data.iter().filter(|doc| self.matches_with(doc, normalization)).count(),
+        }
+    }
+
+    /// Posiciones que pueden cumplir el filtro según un índice de igualdad; `None` sin índice
+    fn candidates(&self, indexes: &[Index], stats: Option<&CollectionStats>) -> Option<Vec<usize>> {
+        let mut usable = self.equalities().into_iter().filter_map(|(field, value)| {
+            let index = index::choose(indexes, field, true, &QueryOptions::default()).ok()??;
+            Some((field, value, index))
+        });
+        let (_, value, index) = match stats {
+            Some(stats) => usable.min_by_key(|(field, value, _)| stats.estimate_eq(field, &Value::String(value.to_string())))?,
+            None => usable.next()?,
+        };
+        Some(index.lookup(value, false))
+    }
+
+    /// Igualdades con un string que tiene que cumplir todo documento, para buscar en un índice
+    fn equalities(&self) -> Vec<(&str, &str)> {
+        match self {
+            Filter::Field(field, Condition::Compare(CmpOp::Eq, Value::String(value))) => vec![(field, value)],
+            Filter::And(all) => all.iter().flat_map(Filter::equalities).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Condition {
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = match spec {
+            Value::Object(obj) if is_operators(obj) => obj,
+            literal => return Ok(Condition::Compare(CmpOp::Eq, literal.clone())),
+        };
+        let mut all = Vec::with_capacity(obj.len());
+        for (op, arg) in obj {
+            let compare = |cmp: CmpOp| Ok(Condition::Compare(cmp, arg.clone()));
+            let list = || match arg {
+                Value::Array(items) => Ok(items.clone()),
+                _ => Err(invalid(format!("'{}' needs an array", op))),
+            };
+            let lowercase = || match arg {
+                Value::String(s) => Ok(s.to_lowercase()),
+                _ => Err(invalid(format!("'{}' needs a string", op))),
+            };
+            all.push(match op.as_str() {
+                "$eq" => compare(CmpOp::Eq),
+                "$ne" => compare(CmpOp::Ne),
+                "$gt" => compare(CmpOp::Gt),
+                "$gte" => compare(CmpOp::Gte),
+                "$lt" => compare(CmpOp::Lt),
+                "$lte" => compare(CmpOp::Lte),
+                "$in" => Ok(Condition::In(list()?)),
+                "$nin" => Ok(Condition::Nin(list()?)),
+                "$regex" => Self::parse_regex(arg, obj.get("$options")),
+                "$ieq" => Ok(Condition::IEq(lowercase()?)),
+                "$ilike" => Ok(Condition::ILike(lowercase()?)),
+                "$options" if obj.contains_key("$regex") => continue,
+                "$exists" => Ok(Condition::Exists(expr::truthy(arg))),
+                "$not" => Ok(Condition::Not(Box::new(Self::parse(arg)?))),
+                _ => Err(invalid(format!("Unknown field operator '{}'", op))),
+            }?);
+        }
+        Ok(match all.len() {
+            1 => all.remove(0),
+            _ => Condition::All(all),
+        })
+    }
+
+    fn parse_regex(pattern: &Value, options: Option<&Value>) -> io::Result<Self> {
+        let pattern = pattern.as_str().ok_or_else(|| invalid("'$regex' needs a string".to_string()))?;
+        let options = match options {
+            Some(Value::String(options)) => options.as_str(),
+            Some(other) => return Err(invalid(format!("Invalid '$options': {}", other))),
+            None => "",
+        };
+        let mut builder = RegexBuilder::new(pattern);
+        for flag in options.chars() {
+            match flag {
+                'i' => builder.case_insensitive(true),
+                'm' => builder.multi_line(true),
+                's' => builder.dot_matches_new_line(true),
+                'x' => builder.ignore_whitespace(true),
+                _ => return Err(invalid(format!("Unknown regex option '{}'", flag))),
+            };
+        }
+        let regex = builder.build().map_err(|e| invalid(format!("Invalid regex '{}': {}", pattern, e)))?;
+        Ok(Condition::Regex(regex))
+    }
+
+    /// `value` es `None` si el documento no tiene el campo, que se trata como `null`
+    pub fn matches(&self, value: Option<&Value>) -> bool {
+        self.matches_with(value, None)
+    }
+
+    /// Como `matches`, con la normalización de `Filter::matches_with`
+    pub fn matches_with(&self, value: Option<&Value>, normalization: Option<&Normalization>) -> bool {
+        let elements = match value {
+            Some(Value::Array(items)) => items.as_slice(),
+            _ => &[],
+        };
+        let mut candidates = std::iter::once(value).chain(elements.iter().map(Some));
+        match self {
+            // Se aplican al campo entero; sus condiciones internas ya entran en los arrays
+            Condition::Exists(_) | Condition::Not(_) | Condition::All(_) => self.matches_one(value, normalization),
+            Condition::Compare(CmpOp::Ne, _) | Condition::Nin(_) => candidates.all(|v| self.matches_one(v, normalization)),
+            _ => candidates.any(|v| self.matches_one(v, normalization)),
+        }
+    }
+
+    /// La condición sobre un solo valor, sin entrar en los arrays
+    fn matches_one(&self, value: Option<&Value>, normalization: Option<&Normalization>) -> bool {
+        let equals = |other: &Value| match (value, other) {
+            (Some(Value::String(a)), Value::String(b)) => normalized(normalization, a) == normalized(normalization, b),
+            _ => compare_values(value.unwrap_or(&Value::Null), other) == Some(Ordering::Equal),
+        };
+        let lowercase = |s: &str| normalized(normalization, s).to_lowercase();
+        match self {
+            Condition::Compare(op, other) => {
+                let ordering = compare_values(value.unwrap_or(&Value::Null), other);
+                match op {
+                    CmpOp::Eq => equals(other),
+                    CmpOp::Ne => !equals(other),
+                    CmpOp::Gt => ordering == Some(Ordering::Greater),
+                    CmpOp::Gte => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+                    CmpOp::Lt => ordering == Some(Ordering::Less),
+                    CmpOp::Lte => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                }
+            }
+            Condition::In(options) => options.iter().any(equals),
+            Condition::Nin(options) => !options.iter().any(equals),
+            Condition::Regex(regex) => matches!(value, Some(Value::String(s)) if regex.is_match(s)),
+            Condition::IEq(text) => matches!(value, Some(Value::String(s)) if lowercase(s) == lowercase(text)),
+            Condition::ILike(text) => matches!(value, Some(Value::String(s)) if lowercase(s).contains(&lowercase(text))),
+            Condition::Exists(exists) => value.is_some() == *exists,
+            Condition::Not(condition) => !condition.matches_with(value, normalization),
+            Condition::All(all) => all.iter().all(|c| c.matches_with(value, normalization)),
+        }
+    }
+}
+
+impl Collection {
+    /// Documentos que cumplen un filtro al estilo de MongoDB (ver `filter` y `Filter::select`)
+    pub fn query(&self, filter: Value) -> io::Result<Vec<Value>> {
+        self.query_sorted(filter, &[])
+    }
+
+    /// Como `query`, ordenado por `sort_by` (ver `comparator::sort_documents`): los empates
+    /// quedan en orden de inserción y los documentos sin el campo van primero en ascendente.
+    pub fn query_sorted(&self, filter: Value, sort_by: &[SortKey]) -> io::Result<Vec<Value>> {
+        self.query_range(filter, sort_by, 0, None)
+    }
+
+    /// Como `query_sorted`, saltando `skip` resultados ya ordenados y devolviendo como mucho
+    /// `limit`, para pedir los resultados por páginas
+    pub fn query_range(&self, filter: Value, sort_by: &[SortKey], skip: usize, limit: Option<usize>) -> io::Result<Vec<Value>> {
+        self.query_range_with(filter, sort_by, skip, limit, ReadConsistency::Latest)
+    }
+
+    /// Como `query_range`, leyendo con `consistency`
+    pub fn query_range_with(
+        &self,
+        filter: Value,
+        sort_by: &[SortKey],
+        skip: usize,
+        limit: Option<usize>,
+        consistency: ReadConsistency,
+    ) -> io::Result<Vec<Value>> {
+        QueryBuilder { sort_by: sort_by.to_vec(), skip, limit, consistency, ..self.query_builder().matching(filter) }.execute()
+    }
+
+    /// Cuántos documentos devolvería `query`, sin copiarlos
+    pub fn count_matching(&self, filter: Value) -> io::Result<usize> {
+        let parsed = Filter::parse(&filter)?;
+        let data = self.data_read()?;
+        let indexes = self.indexes.read().clone();
+        Ok(parsed.count(&data, &indexes, self))
+    }
+
+    /// El primer documento (en orden de inserción) que cumple `filter`, sin revisar los demás
+    pub fn find_one(&self, filter: Value) -> io::Result<Option<Value>> {
+        let parsed = Filter::parse(&filter)?;
+        let found = self.cached(&["find_one", &filter.to_string()], |data, indexes| {
+            Ok(parsed.first(data, indexes, self).into_iter().cloned().collect())
+        })?;
+        Ok(found.into_iter().next())
+    }
+
+    /// Solo los campos `fields` (y `_id`) de los documentos que cumplen `filter`, sin copiar el
+    /// resto de cada documento. Un campo que falta sale como `null`.
+    pub fn query_projected(&self, filter: Value, fields: &[&str]) -> io::Result<Vec<Value>> {
+        self.query_builder().matching(filter).fields(fields).execute()
+    }
+}
@@ -0,0 +1,110 @@
+//! Opciones de salida JSON para exportaciones y lecturas por FFI.
+
+use std::io::Write;
+use serde_json::{Map, Value};
+
+/// Forma del JSON generado. Con `canonical` y sin `pretty` la salida es determinista
+/// y apta para comparar con `diff`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct JsonFormat {
+    /// Indentado con dos espacios
+    pub pretty: bool,
+    /// Claves de los objetos ordenadas, a cualquier profundidad
+    pub canonical: bool,
+    /// Caracteres no ASCII escapados como `\uXXXX`
+    pub ascii: bool,
+}
+
+impl JsonFormat {
+    pub const PRETTY: u32 = 1;
+    pub const CANONICAL: u32 = 2;
+    pub const ASCII: u32 = 4;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn pretty(mut self, enabled: bool) -> Self {
+        self.pretty = enabled;
+        self
+    }
+
+    pub fn canonical(mut self, enabled: bool) -> Self {
+        self.canonical = enabled;
+        self
+    }
+
+    pub fn ascii(mut self, enabled: bool) -> Self {
+        self.ascii = enabled;
+        self
+    }
+
+    /// Formato a partir de los bits `PRETTY | CANONICAL | ASCII` (para la FFI)
+    pub fn from_flags(flags: u32) -> Self {
+        Self {
+            pretty: flags & Self::PRETTY != 0,
+            canonical: flags & Self::CANONICAL != 0,
+            ascii: flags & Self::ASCII != 0,
+        }
+    }
+
+    pub fn flags(&self) -> u32 {
+        (self.pretty as u32 * Self::PRETTY) | (self.canonical as u32 * Self::CANONICAL) | (self.ascii as u32 * Self::ASCII)
+    }
+
+    pub fn to_string<T: serde::Serialize + ?Sized>(&self, value: &T) -> serde_json::Result<String> {
+        let mut value = serde_json::to_value(value)?;
+        if self.canonical {
+            sort_keys(&mut value);
+        }
+        let out = if self.pretty {
+            serde_json::to_string_pretty(&value)?
+        } else {
+            serde_json::to_string(&value)?
+        };
+        Ok(if self.ascii { escape_non_ascii(&out) } else { out })
+    }
+
+    /// Como `to_string`, escribiendo en `writer`. Sin `canonical` ni `ascii` se serializa
+    /// directamente, sin copiar `value` ni reunir la salida en memoria.
+    pub fn to_writer<W: Write, T: serde::Serialize + ?Sized>(&self, mut writer: W, value: &T) -> serde_json::Result<()> {
+        match (self.canonical || self.ascii, self.pretty) {
+            (true, _) => writer.write_all(self.to_string(value)?.as_bytes()).map_err(serde_json::Error::io),
+            (false, true) => serde_json::to_writer_pretty(writer, value),
+            (false, false) => serde_json::to_writer(writer, value),
+        }
+    }
+}
+
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = std::mem::take(map).into_iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut sorted = Map::new();
+            for (key, mut child) in entries {
+                sort_keys(&mut child);
+                sorted.insert(key, child);
+            }
+            *map = sorted;
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Fuera de los strings el JSON ya es ASCII, así que basta con escapar cada carácter
+fn escape_non_ascii(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    for c in json.chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else {
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                out.push_str(&format!("\\u{:04x}", unit));
+            }
+        }
+    }
+    out
+}
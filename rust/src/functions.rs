@@ -0,0 +1,70 @@
+//! Predicados con nombre para las expresiones: `{"$fn": "is_weekend", "field": "created_at"}`
+//! llama al predicado `is_weekend` con el valor del campo (`null` si no existe). En lugar de
+//! `field` se puede pasar cualquier expresión en `arg`. Sirven para la lógica que no se puede
+//! escribir con los operadores.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde_json::Value;
+
+pub type PredicateFn = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+static FUNCTIONS: RwLock<Option<HashMap<String, PredicateFn>>> = parking_lot::const_rwlock(None);
+
+/// Registra (o sustituye) el predicado `name` para todo el proceso. Las expresiones ya
+/// creadas siguen usando el anterior.
+pub fn register_function<F>(name: &str, predicate: F) -> io::Result<()>
+where
+    F: Fn(&Value) -> bool + Send + Sync + 'static,
+{
+    if name.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty function name"));
+    }
+    FUNCTIONS.write().get_or_insert_with(HashMap::new).insert(name.to_string(), Arc::new(predicate));
+    Ok(())
+}
+
+/// `false` si no había ningún predicado con ese nombre
+pub fn unregister_function(name: &str) -> bool {
+    FUNCTIONS.write().as_mut().is_some_and(|all| all.remove(name).is_some())
+}
+
+/// Predicado resuelto al crear la expresión. Dos son iguales si tienen el mismo nombre.
+#[derive(Clone)]
+pub struct Predicate {
+    name: String,
+    call: PredicateFn,
+}
+
+impl Predicate {
+    /// Error si no hay ningún predicado registrado con ese nombre
+    pub fn resolve(name: &str) -> io::Result<Self> {
+        let call = FUNCTIONS.read().as_ref()
+            .and_then(|all| all.get(name).cloned())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown function '{}'", name)))?;
+        Ok(Self { name: name.to_string(), call })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, value: &Value) -> bool {
+        (self.call)(value)
+    }
+}
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.name)
+    }
+}
+
+impl PartialEq for Predicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
@@ -0,0 +1,86 @@
+//! Modelo de uso de los handles de la FFI.
+//!
+//! - `Database` y `Collection` son `Send + Sync` y se sincronizan por dentro: cualquier hilo
+//!   puede usar cualquier handle, también a la vez.
+//! - Cada handle devuelto (`ruggy_open`, `ruggy_get_collection`...) pertenece al host, que lo
+//!   libera una sola vez con su destructor y cuando ningún otro hilo lo está usando. Un handle
+//!   de colección es independiente del de su base de datos.
+//! - Desde el callback de CDC no se puede llamar a Ruggy: se ejecuta con la colección
+//!   bloqueada y la llamada se rechaza.
+//! - Las funciones `ruggy_named_*` no usan handles sino el alias de `ruggy_open_named` (ver
+//!   `registry`).
+//!
+//! En compilaciones de depuración se registran los handles vivos, de modo que un handle
+//! liberado, de otro tipo o desconocido produce un error claro en lugar de un fallo de memoria
+//! (mientras su dirección no se haya reutilizado).
+
+use std::cell::Cell;
+use crate::collection::Collection;
+use crate::db::Database;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum HandleKind {
+    Database,
+    Collection,
+}
+
+// Garantía del modelo: si alguno deja de ser `Send + Sync` esto no compila
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Database>();
+    assert_send_sync::<std::sync::Arc<Collection>>();
+};
+
+thread_local! {
+    static IN_CALLBACK: Cell<bool> = const { Cell::new(false) };
+}
+
+#[cfg(debug_assertions)]
+static LIVE: parking_lot::Mutex<std::collections::BTreeMap<usize, HandleKind>> =
+    parking_lot::const_mutex(std::collections::BTreeMap::new());
+
+pub(crate) fn register<T>(ptr: *mut T, kind: HandleKind) {
+    #[cfg(debug_assertions)]
+    LIVE.lock().insert(ptr as usize, kind);
+    #[cfg(not(debug_assertions))]
+    let _ = (ptr, kind);
+}
+
+/// Comprueba que `ptr` se puede usar como handle de tipo `kind` en este momento
+pub(crate) fn check<T>(ptr: *mut T, kind: HandleKind) -> Result<(), String> {
+    if ptr.is_null() {
+        return Err(format!("Null {:?} handle", kind));
+    }
+    check_not_in_callback()?;
+    #[cfg(debug_assertions)]
+    match LIVE.lock().get(&(ptr as usize)) {
+        Some(found) if *found == kind => {}
+        Some(found) => return Err(format!("Expected a {:?} handle but got a {:?} handle", kind, found)),
+        None => return Err(format!("{:?} handle {:p} is invalid or was already freed", kind, ptr)),
+    }
+    Ok(())
+}
+
+/// Las llamadas por alias (ver `registry`) no llevan handle pero tampoco pueden ser reentrantes
+pub(crate) fn check_not_in_callback() -> Result<(), String> {
+    if IN_CALLBACK.with(|flag| flag.get()) {
+        return Err("Ruggy cannot be called from inside a CDC or predicate callback".to_string());
+    }
+    Ok(())
+}
+
+/// Da de baja el handle antes de liberarlo; error si no se puede liberar (p. ej. doble free)
+pub(crate) fn release<T>(ptr: *mut T, kind: HandleKind) -> Result<(), String> {
+    check(ptr, kind)?;
+    #[cfg(debug_assertions)]
+    LIVE.lock().remove(&(ptr as usize));
+    Ok(())
+}
+
+/// Ejecuta un callback del host marcando el hilo para rechazar llamadas reentrantes
+pub(crate) fn in_callback<R>(f: impl FnOnce() -> R) -> R {
+    let previous = IN_CALLBACK.with(|flag| flag.replace(true));
+    let result = f();
+    IN_CALLBACK.with(|flag| flag.set(previous));
+    result
+}
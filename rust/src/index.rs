@@ -0,0 +1,428 @@
+//! Índices en memoria sobre un campo de primer nivel: de igualdad, de texto completo (con un
+//! `Analyzer`) o de prefijos para autocompletar. Guardan las posiciones de los documentos en la versión actual de los datos:
+//! los inserts añaden la posición nueva y cualquier reescritura (update, delete, expiración,
+//! recarga) los reconstruye.
+//!
+//! Un índice de igualdad puede incluir otros campos (`IndexSpec::include`): guarda además
+//! `_id`, el campo indexado y los incluidos de cada documento, y las consultas que solo
+//! necesitan esos campos se responden sin leer los documentos (ver `covered`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::expr;
+use crate::memory::{string_size, values_size, MAP_ENTRY_OVERHEAD};
+use crate::normalize::{normalized, Normalization};
+use crate::text::Analyzer;
+use crate::types;
+
+#[derive(Clone, Debug)]
+pub enum IndexKind {
+    /// Lo usan `find` y las igualdades de `find_with_operator`
+    Equality,
+    /// Texto completo para `Collection::search`
+    Text(Analyzer),
+    /// Valores en minúsculas ordenados para `Collection::autocomplete`
+    Prefix,
+}
+
+/// Definición de un índice
+#[derive(Clone, Debug)]
+pub struct IndexSpec {
+    pub name: String,
+    pub field: String,
+    pub kind: IndexKind,
+    /// Campos de primer nivel que se guardan en el índice para cubrir consultas; solo en los
+    /// de igualdad
+    pub include: Vec<String>,
+}
+
+impl IndexSpec {
+    pub fn new(name: &str, field: &str, kind: IndexKind) -> Self {
+        Self { name: name.to_string(), field: field.to_string(), kind, include: Vec::new() }
+    }
+
+    pub fn include(mut self, fields: &[&str]) -> Self {
+        self.include.extend(fields.iter().map(|field| field.to_string()));
+        self
+    }
+}
+
+/// Descripción de un índice con su contador de uso
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexInfo {
+    pub name: String,
+    pub field: String,
+    /// `equality`, `text` o `prefix`
+    pub kind: &'static str,
+    /// Valores (o términos) distintos indexados
+    pub keys: usize,
+    /// Campos incluidos (ver `IndexSpec::include`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Consultas resueltas con el índice
+    pub uses: u64,
+}
+
+/// Qué versión de los datos ve una lectura mientras hay una escritura en curso
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// La última: espera a que termine la escritura
+    #[default]
+    Latest,
+    /// La de antes de la escritura, sin esperar, si sigue en memoria (ver `Collection::snapshot_view`)
+    Snapshot,
+}
+
+impl ReadConsistency {
+    /// `"latest"` o `"snapshot"`
+    pub fn parse(name: &str) -> io::Result<Self> {
+        match name {
+            "latest" => Ok(Self::Latest),
+            "snapshot" => Ok(Self::Snapshot),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown read consistency '{}'", name))),
+        }
+    }
+}
+
+/// Preferencias de plan para una consulta
+#[derive(Clone, Debug, Default)]
+pub struct QueryOptions {
+    /// Índice que debe usarse obligatoriamente
+    pub hint: Option<String>,
+    /// Índices que no deben usarse
+    pub forbid: Vec<String>,
+    pub consistency: ReadConsistency,
+}
+
+impl QueryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hint(mut self, index: &str) -> Self {
+        self.hint = Some(index.to_string());
+        self
+    }
+
+    pub fn forbid(mut self, index: &str) -> Self {
+        self.forbid.push(index.to_string());
+        self
+    }
+
+    pub fn consistency(mut self, consistency: ReadConsistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Index {
+    spec: IndexSpec,
+    /// Clave tipada (ver `index_key`), término o valor en minúsculas -> posiciones en orden de
+    /// inserción. En los índices de texto una posición se repite tantas veces como aparece el término.
+    entries: BTreeMap<String, Vec<usize>>,
+    /// Con campos incluidos, `_id`, campo indexado e incluidos de cada posición indexada
+    /// (`Null` en las demás)
+    rows: Vec<Value>,
+    /// Hay claves de valores con tipo, que también se buscan por texto
+    typed: bool,
+    /// Normalización de las claves de texto (la de la colección)
+    normalization: Option<Normalization>,
+    /// Compartido entre las versiones del índice
+    uses: Arc<AtomicU64>,
+}
+
+/// Strings y números se indexan por separado, igual que los compara `scan_operator`. Los
+/// valores con tipo (ver `types`) van por su forma canónica.
+fn index_key(value: &Value, normalization: Option<&Normalization>) -> Option<String> {
+    match value {
+        Value::String(s) => Some(format!("s:{}", normalized(normalization, s))),
+        Value::Number(n) => Some(format!("n:{}", n)),
+        Value::Object(_) => types::index_key(value),
+        _ => None,
+    }
+}
+
+fn prefix_key(text: &str, normalization: Option<&Normalization>) -> String {
+    normalized(normalization, text).to_lowercase()
+}
+
+impl Index {
+    pub(crate) fn build(spec: IndexSpec, normalization: Option<Normalization>, uses: Arc<AtomicU64>, docs: &[Value]) -> Self {
+        Self::build_with_progress(spec, normalization, uses, docs, |_| {})
+    }
+
+    /// Como `build`, llamando a `progress(documentos indexados)` cada `PROGRESS_STEP`
+    /// documentos y al terminar
+    pub(crate) fn build_with_progress<F: Fn(usize)>(spec: IndexSpec, normalization: Option<Normalization>, uses: Arc<AtomicU64>, docs: &[Value], progress: F) -> Self {
+        const PROGRESS_STEP: usize = 4096;
+        let mut index = Self { spec, entries: BTreeMap::new(), rows: Vec::new(), typed: false, normalization, uses };
+        for (pos, doc) in docs.iter().enumerate() {
+            index.add(pos, doc);
+            if (pos + 1) % PROGRESS_STEP == 0 {
+                progress(pos + 1);
+            }
+        }
+        progress(docs.len());
+        index
+    }
+
+    /// Mismo índice reconstruido sobre otros datos, conservando el contador
+    pub(crate) fn rebuilt(&self, docs: &[Value]) -> Self {
+        Self::build(self.spec.clone(), self.normalization, self.uses.clone(), docs)
+    }
+
+    pub(crate) fn add(&mut self, pos: usize, doc: &Value) {
+        let value = match expr::field_value(doc, &self.spec.field) {
+            Some(value) => value,
+            None => return,
+        };
+        match (&self.spec.kind, value) {
+            (IndexKind::Equality, _) => {
+                // De un array se indexa cada elemento, una sola vez aunque se repita
+                let elements = match value {
+                    Value::Array(items) => items.as_slice(),
+                    value => std::slice::from_ref(value),
+                };
+                let mut keys: Vec<String> = elements.iter()
+                    .filter_map(|element| index_key(element, self.normalization.as_ref()))
+                    .collect();
+                keys.sort_unstable();
+                keys.dedup();
+                if keys.is_empty() {
+                    return;
+                }
+                for key in keys {
+                    self.typed |= key.starts_with("t:");
+                    self.entries.entry(key).or_default().push(pos);
+                }
+                if self.is_covering() {
+                    self.add_row(pos, doc);
+                }
+            }
+            (IndexKind::Text(analyzer), Value::String(text)) => {
+                for token in analyzer.analyze(text) {
+                    self.entries.entry(token.term).or_default().push(pos);
+                }
+            }
+            (IndexKind::Prefix, Value::String(text)) => {
+                self.entries.entry(prefix_key(text, self.normalization.as_ref())).or_default().push(pos);
+            }
+            _ => {}
+        }
+    }
+
+    fn add_row(&mut self, pos: usize, doc: &Value) {
+        let mut row = Map::new();
+        for field in ["_id", self.spec.field.as_str()].into_iter().chain(self.spec.include.iter().map(String::as_str)) {
+            match doc.get(field) {
+                Some(value) => {
+                    row.insert(field.to_string(), value.clone());
+                }
+                // Una ruta se guarda anidada, como está en el documento
+                None => if let Some(value) = expr::get_path(doc, field) {
+                    let _ = expr::set_field(&mut row, field, value.clone());
+                },
+            }
+        }
+        if self.rows.len() <= pos {
+            self.rows.resize(pos + 1, Value::Null);
+        }
+        self.rows[pos] = Value::Object(row);
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.spec.name
+    }
+
+    /// Tiene campos incluidos y guarda filas
+    pub(crate) fn is_covering(&self) -> bool {
+        matches!(self.spec.kind, IndexKind::Equality) && !self.spec.include.is_empty()
+    }
+
+    /// Las filas tienen completos todos los campos de primer nivel de `fields`. Una ruta
+    /// incluida (`address.city`) solo guarda esa parte del objeto: no cubre `address`.
+    pub(crate) fn covers(&self, fields: &[String]) -> bool {
+        self.is_covering() && fields.iter().all(|field| {
+            field == "_id" || *field == self.spec.field || self.spec.include.contains(field)
+        })
+    }
+
+    /// Como `lookup` sin números, devolviendo las filas guardadas en lugar de posiciones
+    pub(crate) fn covered_rows(&self, value: &str) -> Vec<Value> {
+        self.lookup(value, false).into_iter().map(|pos| self.rows[pos].clone()).collect()
+    }
+
+    pub(crate) fn field(&self) -> &str {
+        &self.spec.field
+    }
+
+    pub(crate) fn kind(&self) -> &IndexKind {
+        &self.spec.kind
+    }
+
+    pub(crate) fn analyzer(&self) -> Option<&Analyzer> {
+        match &self.spec.kind {
+            IndexKind::Text(analyzer) => Some(analyzer),
+            _ => None,
+        }
+    }
+
+    /// Primera posición de cada valor distinto que empieza por `prefix` (sin distinguir
+    /// mayúsculas), en orden alfabético
+    pub(crate) fn complete(&self, prefix: &str, limit: usize) -> Vec<usize> {
+        self.uses.fetch_add(1, Ordering::Relaxed);
+        let prefix = prefix_key(prefix, self.normalization.as_ref());
+        self.entries.range(prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&prefix))
+            .filter_map(|(_, positions)| positions.first().copied())
+            .take(limit)
+            .collect()
+    }
+
+    /// Documentos que contienen todos los términos de `query`, con su puntuación
+    /// (apariciones de los términos), de mayor a menor
+    pub(crate) fn search(&self, query: &str) -> Vec<(usize, u32)> {
+        let analyzer = match &self.spec.kind {
+            IndexKind::Text(analyzer) => analyzer,
+            _ => return Vec::new(),
+        };
+        self.uses.fetch_add(1, Ordering::Relaxed);
+        let mut terms: Vec<String> = analyzer.analyze(query).into_iter().map(|t| t.term).collect();
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut scores: HashMap<usize, (usize, u32)> = HashMap::new();
+        for term in &terms {
+            let mut last = None;
+            for &pos in self.entries.get(term).map(|p| p.as_slice()).unwrap_or(&[]) {
+                let entry = scores.entry(pos).or_insert((0, 0));
+                if last != Some(pos) {
+                    entry.0 += 1;
+                    last = Some(pos);
+                }
+                entry.1 += 1;
+            }
+        }
+        let mut hits: Vec<(usize, u32)> = scores.into_iter()
+            .filter(|(_, (matched, _))| *matched == terms.len())
+            .map(|(pos, (_, score))| (pos, score))
+            .collect();
+        hits.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hits
+    }
+
+    /// Posiciones de los documentos con `field == value`, en orden. Con `numbers` también
+    /// coinciden los números cuya representación es `value`.
+    pub(crate) fn lookup(&self, value: &str, numbers: bool) -> Vec<usize> {
+        self.uses.fetch_add(1, Ordering::Relaxed);
+        let mut lists = self.keys(value, numbers).into_iter().filter_map(|key| self.entries.get(&key));
+        let mut positions = lists.next().cloned().unwrap_or_default();
+        let mut merged = false;
+        for more in lists {
+            positions.extend_from_slice(more);
+            merged = true;
+        }
+        if merged {
+            // Un array puede tener el mismo valor como string y como número
+            positions.sort_unstable();
+            positions.dedup();
+        }
+        positions
+    }
+
+    /// Claves en las que puede estar `value`: como string, como número con `numbers` y como
+    /// valor con tipo
+    fn keys(&self, value: &str, numbers: bool) -> Vec<String> {
+        let mut keys = vec![format!("s:{}", normalized(self.normalization.as_ref(), value))];
+        if numbers {
+            keys.push(format!("n:{}", value));
+        }
+        if self.typed {
+            keys.extend(types::text_keys(value));
+        }
+        keys
+    }
+
+    /// Cuántas posiciones devolvería `lookup`, sin reunirlas
+    pub(crate) fn count(&self, value: &str, numbers: bool) -> usize {
+        self.uses.fetch_add(1, Ordering::Relaxed);
+        let lists: Vec<&Vec<usize>> = self.keys(value, numbers).iter().filter_map(|key| self.entries.get(key)).collect();
+        match lists.as_slice() {
+            [single] => single.len(),
+            lists => {
+                let mut positions: Vec<usize> = lists.iter().flat_map(|list| list.iter().copied()).collect();
+                positions.sort_unstable();
+                positions.dedup();
+                positions.len()
+            }
+        }
+    }
+
+    pub(crate) fn info(&self) -> IndexInfo {
+        IndexInfo {
+            name: self.spec.name.clone(),
+            field: self.spec.field.clone(),
+            kind: match self.spec.kind {
+                IndexKind::Equality => "equality",
+                IndexKind::Text(_) => "text",
+                IndexKind::Prefix => "prefix",
+            },
+            keys: self.entries.len(),
+            include: self.spec.include.clone(),
+            uses: self.uses.load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn memory_usage(&self) -> usize {
+        self.entries.iter()
+            .map(|(key, positions)| string_size(key) + size_of::<Vec<usize>>() + positions.capacity() * size_of::<usize>() + MAP_ENTRY_OVERHEAD)
+            .sum::<usize>() + values_size(&self.rows)
+    }
+}
+
+pub(crate) fn is_eq(operator: &str) -> bool {
+    matches!(operator, "=" | "==" | "eq")
+}
+
+/// Elige el índice para una consulta sobre `field`. Solo las igualdades usan índices; un
+/// `hint` que no sirve para la consulta es un error en lugar de ignorarse en silencio.
+pub(crate) fn choose<'a>(indexes: &'a [Index], field: &str, eq: bool, options: &QueryOptions) -> io::Result<Option<&'a Index>> {
+    if let Some(name) = &options.hint {
+        let index = indexes.iter()
+            .find(|i| i.name() == name)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Unknown index '{}'", name)))?;
+        if index.field() != field || !eq || !matches!(index.kind(), IndexKind::Equality) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Index '{}' cannot serve this query", name),
+            ));
+        }
+        return Ok(Some(index));
+    }
+    if !eq {
+        return Ok(None);
+    }
+    Ok(indexes.iter().find(|i| {
+        i.field() == field && matches!(i.kind(), IndexKind::Equality) && !options.forbid.iter().any(|f| f == i.name())
+    }))
+}
+
+/// Índice de igualdad sobre `field` que cubre `fields`, si lo hay. Con `hint` solo se
+/// considera ese índice (y es un error si no sirve para la igualdad, como en `choose`).
+pub(crate) fn choose_covering<'a>(indexes: &'a [Index], field: &str, fields: &[String], options: &QueryOptions) -> io::Result<Option<&'a Index>> {
+    if options.hint.is_some() {
+        return Ok(choose(indexes, field, true, options)?.filter(|index| index.covers(fields)));
+    }
+    Ok(indexes.iter().find(|i| {
+        i.field() == field && i.covers(fields) && !options.forbid.iter().any(|f| f == i.name())
+    }))
+}
@@ -0,0 +1,180 @@
+//! Construcción de índices en segundo plano. `create_index_background` indexa una copia de los
+//! documentos en otro hilo mientras la colección sigue aceptando lecturas y escrituras; hasta
+//! que termina, las consultas sobre el campo recorren los documentos como sin índice.
+//!
+//! Al terminar, si nadie ha escrito entretanto el índice se añade tal cual; si no, se vuelve a
+//! construir sobre los datos nuevos, y tras `RETRIES` intentos la última construcción se hace
+//! con las escrituras bloqueadas, como `create_index`.
+//!
+//! ```ignore
+//! col.create_index_background("by_email", "email")?;
+//! for build in col.index_builds() { println!("{}: {}/{}", build.name, build.indexed, build.total); }
+//! col.await_indexes()?;
+//! ```
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use parking_lot::{Condvar, Mutex};
+use serde::Serialize;
+use crate::collection::Collection;
+use crate::index::{Index, IndexKind, IndexSpec};
+
+/// Intentos sin bloquear las escrituras antes de construir con ellas bloqueadas
+const RETRIES: usize = 3;
+
+/// Estado de una construcción en segundo plano
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct IndexBuildProgress {
+    pub name: String,
+    pub field: String,
+    /// Documentos indexados en el intento actual
+    pub indexed: usize,
+    pub total: usize,
+    /// Intentos empezados; más de uno si hubo escrituras durante la construcción
+    pub attempts: usize,
+    pub done: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Build {
+    name: String,
+    field: String,
+    indexed: AtomicUsize,
+    total: AtomicUsize,
+    attempts: AtomicUsize,
+    /// `None` mientras se construye
+    result: Mutex<Option<Result<(), String>>>,
+}
+
+impl Build {
+    fn progress(&self) -> IndexBuildProgress {
+        let result = self.result.lock();
+        IndexBuildProgress {
+            name: self.name.clone(),
+            field: self.field.clone(),
+            indexed: self.indexed.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+            attempts: self.attempts.load(Ordering::Relaxed),
+            done: result.is_some(),
+            error: result.as_ref().and_then(|r| r.as_ref().err().cloned()),
+        }
+    }
+}
+
+/// Construcciones de una colección, hasta que se recogen con `await_indexes`
+#[derive(Default)]
+pub(crate) struct IndexBuilds {
+    builds: Mutex<Vec<Arc<Build>>>,
+    finished: Condvar,
+}
+
+impl IndexBuilds {
+    fn is_building(&self, name: &str) -> bool {
+        self.builds.lock().iter().any(|b| b.name == name && b.result.lock().is_none())
+    }
+
+    fn finish(&self, build: &Build, result: Result<(), String>) {
+        // Con el lock de la lista para que `await_indexes` no se pierda el aviso
+        let _builds = self.builds.lock();
+        *build.result.lock() = Some(result);
+        self.finished.notify_all();
+    }
+}
+
+impl Collection {
+    /// Crea un índice de igualdad como `create_index`, construyéndolo en otro hilo. Vuelve en
+    /// cuanto empieza; el progreso se consulta con `index_builds` y se espera con
+    /// `await_indexes`.
+    pub fn create_index_background(self: &Arc<Self>, name: &str, field: &str) -> io::Result<()> {
+        self.add_index_background(IndexSpec::new(name, field, IndexKind::Equality))
+    }
+
+    /// Como `create_index_background` con cualquier tipo de índice
+    pub fn add_index_background(self: &Arc<Self>, spec: IndexSpec) -> io::Result<()> {
+        if self.indexes.read().iter().any(|i| i.name() == spec.name) || self.index_builds.is_building(&spec.name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Index '{}' already exists", spec.name)));
+        }
+        let build = Arc::new(Build {
+            name: spec.name.clone(),
+            field: spec.field.clone(),
+            indexed: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            attempts: AtomicUsize::new(0),
+            result: Mutex::new(None),
+        });
+        self.index_builds.builds.lock().push(build.clone());
+
+        let col = self.clone();
+        let thread_build = build.clone();
+        let spawned = thread::Builder::new()
+            .name(format!("ruggy-index-{}", spec.name))
+            .spawn(move || {
+                let result = col.build_in_background(spec, &thread_build).map_err(|e| e.to_string());
+                col.index_builds.finish(&thread_build, result);
+            });
+        if let Err(e) = spawned {
+            self.index_builds.finish(&build, Err(e.to_string()));
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn build_in_background(&self, spec: IndexSpec, build: &Build) -> io::Result<()> {
+        let normalization = self.options.read().normalization;
+        loop {
+            let attempt = build.attempts.fetch_add(1, Ordering::Relaxed);
+            let snapshot = self.data_read()?.clone();
+            build.total.store(snapshot.len(), Ordering::Relaxed);
+            build.indexed.store(0, Ordering::Relaxed);
+            let index = Index::build_with_progress(spec.clone(), normalization, Arc::default(), &snapshot, |n| {
+                build.indexed.store(n, Ordering::Relaxed);
+            });
+
+            let guard = self.data_write()?;
+            let index = if Arc::ptr_eq(&*guard, &snapshot) {
+                index
+            } else if attempt < RETRIES {
+                continue;
+            } else {
+                build.total.store(guard.len(), Ordering::Relaxed);
+                Index::build_with_progress(spec.clone(), normalization, Arc::default(), &guard, |n| {
+                    build.indexed.store(n, Ordering::Relaxed);
+                })
+            };
+            let mut indexes = self.indexes.write();
+            if indexes.iter().any(|i| i.name() == spec.name) {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Index '{}' already exists", spec.name)));
+            }
+            Arc::make_mut(&mut indexes).push(index);
+            drop(indexes);
+            drop(guard);
+            self.invalidate_cache();
+            return Ok(());
+        }
+    }
+
+    /// Construcciones en segundo plano en curso y las terminadas que aún no se han esperado
+    pub fn index_builds(&self) -> Vec<IndexBuildProgress> {
+        self.index_builds.builds.lock().iter().map(|b| b.progress()).collect()
+    }
+
+    /// Espera a que terminen todas las construcciones en segundo plano y las retira de
+    /// `index_builds`. Error si alguna falló (el índice no se creó).
+    pub fn await_indexes(&self) -> io::Result<()> {
+        let mut builds = self.index_builds.builds.lock();
+        while builds.iter().any(|b| b.result.lock().is_none()) {
+            self.index_builds.finished.wait(&mut builds);
+        }
+        let errors: Vec<String> = builds.drain(..)
+            .filter_map(|b| b.result.lock().clone()?.err().map(|e| format!("'{}': {}", b.name, e)))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::other(format!("Index build failed: {}", errors.join("; "))))
+        }
+    }
+}
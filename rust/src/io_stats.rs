@@ -0,0 +1,124 @@
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Compactaciones que se guardan en el historial de cada colección
+const COMPACTION_HISTORY: usize = 32;
+
+/// Escritura de una colección desde que se abrió (o desde `reset_io_stats`), para medir cuánto
+/// cuesta la estrategia de persistencia con una carga concreta
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IoStats {
+    /// Mutaciones de documentos (inserts, updates y deletes)
+    pub changes: u64,
+    /// Tamaño en JSON de los documentos insertados o modificados, más el de los `_id` borrados
+    pub logical_bytes: u64,
+    /// Bytes añadidos al final del archivo
+    pub appended_bytes: u64,
+    pub appends: u64,
+    /// Bytes de las reescrituras completas, incluida la época
+    pub rewritten_bytes: u64,
+    pub rewrites: u64,
+    /// Últimas compactaciones que reescribieron el archivo, de la más antigua a la más reciente
+    pub compactions: Vec<CompactionRecord>,
+}
+
+impl IoStats {
+    pub fn bytes_written(&self) -> u64 {
+        self.appended_bytes + self.rewritten_bytes
+    }
+
+    /// Bytes escritos por cada byte de cambio lógico; `None` sin cambios
+    pub fn write_amplification(&self) -> Option<f64> {
+        (self.logical_bytes > 0).then(|| self.bytes_written() as f64 / self.logical_bytes as f64)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompactionRecord {
+    /// Epoch en ms
+    pub at: u64,
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub documents: usize,
+}
+
+/// Contadores de `IoStats`, actualizados sin locks desde las escrituras
+#[derive(Default)]
+pub(crate) struct IoCounters {
+    changes: AtomicU64,
+    logical_bytes: AtomicU64,
+    appended_bytes: AtomicU64,
+    appends: AtomicU64,
+    rewritten_bytes: AtomicU64,
+    rewrites: AtomicU64,
+    compactions: Mutex<VecDeque<CompactionRecord>>,
+}
+
+impl IoCounters {
+    pub(crate) fn change(&self, id: &str, document: Option<&Value>) {
+        let bytes = match document {
+            Some(doc) => json_len(doc),
+            None => id.len() as u64,
+        };
+        self.changes.fetch_add(1, Ordering::Relaxed);
+        self.logical_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn append(&self, bytes: usize) {
+        self.appends.fetch_add(1, Ordering::Relaxed);
+        self.appended_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn rewrite(&self, bytes: usize) {
+        self.rewrites.fetch_add(1, Ordering::Relaxed);
+        self.rewritten_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn compaction(&self, record: CompactionRecord) {
+        let mut compactions = self.compactions.lock();
+        if compactions.len() == COMPACTION_HISTORY {
+            compactions.pop_front();
+        }
+        compactions.push_back(record);
+    }
+
+    pub(crate) fn snapshot(&self) -> IoStats {
+        IoStats {
+            changes: self.changes.load(Ordering::Relaxed),
+            logical_bytes: self.logical_bytes.load(Ordering::Relaxed),
+            appended_bytes: self.appended_bytes.load(Ordering::Relaxed),
+            appends: self.appends.load(Ordering::Relaxed),
+            rewritten_bytes: self.rewritten_bytes.load(Ordering::Relaxed),
+            rewrites: self.rewrites.load(Ordering::Relaxed),
+            compactions: self.compactions.lock().iter().cloned().collect(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in [&self.changes, &self.logical_bytes, &self.appended_bytes, &self.appends, &self.rewritten_bytes, &self.rewrites] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.compactions.lock().clear();
+    }
+}
+
+/// Longitud del JSON compacto de `value` sin generarlo
+fn json_len(value: &Value) -> u64 {
+    struct Counter(u64);
+    impl Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len() as u64;
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    let _ = serde_json::to_writer(&mut counter, value);
+    counter.0
+}
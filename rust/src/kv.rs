@@ -0,0 +1,103 @@
+//! Almacén clave-valor para ajustes y contadores, guardado en la colección interna `_kv` como
+//! documentos `{"key", "value"}`.
+
+use std::io;
+use std::sync::Arc;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use crate::cdc::ChangeOp;
+use crate::collection::{doc_id, Collection};
+use crate::db::Database;
+use crate::options::CollectionOptions;
+
+const COLLECTION: &str = "_kv";
+
+pub struct KvStore {
+    collection: Arc<Collection>,
+}
+
+impl Database {
+    pub fn kv(&self) -> io::Result<KvStore> {
+        let collection = self.collection_with_options(COLLECTION, CollectionOptions::new().index("kv_key", "key"))?;
+        Ok(KvStore { collection })
+    }
+}
+
+impl KvStore {
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> io::Result<()> {
+        let value = serde_json::to_value(value)?;
+        self.collection.upsert_many("key", vec![json!({"key": key, "value": value})])?;
+        Ok(())
+    }
+
+    /// `None` si la clave no existe; error si el valor guardado no es de tipo `T`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> io::Result<Option<T>> {
+        match self.get_value(key) {
+            Some(value) => Ok(Some(serde_json::from_value(value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Valor guardado tal cual
+    pub fn get_value(&self, key: &str) -> Option<Value> {
+        self.collection.find("key", key)
+            .into_iter()
+            .next()
+            .map(|mut doc| doc["value"].take())
+    }
+
+    /// `false` si la clave no existía
+    pub fn remove(&self, key: &str) -> io::Result<bool> {
+        let id = match self.collection.find("key", key).first() {
+            Some(doc) => doc_id(doc).to_string(),
+            None => return Ok(false),
+        };
+        self.collection.delete_by_id(&id)
+    }
+
+    /// Suma `by` al entero guardado en `key` (0 si no existe) de forma atómica y devuelve el
+    /// resultado. Falla si el valor no es un entero o el resultado se desborda.
+    pub fn incr(&self, key: &str, by: i64) -> io::Result<i64> {
+        self.collection.ensure_writable()?;
+        let mut guard = self.collection.data_write()?;
+        let pos = guard.iter().position(|doc| doc.get("key").and_then(|k| k.as_str()) == Some(key));
+        let current = match pos {
+            Some(pos) => guard[pos].get("value").and_then(|v| v.as_i64()).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Value of '{}' is not an integer", key))
+            })?,
+            None => 0,
+        };
+        let next = current.checked_add(by)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Counter '{}' overflowed", key)))?;
+
+        let data = Arc::make_mut(&mut guard);
+        match pos {
+            Some(pos) => {
+                data[pos]["value"] = json!(next);
+                self.collection.emit(ChangeOp::Update, doc_id(&data[pos]), Some(&data[pos]));
+            }
+            None => {
+                let doc = json!({"key": key, "value": next, "_id": Uuid::new_v4().to_string()});
+                self.collection.emit(ChangeOp::Insert, doc_id(&doc), Some(&doc));
+                data.push(doc);
+            }
+        }
+        self.collection.apply_order(data);
+        self.collection.rebuild_indexes(data);
+        self.collection.touch();
+        drop(guard);
+        self.collection.persist_on_write()?;
+        Ok(next)
+    }
+
+    /// Claves guardadas, en orden alfabético
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.collection.snapshot().iter()
+            .filter_map(|doc| doc.get("key").and_then(|k| k.as_str()).map(str::to_string))
+            .collect();
+        keys.sort();
+        keys
+    }
+}
@@ -0,0 +1,175 @@
+//! Base de datos en capas: una base de solo lectura (p. ej. el contenido que trae un juego)
+//! bajo una capa escribible (las modificaciones del jugador). Las lecturas miran primero la
+//! capa: un documento de la capa con el mismo `_id` que uno de la base lo sustituye. Las
+//! escrituras solo tocan la capa; borrar un documento de la base deja en la capa una lápida
+//! `{"_id", "_deleted": true}`.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use serde_json::{json, Value};
+use crate::collection::{check_reserved_field, doc_id, Collection};
+use crate::db::Database;
+use crate::expr;
+
+const DELETED: &str = "_deleted";
+
+pub struct LayeredDatabase {
+    base: Database,
+    overlay: Database,
+}
+
+impl LayeredDatabase {
+    /// Abre `base` como seguidor de solo lectura y `overlay` como base de datos normal
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(base: P, overlay: Q) -> io::Result<Self> {
+        Ok(Self::new(Database::open_follower(base)?, Database::new(overlay)?))
+    }
+
+    pub fn new(base: Database, overlay: Database) -> Self {
+        Self { base, overlay }
+    }
+
+    pub fn base(&self) -> &Database {
+        &self.base
+    }
+
+    pub fn overlay(&self) -> &Database {
+        &self.overlay
+    }
+
+    /// La colección `name` de las dos capas. No hace falta que exista en la base.
+    pub fn collection(&self, name: &str) -> io::Result<LayeredCollection> {
+        Ok(LayeredCollection {
+            base: self.base.collection(name)?,
+            overlay: self.overlay.collection(name)?,
+        })
+    }
+}
+
+pub struct LayeredCollection {
+    base: Arc<Collection>,
+    overlay: Arc<Collection>,
+}
+
+fn is_tombstone(doc: &Value) -> bool {
+    doc.get(DELETED).and_then(|v| v.as_bool()) == Some(true)
+}
+
+impl LayeredCollection {
+    pub fn name(&self) -> &str {
+        self.overlay.name()
+    }
+
+    pub fn base(&self) -> &Arc<Collection> {
+        &self.base
+    }
+
+    pub fn overlay(&self) -> &Arc<Collection> {
+        &self.overlay
+    }
+
+    /// Documentos de la capa por `_id`, lápidas incluidas
+    fn overrides(&self) -> HashMap<String, Value> {
+        self.overlay.snapshot().iter().map(|doc| (doc_id(doc).to_string(), doc.clone())).collect()
+    }
+
+    /// Junta los resultados de una misma consulta en las dos capas: los de la base que la capa
+    /// no sustituye y después los de la capa que no son lápidas
+    fn merge(&self, base: Vec<Value>, overlay: Vec<Value>) -> Vec<Value> {
+        let overrides = self.overrides();
+        base.into_iter()
+            .filter(|doc| !overrides.contains_key(doc_id(doc)))
+            .chain(overlay.into_iter().filter(|doc| !is_tombstone(doc)))
+            .collect()
+    }
+
+    /// Todos los documentos visibles: los de la base en su orden (con la versión de la capa si
+    /// la hay) y después los que solo están en la capa
+    pub fn find_all(&self) -> Vec<Value> {
+        let mut overrides = self.overrides();
+        let mut docs: Vec<Value> = self.base.snapshot().iter()
+            .filter_map(|doc| match overrides.remove(doc_id(doc)) {
+                Some(over) => (!is_tombstone(&over)).then_some(over),
+                None => Some(doc.clone()),
+            })
+            .collect();
+        docs.extend(self.overlay.snapshot().iter()
+            .filter(|doc| overrides.contains_key(doc_id(doc)) && !is_tombstone(doc))
+            .cloned());
+        docs
+    }
+
+    pub fn find(&self, field: &str, value: &str) -> Vec<Value> {
+        self.merge(self.base.find(field, value), self.overlay.find(field, value))
+    }
+
+    pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> Vec<Value> {
+        self.merge(
+            self.base.find_with_operator(field, value, operator),
+            self.overlay.find_with_operator(field, value, operator),
+        )
+    }
+
+    /// Documento visible con ese `_id`
+    pub fn get(&self, id: &str) -> Option<Value> {
+        match self.overlay.snapshot().iter().find(|doc| doc_id(doc) == id) {
+            Some(doc) => (!is_tombstone(doc)).then(|| doc.clone()),
+            None => self.base.snapshot().iter().find(|doc| doc_id(doc) == id).cloned(),
+        }
+    }
+
+    /// Inserta en la capa con un `_id` nuevo
+    pub fn insert(&self, document: Value) -> io::Result<String> {
+        self.overlay.insert(document)
+    }
+
+    /// Con un documento de la base, guarda en la capa una copia con el cambio
+    pub fn update_field(&self, id: &str, field: &str, value: Value) -> io::Result<bool> {
+        if field == "_id" || field == DELETED {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot update '{}' in a layered collection", field)));
+        }
+        let current = match self.get(id) {
+            Some(doc) => doc,
+            None => return Ok(false),
+        };
+        if self.overlay.snapshot().iter().any(|doc| doc_id(doc) == id) {
+            return self.overlay.update_field(id, field, value);
+        }
+        check_reserved_field(field)?;
+        let mut copy = current;
+        if let Some(obj) = copy.as_object_mut() {
+            expr::set_field(obj, field, value)?;
+        }
+        self.overlay.append_documents(&[copy])?;
+        Ok(true)
+    }
+
+    /// Borra de la capa y, si el documento viene de la base, lo oculta con una lápida
+    pub fn delete_by_id(&self, id: &str) -> io::Result<bool> {
+        if self.get(id).is_none() {
+            return Ok(false);
+        }
+        if self.base.snapshot().iter().any(|doc| doc_id(doc) == id) {
+            self.overlay.append_documents(&[json!({"_id": id, DELETED: true})])?;
+            return Ok(true);
+        }
+        self.overlay.delete_by_id(id)
+    }
+
+    /// Descarta los cambios de la capa sobre ese `_id`, volviendo a la versión de la base.
+    /// `false` si la capa no lo tocaba.
+    pub fn revert(&self, id: &str) -> io::Result<bool> {
+        self.overlay.delete_by_id(id)
+    }
+
+    /// `_id` de los documentos de la base que la capa modifica u oculta
+    pub fn overridden_ids(&self) -> Vec<String> {
+        let overrides = self.overrides();
+        self.base.snapshot().iter()
+            .map(doc_id)
+            .filter(|id| overrides.contains_key(*id))
+            .map(str::to_string)
+            .collect()
+    }
+}
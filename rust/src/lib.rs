@@ -1,7 +1,119 @@
+pub mod archive;
+pub mod bench;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod bulk;
+pub mod cache;
+pub mod cdc;
+pub mod collation;
 pub mod collection;
+pub mod comparator;
+mod compaction;
+mod compression;
+pub mod config;
+pub mod contention;
+pub mod covered;
+pub mod csv_import;
+pub mod datetime;
 pub mod db;
+pub mod dedupe;
+pub mod dynamic;
+pub mod encryption;
+pub mod export;
+pub mod expr;
+pub mod facets;
+#[cfg(feature = "fault-injection")]
+pub mod faults;
 pub mod ffi;
+pub mod filter;
+pub mod format;
+pub mod functions;
+mod handles;
+pub mod index;
+pub mod index_build;
+pub mod io_stats;
+pub mod kv;
+pub mod layered;
+#[cfg(feature = "lua")]
+pub mod lua;
+pub mod load;
+pub mod limits;
+pub mod lock_stats;
+pub mod maintenance;
+pub mod memory;
+mod msgpack;
+pub mod normalize;
+pub mod options;
+pub mod pack;
+pub mod page;
+pub mod partition;
+pub mod pivot;
+pub mod pushdown;
+pub mod query;
+pub mod queue;
+pub mod raw;
+pub mod registry;
+pub mod replica;
+pub mod rules;
+#[cfg(feature = "s3")]
+pub mod s3;
+pub mod sharding;
+pub mod snapshot;
+pub mod stats;
+pub mod storage;
+pub mod testing;
+pub mod text;
+pub mod transfer;
+pub mod types;
+pub mod window;
 
-pub use collection::Collection;
+pub use archive::{Archive, ArchivePolicy};
+pub use bench::{Latency, Mix, Workload, WorkloadReport};
+pub use bulk::{ImportResult, UpsertReport};
+pub use cache::CacheStats;
+pub use cdc::{CdcSink, ChangeEvent, ChangeOp};
+pub use collation::Collation;
+pub use contention::{Busy, ContentionPolicy};
+pub use collection::{Collection, ExpiryCallback};
+pub use comparator::{register_comparator, sort_documents, SortKey};
+pub use config::{DatabaseConfig, Durability, MaintenanceSettings};
+pub use covered::{EqQuery, EqResult};
+pub use csv_import::CsvOptions;
 pub use db::Database;
+pub use dedupe::content_hash;
+pub use encryption::{FieldCipher, FieldEncryption, FieldKey};
+pub use export::ExportOptions;
+pub use expr::{Expr, Projection};
+pub use facets::{FacetCount, FacetedResult};
 pub use ffi::*;
+pub use filter::{Condition, Filter};
+pub use format::JsonFormat;
+pub use functions::{register_function, unregister_function};
+pub use index::{IndexInfo, IndexKind, IndexSpec, QueryOptions, ReadConsistency};
+pub use index_build::IndexBuildProgress;
+pub use io_stats::{CompactionRecord, IoStats};
+pub use limits::{LimitExceeded, ParseLimits};
+pub use lock_stats::{LockStats, LockWait, LockWaitCallback};
+pub use load::{CorruptRecord, DuplicateId, DuplicatePolicy, LoadMode, LoadReport};
+pub use kv::KvStore;
+pub use layered::{LayeredCollection, LayeredDatabase};
+pub use maintenance::{MaintenanceConfig, MaintenanceHandle, MaintenanceReport};
+pub use memory::MemoryUsage;
+pub use normalize::{NormalForm, Normalization};
+pub use options::{CollectionOptions, DocumentOrder, PersistStrategy, Ttl};
+pub use pack::{PackBuilder, PackCollection, PackManifest, PackStorage};
+pub use page::{Page, PageQuery};
+pub use partition::{PartitionBy, PartitionedCollection};
+pub use pivot::{Aggregate, Pivot};
+pub use query::QueryBuilder;
+pub use queue::{Job, JobQueue};
+pub use raw::RawLines;
+pub use rules::{Rule, RuleViolation, UpdateExpr};
+pub use sharding::{ShardBy, ShardedCollection};
+pub use snapshot::{SnapshotCollection, SnapshotIndex, SnapshotManifest};
+pub use stats::{CollectionStats, FieldStats};
+pub use storage::{LocalStorage, MemoryStorage, StorageBackend};
+pub use testing::{MockClock, MockCollection, MockDatabase, MockIds};
+pub use text::{Analyzer, Highlight, Language, SearchHit, Token, TokenFilter};
+pub use types::{as_bytes, bytes, register_type, typed, CustomType};
+pub use window::{Window, WindowFn};
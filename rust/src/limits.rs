@@ -0,0 +1,158 @@
+//! Límites al recibir JSON de fuera (la FFI, `dispatch`, `insert` e `import`), para que una
+//! entrada maliciosa o rota no agote la pila con anidamientos profundos ni reserve memoria sin
+//! medida. El texto JSON se comprueba antes de interpretarlo y los buffers MessagePack mientras
+//! se decodifican; al pasarse, el error es un `LimitExceeded` (`io::ErrorKind::InvalidData`).
+//!
+//! ```ignore
+//! limits::set_parse_limits(ParseLimits { max_depth: 32, max_bytes: 1 << 20 });
+//! if let Err(e) = col.insert(doc) {
+//!     if limits::is_limit_exceeded(&e) { /* rechazar la petición */ }
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// serde_json no interpreta anidamientos de más de 128 niveles
+const PARSER_DEPTH: usize = 128;
+const DEFAULT_DEPTH: usize = 64;
+const DEFAULT_BYTES: usize = 64 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Niveles de arrays y objetos anidados; como mucho 128
+    pub max_depth: usize,
+    /// Bytes del texto (o del buffer MessagePack, o de la línea de un documento)
+    pub max_bytes: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self { max_depth: DEFAULT_DEPTH, max_bytes: DEFAULT_BYTES }
+    }
+}
+
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(DEFAULT_DEPTH);
+static MAX_BYTES: AtomicUsize = AtomicUsize::new(DEFAULT_BYTES);
+
+/// Límites actuales, comunes a todo el proceso
+pub fn parse_limits() -> ParseLimits {
+    ParseLimits { max_depth: MAX_DEPTH.load(Ordering::Relaxed), max_bytes: MAX_BYTES.load(Ordering::Relaxed) }
+}
+
+pub fn set_parse_limits(limits: ParseLimits) {
+    MAX_DEPTH.store(limits.max_depth.min(PARSER_DEPTH), Ordering::Relaxed);
+    MAX_BYTES.store(limits.max_bytes, Ordering::Relaxed);
+}
+
+/// Una entrada superó uno de los `ParseLimits`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LimitExceeded {
+    /// `"depth"` o `"size"`
+    pub limit: &'static str,
+    pub max: usize,
+    /// Lo encontrado; en profundidad, el primer nivel que se pasa
+    pub found: usize,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            "depth" => write!(f, "JSON nesting deeper than {} levels", self.max),
+            _ => write!(f, "Input of {} bytes exceeds the limit of {} bytes", self.found, self.max),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// El error viene de una entrada que superó los `ParseLimits` (el detalle es un `LimitExceeded`)
+pub fn is_limit_exceeded(err: &io::Error) -> bool {
+    err.get_ref().is_some_and(|inner| inner.is::<LimitExceeded>())
+}
+
+thread_local! {
+    static LAST_EXCEEDED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Si la última entrada comprobada en este hilo superó los límites
+pub(crate) fn last_exceeded() -> bool {
+    LAST_EXCEEDED.with(Cell::get)
+}
+
+fn exceeded(limit: &'static str, max: usize, found: usize) -> io::Error {
+    LAST_EXCEEDED.with(|last| last.set(true));
+    io::Error::new(io::ErrorKind::InvalidData, LimitExceeded { limit, max, found })
+}
+
+/// Para los decodificadores que llevan su propia cuenta de la profundidad (ver `msgpack`)
+pub(crate) fn depth_exceeded(max: usize) -> io::Error {
+    exceeded("depth", max, max + 1)
+}
+
+pub(crate) fn check_size(bytes: usize) -> io::Result<()> {
+    LAST_EXCEEDED.with(|last| last.set(false));
+    let max = MAX_BYTES.load(Ordering::Relaxed);
+    if bytes > max {
+        return Err(exceeded("size", max, bytes));
+    }
+    Ok(())
+}
+
+/// Recorre el texto contando corchetes y llaves fuera de los strings, sin interpretarlo
+fn check_text_depth(text: &[u8]) -> io::Result<()> {
+    let max = MAX_DEPTH.load(Ordering::Relaxed);
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for &byte in text {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max {
+                    return Err(exceeded("depth", max, depth));
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Profundidad de un valor ya construido (p. ej. un documento para `insert`), sin recursión
+pub(crate) fn check_depth(value: &Value) -> io::Result<()> {
+    LAST_EXCEEDED.with(|last| last.set(false));
+    let max = MAX_DEPTH.load(Ordering::Relaxed);
+    let mut pending = vec![(value, 1)];
+    while let Some((value, depth)) = pending.pop() {
+        if matches!(value, Value::Array(_) | Value::Object(_)) && depth > max {
+            return Err(exceeded("depth", max, depth));
+        }
+        match value {
+            Value::Array(items) => pending.extend(items.iter().map(|child| (child, depth + 1))),
+            Value::Object(map) => pending.extend(map.values().map(|child| (child, depth + 1))),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// `serde_json::from_str` comprobando antes el tamaño y la profundidad
+pub(crate) fn parse_json<T: DeserializeOwned>(text: &str) -> io::Result<T> {
+    check_size(text.len())?;
+    check_text_depth(text.as_bytes())?;
+    Ok(serde_json::from_str(text)?)
+}
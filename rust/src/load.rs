@@ -0,0 +1,167 @@
+//! Qué hacer con los registros ilegibles o con `_id` repetido al cargar una colección y
+//! resumen de la última carga.
+
+use std::collections::HashMap;
+use std::io;
+use std::thread;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadMode {
+    /// Se saltan las líneas ilegibles y quedan anotadas en el `LoadReport`
+    #[default]
+    Lossy,
+    /// La carga falla si alguna línea no se puede leer
+    Strict,
+}
+
+/// Qué documento se queda cuando varias líneas tienen el mismo `_id` (p. ej. tras una
+/// reescritura interrumpida). Se queda en la posición de la primera línea, para no alterar el
+/// orden de inserción.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicatePolicy {
+    /// La última línea
+    #[default]
+    KeepLast,
+    /// La primera línea
+    KeepFirst,
+    /// La carga falla
+    Fail,
+}
+
+/// Línea del archivo que no es un documento JSON válido
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CorruptRecord {
+    /// Número de línea desde 1 (en la actualización incremental de un seguidor, contado desde
+    /// lo ya leído)
+    pub line: usize,
+    /// Posición del inicio de la línea en el archivo
+    pub offset: u64,
+    pub error: String,
+}
+
+/// `_id` repetido en el archivo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DuplicateId {
+    pub id: String,
+    /// Líneas con ese `_id`, en orden
+    pub lines: Vec<usize>,
+    /// Línea que se conservó
+    pub kept: usize,
+}
+
+/// Resumen de la última lectura del archivo (`Collection::load_report`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LoadReport {
+    pub loaded: usize,
+    pub skipped: usize,
+    pub corrupt: Vec<CorruptRecord>,
+    pub duplicates: Vec<DuplicateId>,
+}
+
+impl LoadReport {
+    pub(crate) fn skip(&mut self, line: usize, offset: u64, error: String) {
+        self.skipped += 1;
+        self.corrupt.push(CorruptRecord { line, offset, error });
+    }
+
+    /// Error del modo estricto con la ubicación de los primeros registros corruptos
+    pub(crate) fn corruption_error(&self) -> io::Error {
+        const SHOWN: usize = 10;
+        let mut places: Vec<String> = self.corrupt.iter()
+            .take(SHOWN)
+            .map(|r| format!("line {} (byte {}): {}", r.line, r.offset, r.error))
+            .collect();
+        if self.corrupt.len() > SHOWN {
+            places.push(format!("and {} more", self.corrupt.len() - SHOWN));
+        }
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} corrupt record(s): {}", self.corrupt.len(), places.join("; ")),
+        )
+    }
+}
+
+/// Tamaño a partir del que el archivo se interpreta en paralelo al abrir
+pub(crate) const PARALLEL_BYTES: usize = 4 << 20;
+/// Documentos a partir de los que los índices se construyen en paralelo
+pub(crate) const PARALLEL_DOCS: usize = 50_000;
+
+/// `items.iter().map(f)` repartido en trozos consecutivos entre los núcleos disponibles, con
+/// el resultado en el mismo orden
+pub(crate) fn parallel_map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(items.len());
+    if threads < 2 {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items.chunks(items.len().div_ceil(threads))
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<R>>()))
+            .collect();
+        handles.into_iter()
+            .flat_map(|h| h.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+            .collect()
+    })
+}
+
+/// Deja un solo documento por `_id` según `policy` y anota los conflictos en `report`.
+/// `lines` es la línea de cada documento de `data`.
+pub(crate) fn resolve_duplicates(data: &mut Vec<Value>, lines: &[usize], policy: DuplicatePolicy, report: &mut LoadReport) -> io::Result<()> {
+    let mut seen: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (pos, doc) in data.iter().enumerate() {
+        if let Some(id) = doc.get("_id").and_then(|v| v.as_str()) {
+            seen.entry(id).or_default().push(pos);
+        }
+    }
+    let mut conflicts: Vec<(String, Vec<usize>)> = seen.into_iter()
+        .filter(|(_, positions)| positions.len() > 1)
+        .map(|(id, positions)| (id.to_string(), positions))
+        .collect();
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    conflicts.sort_by_key(|(_, positions)| positions[0]);
+
+    if policy == DuplicatePolicy::Fail {
+        let ids: Vec<String> = conflicts.iter()
+            .map(|(id, positions)| {
+                let lines: Vec<String> = positions.iter().map(|&p| lines[p].to_string()).collect();
+                format!("'{}' (lines {})", id, lines.join(", "))
+            })
+            .collect();
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Duplicate _id: {}", ids.join("; "))));
+    }
+
+    let mut drop = vec![false; data.len()];
+    for (id, positions) in conflicts {
+        let kept = match policy {
+            DuplicatePolicy::KeepFirst => positions[0],
+            _ => positions[positions.len() - 1],
+        };
+        // El contenido elegido pasa a la posición de la primera línea
+        data.swap(positions[0], kept);
+        for &pos in &positions[1..] {
+            drop[pos] = true;
+        }
+        report.duplicates.push(DuplicateId {
+            id,
+            lines: positions.iter().map(|&p| lines[p]).collect(),
+            kept: lines[kept],
+        });
+    }
+    let mut pos = 0;
+    data.retain(|_| {
+        pos += 1;
+        !drop[pos - 1]
+    });
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! Esperas por los locks de una colección, para diagnosticar bloqueos en producción. Solo se
+//! mide cuando el lock está ocupado; tomarlo libre no cuesta nada extra. Las esperas que
+//! superan `CollectionOptions::lock_wait_threshold` se guardan en `LockStats::recent` y se
+//! avisan al callback de `Collection::set_lock_wait_callback`.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use serde::Serialize;
+use crate::collection::{now_millis, Collection};
+
+/// Esperas lentas que se guardan en el historial de cada colección
+const RECENT_WAITS: usize = 32;
+
+/// Una espera que superó el umbral
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LockWait {
+    pub collection: String,
+    /// `read` o `write` (documentos en memoria) o `io` (escritura del archivo)
+    pub operation: &'static str,
+    pub waited_ms: f64,
+    /// Epoch en ms al conseguir el lock (o rendirse, ver `contention`)
+    pub at: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LockStats {
+    /// Veces que un lock estaba ocupado y hubo que esperar
+    pub contended: u64,
+    pub total_wait_ms: f64,
+    pub max_wait_ms: f64,
+    /// Esperas por encima del umbral
+    pub slow: u64,
+    /// Últimas esperas por encima del umbral, de la más antigua a la más reciente
+    pub recent: Vec<LockWait>,
+}
+
+pub type LockWaitCallback = Box<dyn Fn(&LockWait) + Send + Sync>;
+
+/// Contadores de `LockStats`
+#[derive(Default)]
+pub(crate) struct LockCounters {
+    contended: AtomicU64,
+    total_wait_us: AtomicU64,
+    max_wait_us: AtomicU64,
+    slow: AtomicU64,
+    recent: Mutex<VecDeque<LockWait>>,
+}
+
+impl LockCounters {
+    /// Cuenta una espera; devuelve el evento si supera `threshold`
+    pub(crate) fn record(&self, collection: &str, operation: &'static str, waited: Duration, threshold: Option<Duration>, at: u64) -> Option<LockWait> {
+        let micros = waited.as_micros() as u64;
+        self.contended.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_us.fetch_add(micros, Ordering::Relaxed);
+        self.max_wait_us.fetch_max(micros, Ordering::Relaxed);
+        if threshold.is_none_or(|threshold| waited < threshold) {
+            return None;
+        }
+        self.slow.fetch_add(1, Ordering::Relaxed);
+        let event = LockWait { collection: collection.to_string(), operation, waited_ms: micros as f64 / 1000.0, at };
+        let mut recent = self.recent.lock();
+        if recent.len() == RECENT_WAITS {
+            recent.pop_front();
+        }
+        recent.push_back(event.clone());
+        Some(event)
+    }
+
+    pub(crate) fn snapshot(&self) -> LockStats {
+        LockStats {
+            contended: self.contended.load(Ordering::Relaxed),
+            total_wait_ms: self.total_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            max_wait_ms: self.max_wait_us.load(Ordering::Relaxed) as f64 / 1000.0,
+            slow: self.slow.load(Ordering::Relaxed),
+            recent: self.recent.lock().iter().cloned().collect(),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        for counter in [&self.contended, &self.total_wait_us, &self.max_wait_us, &self.slow] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.recent.lock().clear();
+    }
+}
+
+impl Collection {
+    pub(crate) fn lock_waited(&self, operation: &'static str, waited: Duration) {
+        let threshold = self.options.read().lock_wait_threshold;
+        let Some(event) = self.lock_counters.record(self.name(), operation, waited, threshold, now_millis()) else { return; };
+        let callback = self.lock_wait_callback.read().clone();
+        if let Some(callback) = callback {
+            callback(&event);
+        }
+    }
+
+    /// Esperas por los locks desde que se abrió la colección o desde `reset_lock_stats`
+    pub fn lock_stats(&self) -> LockStats {
+        self.lock_counters.snapshot()
+    }
+
+    pub fn reset_lock_stats(&self) {
+        self.lock_counters.reset();
+    }
+
+    /// Cambia el umbral de `CollectionOptions::lock_wait_threshold`; `None` no avisa de ninguna
+    pub fn set_lock_wait_threshold(&self, threshold: Option<Duration>) {
+        self.options.write().lock_wait_threshold = threshold;
+    }
+
+    /// Callback para cada espera por encima de `CollectionOptions::lock_wait_threshold`. Se
+    /// llama con el lock ya tomado, así que no puede usar la colección.
+    pub fn set_lock_wait_callback(&self, callback: Option<LockWaitCallback>) {
+        *self.lock_wait_callback.write() = callback.map(Arc::new);
+    }
+}
@@ -0,0 +1,105 @@
+//! Acceso desde Lua (feature `lua`, con mlua y Lua 5.4). Las colecciones son tablas de
+//! funciones y los documentos se convierten entre tablas de Lua y JSON; un `null` de JSON
+//! llega a Lua como `nil`.
+//!
+//! ```lua
+//! local items = ruggy.collection("items")
+//! local id = items.insert({ name = "sword", dmg = 5 })
+//! items.update(id, "dmg", 9)
+//! for _, doc in ipairs(items.find("name", "sword")) do print(doc.dmg) end
+//! ```
+//!
+//! La biblioteca se carga desde un intérprete que ya existe y usa su Lua, sin llevar otro
+//! dentro: copiada como `ruggy.so` (`ruggy.dll` en Windows) en el `package.cpath`,
+//! `require("ruggy")` llama a `luaopen_ruggy` y devuelve una tabla con `open(path)`:
+//!
+//! ```lua
+//! local ruggy = require("ruggy").open("data/")
+//! ```
+
+use std::sync::Arc;
+use mlua::{ExternalResult, Lua, LuaSerdeExt, Table, Value as LuaValue};
+use mlua::serde::SerializeOptions;
+use serde::Serialize;
+use serde_json::Value;
+use crate::collection::Collection;
+use crate::db::Database;
+
+fn to_lua<T: Serialize>(lua: &Lua, value: &T) -> mlua::Result<LuaValue> {
+    let options = SerializeOptions::new()
+        .serialize_none_to_null(false)
+        .serialize_unit_to_null(false);
+    lua.to_value_with(value, options)
+}
+
+fn from_lua(lua: &Lua, value: LuaValue) -> mlua::Result<Value> {
+    lua.from_value(value)
+}
+
+/// Tabla `ruggy` con `collection(name)` sobre `db`
+pub fn module(lua: &Lua, db: Arc<Database>) -> mlua::Result<Table> {
+    let module = lua.create_table()?;
+    module.set("collection", lua.create_function(move |lua, name: String| {
+        collection_table(lua, db.collection(&name).into_lua_err()?)
+    })?)?;
+    Ok(module)
+}
+
+/// Punto de entrada de `require("ruggy")`. `open(path)` usa `Database::open_shared`, así
+/// que dos `open` de la misma ruta comparten la base de datos.
+#[mlua::lua_module(skip_memory_check)]
+fn ruggy(lua: &Lua) -> mlua::Result<Table> {
+    let exports = lua.create_table()?;
+    exports.set("open", lua.create_function(|lua, path: String| {
+        module(lua, Database::open_shared(&path).into_lua_err()?)
+    })?)?;
+    Ok(exports)
+}
+
+/// Registra el módulo como la global `name`
+pub fn install(lua: &Lua, name: &str, db: Arc<Database>) -> mlua::Result<()> {
+    lua.globals().set(name, module(lua, db)?)
+}
+
+/// Tabla con las operaciones de una colección
+pub fn collection_table(lua: &Lua, col: Arc<Collection>) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    table.set("name", col.name())?;
+
+    let c = col.clone();
+    table.set("insert", lua.create_function(move |lua, doc: LuaValue| {
+        c.insert(from_lua(lua, doc)?).into_lua_err()
+    })?)?;
+
+    let c = col.clone();
+    table.set("find_all", lua.create_function(move |lua, ()| to_lua(lua, &c.find_all()))?)?;
+
+    let c = col.clone();
+    table.set("find", lua.create_function(move |lua, (field, value): (String, LuaValue)| {
+        to_lua(lua, &c.find(&field, &query_value(lua, value)?))
+    })?)?;
+
+    let c = col.clone();
+    table.set("find_op", lua.create_function(move |lua, (field, operator, value): (String, String, LuaValue)| {
+        to_lua(lua, &c.find_with_operator(&field, &query_value(lua, value)?, &operator))
+    })?)?;
+
+    let c = col.clone();
+    table.set("update", lua.create_function(move |lua, (id, field, value): (String, String, LuaValue)| {
+        c.update_field(&id, &field, from_lua(lua, value)?).into_lua_err()
+    })?)?;
+
+    let c = col.clone();
+    table.set("delete", lua.create_function(move |_, id: String| c.delete_by_id(&id).into_lua_err())?)?;
+
+    table.set("persist", lua.create_function(move |_, ()| col.persist_now().into_lua_err())?)?;
+    Ok(table)
+}
+
+/// Las consultas comparan con texto: los números y booleanos de Lua se pasan como su JSON
+fn query_value(lua: &Lua, value: LuaValue) -> mlua::Result<String> {
+    Ok(match from_lua(lua, value)? {
+        Value::String(s) => s,
+        other => other.to_string(),
+    })
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use parking_lot::{Condvar, Mutex, RwLock};
+use serde::Serialize;
+use crate::collection::Collection;
+
+/// Tareas y frecuencia del mantenimiento en segundo plano
+#[derive(Clone, Debug)]
+pub struct MaintenanceConfig {
+    pub interval: Duration,
+    /// Si se indica, solo se tocan colecciones sin escrituras durante este tiempo
+    pub idle_after: Option<Duration>,
+    pub compact: bool,
+    pub expire: bool,
+    /// Archivar documentos fríos en las colecciones con `CollectionOptions::archive`
+    pub archive: bool,
+    /// Directorio donde copiar los archivos `.col` en cada pasada
+    pub backup_dir: Option<PathBuf>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            idle_after: None,
+            compact: true,
+            expire: true,
+            archive: true,
+            backup_dir: None,
+        }
+    }
+}
+
+impl MaintenanceConfig {
+    pub fn every(interval: Duration) -> Self {
+        Self { interval, ..Self::default() }
+    }
+
+    pub fn idle_after(mut self, idle: Duration) -> Self {
+        self.idle_after = Some(idle);
+        self
+    }
+
+    pub fn compact(mut self, enabled: bool) -> Self {
+        self.compact = enabled;
+        self
+    }
+
+    pub fn expire(mut self, enabled: bool) -> Self {
+        self.expire = enabled;
+        self
+    }
+
+    pub fn archive(mut self, enabled: bool) -> Self {
+        self.archive = enabled;
+        self
+    }
+
+    pub fn backup_to<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.backup_dir = Some(dir.into());
+        self
+    }
+}
+
+/// Contadores acumulados del mantenimiento
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MaintenanceReport {
+    pub runs: u64,
+    pub compacted: u64,
+    pub expired: u64,
+    pub archived: u64,
+    pub backups: u64,
+    pub errors: u64,
+}
+
+impl MaintenanceReport {
+    fn merge(&mut self, other: &MaintenanceReport) {
+        self.runs += other.runs;
+        self.compacted += other.compacted;
+        self.expired += other.expired;
+        self.archived += other.archived;
+        self.backups += other.backups;
+        self.errors += other.errors;
+    }
+}
+
+type Collections = Arc<RwLock<HashMap<String, Arc<Collection>>>>;
+
+/// Hilo de mantenimiento. Se detiene al llamar `stop()` o al soltar el handle.
+pub struct MaintenanceHandle {
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    report: Arc<Mutex<MaintenanceReport>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MaintenanceHandle {
+    pub(crate) fn spawn(collections: Collections, config: MaintenanceConfig) -> Self {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let report = Arc::new(Mutex::new(MaintenanceReport::default()));
+
+        let thread_stop = stop.clone();
+        let thread_report = report.clone();
+        let thread = thread::Builder::new()
+            .name("ruggy-maintenance".to_string())
+            .spawn(move || loop {
+                {
+                    let (lock, cvar) = &*thread_stop;
+                    let mut stopped = lock.lock();
+                    if !*stopped {
+                        cvar.wait_for(&mut stopped, config.interval);
+                    }
+                    if *stopped {
+                        break;
+                    }
+                }
+                let pass = run_once(&collections, &config);
+                thread_report.lock().merge(&pass);
+            })
+            .expect("failed to spawn maintenance thread");
+
+        Self { stop, report, thread: Some(thread) }
+    }
+
+    pub fn report(&self) -> MaintenanceReport {
+        *self.report.lock()
+    }
+
+    pub fn stop(mut self) -> MaintenanceReport {
+        self.shutdown();
+        self.report()
+    }
+
+    fn shutdown(&mut self) {
+        let (lock, cvar) = &*self.stop;
+        *lock.lock() = true;
+        cvar.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Una pasada de mantenimiento sobre las colecciones abiertas
+pub(crate) fn run_once(collections: &RwLock<HashMap<String, Arc<Collection>>>, config: &MaintenanceConfig) -> MaintenanceReport {
+    let mut report = MaintenanceReport { runs: 1, ..Default::default() };
+    let open: Vec<(String, Arc<Collection>)> = collections.read()
+        .iter()
+        .map(|(name, col)| (name.clone(), col.clone()))
+        .collect();
+
+    if let Some(dir) = &config.backup_dir {
+        if let Err(e) = fs::create_dir_all(dir) {
+            eprintln!("Ruggy Error: Backup directory failed: {}", e);
+            report.errors += 1;
+        }
+    }
+
+    for (name, col) in open {
+        if col.is_read_only() {
+            continue;
+        }
+        if let Some(idle) = config.idle_after {
+            if col.idle_for() < idle {
+                continue;
+            }
+        }
+        if config.expire {
+            match col.expire_now() {
+                Ok(n) => report.expired += n as u64,
+                Err(e) => {
+                    eprintln!("Ruggy Error: Expiry of '{}' failed: {}", name, e);
+                    report.errors += 1;
+                }
+            }
+        }
+        if config.archive {
+            match col.archive_now() {
+                Ok(n) => report.archived += n as u64,
+                Err(e) => {
+                    eprintln!("Ruggy Error: Archival of '{}' failed: {}", name, e);
+                    report.errors += 1;
+                }
+            }
+        }
+        if let Err(e) = col.flush_if_due() {
+            eprintln!("Ruggy Error: Saving '{}' failed: {}", name, e);
+            report.errors += 1;
+        }
+        if config.compact {
+            match col.compact() {
+                Ok(true) => report.compacted += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    eprintln!("Ruggy Error: Compaction of '{}' failed: {}", name, e);
+                    report.errors += 1;
+                }
+            }
+        }
+        if let Some(dir) = &config.backup_dir {
+            match col.backup_to(&dir.join(format!("{}.col", name))) {
+                Ok(()) => report.backups += 1,
+                Err(e) => {
+                    eprintln!("Ruggy Error: Backup of '{}' failed: {}", name, e);
+                    report.errors += 1;
+                }
+            }
+        }
+    }
+    report
+}
@@ -0,0 +1,50 @@
+use std::mem::size_of;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Estimación de la memoria ocupada por una colección, en bytes
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct MemoryUsage {
+    pub documents: usize,
+    pub indexes: usize,
+    /// Caché de resultados
+    pub cache: usize,
+    /// Estadísticas del último `analyze()`
+    pub stats: usize,
+    pub total: usize,
+}
+
+impl MemoryUsage {
+    pub(crate) fn new(documents: usize, indexes: usize, cache: usize, stats: usize) -> Self {
+        Self { documents, indexes, cache, stats, total: documents + indexes + cache + stats }
+    }
+}
+
+/// Sobrecoste aproximado por entrada de un mapa (nodos y punteros)
+pub(crate) const MAP_ENTRY_OVERHEAD: usize = 16;
+
+/// Bytes aproximados de un valor JSON, incluyendo la memoria dinámica de sus hijos
+pub fn value_size(value: &Value) -> usize {
+    size_of::<Value>() + heap_size(value)
+}
+
+fn heap_size(value: &Value) -> usize {
+    match value {
+        Value::String(s) => s.capacity(),
+        Value::Array(items) => {
+            items.capacity() * size_of::<Value>() + items.iter().map(heap_size).sum::<usize>()
+        }
+        Value::Object(map) => map.iter()
+            .map(|(key, v)| string_size(key) + value_size(v) + MAP_ENTRY_OVERHEAD)
+            .sum(),
+        _ => 0,
+    }
+}
+
+pub(crate) fn string_size(s: &str) -> usize {
+    size_of::<String>() + s.len()
+}
+
+pub(crate) fn values_size(values: &[Value]) -> usize {
+    size_of::<Vec<Value>>() + values.iter().map(value_size).sum::<usize>()
+}
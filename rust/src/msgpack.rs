@@ -0,0 +1,147 @@
+//! Codificación MessagePack de los documentos para las variantes `_mp` de la FFI. Los valores
+//! `bytes` (ver `types`) viajan como `bin` en los dos sentidos, sin pasar a texto. Con
+//! `arbitrary-precision` los números van como números nativos, y como texto los que no caben
+//! en un entero de 64 bits o un f64 sin perder precisión.
+
+use std::fmt;
+use std::io;
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Map, Value};
+use crate::limits;
+use crate::types;
+
+pub(crate) fn encode(docs: &[Value]) -> io::Result<Vec<u8>> {
+    let docs: Vec<Encoded> = docs.iter().map(Encoded).collect();
+    rmp_serde::to_vec(&docs).map_err(io::Error::other)
+}
+
+/// Decodifica con la profundidad de `limits::ParseLimits`: un buffer más anidado se rechaza
+/// al llegar al primer nivel de más, sin decodificar el resto
+pub(crate) fn decode(bytes: &[u8]) -> io::Result<Value> {
+    let max_depth = limits::parse_limits().max_depth;
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+    // rmp_serde falla en el nivel con el que el contador llega a 0
+    deserializer.set_max_depth(max_depth + 1);
+    match Decoded::deserialize(&mut deserializer) {
+        Ok(Decoded(value)) => Ok(value),
+        Err(rmp_serde::decode::Error::DepthLimitExceeded) => Err(limits::depth_exceeded(max_depth)),
+        Err(e) => Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    }
+}
+
+struct Encoded<'a>(&'a Value);
+
+impl Serialize for Encoded<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            #[cfg(feature = "arbitrary-precision")]
+            Value::Number(n) => {
+                if let Some(i) = n.as_i64() {
+                    serializer.serialize_i64(i)
+                } else if let Some(u) = n.as_u64() {
+                    serializer.serialize_u64(u)
+                } else {
+                    // Solo si el f64 se escribe igual que el número original
+                    match n.as_f64().filter(|f| serde_json::Number::from_f64(*f).is_some_and(|exact| exact.as_str() == n.as_str())) {
+                        Some(f) => serializer.serialize_f64(f),
+                        None => serializer.serialize_str(n.as_str()),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&Encoded(item))?;
+                }
+                seq.end()
+            }
+            Value::Object(obj) => {
+                if let Some(bytes) = types::as_bytes(self.0) {
+                    return serializer.serialize_bytes(&bytes);
+                }
+                let mut map = serializer.serialize_map(Some(obj.len()))?;
+                for (key, value) in obj {
+                    map.serialize_entry(key, &Encoded(value))?;
+                }
+                map.end()
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+/// Como el `Deserialize` de `Value`, que no acepta `bin`
+struct Decoded(Value);
+
+impl<'de> Deserialize<'de> for Decoded {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DecodedVisitor).map(Decoded)
+    }
+}
+
+struct DecodedVisitor;
+
+impl<'de> Visitor<'de> for DecodedVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a MessagePack value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(serde_json::Number::from_f64(v).map_or(Value::Null, Value::Number))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(types::bytes(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(Decoded(item)) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut obj = Map::new();
+        while let Some((key, Decoded(value))) = map.next_entry::<String, Decoded>()? {
+            obj.insert(key, value);
+        }
+        Ok(Value::Object(obj))
+    }
+}
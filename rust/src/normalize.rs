@@ -0,0 +1,66 @@
+//! Normalización Unicode de strings al indexar y comparar, para que "café" escrito con `é`
+//! precompuesta o con `e` + acento combinado sean el mismo valor.
+
+use std::borrow::Cow;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalForm {
+    /// Composición canónica: solo unifica formas equivalentes
+    #[default]
+    Nfc,
+    /// Composición de compatibilidad: además unifica ligaduras, anchos, superíndices...
+    Nfkc,
+}
+
+/// Normalización de los strings de una colección (`CollectionOptions::normalization`).
+/// Se aplica a las claves de los índices y a ambos lados de `=`, `contains`, `starts_with`
+/// y `ends_with`; los documentos se guardan tal cual.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Normalization {
+    pub form: NormalForm,
+    /// Quitar acentos y otras marcas combinables ("Álvarez" == "Alvarez")
+    pub strip_accents: bool,
+}
+
+impl Normalization {
+    pub fn nfc() -> Self {
+        Self { form: NormalForm::Nfc, strip_accents: false }
+    }
+
+    pub fn nfkc() -> Self {
+        Self { form: NormalForm::Nfkc, strip_accents: false }
+    }
+
+    pub fn strip_accents(mut self, enabled: bool) -> Self {
+        self.strip_accents = enabled;
+        self
+    }
+
+    pub fn apply<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        if s.is_ascii() {
+            return Cow::Borrowed(s);
+        }
+        if self.strip_accents {
+            let stripped: String = match self.form {
+                NormalForm::Nfc => s.nfd().filter(|c| !is_combining_mark(*c)).collect(),
+                NormalForm::Nfkc => s.nfkd().filter(|c| !is_combining_mark(*c)).collect(),
+            };
+            return Cow::Owned(stripped.nfc().collect());
+        }
+        match self.form {
+            NormalForm::Nfc if is_nfc_quick(s.chars()) == IsNormalized::Yes => Cow::Borrowed(s),
+            NormalForm::Nfc => Cow::Owned(s.nfc().collect()),
+            NormalForm::Nfkc => Cow::Owned(s.nfkc().collect()),
+        }
+    }
+}
+
+/// `s` normalizado si la colección tiene normalización
+pub(crate) fn normalized<'a>(normalization: Option<&Normalization>, s: &'a str) -> Cow<'a, str> {
+    match normalization {
+        Some(n) => n.apply(s),
+        None => Cow::Borrowed(s),
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::Duration;
+use crate::archive::ArchivePolicy;
+use crate::contention::ContentionPolicy;
+use crate::encryption::FieldEncryption;
+use crate::index::{IndexKind, IndexSpec};
+use crate::load::{DuplicatePolicy, LoadMode};
+use crate::normalize::Normalization;
+use crate::rules::Rule;
+use crate::text::Analyzer;
+
+/// Expiración de documentos por un campo con la hora en epoch (milisegundos)
+#[derive(Clone, Debug)]
+pub struct Ttl {
+    pub field: String,
+    pub max_age: Duration,
+}
+
+/// Cuándo se guardan en el archivo las escrituras
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PersistStrategy {
+    /// Cada escritura se guarda antes de volver (los inserts se añaden al final del archivo)
+    #[default]
+    OnEveryWrite,
+    /// Solo con `Collection::persist_now()`; lo no guardado se pierde al cerrar
+    Manual,
+    /// Como mucho un guardado por intervalo: las escrituras dentro del intervalo quedan
+    /// pendientes hasta la siguiente escritura o pasada de mantenimiento posterior al plazo,
+    /// o hasta cerrar la colección
+    Debounced(Duration),
+}
+
+/// Orden en que se guardan y devuelven los documentos. Todas las lecturas (`find_all`,
+/// `find`, `snapshot`...) y el archivo siguen este orden, y las reescrituras (update, delete,
+/// compactación, expiración) lo conservan.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DocumentOrder {
+    /// Orden de inserción. Un update no mueve el documento.
+    #[default]
+    Insertion,
+    /// Por `_id` (comparación de strings)
+    ById,
+}
+
+/// Opciones por colección. Se aplican la primera vez que se abre la colección.
+#[derive(Clone, Default)]
+pub struct CollectionOptions {
+    /// Campos cifrados en disco (los datos en memoria quedan en claro)
+    pub encryption: Option<FieldEncryption>,
+    /// Capacidad de la caché de resultados (`None` = sin caché)
+    pub result_cache: Option<usize>,
+    /// Los documentos expiran con `Collection::expire_now()` o el mantenimiento en segundo plano
+    pub ttl: Option<Ttl>,
+    /// Política para mover documentos fríos al archivo comprimido (`Collection::archive_now()`)
+    pub archive: Option<ArchivePolicy>,
+    /// Índices que se construyen al abrir la colección
+    pub indexes: Vec<IndexSpec>,
+    /// Normalización Unicode al indexar y comparar strings
+    pub normalization: Option<Normalization>,
+    /// Qué hacer con las líneas ilegibles al cargar el archivo
+    pub load_mode: LoadMode,
+    /// Qué documento se conserva si varias líneas tienen el mismo `_id`
+    pub duplicate_ids: DuplicatePolicy,
+    pub persist_strategy: PersistStrategy,
+    /// Guardar los documentos con el mismo contenido como referencia al primero (ver `dedupe`)
+    pub dedupe: bool,
+    /// Comprimir en disco los documentos cuya línea ocupa más de estos bytes (ver `compression`)
+    pub compress_above: Option<usize>,
+    pub order: DocumentOrder,
+    /// Reglas que tienen que cumplir los documentos que se escriben (ver `rules`)
+    pub rules: Vec<Rule>,
+    /// Cuánto esperan las escrituras por los locks de la colección (ver `contention`)
+    pub contention: ContentionPolicy,
+    /// Esperas por un lock a partir de las que se avisa (ver `lock_stats`)
+    pub lock_wait_threshold: Option<Duration>,
+}
+
+impl CollectionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn encryption(mut self, encryption: FieldEncryption) -> Self {
+        self.encryption = Some(encryption);
+        self
+    }
+
+    pub fn result_cache(mut self, capacity: usize) -> Self {
+        self.result_cache = Some(capacity);
+        self
+    }
+
+    pub fn ttl(mut self, field: &str, max_age: Duration) -> Self {
+        self.ttl = Some(Ttl { field: field.to_string(), max_age });
+        self
+    }
+
+    pub fn archive(mut self, policy: ArchivePolicy) -> Self {
+        self.archive = Some(policy);
+        self
+    }
+
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    pub fn load_mode(mut self, mode: LoadMode) -> Self {
+        self.load_mode = mode;
+        self
+    }
+
+    pub fn duplicate_ids(mut self, policy: DuplicatePolicy) -> Self {
+        self.duplicate_ids = policy;
+        self
+    }
+
+    pub fn persist_strategy(mut self, strategy: PersistStrategy) -> Self {
+        self.persist_strategy = strategy;
+        self
+    }
+
+    pub fn dedupe(mut self, enabled: bool) -> Self {
+        self.dedupe = enabled;
+        self
+    }
+
+    pub fn compress_above(mut self, bytes: usize) -> Self {
+        self.compress_above = Some(bytes);
+        self
+    }
+
+    pub fn contention(mut self, policy: ContentionPolicy) -> Self {
+        self.contention = policy;
+        self
+    }
+
+    pub fn lock_wait_threshold(mut self, threshold: Duration) -> Self {
+        self.lock_wait_threshold = Some(threshold);
+        self
+    }
+
+    pub fn order(mut self, order: DocumentOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn index(mut self, name: &str, field: &str) -> Self {
+        self.indexes.push(IndexSpec::new(name, field, IndexKind::Equality));
+        self
+    }
+
+    /// Índice de igualdad que incluye `include` para cubrir consultas (ver `covered`)
+    pub fn covering_index(mut self, name: &str, field: &str, include: &[&str]) -> Self {
+        self.indexes.push(IndexSpec::new(name, field, IndexKind::Equality).include(include));
+        self
+    }
+
+    pub fn text_index(mut self, name: &str, field: &str, analyzer: Analyzer) -> Self {
+        self.indexes.push(IndexSpec::new(name, field, IndexKind::Text(analyzer)));
+        self
+    }
+
+    pub fn prefix_index(mut self, name: &str, field: &str) -> Self {
+        self.indexes.push(IndexSpec::new(name, field, IndexKind::Prefix));
+        self
+    }
+}
@@ -0,0 +1,278 @@
+//! Colecciones de solo lectura empaquetadas en un único archivo `ruggy-pack`, para distribuir
+//! datos estáticos (tablas de un juego, datos de referencia) dentro de la aplicación. El
+//! archivo es una línea de manifiesto en JSON seguida del contenido `.col` de cada colección,
+//! sin comprimir, así que se puede mapear en memoria y leer sin copiarlo entero.
+//!
+//! ```ignore
+//! PackBuilder::new().collection(&items)?.documents("niveles", &niveles)?.write("data.pack")?;
+//! let db = Database::open_pack("data.pack")?;
+//! ```
+//!
+//! Para llevarla dentro del binario, `embed!` la incluye al compilar y la abre sin tocar el
+//! sistema de archivos. Un directorio de colecciones se empaqueta antes, en `build.rs`:
+//!
+//! ```ignore
+//! // build.rs
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR")?).join("data.pack");
+//! ruggy_db::PackBuilder::from_dir("data/")?.write(&out)?;
+//! println!("cargo:rerun-if-changed=data/");
+//!
+//! // main.rs
+//! let db = ruggy_db::embed!(concat!(env!("OUT_DIR"), "/data.pack"))?;
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::collection::{now_millis, Collection};
+use crate::db::Database;
+use crate::storage::StorageBackend;
+
+const FORMAT: &str = "ruggy-pack";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackManifest {
+    pub format: String,
+    pub version: u32,
+    /// Epoch en ms
+    pub created_at: u64,
+    pub collections: Vec<PackCollection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackCollection {
+    pub name: String,
+    pub documents: usize,
+    /// Posición del contenido, contada desde el final del manifiesto
+    pub offset: u64,
+    pub bytes: u64,
+}
+
+/// Construye un archivo `ruggy-pack`. Las colecciones se guardan en el orden en que se añaden.
+#[derive(Default)]
+pub struct PackBuilder {
+    collections: Vec<(String, usize, Vec<u8>)>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Añade los documentos actuales de una colección abierta, tal como se guardan en su
+    /// archivo (con cifrado, la pack hay que abrirla con las mismas opciones)
+    pub fn collection(self, col: &Collection) -> io::Result<Self> {
+        let docs = col.snapshot();
+        let mut bytes = Vec::new();
+        for doc in docs.iter() {
+            writeln!(bytes, "{}", col.encode_line(doc)?)?;
+        }
+        self.add(col.name(), docs.len(), bytes)
+    }
+
+    /// Añade una colección `name` con `docs` tal cual (sin añadir `_id`)
+    pub fn documents(self, name: &str, docs: &[Value]) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        for doc in docs {
+            writeln!(bytes, "{}", serde_json::to_string(doc)?)?;
+        }
+        self.add(name, docs.len(), bytes)
+    }
+
+    /// Añade todos los archivos `.col` de un directorio (p. ej. el de una base de datos), con
+    /// el contenido tal cual, en orden alfabético
+    pub fn from_dir<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("col") {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                files.push((name.to_string(), path));
+            }
+        }
+        files.sort();
+        files.into_iter().try_fold(Self::new(), |pack, (name, path)| {
+            let bytes = fs::read(&path)?;
+            let documents = bytes.split(|&b| b == b'\n').filter(|line| !line.trim_ascii().is_empty()).count();
+            pack.add(&name, documents, bytes)
+        })
+    }
+
+    fn add(mut self, name: &str, documents: usize, bytes: Vec<u8>) -> io::Result<Self> {
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid collection name '{}'", name)));
+        }
+        if self.collections.iter().any(|(existing, _, _)| existing == name) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("Collection '{}' is already in the pack", name)));
+        }
+        self.collections.push((name.to_string(), documents, bytes));
+        Ok(self)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> io::Result<PackManifest> {
+        let mut offset = 0;
+        let collections = self.collections.iter()
+            .map(|(name, documents, bytes)| {
+                let col = PackCollection { name: name.clone(), documents: *documents, offset, bytes: bytes.len() as u64 };
+                offset += bytes.len() as u64;
+                col
+            })
+            .collect();
+        let manifest = PackManifest {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created_at: now_millis(),
+            collections,
+        };
+        let mut file = io::BufWriter::new(File::create(path)?);
+        writeln!(file, "{}", serde_json::to_string(&manifest)?)?;
+        for (_, _, bytes) in &self.collections {
+            file.write_all(bytes)?;
+        }
+        file.into_inner()?.sync_all()?;
+        Ok(manifest)
+    }
+}
+
+/// Contenido de una pack: un archivo mapeado o bytes incluidos en el binario
+#[derive(Clone)]
+enum PackBytes {
+    Mapped(Arc<Mmap>),
+    Static(&'static [u8]),
+}
+
+impl AsRef<[u8]> for PackBytes {
+    fn as_ref(&self) -> &[u8] {
+        match self {
+            PackBytes::Mapped(map) => map,
+            PackBytes::Static(bytes) => bytes,
+        }
+    }
+}
+
+/// Archivo `ruggy-pack` como almacenamiento de solo lectura. Un archivo abierto con `open` se
+/// mapea en memoria y no se debe modificar mientras está abierto.
+pub struct PackStorage {
+    bytes: PackBytes,
+    manifest: PackManifest,
+    /// Inicio del contenido (final del manifiesto)
+    data_start: usize,
+}
+
+impl PackStorage {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Seguro mientras nadie modifique el archivo, ver la documentación del tipo
+        let map = unsafe { Mmap::map(&file)? };
+        Self::parse(PackBytes::Mapped(Arc::new(map)))
+    }
+
+    /// Pack incluida en el binario, p. ej. con `include_bytes!` (ver `embed!`)
+    pub fn from_static(bytes: &'static [u8]) -> io::Result<Self> {
+        Self::parse(PackBytes::Static(bytes))
+    }
+
+    fn parse(bytes: PackBytes) -> io::Result<Self> {
+        let map = bytes.as_ref();
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let header_len = map.iter().position(|&b| b == b'\n')
+            .ok_or_else(|| invalid("Invalid pack: missing manifest".to_string()))?;
+        let manifest: PackManifest = serde_json::from_slice(&map[..header_len])
+            .map_err(|e| invalid(format!("Invalid pack manifest: {}", e)))?;
+        if manifest.format != FORMAT || manifest.version != VERSION {
+            return Err(invalid(format!("Unsupported pack '{}' version {}", manifest.format, manifest.version)));
+        }
+        let data_start = header_len + 1;
+        let data_len = (map.len() - data_start) as u64;
+        if let Some(col) = manifest.collections.iter().find(|col| col.offset.checked_add(col.bytes).is_none_or(|end| end > data_len)) {
+            return Err(invalid(format!("Invalid pack: collection '{}' is truncated", col.name)));
+        }
+        Ok(Self { bytes, manifest, data_start })
+    }
+
+    pub fn manifest(&self) -> &PackManifest {
+        &self.manifest
+    }
+
+    fn find(&self, name: &str) -> Option<&PackCollection> {
+        let name = name.strip_suffix(".col")?;
+        self.manifest.collections.iter().find(|col| col.name == name)
+    }
+}
+
+fn read_only() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "Pack files are read-only")
+}
+
+/// Trozo del mapa que se puede mover a otro hilo con el lector
+struct PackSlice {
+    bytes: PackBytes,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for PackSlice {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes.as_ref()[self.start..self.end]
+    }
+}
+
+impl StorageBackend for PackStorage {
+    fn open(&self, name: &str, offset: u64) -> io::Result<Option<Box<dyn Read + Send>>> {
+        Ok(self.find(name).map(|col| {
+            let start = self.data_start + col.offset as usize;
+            let mut cursor = Cursor::new(PackSlice { bytes: self.bytes.clone(), start, end: start + col.bytes as usize });
+            cursor.set_position(offset);
+            Box::new(cursor) as Box<dyn Read + Send>
+        }))
+    }
+
+    fn len(&self, name: &str) -> io::Result<Option<u64>> {
+        Ok(self.find(name).map(|col| col.bytes))
+    }
+
+    fn append(&self, _name: &str, _bytes: &[u8]) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    fn replace(&self, _name: &str, _bytes: &[u8]) -> io::Result<()> {
+        Err(read_only())
+    }
+
+    fn remove(&self, _name: &str) -> io::Result<bool> {
+        Err(read_only())
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        Ok(self.manifest.collections.iter().map(|col| format!("{}.col", col.name)).collect())
+    }
+}
+
+impl Database {
+    /// Abre un archivo `ruggy-pack`: las colecciones son de solo lectura y las escrituras
+    /// devuelven `PermissionDenied`
+    pub fn open_pack<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::follower_with_storage(Arc::new(PackStorage::open(path)?)))
+    }
+
+    /// Como `open_pack`, con una pack incluida en el binario (ver `embed!`)
+    pub fn open_embedded(bytes: &'static [u8]) -> io::Result<Self> {
+        Ok(Self::follower_with_storage(Arc::new(PackStorage::from_static(bytes)?)))
+    }
+}
+
+/// Incluye al compilar una pack (ruta como la de `include_bytes!`) y la abre como base de
+/// datos de solo lectura con `Database::open_embedded`
+#[macro_export]
+macro_rules! embed {
+    ($path:expr) => {
+        $crate::Database::open_embedded(include_bytes!($path))
+    };
+}
@@ -0,0 +1,156 @@
+//! Paginación por clave: cada página continúa justo después del último documento de la
+//! anterior según el orden pedido más `_id` como desempate, así que aunque haya valores
+//! repetidos no se salta ni se repite ningún documento, y los inserts o deletes entre
+//! páginas no desplazan las siguientes.
+//!
+//! ```json
+//! {"sortBy": ["-score", "name:natural"], "limit": 20, "after": "<next de la página anterior>"}
+//! ```
+
+use std::cmp::Ordering;
+use std::io;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::collection::{doc_id, Collection};
+use crate::comparator::SortKey;
+use crate::expr::get_path;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageQuery {
+    /// Siempre se desempata por `_id` ascendente
+    sort_by: Vec<SortKey>,
+    limit: usize,
+    after: Option<String>,
+}
+
+/// Una página de resultados. `next` es el token para pedir la siguiente (`None` si es la
+/// última).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Page {
+    pub documents: Vec<Value>,
+    pub next: Option<String>,
+}
+
+/// Paginación por posición: salta `skip` documentos y devuelve como mucho `limit`. Más simple
+/// que `PageQuery`, pero las escrituras entre páginas pueden desplazarlas.
+pub(crate) fn skip_limit(mut docs: Vec<Value>, skip: usize, limit: Option<usize>) -> Vec<Value> {
+    docs.drain(..skip.min(docs.len()));
+    if let Some(limit) = limit {
+        docs.truncate(limit);
+    }
+    docs
+}
+
+/// Posición de un documento en el orden: valores de los campos de orden y `_id`
+struct Cursor {
+    keys: Vec<Value>,
+    id: String,
+}
+
+impl Cursor {
+    fn of(doc: &Value, sort_by: &[SortKey]) -> Self {
+        Self {
+            keys: sort_by.iter().map(|key| get_path(doc, &key.field).cloned().unwrap_or(Value::Null)).collect(),
+            id: doc_id(doc).to_string(),
+        }
+    }
+
+    /// Token opaco para el host; contiene los valores de orden y el `_id`
+    fn token(&self) -> String {
+        json!([self.keys, self.id]).to_string()
+    }
+
+    fn parse(token: &str, sort_by: &[SortKey]) -> io::Result<Self> {
+        let bad = || invalid("Invalid page token".to_string());
+        let value: Value = serde_json::from_str(token).map_err(|_| bad())?;
+        let keys = value.get(0).and_then(|v| v.as_array()).ok_or_else(bad)?;
+        let id = value.get(1).and_then(|v| v.as_str()).ok_or_else(bad)?;
+        if keys.len() != sort_by.len() {
+            return Err(invalid("Page token does not match the sort order".to_string()));
+        }
+        Ok(Self { keys: keys.clone(), id: id.to_string() })
+    }
+}
+
+impl PageQuery {
+    pub fn new(limit: usize) -> Self {
+        Self { sort_by: Vec::new(), limit, after: None }
+    }
+
+    pub fn sort_by(self, field: &str, descending: bool) -> Self {
+        self.sort_by_key(SortKey::new(field, descending))
+    }
+
+    /// Como `sort_by`, con un comparador (ver `comparator`)
+    pub fn sort_by_key(mut self, key: SortKey) -> Self {
+        self.sort_by.push(key);
+        self
+    }
+
+    /// Continúa después del token `next` de la página anterior
+    pub fn after(mut self, token: &str) -> Self {
+        self.after = Some(token.to_string());
+        self
+    }
+
+    /// Lee la especificación JSON del ejemplo del módulo (`-` delante del campo = descendente,
+    /// `:nombre` detrás = comparador)
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Page query must be an object".to_string()))?;
+        let limit = obj.get("limit").and_then(|v| v.as_u64())
+            .ok_or_else(|| invalid("Page query needs a positive 'limit'".to_string()))? as usize;
+        let mut query = Self::new(limit);
+        if let Some(sort) = obj.get("sortBy") {
+            let fields = sort.as_array().ok_or_else(|| invalid("'sortBy' must be an array".to_string()))?;
+            for field in fields {
+                let field = field.as_str().ok_or_else(|| invalid("'sortBy' must contain field names".to_string()))?;
+                query = query.sort_by_key(SortKey::parse(field)?);
+            }
+        }
+        match obj.get("after") {
+            None | Some(Value::Null) => {}
+            Some(Value::String(token)) => query = query.after(token),
+            Some(_) => return Err(invalid("'after' must be a page token".to_string())),
+        }
+        Ok(query)
+    }
+
+    fn compare(&self, a: &Cursor, b: &Cursor) -> Ordering {
+        for (key, (x, y)) in self.sort_by.iter().zip(a.keys.iter().zip(&b.keys)) {
+            let ordering = key.compare_values(Some(x), Some(y));
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a.id.cmp(&b.id)
+    }
+
+    /// Página de `docs` según la consulta
+    pub fn apply<'a>(&self, docs: impl Iterator<Item = &'a Value>) -> io::Result<Page> {
+        if self.limit == 0 {
+            return Err(invalid("Page limit must be positive".to_string()));
+        }
+        let after = self.after.as_deref().map(|token| Cursor::parse(token, &self.sort_by)).transpose()?;
+        let mut rows: Vec<(Cursor, &Value)> = docs
+            .map(|doc| (Cursor::of(doc, &self.sort_by), doc))
+            .filter(|(cursor, _)| after.as_ref().is_none_or(|after| self.compare(cursor, after) == Ordering::Greater))
+            .collect();
+        rows.sort_by(|a, b| self.compare(&a.0, &b.0));
+
+        let more = rows.len() > self.limit;
+        rows.truncate(self.limit);
+        let next = if more { rows.last().map(|(cursor, _)| cursor.token()) } else { None };
+        Ok(Page { documents: rows.into_iter().map(|(_, doc)| doc.clone()).collect(), next })
+    }
+}
+
+impl Collection {
+    /// Página de los documentos que cumplen `filter` (ver `PageQuery`)
+    pub fn find_page<F: Fn(&Value) -> bool>(&self, filter: F, query: &PageQuery) -> io::Result<Page> {
+        query.apply(self.snapshot().iter().filter(|doc| filter(doc)))
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde_json::Value;
+use crate::collection::Collection;
+use crate::datetime::{self, Civil};
+use crate::db::Database;
+use crate::options::CollectionOptions;
+
+/// Tamaño de cada partición
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartitionBy {
+    Day,
+    Month,
+}
+
+impl PartitionBy {
+    /// Clave de partición (`2026-01-31` o `2026-01`) de un instante en milisegundos
+    pub fn key_for(&self, ms: i64) -> String {
+        let c = Civil::from_millis(ms);
+        match self {
+            PartitionBy::Day => format!("{:04}-{:02}-{:02}", c.year, c.month, c.day),
+            PartitionBy::Month => format!("{:04}-{:02}", c.year, c.month),
+        }
+    }
+}
+
+/// Colección de tipo log con un archivo por día o mes según un campo de fecha
+/// (milisegundos desde epoch o string ISO-8601). Las consultas por rango solo abren
+/// las particiones que se solapan y las particiones viejas se borran enteras.
+pub struct PartitionedCollection<'a> {
+    db: &'a Database,
+    name: String,
+    field: String,
+    by: PartitionBy,
+    options: CollectionOptions,
+    partitions: RwLock<BTreeMap<String, Arc<Collection>>>,
+}
+
+impl<'a> PartitionedCollection<'a> {
+    pub(crate) fn open(db: &'a Database, name: &str, field: &str, by: PartitionBy, options: CollectionOptions) -> io::Result<Self> {
+        let prefix = Self::file_prefix(name);
+        let mut keys = Vec::new();
+        for file_name in db.storage.list()? {
+            if let Some(key) = file_name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(".col")) {
+                keys.push(key.to_string());
+            }
+        }
+        let names: Vec<String> = keys.iter().map(|k| format!("{}{}", prefix, k)).collect();
+        let cols = db.open_many(&names, &options)?;
+
+        Ok(Self {
+            db,
+            name: name.to_string(),
+            field: field.to_string(),
+            by,
+            options,
+            partitions: RwLock::new(keys.into_iter().zip(cols).collect()),
+        })
+    }
+
+    fn file_prefix(name: &str) -> String {
+        format!("{}.p-", name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Claves de las particiones existentes, en orden cronológico
+    pub fn partitions(&self) -> Vec<String> {
+        self.partitions.read().keys().cloned().collect()
+    }
+
+    fn partition(&self, key: &str) -> io::Result<Arc<Collection>> {
+        if let Some(col) = self.partitions.read().get(key) {
+            return Ok(col.clone());
+        }
+        let mut parts = self.partitions.write();
+        if let Some(col) = parts.get(key) {
+            return Ok(col.clone());
+        }
+        let col_name = format!("{}{}", Self::file_prefix(&self.name), key);
+        let col = self.db.collection_with_options(&col_name, self.options.clone())?;
+        parts.insert(key.to_string(), col.clone());
+        Ok(col)
+    }
+
+    /// El documento debe tener el campo de fecha
+    pub fn insert(&self, document: Value) -> io::Result<String> {
+        let ms = document.get(&self.field)
+            .and_then(datetime::value_to_millis)
+            .ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Document needs a date in '{}'", self.field),
+            ))?;
+        self.partition(&self.by.key_for(ms))?.insert(document)
+    }
+
+    fn all(&self) -> Vec<Arc<Collection>> {
+        self.partitions.read().values().cloned().collect()
+    }
+
+    pub fn find_all(&self) -> Vec<Value> {
+        self.all().iter().flat_map(|p| p.find_all()).collect()
+    }
+
+    pub fn find(&self, field: &str, value: &str) -> Vec<Value> {
+        self.all().iter().flat_map(|p| p.find(field, value)).collect()
+    }
+
+    pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> Vec<Value> {
+        self.all().iter().flat_map(|p| p.find_with_operator(field, value, operator)).collect()
+    }
+
+    /// Documentos con fecha en `[from_ms, to_ms)`, leyendo solo las particiones del rango
+    pub fn find_range(&self, from_ms: i64, to_ms: i64) -> Vec<Value> {
+        let first = self.by.key_for(from_ms);
+        let last = self.by.key_for(to_ms);
+        let candidates: Vec<Arc<Collection>> = self.partitions.read()
+            .range(first..=last)
+            .map(|(_, col)| col.clone())
+            .collect();
+
+        candidates.iter()
+            .flat_map(|p| p.find_all())
+            .filter(|doc| {
+                doc.get(&self.field)
+                    .and_then(datetime::value_to_millis)
+                    .is_some_and(|ms| ms >= from_ms && ms < to_ms)
+            })
+            .collect()
+    }
+
+    pub fn update_field(&self, id: &str, field: &str, value: Value) -> io::Result<bool> {
+        if field == self.field {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot update the partition field"));
+        }
+        for part in self.all() {
+            if part.update_field(id, field, value.clone())? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn delete_by_id(&self, id: &str) -> io::Result<bool> {
+        for part in self.all() {
+            if part.delete_by_id(id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Borra los archivos de las particiones que terminan antes de `ms`.
+    /// Devuelve cuántas particiones se eliminaron.
+    pub fn drop_partitions_before(&self, ms: i64) -> io::Result<usize> {
+        let limit = self.by.key_for(ms);
+        let mut parts = self.partitions.write();
+        let old: Vec<String> = parts.range(..limit).map(|(k, _)| k.clone()).collect();
+        for key in &old {
+            parts.remove(key);
+            self.db.drop_collection(&format!("{}{}", Self::file_prefix(&self.name), key))?;
+        }
+        Ok(old.len())
+    }
+}
@@ -0,0 +1,182 @@
+//! Tabla dinámica: convierte documentos de un evento por fila en un resumen ancho con las
+//! categorías como columnas y el valor agregado en cada celda.
+//!
+//! ```json
+//! {"rows": ["region"], "columns": "month", "values": {"$sum": "$amount"}, "fill": 0}
+//! ```
+//!
+//! produce `{"region": "norte", "2026-01": 120, "2026-02": 0, ...}` por cada región.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use serde_json::{Map, Value};
+use crate::collection::Collection;
+use crate::expr::{compare_values, get_path, number, Expr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// Documentos de la celda, tengan o no valor
+    Count,
+}
+
+/// Definición de la tabla
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pivot {
+    rows: Vec<String>,
+    column: String,
+    aggregate: Aggregate,
+    value: Expr,
+    fill: Value,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Acumulado de una celda
+#[derive(Default)]
+struct Cell {
+    count: usize,
+    sum: f64,
+    numbers: usize,
+    best: Option<Value>,
+}
+
+impl Pivot {
+    /// Una fila por cada combinación de `rows` y una columna por cada valor de `column`.
+    /// Por defecto cuenta documentos y las celdas vacías quedan a `null`.
+    pub fn new(rows: &[&str], column: &str) -> Self {
+        Self {
+            rows: rows.iter().map(|r| r.to_string()).collect(),
+            column: column.to_string(),
+            aggregate: Aggregate::Count,
+            value: Expr::Literal(Value::Null),
+            fill: Value::Null,
+        }
+    }
+
+    /// Valor de cada celda: `aggregate` de `value` sobre sus documentos
+    pub fn values(mut self, aggregate: Aggregate, value: Expr) -> Self {
+        self.aggregate = aggregate;
+        self.value = value;
+        self
+    }
+
+    /// Valor de las celdas sin documentos
+    pub fn fill(mut self, fill: Value) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Lee la especificación JSON del ejemplo del módulo
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Pivot must be an object".to_string()))?;
+        let rows: Vec<&str> = match obj.get("rows") {
+            None => Vec::new(),
+            Some(Value::String(s)) => vec![s.as_str()],
+            Some(Value::Array(items)) => items.iter()
+                .map(|item| item.as_str().ok_or_else(|| invalid("'rows' must contain field names".to_string())))
+                .collect::<io::Result<_>>()?,
+            Some(_) => return Err(invalid("'rows' must be a field name or an array of them".to_string())),
+        };
+        let column = obj.get("columns").and_then(|v| v.as_str())
+            .ok_or_else(|| invalid("Pivot needs a 'columns' field name".to_string()))?;
+        let mut pivot = Self::new(&rows, column);
+
+        if let Some(values) = obj.get("values") {
+            let (op, arg) = values.as_object()
+                .filter(|o| o.len() == 1)
+                .and_then(|o| o.iter().next())
+                .ok_or_else(|| invalid(format!("Invalid pivot values: {}", values)))?;
+            let aggregate = match op.as_str() {
+                "$sum" => Aggregate::Sum,
+                "$avg" => Aggregate::Avg,
+                "$min" => Aggregate::Min,
+                "$max" => Aggregate::Max,
+                "$count" => Aggregate::Count,
+                _ => return Err(invalid(format!("Unknown pivot aggregate '{}'", op))),
+            };
+            pivot = pivot.values(aggregate, Expr::parse(arg)?);
+        }
+        if let Some(fill) = obj.get("fill") {
+            pivot = pivot.fill(fill.clone());
+        }
+        Ok(pivot)
+    }
+
+    /// Calcula la tabla. Las filas salen en el orden en que aparece cada combinación y todas
+    /// tienen todas las columnas. Los documentos sin valor en el campo de columna no cuentan;
+    /// si una categoría coincide con un campo de fila prevalece el campo de fila.
+    pub fn apply<'a>(&self, docs: impl IntoIterator<Item = &'a Value>) -> Vec<Value> {
+        let mut rows: Vec<(Vec<Value>, HashMap<String, Cell>)> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut columns = BTreeSet::new();
+
+        for doc in docs {
+            let category = match get_path(doc, &self.column) {
+                None | Some(Value::Null) => continue,
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            };
+            let key: Vec<Value> = self.rows.iter()
+                .map(|field| get_path(doc, field).cloned().unwrap_or(Value::Null))
+                .collect();
+            let pos = *positions.entry(Value::Array(key.clone()).to_string()).or_insert_with(|| {
+                rows.push((key, HashMap::new()));
+                rows.len() - 1
+            });
+            let cell = rows[pos].1.entry(category.clone()).or_default();
+            columns.insert(category);
+            self.accumulate(cell, doc);
+        }
+
+        rows.into_iter().map(|(key, cells)| {
+            let mut out = Map::new();
+            for name in &columns {
+                let value = cells.get(name).map_or_else(|| self.fill.clone(), |cell| self.finish(cell));
+                out.insert(name.clone(), value);
+            }
+            for (field, value) in self.rows.iter().zip(key) {
+                out.insert(field.clone(), value);
+            }
+            Value::Object(out)
+        }).collect()
+    }
+
+    fn accumulate(&self, cell: &mut Cell, doc: &Value) {
+        cell.count += 1;
+        if self.aggregate == Aggregate::Count {
+            return;
+        }
+        let value = self.value.eval(doc);
+        if let Some(n) = value.as_f64() {
+            cell.sum += n;
+            cell.numbers += 1;
+        }
+        let wanted = if self.aggregate == Aggregate::Min { std::cmp::Ordering::Less } else { std::cmp::Ordering::Greater };
+        if !value.is_null() && cell.best.as_ref().is_none_or(|best| compare_values(&value, best) == Some(wanted)) {
+            cell.best = Some(value);
+        }
+    }
+
+    fn finish(&self, cell: &Cell) -> Value {
+        match self.aggregate {
+            Aggregate::Count => Value::from(cell.count),
+            Aggregate::Sum => number(cell.sum),
+            Aggregate::Avg if cell.numbers == 0 => Value::Null,
+            Aggregate::Avg => number(cell.sum / cell.numbers as f64),
+            Aggregate::Min | Aggregate::Max => cell.best.clone().unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl Collection {
+    /// Tabla dinámica sobre los documentos que cumplen `filter`
+    pub fn pivot<F: Fn(&Value) -> bool>(&self, filter: F, pivot: &Pivot) -> Vec<Value> {
+        pivot.apply(self.snapshot().iter().filter(|doc| filter(doc)))
+    }
+}
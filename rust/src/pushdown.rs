@@ -0,0 +1,89 @@
+//! Lectura de solo algunos campos directamente del archivo. Cada línea se recorre con un
+//! deserializador que guarda los campos pedidos y salta el resto sin construir sus `Value`,
+//! mucho más rápido que cargar documentos anchos enteros para usar unos pocos campos.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufReader};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, Visitor};
+use serde_json::{Map, Value};
+use crate::collection::{read_records_with, Collection};
+use crate::expr::Projection;
+
+/// Campos que se conservan siempre: el `_id`, la referencia de `dedupe` y el cuerpo de los
+/// documentos comprimidos (ver `compression`), que se expanden enteros
+const ALWAYS: [&str; 3] = ["_id", "_same_as", "$z"];
+
+struct Fields<'a>(&'a HashSet<&'a str>);
+
+impl<'de> DeserializeSeed<'de> for Fields<'_> {
+    type Value = Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Fields<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let mut out = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if self.0.contains(key.as_str()) {
+                let value = map.next_value::<Value>()?;
+                out.insert(key, value);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        Ok(Value::Object(out))
+    }
+}
+
+/// Extrae de una línea JSON solo los campos de `fields` (y los de `ALWAYS`)
+pub(crate) fn extract_fields(line: &str, fields: &HashSet<&str>) -> serde_json::Result<Value> {
+    let mut deserializer = serde_json::Deserializer::from_str(line);
+    let value = Fields(fields).deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+impl Collection {
+    /// Documentos del archivo con solo `_id` y los campos de primer nivel indicados, sin
+    /// cargarlos enteros. Lee lo que hay en disco: no incluye escrituras pendientes de
+    /// guardar (ver `PersistStrategy`).
+    pub fn scan_fields(&self, fields: &[&str]) -> io::Result<Vec<Value>> {
+        let wanted: HashSet<&str> = fields.iter().copied().chain(ALWAYS).collect();
+        // Con el lock del archivo tomado no cambia mientras se lee (espera según `contention`)
+        let writer = self.file_lock()?;
+        let options = self.options.read().clone();
+        let (docs, _, _) = match self.storage().open(&self.file_name, 0)? {
+            Some(file) => read_records_with(BufReader::new(file), &options, 0, false, |line| extract_fields(line, &wanted))?,
+            None => Default::default(),
+        };
+        drop(writer);
+
+        // Los comprimidos llegan enteros
+        Ok(docs.into_iter()
+            .map(|mut doc| {
+                if let Some(obj) = doc.as_object_mut() {
+                    obj.retain(|key, _| key == "_id" || fields.contains(&key.as_str()));
+                }
+                doc
+            })
+            .collect())
+    }
+
+    /// Como `find_projected` sin filtro, pero leyendo del archivo solo los campos que usa la
+    /// proyección
+    pub fn scan_projected(&self, projection: &Projection) -> io::Result<Vec<Value>> {
+        let fields = projection.source_fields();
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        Ok(self.scan_fields(&fields)?.iter().map(|doc| projection.apply(doc)).collect())
+    }
+}
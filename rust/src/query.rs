@@ -0,0 +1,223 @@
+//! Constructor de consultas compuestas. Genera el mismo filtro JSON que acepta
+//! `Collection::query` (ver `filter`), así que las dos formas se comportan igual.
+//!
+//! ```ignore
+//! let docs = col.query_builder()
+//!     .eq("status", "active")
+//!     .gt("age", 18)
+//!     .or(|q| q.eq("role", "admin"))
+//!     .sort("age", true)
+//!     .skip(20)
+//!     .limit(10)
+//!     .fields(&["name", "email"])
+//!     .execute()?;
+//! ```
+//!
+//! Las condiciones seguidas se combinan con AND; `or` une todo lo anterior con el grupo del
+//! cierre, y `and` y `not` agrupan sin cambiar el resto.
+
+use std::io;
+use serde_json::{json, Map, Value};
+use crate::collection::Collection;
+use crate::comparator::{sort_documents, SortKey};
+use crate::expr::Projection;
+use crate::filter::Filter;
+use crate::index::ReadConsistency;
+
+#[derive(Clone, Default)]
+pub struct QueryBuilder<'a> {
+    pub(crate) col: Option<&'a Collection>,
+    /// Filtros que se combinan con AND
+    pub(crate) clauses: Vec<Value>,
+    pub(crate) sort_by: Vec<SortKey>,
+    pub(crate) skip: usize,
+    pub(crate) limit: Option<usize>,
+    pub(crate) projection: Option<Projection>,
+    pub(crate) consistency: ReadConsistency,
+}
+
+impl<'a> QueryBuilder<'a> {
+    /// Consulta sin colección, p. ej. para construir un filtro y pasarlo a `Collection::query`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn field(mut self, field: &str, op: &str, value: Value) -> Self {
+        self.clauses.push(json!({ field: { op: value } }));
+        self
+    }
+
+    pub fn eq(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$eq", value.into())
+    }
+
+    pub fn ne(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$ne", value.into())
+    }
+
+    pub fn gt(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$gt", value.into())
+    }
+
+    pub fn gte(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$gte", value.into())
+    }
+
+    pub fn lt(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$lt", value.into())
+    }
+
+    pub fn lte(self, field: &str, value: impl Into<Value>) -> Self {
+        self.field(field, "$lte", value.into())
+    }
+
+    pub fn is_in<V: Into<Value>>(self, field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        self.field(field, "$in", Value::Array(values.into_iter().map(Into::into).collect()))
+    }
+
+    pub fn not_in<V: Into<Value>>(self, field: &str, values: impl IntoIterator<Item = V>) -> Self {
+        self.field(field, "$nin", Value::Array(values.into_iter().map(Into::into).collect()))
+    }
+
+    /// Expresión regular (sintaxis del crate `regex`) sobre un campo de texto
+    pub fn regex(self, field: &str, pattern: &str) -> Self {
+        self.field(field, "$regex", Value::String(pattern.to_string()))
+    }
+
+    /// Igualdad con un texto sin distinguir mayúsculas
+    pub fn ieq(self, field: &str, text: &str) -> Self {
+        self.field(field, "$ieq", Value::String(text.to_string()))
+    }
+
+    /// El campo contiene `text` sin distinguir mayúsculas
+    pub fn ilike(self, field: &str, text: &str) -> Self {
+        self.field(field, "$ilike", Value::String(text.to_string()))
+    }
+
+    pub fn exists(self, field: &str, exists: bool) -> Self {
+        self.field(field, "$exists", Value::Bool(exists))
+    }
+
+    /// Filtro JSON al estilo de MongoDB (ver `filter`), combinado con AND con el resto
+    pub fn matching(mut self, filter: Value) -> Self {
+        self.clauses.push(filter);
+        self
+    }
+
+    /// Todo lo anterior o el grupo de `group`
+    pub fn or<F: FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a>>(mut self, group: F) -> Self {
+        let before = Self { clauses: std::mem::take(&mut self.clauses), ..Self::new() }.filter();
+        let other = group(Self::new()).filter();
+        self.clauses.push(json!({ "$or": [before, other] }));
+        self
+    }
+
+    /// Grupo que se combina con AND, para anidar un `or` sin que afecte a lo anterior
+    pub fn and<F: FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a>>(mut self, group: F) -> Self {
+        self.clauses.push(group(Self::new()).filter());
+        self
+    }
+
+    /// Documentos que no cumplen el grupo
+    pub fn not<F: FnOnce(QueryBuilder<'a>) -> QueryBuilder<'a>>(mut self, group: F) -> Self {
+        self.clauses.push(json!({ "$not": group(Self::new()).filter() }));
+        self
+    }
+
+    /// Ordena el resultado por `field`; con varias llamadas, la primera tiene prioridad
+    pub fn sort(self, field: &str, descending: bool) -> Self {
+        self.sort_by_key(SortKey::new(field, descending))
+    }
+
+    /// Como `sort`, con un comparador (ver `comparator`)
+    pub fn sort_by_key(mut self, key: SortKey) -> Self {
+        self.sort_by.push(key);
+        self
+    }
+
+    /// Salta los `n` primeros resultados (después de ordenar)
+    pub fn skip(mut self, n: usize) -> Self {
+        self.skip = n;
+        self
+    }
+
+    /// Devuelve como mucho `n` resultados
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Devuelve cada documento transformado por `projection` en lugar de entero
+    pub fn project(mut self, projection: Projection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    /// Devuelve solo `fields` (y `_id`) de cada documento
+    pub fn fields(self, fields: &[&str]) -> Self {
+        self.project(Projection::fields(fields))
+    }
+
+    /// Con `ReadConsistency::Snapshot` no espera a una escritura en curso
+    pub fn consistency(mut self, consistency: ReadConsistency) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    /// Aplica las opciones JSON `{"sortBy": ["-age"], "skip": 20, "limit": 10, "projection":
+    /// {"name": 1}, "consistency": "snapshot"}`, todas opcionales
+    pub fn options(mut self, spec: &Value) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, msg.to_string());
+        if let Some(keys) = spec.get("sortBy") {
+            self.sort_by.extend(SortKey::parse_list(keys)?);
+        }
+        if let Some(skip) = spec.get("skip") {
+            self.skip = skip.as_u64().ok_or_else(|| invalid("'skip' must be a non-negative integer"))? as usize;
+        }
+        if let Some(limit) = spec.get("limit") {
+            self.limit = Some(limit.as_u64().ok_or_else(|| invalid("'limit' must be a non-negative integer"))? as usize);
+        }
+        if let Some(projection) = spec.get("projection") {
+            self.projection = Some(Projection::parse(projection)?);
+        }
+        if let Some(consistency) = spec.get("consistency") {
+            let name = consistency.as_str().ok_or_else(|| invalid("'consistency' must be a string"))?;
+            self.consistency = ReadConsistency::parse(name)?;
+        }
+        Ok(self)
+    }
+
+    /// Filtro JSON equivalente (sin orden ni paginación); `{}` sin condiciones
+    pub fn filter(&self) -> Value {
+        match self.clauses.as_slice() {
+            [] => Value::Object(Map::new()),
+            [single] => single.clone(),
+            all => json!({ "$and": all }),
+        }
+    }
+
+    /// Ejecuta la consulta en la colección de `Collection::query_builder`. Se ordena y pagina
+    /// sobre los documentos sin copiarlos, y solo se copian (o proyectan) los de la página.
+    pub fn execute(&self) -> io::Result<Vec<Value>> {
+        let col = self.col.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Query has no collection"))?;
+        let filter = self.filter();
+        let parsed = Filter::parse(&filter)?;
+        let shape = format!("{:?}", (&self.sort_by, self.skip, self.limit, &self.projection));
+        col.cached_with(&["query", &filter.to_string(), &shape], self.consistency, |data, indexes| {
+            let mut docs = parsed.select(data, indexes, col);
+            sort_documents(&mut docs, &self.sort_by);
+            let page = docs.into_iter().skip(self.skip).take(self.limit.unwrap_or(usize::MAX));
+            Ok(match &self.projection {
+                Some(projection) => page.map(|doc| projection.apply(doc)).collect(),
+                None => page.cloned().collect(),
+            })
+        })
+    }
+}
+
+impl Collection {
+    /// Consulta compuesta sobre esta colección (ver `QueryBuilder`)
+    pub fn query_builder(&self) -> QueryBuilder<'_> {
+        QueryBuilder { col: Some(self), ..QueryBuilder::new() }
+    }
+}
@@ -0,0 +1,151 @@
+//! Cola de trabajos sobre una colección. Cada trabajo es un documento
+//! `{"payload", "visible_at", "attempts", "claim"}`: `claim` reserva los visibles durante un
+//! tiempo y, si no se confirman con `ack`, vuelven a estar disponibles al vencer el plazo.
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use crate::cdc::ChangeOp;
+use crate::collection::{doc_id, now_millis, Collection};
+use crate::db::Database;
+
+/// Trabajo reservado con `JobQueue::claim`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub payload: Value,
+    /// Veces que se ha reservado, contando esta
+    pub attempts: u64,
+    /// Identifica esta reserva: `ack` y `nack` no tienen efecto si el trabajo se volvió a reservar
+    pub claim: String,
+}
+
+pub struct JobQueue {
+    collection: Arc<Collection>,
+}
+
+impl Database {
+    /// Cola de trabajos guardada en la colección `name`
+    pub fn queue(&self, name: &str) -> io::Result<JobQueue> {
+        Ok(JobQueue::new(self.collection(name)?))
+    }
+}
+
+impl JobQueue {
+    pub fn new(collection: Arc<Collection>) -> Self {
+        Self { collection }
+    }
+
+    pub fn collection(&self) -> &Arc<Collection> {
+        &self.collection
+    }
+
+    /// Añade un trabajo visible de inmediato. Devuelve su id.
+    pub fn enqueue(&self, payload: Value) -> io::Result<String> {
+        self.collection.insert(json!({
+            "payload": payload,
+            "visible_at": now_millis(),
+            "attempts": 0,
+            "claim": null,
+        }))
+    }
+
+    /// Reserva hasta `n` trabajos visibles, en orden de llegada, ocultándolos durante
+    /// `visibility_timeout`. Se hace con el lock de escritura tomado: dos llamadas
+    /// concurrentes nunca reciben el mismo trabajo.
+    pub fn claim(&self, n: usize, visibility_timeout: Duration) -> io::Result<Vec<Job>> {
+        self.collection.ensure_writable()?;
+        let now = now_millis();
+        let mut guard = self.collection.data_write()?;
+        let data = Arc::make_mut(&mut guard);
+        let mut jobs = Vec::new();
+
+        for doc in data.iter_mut() {
+            if jobs.len() == n {
+                break;
+            }
+            if doc.get("visible_at").and_then(|v| v.as_u64()).is_none_or(|at| at > now) {
+                continue;
+            }
+            let attempts = doc.get("attempts").and_then(|v| v.as_u64()).unwrap_or(0) + 1;
+            let claim = Uuid::new_v4().to_string();
+            if let Some(obj) = doc.as_object_mut() {
+                obj.insert("visible_at".to_string(), json!(now + visibility_timeout.as_millis() as u64));
+                obj.insert("attempts".to_string(), json!(attempts));
+                obj.insert("claim".to_string(), json!(claim));
+            }
+            let id = doc_id(doc).to_string();
+            self.collection.emit(ChangeOp::Update, &id, Some(doc));
+            jobs.push(Job { id, payload: doc.get("payload").cloned().unwrap_or(Value::Null), attempts, claim });
+        }
+
+        if !jobs.is_empty() {
+            self.collection.rebuild_indexes(data);
+            self.collection.touch();
+            drop(guard);
+            self.collection.persist_on_write()?;
+        }
+        Ok(jobs)
+    }
+
+    /// Confirma el trabajo y lo elimina. `false` si la reserva venció o el trabajo se volvió
+    /// a reservar.
+    pub fn ack(&self, job: &Job) -> io::Result<bool> {
+        self.collection.ensure_writable()?;
+        let now = now_millis();
+        let mut guard = self.collection.data_write()?;
+        let pos = guard.iter().position(|doc| {
+            is_claimed_by(doc, job) && doc.get("visible_at").and_then(|v| v.as_u64()).is_some_and(|at| at > now)
+        });
+        let pos = match pos {
+            Some(pos) => pos,
+            None => return Ok(false),
+        };
+        let data = Arc::make_mut(&mut guard);
+        data.remove(pos);
+        self.collection.emit(ChangeOp::Delete, &job.id, None);
+        self.collection.rebuild_indexes(data);
+        self.collection.touch();
+        drop(guard);
+        self.collection.persist_on_write()?;
+        Ok(true)
+    }
+
+    /// Devuelve el trabajo a la cola para que se pueda reservar de inmediato. `false` si la
+    /// reserva ya no es válida.
+    pub fn nack(&self, job: &Job) -> io::Result<bool> {
+        self.collection.ensure_writable()?;
+        let mut guard = self.collection.data_write()?;
+        let data = Arc::make_mut(&mut guard);
+        let doc = match data.iter_mut().find(|doc| is_claimed_by(doc, job)) {
+            Some(doc) => doc,
+            None => return Ok(false),
+        };
+        if let Some(obj) = doc.as_object_mut() {
+            obj.insert("visible_at".to_string(), json!(now_millis()));
+            obj.insert("claim".to_string(), Value::Null);
+        }
+        self.collection.emit(ChangeOp::Update, &job.id, Some(doc));
+        self.collection.rebuild_indexes(data);
+        self.collection.touch();
+        drop(guard);
+        self.collection.persist_on_write()?;
+        Ok(true)
+    }
+
+    /// Trabajos en la cola, reservados o no
+    pub fn len(&self) -> usize {
+        self.collection.snapshot().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn is_claimed_by(doc: &Value, job: &Job) -> bool {
+    doc_id(doc) == job.id && doc.get("claim").and_then(|v| v.as_str()) == Some(job.claim.as_str())
+}
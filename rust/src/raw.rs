@@ -0,0 +1,46 @@
+//! Las líneas del archivo de una colección tal como están guardadas (cifradas, comprimidas o
+//! como referencias de `dedupe`), sin interpretarlas, para exportar, replicar o calcular
+//! sumas de comprobación.
+//!
+//! ```ignore
+//! for line in col.raw_lines()? {
+//!     hasher.update(line?.as_bytes());
+//! }
+//! ```
+
+use std::io::{self, BufRead, BufReader, Lines, Read};
+use parking_lot::MutexGuard;
+use crate::collection::Collection;
+
+/// Iterador de `Collection::raw_lines`. Mientras existe tiene tomado el lock de escritura del
+/// archivo, así que las escrituras de la colección esperan a que se suelte. Tomarlo espera
+/// según `CollectionOptions::contention`.
+pub struct RawLines<'a> {
+    _writer: MutexGuard<'a, ()>,
+    lines: Option<Lines<BufReader<Box<dyn Read + Send>>>>,
+}
+
+impl Iterator for RawLines<'_> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.lines.as_mut()?.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                other => return Some(other),
+            }
+        }
+    }
+}
+
+impl Collection {
+    /// Líneas del archivo sin el `\n` y sin las vacías. Lee lo que hay en disco: no incluye
+    /// escrituras pendientes de guardar (ver `PersistStrategy`). Una línea que no es UTF-8
+    /// sale como error `InvalidData` y termina la lectura.
+    pub fn raw_lines(&self) -> io::Result<RawLines<'_>> {
+        // Con el writer tomado el archivo no cambia mientras se lee
+        let writer = self.file_lock()?;
+        let lines = self.storage().open(&self.file_name, 0)?.map(|file| BufReader::new(file).lines());
+        Ok(RawLines { _writer: writer, lines })
+    }
+}
@@ -0,0 +1,67 @@
+//! Bases de datos abiertas por alias para todo el proceso. Pensado para hosts en los que
+//! guardar y liberar punteros es incómodo (Lua, GDScript, scripts): se abre una vez con
+//! `ruggy_open_named` y después se usan el alias y el nombre de la colección.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::Mutex;
+use crate::collection::Collection;
+use crate::db::Database;
+use crate::storage::canonical_dir;
+
+struct Entry {
+    path: PathBuf,
+    db: Arc<Database>,
+}
+
+static REGISTRY: Mutex<Option<HashMap<String, Entry>>> = parking_lot::const_mutex(None);
+
+/// Abre la base de datos de `path` con el alias `alias`. Abrir otra vez el mismo alias con la
+/// misma ruta devuelve la ya abierta; con otra ruta es un error. Los alias de un mismo
+/// directorio comparten la instancia (ver `Database::open_shared`).
+pub fn open_named<P: AsRef<Path>>(alias: &str, path: P) -> io::Result<Arc<Database>> {
+    if alias.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty database alias"));
+    }
+    let path = canonical_dir(path.as_ref(), true)?;
+    let mut registry = REGISTRY.lock();
+    let entries = registry.get_or_insert_with(HashMap::new);
+    if let Some(entry) = entries.get(alias) {
+        if entry.path != path {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Alias '{}' is already open at '{}'", alias, entry.path.display()),
+            ));
+        }
+        return Ok(entry.db.clone());
+    }
+    let db = Database::open_shared(&path)?;
+    entries.insert(alias.to_string(), Entry { path, db: db.clone() });
+    Ok(db)
+}
+
+pub fn named(alias: &str) -> Option<Arc<Database>> {
+    REGISTRY.lock().as_ref()?.get(alias).map(|entry| entry.db.clone())
+}
+
+/// Colección `name` de la base de datos con alias `alias`
+pub fn named_collection(alias: &str, name: &str) -> io::Result<Arc<Collection>> {
+    named(alias)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No database open as '{}'", alias)))?
+        .collection(name)
+}
+
+/// Quita el alias; la base de datos se cierra cuando se suelta la última referencia.
+/// `false` si el alias no existía.
+pub fn close_named(alias: &str) -> bool {
+    REGISTRY.lock().as_mut().is_some_and(|entries| entries.remove(alias).is_some())
+}
+
+/// Alias abiertos, en orden alfabético
+pub fn aliases() -> Vec<String> {
+    let mut aliases: Vec<String> = REGISTRY.lock().as_ref().map_or_else(Vec::new, |entries| entries.keys().cloned().collect());
+    aliases.sort();
+    aliases
+}
@@ -0,0 +1,123 @@
+//! Seguidores de solo lectura que siguen el archivo de otra instancia (posiblemente en otro
+//! proceso). Los inserts del líder se añaden al final del `.col`, así que el seguidor lee solo
+//! los bytes nuevos. Cuando el líder reescribe el archivo (update, delete, compactación)
+//! incrementa la época del sidecar `<name>.epoch`: impar durante la reescritura y par al
+//! terminar. Si el seguidor ve otra época recarga el archivo entero, y si la ve impar o cambia
+//! mientras lee, descarta la lectura y conserva la versión anterior.
+
+use std::io::{self, BufReader};
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use serde_json::Value;
+use crate::collection::{read_records, Collection};
+use crate::load::LoadReport;
+use crate::options::CollectionOptions;
+use crate::storage::{self, StorageBackend};
+
+pub(crate) struct FollowState {
+    /// Bytes del `.col` ya aplicados
+    offset: u64,
+    /// Época con la que se leyó `offset`; `None` obliga a recargar
+    epoch: Option<u64>,
+}
+
+/// Época actual del archivo `name` (0 si nunca se ha reescrito)
+pub(crate) fn read_epoch(storage: &dyn StorageBackend, name: &str) -> io::Result<u64> {
+    match storage::read_all(storage, &storage::sibling(name, "epoch"))? {
+        Some(bytes) => Ok(String::from_utf8_lossy(&bytes).trim().parse().unwrap_or(0)),
+        None => Ok(0),
+    }
+}
+
+pub(crate) fn write_epoch(storage: &dyn StorageBackend, name: &str, epoch: u64) -> io::Result<()> {
+    storage.replace(&storage::sibling(name, "epoch"), epoch.to_string().as_bytes())
+}
+
+/// Sustituye el contenido de un archivo que no está abierto, avanzando su época para que los
+/// seguidores lo recarguen
+pub(crate) fn replace_file(storage: &dyn StorageBackend, name: &str, contents: &[u8]) -> io::Result<()> {
+    let epoch = read_epoch(storage, name)?;
+    let epoch = if epoch.is_multiple_of(2) { epoch + 1 } else { epoch + 2 };
+    write_epoch(storage, name, epoch)?;
+    storage.replace(name, contents)?;
+    storage.sync(name)?;
+    write_epoch(storage, name, epoch + 1)
+}
+
+/// Lee el archivo desde `offset`. `None` si el archivo aún no existe.
+fn read_from(storage: &dyn StorageBackend, name: &str, offset: u64, options: &CollectionOptions) -> io::Result<Option<(Vec<Value>, u64, LoadReport)>> {
+    match storage.open(name, offset)? {
+        Some(reader) => read_records(BufReader::new(reader), options, offset, true).map(Some),
+        None => Ok(None),
+    }
+}
+
+impl Collection {
+    /// Abre la colección como seguidor de solo lectura: no crea ni modifica el archivo y las
+    /// escrituras devuelven `PermissionDenied`. Llamar `refresh()` para ver los cambios del líder.
+    pub fn follower(name: &str, file_path: PathBuf, options: CollectionOptions) -> io::Result<Self> {
+        let (storage, file_name) = storage::local_file(&file_path)?;
+        Self::follow_in(name, Arc::new(storage), file_name, options)
+    }
+
+    /// Como `follower`, para la colección `<name>.col` de `storage`
+    pub fn follower_with_storage(name: &str, storage: Arc<dyn StorageBackend>, options: CollectionOptions) -> io::Result<Self> {
+        Self::follow_in(name, storage, format!("{}.col", name), options)
+    }
+
+    pub(crate) fn follow_in(name: &str, storage: Arc<dyn StorageBackend>, file_name: String, options: CollectionOptions) -> io::Result<Self> {
+        let epoch = read_epoch(storage.as_ref(), &file_name)?;
+        let (data, offset, report) = read_from(storage.as_ref(), &file_name, 0, &options)?.unwrap_or_default();
+        let stable = epoch.is_multiple_of(2) && read_epoch(storage.as_ref(), &file_name)? == epoch;
+        let state = FollowState { offset, epoch: if stable { Some(epoch) } else { None } };
+        Ok(Self::from_parts(name, storage, file_name, data, options, epoch, Some(state)).with_load_report(report))
+    }
+
+    /// Aplica los cambios del líder. Devuelve `true` si los datos cambiaron.
+    /// En una colección normal no hace nada.
+    pub fn refresh(&self) -> io::Result<bool> {
+        let follow = match &self.follow {
+            Some(follow) => follow,
+            None => return Ok(false),
+        };
+        let mut state = follow.lock();
+        let options = self.options.read().clone();
+
+        let storage = self.storage();
+        let before = read_epoch(storage.as_ref(), &self.file_name)?;
+        if !before.is_multiple_of(2) {
+            return Ok(false);
+        }
+        let len = storage.len(&self.file_name)?.unwrap_or(0);
+        let reload = state.epoch != Some(before) || len < state.offset;
+        let start = if reload { 0 } else { state.offset };
+        let (docs, consumed, report) = match read_from(storage.as_ref(), &self.file_name, start, &options)? {
+            Some(read) => read,
+            None => return Ok(false),
+        };
+        if read_epoch(storage.as_ref(), &self.file_name)? != before {
+            // El líder empezó a reescribir mientras leíamos
+            return Ok(false);
+        }
+
+        let mut data = self.data.write();
+        if reload {
+            *data = Arc::new(docs);
+            *self.load_report.write() = report;
+            state.offset = consumed;
+        } else if consumed > 0 {
+            let all = Arc::make_mut(&mut data);
+            all.extend(docs);
+            self.apply_order(all);
+            state.offset += consumed;
+        } else {
+            return Ok(false);
+        }
+        self.rebuild_indexes(&data);
+        state.epoch = Some(before);
+        self.epoch.store(before, Ordering::Relaxed);
+        self.touch();
+        Ok(true)
+    }
+}
@@ -0,0 +1,169 @@
+//! Reglas de validación y updates calculados con el lenguaje de `expr`, para cambiar el
+//! comportamiento con datos (p. ej. leídos de la configuración) sin recompilar el host.
+//!
+//! ```json
+//! {"precio_positivo": {"$gt": ["$price", 0]},
+//!  "con_nombre": {"$ne": [{"$ifNull": ["$name", ""]}, ""]}}
+//! ```
+//!
+//! Los updates asignan a cada campo el resultado de su expresión, calculada sobre el documento
+//! anterior al update: `{"total": {"$multiply": ["$price", "$qty"]}, "visitas": {"$add": ["$visitas", 1]}}`.
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use serde_json::Value;
+use crate::cdc::ChangeOp;
+use crate::collection::{check_reserved, doc_id, Collection};
+use crate::expr::Expr;
+use crate::types;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Expresión que tienen que cumplir todos los documentos que se escriben
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub name: String,
+    pub expr: Expr,
+}
+
+impl Rule {
+    pub fn new(name: &str, expr: Expr) -> Self {
+        Self { name: name.to_string(), expr }
+    }
+
+    /// Reglas de un objeto nombre -> expresión (ejemplo del módulo)
+    pub fn parse_all(spec: &Value) -> io::Result<Vec<Rule>> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Rules must be an object".to_string()))?;
+        obj.iter().map(|(name, expr)| Ok(Rule::new(name, Expr::parse(expr)?))).collect()
+    }
+}
+
+/// Un documento no cumple la regla `rule`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleViolation {
+    pub rule: String,
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Document violates rule '{}'", self.rule)
+    }
+}
+
+impl std::error::Error for RuleViolation {}
+
+/// Regla incumplida si el error viene de las reglas de validación (el detalle es un `RuleViolation`)
+pub fn violated_rule(err: &io::Error) -> Option<&str> {
+    err.get_ref()
+        .and_then(|inner| inner.downcast_ref::<RuleViolation>())
+        .map(|violation| violation.rule.as_str())
+}
+
+/// Error con el nombre de la primera regla que no cumple `doc`
+pub(crate) fn check(rules: &[Rule], doc: &Value) -> io::Result<()> {
+    match rules.iter().find(|rule| !rule.expr.matches(doc)) {
+        Some(rule) => Err(io::Error::new(io::ErrorKind::InvalidInput, RuleViolation { rule: rule.name.clone() })),
+        None => Ok(()),
+    }
+}
+
+/// Campos que se asignan con expresiones
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UpdateExpr {
+    fields: Vec<(String, Expr)>,
+}
+
+impl UpdateExpr {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(mut self, field: &str, expr: Expr) -> Self {
+        self.fields.push((field.to_string(), expr));
+        self
+    }
+
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Update must be an object".to_string()))?;
+        let mut update = Self::new();
+        for (field, expr) in obj {
+            if field == "_id" {
+                return Err(invalid("Updates cannot change '_id'".to_string()));
+            }
+            update = update.set(field, Expr::parse(expr)?);
+        }
+        Ok(update)
+    }
+
+    /// Documento con el update aplicado; todas las expresiones ven el documento original
+    pub fn apply(&self, doc: &Value) -> Value {
+        let values: Vec<Value> = self.fields.iter().map(|(_, expr)| expr.eval(doc)).collect();
+        let mut out = doc.clone();
+        if let Some(obj) = out.as_object_mut() {
+            for ((field, _), value) in self.fields.iter().zip(values) {
+                obj.insert(field.clone(), value);
+            }
+        }
+        out
+    }
+}
+
+impl Collection {
+    /// Sustituye las reglas de validación. Los documentos que ya están guardados no se
+    /// revisan: las reglas se comprueban en inserts, updates y `upsert_many`/`import`.
+    pub fn set_rules(&self, rules: Vec<Rule>) {
+        self.options.write().rules = rules;
+    }
+
+    pub(crate) fn check_rules(&self, doc: &Value) -> io::Result<()> {
+        check(&self.options.read().rules, doc)
+    }
+
+    /// Aplica el update al documento `id`. `false` si no existe; error si el resultado no
+    /// cumple las reglas (y entonces no cambia nada).
+    pub fn update_with(&self, id: &str, update: &UpdateExpr) -> io::Result<bool> {
+        Ok(self.update_matching(|doc| doc_id(doc) == id, update, true)? > 0)
+    }
+
+    /// Aplica el update a todos los documentos que cumplen `filter`. Si alguno no cumpliría
+    /// las reglas no se cambia ninguno. Devuelve cuántos se han actualizado.
+    pub fn update_where(&self, filter: &Expr, update: &UpdateExpr) -> io::Result<usize> {
+        self.update_matching(|doc| filter.matches(doc), update, false)
+    }
+
+    fn update_matching<F: Fn(&Value) -> bool>(&self, filter: F, update: &UpdateExpr, first_only: bool) -> io::Result<usize> {
+        self.ensure_writable()?;
+        let mut guard = self.data_write()?;
+        let mut updated = Vec::new();
+        for (pos, doc) in guard.iter().enumerate() {
+            if filter(doc) {
+                let mut new_doc = update.apply(doc);
+                check_reserved(&new_doc)?;
+                types::canonicalize(&mut new_doc)?;
+                self.check_rules(&new_doc)?;
+                updated.push((pos, new_doc));
+                if first_only {
+                    break;
+                }
+            }
+        }
+        if updated.is_empty() {
+            return Ok(0);
+        }
+
+        let data = Arc::make_mut(&mut guard);
+        let count = updated.len();
+        for (pos, doc) in updated {
+            self.emit(ChangeOp::Update, doc_id(&doc), Some(&doc));
+            data[pos] = doc;
+        }
+        self.rebuild_indexes(data);
+        self.touch();
+        drop(guard);
+        self.persist_on_write()?;
+        Ok(count)
+    }
+}
@@ -0,0 +1,327 @@
+//! Almacenamiento en un bucket compatible con S3 (AWS, MinIO, R2...) con caché local
+//! (feature `s3`), para servidores pequeños que quieren datos duraderos sin gestionar discos.
+//!
+//! Cada archivo de la base de datos es una serie de objetos `<prefix><archivo>/<n>`: cada
+//! `append` (p. ej. un insert) sube un segmento nuevo y cada `replace` (reescritura completa)
+//! sube un objeto `<n>.base` y borra los anteriores. El contenido es el último `.base` seguido
+//! de los segmentos posteriores, así que un lector nunca ve una reescritura a medias.
+//!
+//! Las lecturas salen de una copia en un directorio local, que se descarga la primera vez que
+//! se usa cada archivo; si en el bucket no ha cambiado desde la última vez (mismos objetos y
+//! ETags) se reutiliza sin descargar. Cada escritura termina cuando el bucket la ha
+//! confirmado. Solo una instancia debe escribir en cada prefijo; un seguidor en otro proceso
+//! tiene que llamar `invalidate()` antes de `refresh()` para ver los cambios.
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::Mutex;
+use rusty_s3::actions::ListObjectsV2;
+use rusty_s3::{Bucket, Credentials, S3Action, UrlStyle};
+use serde::{Deserialize, Serialize};
+use crate::storage::{self, LocalStorage, StorageBackend};
+
+/// Validez de las URLs firmadas de cada petición
+const SIGNATURE_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct S3Options {
+    /// URL del servicio, p. ej. `https://s3.eu-west-1.amazonaws.com` o `http://minio:9000`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    /// Sin claves se usan `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` (y
+    /// `AWS_SESSION_TOKEN`); sin nada, peticiones anónimas
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    /// Antepuesto a las claves de los objetos, p. ej. `ruggy/produccion/`
+    pub prefix: String,
+    /// URLs `endpoint/bucket/clave` en vez de `bucket.endpoint/clave` (MinIO y la mayoría de
+    /// servicios autoalojados)
+    pub path_style: bool,
+}
+
+impl S3Options {
+    pub fn new(endpoint: &str, bucket: &str, region: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn credentials(mut self, access_key: &str, secret_key: &str) -> Self {
+        self.access_key = Some(access_key.to_string());
+        self.secret_key = Some(secret_key.to_string());
+        self
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    pub fn path_style(mut self, path_style: bool) -> Self {
+        self.path_style = path_style;
+        self
+    }
+}
+
+/// Objeto de un archivo en el bucket
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Object {
+    key: String,
+    etag: String,
+}
+
+/// Lo que se sabe de un archivo en el bucket
+struct Remote {
+    /// Objetos actuales, desde el último `.base`, en orden
+    objects: Vec<Object>,
+    /// Número del siguiente objeto
+    next: u64,
+}
+
+impl Remote {
+    fn exists(&self) -> bool {
+        !self.objects.is_empty()
+    }
+}
+
+pub struct S3Storage {
+    bucket: Bucket,
+    credentials: Option<Credentials>,
+    prefix: String,
+    agent: ureq::Agent,
+    cache: LocalStorage,
+    /// Archivos ya sincronizados con el bucket en este proceso. Cada uno tiene su lock para
+    /// que la IO de archivos distintos no se espere.
+    files: Mutex<HashMap<String, Arc<Mutex<Option<Remote>>>>>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn request_error(e: ureq::Error) -> io::Error {
+    match e {
+        ureq::Error::StatusCode(404) => io::Error::new(io::ErrorKind::NotFound, "S3 object not found"),
+        ureq::Error::StatusCode(status) => io::Error::other(format!("S3 request failed with status {}", status)),
+        other => io::Error::other(format!("S3 request failed: {}", other.into_io())),
+    }
+}
+
+/// Número de un objeto `<n>` o `<n>.base` y si es base
+fn object_number(file_key: &str) -> Option<(u64, bool)> {
+    match file_key.strip_suffix(".base") {
+        Some(n) => n.parse().ok().map(|n| (n, true)),
+        None => file_key.parse().ok().map(|n| (n, false)),
+    }
+}
+
+/// Nombre del manifiesto de objetos de `name` en la caché
+fn manifest_name(name: &str) -> String {
+    format!("{}.objects", name)
+}
+
+impl S3Storage {
+    /// `cache_dir` guarda la copia local de los archivos; se crea si no existe
+    pub fn new<P: AsRef<Path>>(options: S3Options, cache_dir: P) -> io::Result<Self> {
+        let endpoint = options.endpoint.parse()
+            .map_err(|e| invalid(format!("Invalid S3 endpoint '{}': {}", options.endpoint, e)))?;
+        let style = if options.path_style { UrlStyle::Path } else { UrlStyle::VirtualHost };
+        let bucket = Bucket::new(endpoint, style, options.bucket.clone(), options.region.clone())
+            .map_err(|e| invalid(format!("Invalid S3 bucket '{}': {}", options.bucket, e)))?;
+        let credentials = match (options.access_key, options.secret_key) {
+            (Some(key), Some(secret)) => Some(Credentials::new(key, secret)),
+            (None, None) => Credentials::from_env(),
+            _ => return Err(invalid("S3 access_key and secret_key must be set together".to_string())),
+        };
+        Ok(Self {
+            bucket,
+            credentials,
+            prefix: options.prefix,
+            agent: ureq::Agent::new_with_defaults(),
+            cache: LocalStorage::new(cache_dir)?,
+            files: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Olvida lo que se sabe del bucket: el siguiente acceso a cada archivo vuelve a mirar
+    /// si ha cambiado (y lo descarga si es así)
+    pub fn invalidate(&self) {
+        self.files.lock().clear();
+    }
+
+    fn key(&self, name: &str, file_key: &str) -> String {
+        format!("{}{}/{}", self.prefix, name, file_key)
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        let url = self.bucket.get_object(self.credentials.as_ref(), key).sign(SIGNATURE_TTL);
+        let mut response = self.agent.get(url.as_str()).call().map_err(request_error)?;
+        response.body_mut().with_config().limit(u64::MAX).read_to_vec().map_err(request_error)
+    }
+
+    /// Sube un objeto y devuelve su ETag
+    fn put(&self, key: &str, bytes: &[u8]) -> io::Result<String> {
+        let url = self.bucket.put_object(self.credentials.as_ref(), key).sign(SIGNATURE_TTL);
+        let response = self.agent.put(url.as_str()).send(bytes).map_err(request_error)?;
+        Ok(response.headers().get("etag").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string())
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        let url = self.bucket.delete_object(self.credentials.as_ref(), key).sign(SIGNATURE_TTL);
+        self.agent.delete(url.as_str()).call().map_err(request_error)?;
+        Ok(())
+    }
+
+    /// Claves y ETags de todos los objetos que empiezan por `prefix`
+    fn list_objects(&self, prefix: &str) -> io::Result<Vec<Object>> {
+        let mut objects = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut action = self.bucket.list_objects_v2(self.credentials.as_ref());
+            action.with_prefix(prefix);
+            if let Some(token) = &token {
+                action.with_continuation_token(token.as_str());
+            }
+            let url = action.sign(SIGNATURE_TTL);
+            let body = self.agent.get(url.as_str()).call().map_err(request_error)?
+                .body_mut().with_config().limit(u64::MAX).read_to_string().map_err(request_error)?;
+            let page = ListObjectsV2::parse_response(&body)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid S3 listing: {}", e)))?;
+            objects.extend(page.contents.into_iter().map(|c| Object { key: c.key, etag: c.etag }));
+            match page.next_continuation_token {
+                Some(next) => token = Some(next),
+                None => return Ok(objects),
+            }
+        }
+    }
+
+    /// Llama a `f` con el estado de `name` bloqueado, sincronizando antes la caché con el
+    /// bucket si aún no se había hecho
+    fn with_file<R>(&self, name: &str, f: impl FnOnce(&mut Remote) -> io::Result<R>) -> io::Result<R> {
+        let file = self.files.lock().entry(name.to_string()).or_default().clone();
+        let mut state = file.lock();
+        let remote = match state.take() {
+            Some(remote) => state.insert(remote),
+            None => state.insert(self.fetch(name)?),
+        };
+        f(remote)
+    }
+
+    fn fetch(&self, name: &str) -> io::Result<Remote> {
+        let dir = self.key(name, "");
+        let mut numbered: Vec<(u64, bool, Object)> = self.list_objects(&dir)?
+            .into_iter()
+            .filter_map(|object| {
+                let (n, base) = object_number(object.key.strip_prefix(&dir)?)?;
+                Some((n, base, object))
+            })
+            .collect();
+        numbered.sort_by_key(|(n, _, _)| *n);
+        let next = numbered.last().map_or(0, |(n, _, _)| n + 1);
+        // Lo anterior al último `.base` son restos de reescrituras
+        let start = numbered.iter().rposition(|(_, base, _)| *base).unwrap_or(0);
+        let objects: Vec<Object> = numbered.into_iter().skip(start).map(|(_, _, object)| object).collect();
+
+        if objects.is_empty() {
+            self.cache.remove(name)?;
+            self.cache.remove(&manifest_name(name))?;
+            return Ok(Remote { objects, next });
+        }
+        let cached = storage::read_all(&self.cache, &manifest_name(name))?
+            .and_then(|bytes| serde_json::from_slice::<Vec<Object>>(&bytes).ok());
+        if cached.as_ref() != Some(&objects) || self.cache.len(name)?.is_none() {
+            let mut contents = Vec::new();
+            for object in &objects {
+                contents.extend(self.get(&object.key)?);
+            }
+            self.cache.replace(name, &contents)?;
+            self.save_manifest(name, &objects)?;
+        }
+        Ok(Remote { objects, next })
+    }
+
+    fn save_manifest(&self, name: &str, objects: &[Object]) -> io::Result<()> {
+        self.cache.replace(&manifest_name(name), &serde_json::to_vec(objects)?)
+    }
+}
+
+impl StorageBackend for S3Storage {
+    fn open(&self, name: &str, offset: u64) -> io::Result<Option<Box<dyn Read + Send>>> {
+        self.with_file(name, |remote| match remote.exists() {
+            true => self.cache.open(name, offset),
+            false => Ok(None),
+        })
+    }
+
+    fn len(&self, name: &str) -> io::Result<Option<u64>> {
+        self.with_file(name, |remote| match remote.exists() {
+            true => self.cache.len(name),
+            false => Ok(None),
+        })
+    }
+
+    fn append(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        self.with_file(name, |remote| {
+            if bytes.is_empty() && remote.exists() {
+                return Ok(());
+            }
+            let key = self.key(name, &remote.next.to_string());
+            let etag = self.put(&key, bytes)?;
+            remote.next += 1;
+            remote.objects.push(Object { key, etag });
+            self.cache.append(name, bytes)?;
+            self.save_manifest(name, &remote.objects)
+        })
+    }
+
+    fn replace(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        self.with_file(name, |remote| {
+            let key = self.key(name, &format!("{}.base", remote.next));
+            let etag = self.put(&key, bytes)?;
+            remote.next += 1;
+            let old = std::mem::replace(&mut remote.objects, vec![Object { key, etag }]);
+            self.cache.replace(name, bytes)?;
+            self.save_manifest(name, &remote.objects)?;
+            // El `.base` nuevo ya tapa los anteriores: si el borrado falla solo quedan restos
+            for object in old {
+                if let Err(e) = self.delete(&object.key) {
+                    eprintln!("Ruggy Error: Removing replaced S3 object '{}' failed: {}", object.key, e);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn remove(&self, name: &str) -> io::Result<bool> {
+        self.with_file(name, |remote| {
+            let existed = remote.exists();
+            while let Some(object) = remote.objects.pop() {
+                self.delete(&object.key)?;
+            }
+            self.cache.remove(name)?;
+            self.cache.remove(&manifest_name(name))?;
+            Ok(existed)
+        })
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names: Vec<String> = self.list_objects(&self.prefix)?
+            .into_iter()
+            .filter_map(|object| {
+                let (name, file_key) = object.key.strip_prefix(&self.prefix)?.rsplit_once('/')?;
+                object_number(file_key).map(|_| name.to_string())
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+}
@@ -0,0 +1,169 @@
+use std::cmp::Ordering;
+use std::io;
+use std::sync::Arc;
+use serde_json::Value;
+use crate::collection::Collection;
+use crate::expr::compare_numbers;
+
+/// Cómo se reparte una colección lógica entre varios archivos
+#[derive(Clone, Debug)]
+pub enum ShardBy {
+    /// `shards` archivos, elegidos por hash estable del valor de `field`
+    Hash { field: String, shards: usize },
+    /// Un archivo por tramo: el shard `i` recibe los valores `< bounds[i]`,
+    /// el último recibe el resto. `bounds` debe estar ordenado.
+    Range { field: String, bounds: Vec<Value> },
+}
+
+impl ShardBy {
+    pub fn hash(field: &str, shards: usize) -> Self {
+        ShardBy::Hash { field: field.to_string(), shards: shards.max(1) }
+    }
+
+    pub fn range(field: &str, bounds: Vec<Value>) -> Self {
+        ShardBy::Range { field: field.to_string(), bounds }
+    }
+
+    pub fn field(&self) -> &str {
+        match self {
+            ShardBy::Hash { field, .. } | ShardBy::Range { field, .. } => field,
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        match self {
+            ShardBy::Hash { shards, .. } => *shards,
+            ShardBy::Range { bounds, .. } => bounds.len() + 1,
+        }
+    }
+
+    /// Shard de un valor de la clave. Los documentos sin clave van al shard 0.
+    pub fn shard_for(&self, key: Option<&Value>) -> usize {
+        let key = match key {
+            Some(key) => key,
+            None => return 0,
+        };
+        match self {
+            ShardBy::Hash { shards, .. } => (fnv1a(key.to_string().as_bytes()) % *shards as u64) as usize,
+            ShardBy::Range { bounds, .. } => bounds
+                .iter()
+                .position(|bound| compare_keys(key, bound) == Ordering::Less)
+                .unwrap_or(bounds.len()),
+        }
+    }
+
+    /// Nombre de la colección física de un shard
+    pub fn shard_name(name: &str, shard: usize) -> String {
+        format!("{}.shard-{}", name, shard)
+    }
+}
+
+/// Colección lógica repartida en varios archivos. Cada shard es una `Collection` normal
+/// registrada en la base de datos, así que un update o delete solo reescribe su archivo.
+pub struct ShardedCollection {
+    name: String,
+    spec: ShardBy,
+    shards: Vec<Arc<Collection>>,
+}
+
+impl ShardedCollection {
+    pub(crate) fn new(name: &str, spec: ShardBy, shards: Vec<Arc<Collection>>) -> Self {
+        Self { name: name.to_string(), spec, shards }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn spec(&self) -> &ShardBy {
+        &self.spec
+    }
+
+    pub fn shards(&self) -> &[Arc<Collection>] {
+        &self.shards
+    }
+
+    pub fn insert(&self, document: Value) -> io::Result<String> {
+        let shard = self.spec.shard_for(document.get(self.spec.field()));
+        self.shards[shard].insert(document)
+    }
+
+    pub fn find_all(&self) -> Vec<Value> {
+        self.shards.iter().flat_map(|s| s.find_all()).collect()
+    }
+
+    pub fn find(&self, field: &str, value: &str) -> Vec<Value> {
+        if field == self.spec.field() {
+            let shard = self.spec.shard_for(Some(&Value::String(value.to_string())));
+            return self.shards[shard].find(field, value);
+        }
+        self.shards.iter().flat_map(|s| s.find(field, value)).collect()
+    }
+
+    pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> Vec<Value> {
+        let is_eq = matches!(operator, "=" | "==" | "eq");
+        if is_eq && field == self.spec.field() {
+            // La igualdad también compara números como texto: mirar el shard de ambas formas
+            let mut targets = vec![self.spec.shard_for(Some(&Value::String(value.to_string())))];
+            if let Ok(n) = serde_json::from_str::<serde_json::Number>(value) {
+                targets.push(self.spec.shard_for(Some(&Value::Number(n))));
+            }
+            targets.dedup();
+            return targets.into_iter()
+                .flat_map(|shard| self.shards[shard].find_with_operator(field, value, operator))
+                .collect();
+        }
+        self.shards.iter().flat_map(|s| s.find_with_operator(field, value, operator)).collect()
+    }
+
+    /// No se permite cambiar la clave de shard: el documento tendría que cambiar de archivo
+    pub fn update_field(&self, id: &str, field: &str, value: Value) -> io::Result<bool> {
+        if field == self.spec.field() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Cannot update the shard key"));
+        }
+        for shard in &self.shards {
+            if shard.update_field(id, field, value.clone())? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn delete_by_id(&self, id: &str) -> io::Result<bool> {
+        for shard in &self.shards {
+            if shard.delete_by_id(id)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn persist(&self) -> io::Result<()> {
+        for shard in &self.shards {
+            shard.persist()?;
+        }
+        Ok(())
+    }
+}
+
+/// Orden entre claves de shard: números por valor, strings lexicográficamente,
+/// y los números antes que los strings
+pub(crate) fn compare_keys(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => compare_numbers(x, y).unwrap_or(Ordering::Equal),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Number(_), _) => Ordering::Less,
+        (_, Value::Number(_)) => Ordering::Greater,
+        _ => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+/// FNV-1a de 64 bits: estable entre ejecuciones, a diferencia de `DefaultHasher`
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
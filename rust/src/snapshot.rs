@@ -0,0 +1,193 @@
+//! Copia de la base de datos entera en un solo archivo `.ruggy`: un flujo gzip con una línea
+//! de manifiesto en JSON seguida del contenido de cada colección (y de su archivo de
+//! documentos fríos), en el orden del manifiesto.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use crate::collection::{now_millis, read_records};
+use crate::db::Database;
+use crate::options::CollectionOptions;
+use crate::replica;
+use crate::storage;
+
+const FORMAT: &str = "ruggy-snapshot";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub format: String,
+    pub version: u32,
+    /// Epoch en ms
+    pub created_at: u64,
+    pub collections: Vec<SnapshotCollection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotCollection {
+    pub name: String,
+    pub documents: usize,
+    /// Bytes del `.col` en el flujo
+    pub bytes: u64,
+    /// Bytes del `.archive.gz` en el flujo (0 si no tiene)
+    pub archive_bytes: u64,
+    /// Índices de la colección si estaba abierta al exportar
+    pub indexes: Vec<SnapshotIndex>,
+}
+
+/// Definición de un índice. Los índices viven en memoria: al importar no se crean solos,
+/// ver `SnapshotCollection::options`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotIndex {
+    pub name: String,
+    pub field: String,
+    /// `equality`, `text` o `prefix`
+    pub kind: String,
+    /// Campos incluidos de un índice de igualdad
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+}
+
+impl SnapshotCollection {
+    /// Opciones con los índices de igualdad y de prefijos del manifiesto, para abrir la
+    /// colección importada. Los de texto hay que añadirlos con su `Analyzer`.
+    pub fn options(&self) -> CollectionOptions {
+        self.indexes.iter().fold(CollectionOptions::new(), |options, index| match index.kind.as_str() {
+            "equality" => {
+                let include: Vec<&str> = index.include.iter().map(String::as_str).collect();
+                options.covering_index(&index.name, &index.field, &include)
+            }
+            "prefix" => options.prefix_index(&index.name, &index.field),
+            _ => options,
+        })
+    }
+}
+
+/// Nombre de colección que se puede usar como nombre de archivo dentro de la base de datos
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']) && name != ".."
+}
+
+impl Database {
+    /// Escribe en `path` todas las colecciones de la base de datos, abiertas o no, con sus
+    /// documentos fríos y la definición de los índices de las abiertas. De las abiertas se
+    /// guarda la versión en memoria, incluidas las escrituras aún no persistidas.
+    pub fn export_snapshot<P: AsRef<Path>>(&self, path: P) -> io::Result<SnapshotManifest> {
+        // Sin abrir ni cerrar colecciones mientras tanto
+        let cols = self.collections.read();
+        let mut names: Vec<String> = Vec::new();
+        for file_name in self.storage.list()? {
+            if let Some(name) = file_name.strip_suffix(".col") {
+                names.push(name.to_string());
+            }
+        }
+        names.extend(cols.keys().filter(|name| !names.contains(name)).cloned().collect::<Vec<_>>());
+        names.sort();
+
+        let mut collections = Vec::new();
+        let mut contents: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+        for name in names {
+            let col_name = format!("{}.col", name);
+            let (bytes, documents, indexes) = match cols.get(&name) {
+                Some(col) => {
+                    let docs = col.snapshot();
+                    let mut bytes = Vec::new();
+                    for doc in docs.iter() {
+                        writeln!(bytes, "{}", col.encode_line(doc)?)?;
+                    }
+                    let indexes = col.indexes().into_iter()
+                        .map(|info| SnapshotIndex { name: info.name, field: info.field, kind: info.kind.to_string(), include: info.include })
+                        .collect();
+                    (bytes, docs.len(), indexes)
+                }
+                None => {
+                    let bytes = storage::read_all(self.storage.as_ref(), &col_name)?.unwrap_or_default();
+                    let documents = bytes.split(|&b| b == b'\n').filter(|line| !line.trim_ascii().is_empty()).count();
+                    (bytes, documents, Vec::new())
+                }
+            };
+            let archive = storage::read_all(self.storage.as_ref(), &storage::sibling(&col_name, "archive.gz"))?.unwrap_or_default();
+            collections.push(SnapshotCollection {
+                name,
+                documents,
+                bytes: bytes.len() as u64,
+                archive_bytes: archive.len() as u64,
+                indexes,
+            });
+            contents.push((bytes, archive));
+        }
+        drop(cols);
+
+        let manifest = SnapshotManifest {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            created_at: now_millis(),
+            collections,
+        };
+        let mut encoder = GzEncoder::new(File::create(path)?, Compression::default());
+        writeln!(encoder, "{}", serde_json::to_string(&manifest)?)?;
+        for (bytes, archive) in &contents {
+            encoder.write_all(bytes)?;
+            encoder.write_all(archive)?;
+        }
+        encoder.finish()?.sync_all()?;
+        Ok(manifest)
+    }
+
+    /// Carga un archivo de `export_snapshot`. Las colecciones del archivo sustituyen a las
+    /// del mismo nombre (las abiertas se actualizan en memoria y emiten sus cambios); el resto
+    /// no se toca. Los índices no se crean: ver `SnapshotCollection::options`.
+    pub fn import_snapshot<P: AsRef<Path>>(&self, path: P) -> io::Result<SnapshotManifest> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Database is a read-only follower"));
+        }
+        let mut reader = BufReader::new(GzDecoder::new(File::open(path)?));
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let manifest: SnapshotManifest = serde_json::from_str(&header)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid snapshot manifest: {}", e)))?;
+        if manifest.format != FORMAT || manifest.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported snapshot '{}' version {}", manifest.format, manifest.version),
+            ));
+        }
+        if let Some(col) = manifest.collections.iter().find(|col| !valid_name(&col.name)) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid collection name '{}' in snapshot", col.name)));
+        }
+
+        // Leer todo antes de escribir nada: un archivo truncado no deja la importación a medias
+        let mut contents = Vec::new();
+        for col in &manifest.collections {
+            let mut bytes = vec![0; col.bytes as usize];
+            reader.read_exact(&mut bytes)?;
+            let mut archive = vec![0; col.archive_bytes as usize];
+            reader.read_exact(&mut archive)?;
+            contents.push((bytes, archive));
+        }
+
+        let cols = self.collections.read();
+        for (col, (bytes, archive)) in manifest.collections.iter().zip(contents) {
+            let col_name = format!("{}.col", col.name);
+            let archive_name = storage::sibling(&col_name, "archive.gz");
+            match cols.get(&col.name) {
+                Some(open) => {
+                    let options = open.options.read().clone();
+                    let (docs, _, _) = read_records(bytes.as_slice(), &options, 0, false)?;
+                    open.replace_documents(docs)?;
+                }
+                None => replica::replace_file(self.storage.as_ref(), &col_name, &bytes)?,
+            }
+            if archive.is_empty() {
+                self.storage.remove(&archive_name)?;
+            } else {
+                self.storage.replace(&archive_name, &archive)?;
+            }
+        }
+        Ok(manifest)
+    }
+}
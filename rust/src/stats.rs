@@ -0,0 +1,160 @@
+use std::collections::{HashMap, HashSet};
+use serde::Serialize;
+use serde_json::Value;
+use crate::memory::{string_size, value_size, MAP_ENTRY_OVERHEAD};
+
+/// Número de cubetas del histograma de campos numéricos
+pub const HISTOGRAM_BUCKETS: usize = 10;
+
+/// Estadísticas de un campo de primer nivel
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldStats {
+    /// Documentos que tienen el campo
+    pub present: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    /// Mínimo/máximo numérico si el campo tiene números, si no de los strings
+    pub min: Option<Value>,
+    pub max: Option<Value>,
+    /// Histograma de ancho fijo entre `min` y `max` (solo campos numéricos)
+    pub histogram: Vec<usize>,
+}
+
+/// Resultado de `Collection::analyze()`
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionStats {
+    pub documents: usize,
+    pub fields: HashMap<String, FieldStats>,
+}
+
+#[derive(Default)]
+struct FieldAccumulator {
+    present: usize,
+    nulls: usize,
+    distinct: HashSet<String>,
+    numbers: Vec<f64>,
+    min_str: Option<String>,
+    max_str: Option<String>,
+}
+
+impl CollectionStats {
+    /// Bytes aproximados que ocupan estas estadísticas
+    pub fn memory_usage(&self) -> usize {
+        self.fields.iter()
+            .map(|(key, field)| {
+                string_size(key) + std::mem::size_of::<FieldStats>() + MAP_ENTRY_OVERHEAD
+                    + field.histogram.capacity() * std::mem::size_of::<usize>()
+                    + field.min.as_ref().map_or(0, value_size)
+                    + field.max.as_ref().map_or(0, value_size)
+            })
+            .sum()
+    }
+
+    pub fn compute(docs: &[Value]) -> Self {
+        let mut acc: HashMap<String, FieldAccumulator> = HashMap::new();
+
+        for doc in docs {
+            let obj = match doc.as_object() {
+                Some(obj) => obj,
+                None => continue,
+            };
+            for (key, value) in obj {
+                let field = acc.entry(key.clone()).or_default();
+                field.present += 1;
+                match value {
+                    Value::Null => field.nulls += 1,
+                    Value::Number(n) => {
+                        if let Some(f) = n.as_f64() {
+                            field.numbers.push(f);
+                        }
+                    }
+                    Value::String(s) => {
+                        if field.min_str.as_ref().is_none_or(|m| s < m) {
+                            field.min_str = Some(s.clone());
+                        }
+                        if field.max_str.as_ref().is_none_or(|m| s > m) {
+                            field.max_str = Some(s.clone());
+                        }
+                    }
+                    _ => {}
+                }
+                if !value.is_null() {
+                    field.distinct.insert(value.to_string());
+                }
+            }
+        }
+
+        let fields = acc.into_iter()
+            .map(|(key, field)| (key, field.finish()))
+            .collect();
+
+        Self { documents: docs.len(), fields }
+    }
+
+    /// Estimación del número de documentos con `field == value`, para elegir el orden de
+    /// evaluación de varios predicados (el más selectivo primero)
+    pub fn estimate_eq(&self, field: &str, value: &Value) -> usize {
+        let stats = match self.fields.get(field) {
+            Some(stats) => stats,
+            None => return 0,
+        };
+        if value.is_null() {
+            return stats.nulls;
+        }
+        if let (Some(x), Some(min), Some(max)) = (value.as_f64(), stats.min.as_ref().and_then(|v| v.as_f64()), stats.max.as_ref().and_then(|v| v.as_f64())) {
+            if x < min || x > max {
+                return 0;
+            }
+            if !stats.histogram.is_empty() && max > min {
+                let bucket = bucket_of(x, min, max);
+                let in_bucket = stats.histogram[bucket];
+                let distinct_per_bucket = (stats.distinct / HISTOGRAM_BUCKETS).max(1);
+                return in_bucket.div_ceil(distinct_per_bucket);
+            }
+        }
+        let non_null = stats.present - stats.nulls;
+        non_null.div_ceil(stats.distinct.max(1))
+    }
+}
+
+impl FieldAccumulator {
+    fn finish(self) -> FieldStats {
+        let mut histogram = Vec::new();
+        let (min, max) = if self.numbers.is_empty() {
+            (self.min_str.map(Value::String), self.max_str.map(Value::String))
+        } else {
+            let min = self.numbers.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max = self.numbers.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            histogram = vec![0; HISTOGRAM_BUCKETS];
+            for &x in &self.numbers {
+                histogram[bucket_of(x, min, max)] += 1;
+            }
+            (number_value(min), number_value(max))
+        };
+
+        FieldStats {
+            present: self.present,
+            nulls: self.nulls,
+            distinct: self.distinct.len(),
+            min,
+            max,
+            histogram,
+        }
+    }
+}
+
+fn bucket_of(x: f64, min: f64, max: f64) -> usize {
+    if max <= min {
+        return 0;
+    }
+    let pos = ((x - min) / (max - min) * HISTOGRAM_BUCKETS as f64) as usize;
+    pos.min(HISTOGRAM_BUCKETS - 1)
+}
+
+fn number_value(f: f64) -> Option<Value> {
+    if f.fract() == 0.0 && f.abs() < i64::MAX as f64 {
+        Some(Value::from(f as i64))
+    } else {
+        serde_json::Number::from_f64(f).map(Value::Number)
+    }
+}
@@ -0,0 +1,432 @@
+//! Dónde se guardan los archivos de la base de datos. Todo lo que Ruggy lee o escribe dentro
+//! de una base de datos (`.col`, épocas, archivo comprimido, diario de movimientos) pasa por un
+//! `StorageBackend` con nombres de archivo relativos, así que una plataforma con un
+//! almacenamiento poco habitual (consolas, navegador, object storage) puede usar el suyo con
+//! `Database::with_storage` sin cambiar el resto.
+//!
+//! Se incluyen `LocalStorage` (archivos en un directorio, el de `Database::new`) y
+//! `MemoryStorage` (nada sale de memoria).
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use parking_lot::{Mutex, RwLock};
+
+pub trait StorageBackend: Send + Sync {
+    /// Lector del contenido a partir de `offset`; `None` si el archivo no existe
+    fn open(&self, name: &str, offset: u64) -> io::Result<Option<Box<dyn Read + Send>>>;
+
+    /// Tamaño en bytes; `None` si el archivo no existe
+    fn len(&self, name: &str) -> io::Result<Option<u64>>;
+
+    /// Añade `bytes` al final, creando el archivo si no existe
+    fn append(&self, name: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// Sustituye todo el contenido. Quien lea a la vez debe ver el contenido anterior o el
+    /// nuevo, nunca una mezcla.
+    fn replace(&self, name: &str, bytes: &[u8]) -> io::Result<()>;
+
+    /// `false` si no existía
+    fn remove(&self, name: &str) -> io::Result<bool>;
+
+    /// Nombres de todos los archivos, en cualquier orden
+    fn list(&self) -> io::Result<Vec<String>>;
+
+    /// Cambia el nombre de `from` a `to`, sustituyendo `to` si existe. Como en `replace`, quien
+    /// lea `to` a la vez ve el contenido anterior o el nuevo. Por defecto copia y borra.
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut reader = self.open(from, 0)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' does not exist", from)))?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.replace(to, &bytes)?;
+        self.remove(from)?;
+        Ok(())
+    }
+
+    /// Espera a que lo escrito en `name` sea duradero. Los backends sin caché de escritura
+    /// no tienen que hacer nada.
+    fn sync(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Contenido completo de `name`; `None` si no existe
+pub(crate) fn read_all(storage: &dyn StorageBackend, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let Some(mut reader) = storage.open(name, 0)? else { return Ok(None); };
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+/// Archivo hermano de `name` con otra extensión (`x.col` -> `x.epoch`)
+pub(crate) fn sibling(name: &str, extension: &str) -> String {
+    Path::new(name).with_extension(extension).to_string_lossy().into_owned()
+}
+
+/// Directorio y nombre de un archivo local, para las APIs que reciben una ruta
+pub(crate) fn local_file(path: &Path) -> io::Result<(LocalStorage, String)> {
+    let name = path.file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid file path '{}'", path.display())))?;
+    let root = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    // Si el directorio aún no existe el error sale al abrir el archivo
+    let root = canonical_dir(&root, false).unwrap_or(root);
+    Ok((LocalStorage::existing(root), name.to_string()))
+}
+
+/// Ruta real de un directorio: absoluta, sin `.`/`..`, separadores finales ni enlaces
+/// simbólicos, y con las mayúsculas que tiene en disco. Dos rutas al mismo directorio dan la
+/// misma, también en sistemas de archivos que no distinguen mayúsculas. Con `create` lo crea
+/// si no existe.
+pub(crate) fn canonical_dir(path: &Path, create: bool) -> io::Result<PathBuf> {
+    if path.as_os_str().is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Empty database path"));
+    }
+    if create {
+        fs::create_dir_all(path)?;
+    }
+    let real = match fs::canonicalize(path) {
+        Ok(real) => real,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("Database directory '{}' does not exist", path.display())));
+        }
+        Err(e) => return Err(e),
+    };
+    if !real.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("'{}' is not a directory", path.display())));
+    }
+    Ok(platform_path(real))
+}
+
+/// En Windows `canonicalize` devuelve rutas `\\?\C:\...` o `\\?\UNC\servidor\...`, que
+/// muchas APIs y programas no aceptan; se quita el prefijo si la ruta cabe sin él.
+#[cfg(windows)]
+fn platform_path(path: PathBuf) -> PathBuf {
+    use std::path::{Component, Prefix};
+    const MAX_PATH: usize = 260;
+    let Some(Component::Prefix(prefix)) = path.components().next() else { return path; };
+    let Some(text) = path.to_str() else { return path; };
+    let plain = match prefix.kind() {
+        Prefix::VerbatimDisk(_) => text[4..].to_string(),
+        Prefix::VerbatimUNC(..) => format!(r"\\{}", &text[8..]),
+        _ => return path,
+    };
+    if plain.len() < MAX_PATH { PathBuf::from(plain) } else { path }
+}
+
+/// `realpath` en macOS no corrige las mayúsculas de lo que no son enlaces, así que se buscan
+/// los nombres reales en cada directorio de la ruta.
+#[cfg(target_os = "macos")]
+fn platform_path(path: PathBuf) -> PathBuf {
+    use std::path::Component;
+    let mut real = PathBuf::new();
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            real.push(component);
+            continue;
+        };
+        let wanted = name.to_string_lossy().to_lowercase();
+        let on_disk = fs::read_dir(&real).ok().and_then(|entries| {
+            let names: Vec<_> = entries.flatten().map(|entry| entry.file_name()).collect();
+            if names.iter().any(|n| n == name) {
+                return None;
+            }
+            names.into_iter().find(|n| n.to_string_lossy().to_lowercase() == wanted)
+        });
+        real.push(on_disk.as_deref().unwrap_or(name));
+    }
+    real
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn platform_path(path: PathBuf) -> PathBuf {
+    path
+}
+
+/// Archivos en un directorio del sistema de archivos local
+pub struct LocalStorage {
+    root: PathBuf,
+    /// Archivos abiertos para añadir, para no abrirlos en cada insert
+    appenders: Mutex<HashMap<String, File>>,
+}
+
+impl LocalStorage {
+    /// Crea el directorio si no existe. La raíz es la ruta real del directorio (ver `root`).
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        Ok(Self::existing(canonical_dir(root.as_ref(), true)?))
+    }
+
+    /// Sin crear el directorio (p. ej. para seguidores de solo lectura)
+    pub(crate) fn existing(root: PathBuf) -> Self {
+        Self { root, appenders: Mutex::new(HashMap::new()) }
+    }
+
+    /// Ruta absoluta y sin enlaces simbólicos, no necesariamente la que se pasó a `new`
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+}
+
+impl StorageBackend for LocalStorage {
+    fn open(&self, name: &str, offset: u64) -> io::Result<Option<Box<dyn Read + Send>>> {
+        let path = self.path(name);
+        let mut file = match open_options().read(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if offset > 0 {
+            file.seek(SeekFrom::Start(offset))?;
+        }
+        #[cfg(feature = "fault-injection")]
+        return Ok(Some(Box::new(crate::faults::reader(file, &path))));
+        #[cfg(not(feature = "fault-injection"))]
+        Ok(Some(Box::new(file)))
+    }
+
+    fn len(&self, name: &str) -> io::Result<Option<u64>> {
+        match fs::metadata(self.path(name)) {
+            Ok(meta) => Ok(Some(meta.len())),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn append(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        // Crear el archivo vacío no cuenta como escritura
+        #[cfg(feature = "fault-injection")]
+        if !bytes.is_empty() {
+            crate::faults::before_write(&self.path(name), bytes.len())?;
+        }
+        let mut appenders = self.appenders.lock();
+        let file = match appenders.entry(name.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(open_options().create(true).append(true).open(self.path(name))?)
+            }
+        };
+        file.write_all(bytes)
+    }
+
+    fn replace(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let path = self.path(name);
+        #[cfg(feature = "fault-injection")]
+        crate::faults::before_write(&path, bytes.len())?;
+        // Se escribe aparte, en disco antes de renombrar, para que un corte deje el contenido
+        // anterior o el nuevo; el archivo abierto para añadir ya no es el bueno
+        let mut appenders = self.appenders.lock();
+        appenders.remove(name);
+        let tmp = self.path(&format!("{}.tmp", name));
+        let mut file = open_options().write(true).create(true).truncate(true).open(&tmp)?;
+        file.write_all(bytes)?;
+        // En Windows es `FlushFileBuffers`
+        file.sync_all()?;
+        drop(file);
+        move_file(&tmp, &path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut appenders = self.appenders.lock();
+        appenders.remove(from);
+        appenders.remove(to);
+        move_file(&self.path(from), &self.path(to))
+    }
+
+    fn remove(&self, name: &str) -> io::Result<bool> {
+        self.appenders.lock().remove(name);
+        match remove_file(&self.path(name)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                if let Some(name) = entry.file_name().to_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    fn sync(&self, name: &str) -> io::Result<()> {
+        match self.appenders.lock().get(name) {
+            Some(file) => file.sync_data(),
+            None => match File::open(self.path(name)) {
+                Ok(file) => file.sync_all(),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e),
+            },
+        }
+    }
+}
+
+/// Opciones para abrir los archivos de `LocalStorage`. En Windows se comparten para leer,
+/// escribir y borrar, para poder sustituir o borrar un archivo que otro tiene abierto (un
+/// lector de `open`, un seguidor de `replica`, el host).
+#[cfg(windows)]
+fn open_options() -> OpenOptions {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_SHARE_READ: u32 = 0x1;
+    const FILE_SHARE_WRITE: u32 = 0x2;
+    const FILE_SHARE_DELETE: u32 = 0x4;
+    let mut options = OpenOptions::new();
+    options.share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE);
+    options
+}
+
+#[cfg(not(windows))]
+fn open_options() -> OpenOptions {
+    OpenOptions::new()
+}
+
+/// Sustituye `to` por `from` de forma atómica y con el cambio ya en disco
+#[cfg(not(windows))]
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)?;
+    sync_dir(to)
+}
+
+/// En Windows no se puede abrir un directorio para sincronizarlo: `MoveFileExW` con
+/// `MOVEFILE_WRITE_THROUGH` vuelve cuando el cambio de nombre ya está en disco.
+#[cfg(windows)]
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn MoveFileExW(existing: *const u16, new: *const u16, flags: u32) -> i32;
+    }
+    const MOVEFILE_REPLACE_EXISTING: u32 = 0x1;
+    const MOVEFILE_WRITE_THROUGH: u32 = 0x8;
+    let wide = |path: &Path| path.as_os_str().encode_wide().chain(Some(0)).collect::<Vec<u16>>();
+    let (from, to) = (wide(from), wide(to));
+    retry_shared(|| {
+        // SAFETY: rutas terminadas en 0 que viven hasta después de la llamada
+        let moved = unsafe { MoveFileExW(from.as_ptr(), to.as_ptr(), MOVEFILE_REPLACE_EXISTING | MOVEFILE_WRITE_THROUGH) };
+        if moved != 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+    })
+}
+
+/// Guarda en disco la entrada de directorio de `path` tras renombrarlo
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn remove_file(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)
+}
+
+#[cfg(windows)]
+fn remove_file(path: &Path) -> io::Result<()> {
+    retry_shared(|| fs::remove_file(path))
+}
+
+/// Reintenta `op` mientras Windows diga que otro proceso tiene el archivo abierto sin
+/// compartirlo (antivirus, indexador, copias de seguridad), que suele durar muy poco
+#[cfg(windows)]
+fn retry_shared(mut op: impl FnMut() -> io::Result<()>) -> io::Result<()> {
+    const ERROR_ACCESS_DENIED: i32 = 5;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    const ATTEMPTS: u32 = 10;
+    let mut delay = std::time::Duration::from_millis(5);
+    for _ in 1..ATTEMPTS {
+        match op() {
+            Err(e) if matches!(e.raw_os_error(), Some(ERROR_ACCESS_DENIED | ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)) => {
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            other => return other,
+        }
+    }
+    op()
+}
+
+/// Archivos en memoria, p. ej. para tests o datos que no deben salir del proceso. Los clones
+/// comparten los archivos.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+    files: Arc<RwLock<BTreeMap<String, Arc<Vec<u8>>>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn open(&self, name: &str, offset: u64) -> io::Result<Option<Box<dyn Read + Send>>> {
+        // El lector se queda con la versión actual aunque después se sustituya
+        Ok(self.files.read().get(name).map(|bytes| {
+            let mut cursor = Cursor::new(ArcBytes(bytes.clone()));
+            cursor.set_position(offset);
+            Box::new(cursor) as Box<dyn Read + Send>
+        }))
+    }
+
+    fn len(&self, name: &str) -> io::Result<Option<u64>> {
+        Ok(self.files.read().get(name).map(|bytes| bytes.len() as u64))
+    }
+
+    fn append(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        let mut files = self.files.write();
+        Arc::make_mut(files.entry(name.to_string()).or_default()).extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn replace(&self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        self.files.write().insert(name.to_string(), Arc::new(bytes.to_vec()));
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> io::Result<bool> {
+        Ok(self.files.write().remove(name).is_some())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut files = self.files.write();
+        let bytes = files.remove(from)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' does not exist", from)))?;
+        files.insert(to.to_string(), bytes);
+        Ok(())
+    }
+
+    fn list(&self) -> io::Result<Vec<String>> {
+        Ok(self.files.read().keys().cloned().collect())
+    }
+}
+
+struct ArcBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for ArcBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
@@ -0,0 +1,201 @@
+//! Base de datos en memoria para los tests unitarios del host: la misma API de lectura y
+//! escritura que `Database`/`Collection`, sin tocar el sistema de archivos, con `_id`
+//! consecutivos y un reloj que solo avanza cuando lo pide el test. Dos ejecuciones del mismo
+//! test dan los mismos ids y las mismas expiraciones.
+//!
+//! Los `_id` tienen formato UUID (`00000000-0000-0000-0000-000000000001`, `...002`...) para
+//! que el código que los valida los acepte.
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use parking_lot::{Mutex, RwLock};
+use serde_json::Value;
+use uuid::Uuid;
+use crate::collection::{doc_id, Collection};
+use crate::expr::{self, Expr};
+use crate::options::Ttl;
+
+/// Reloj manual en milisegundos desde epoch. Las copias comparten la hora.
+#[derive(Clone, Debug, Default)]
+pub struct MockClock {
+    now_ms: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(now_ms: u64) -> Self {
+        Self { now_ms: Arc::new(AtomicU64::new(now_ms)) }
+    }
+
+    pub fn now_millis(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now_ms.fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+/// Generador de `_id` consecutivos, compartido por todas las colecciones de la base de datos
+#[derive(Clone, Debug, Default)]
+pub struct MockIds {
+    last: Arc<AtomicU64>,
+}
+
+impl MockIds {
+    /// El siguiente id será el `next`-ésimo
+    pub fn reset_to(&self, next: u64) {
+        self.last.store(next.saturating_sub(1), Ordering::SeqCst);
+    }
+
+    pub fn next_id(&self) -> String {
+        Uuid::from_u128(self.last.fetch_add(1, Ordering::SeqCst) as u128 + 1).to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct MockDatabase {
+    clock: MockClock,
+    ids: MockIds,
+    collections: Mutex<HashMap<String, Arc<MockCollection>>>,
+}
+
+impl MockDatabase {
+    /// Reloj en 0 y el primer id es el 1
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock(clock: MockClock) -> Self {
+        Self { clock, ..Self::default() }
+    }
+
+    pub fn clock(&self) -> &MockClock {
+        &self.clock
+    }
+
+    pub fn ids(&self) -> &MockIds {
+        &self.ids
+    }
+
+    pub fn collection(&self, name: &str) -> io::Result<Arc<MockCollection>> {
+        Ok(self.collections.lock()
+            .entry(name.to_string())
+            .or_insert_with(|| Arc::new(MockCollection {
+                name: name.to_string(),
+                data: RwLock::new(Arc::new(Vec::new())),
+                ttl: RwLock::new(None),
+                clock: self.clock.clone(),
+                ids: self.ids.clone(),
+            }))
+            .clone())
+    }
+
+    pub fn drop_collection(&self, name: &str) -> io::Result<bool> {
+        Ok(self.collections.lock().remove(name).is_some())
+    }
+
+    pub fn collection_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.collections.lock().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
+
+pub struct MockCollection {
+    name: String,
+    data: RwLock<Arc<Vec<Value>>>,
+    ttl: RwLock<Option<Ttl>>,
+    clock: MockClock,
+    ids: MockIds,
+}
+
+impl MockCollection {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Expiración por `field` (epoch en ms) medida con el reloj de la base de datos
+    pub fn set_ttl(&self, field: &str, max_age: Duration) {
+        *self.ttl.write() = Some(Ttl { field: field.to_string(), max_age });
+    }
+
+    pub fn insert(&self, mut document: Value) -> io::Result<String> {
+        let id = self.ids.next_id();
+        match document.as_object_mut() {
+            Some(obj) => obj.insert("_id".to_string(), Value::String(id.clone())),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Not an object")),
+        };
+        Arc::make_mut(&mut self.data.write()).push(document);
+        Ok(id)
+    }
+
+    pub fn snapshot(&self) -> Arc<Vec<Value>> {
+        self.data.read().clone()
+    }
+
+    pub fn find_all(&self) -> Vec<Value> {
+        self.snapshot().to_vec()
+    }
+
+    pub fn get(&self, id: &str) -> Option<Value> {
+        self.snapshot().iter().find(|doc| doc_id(doc) == id).cloned()
+    }
+
+    pub fn find(&self, field: &str, value: &str) -> Vec<Value> {
+        self.snapshot().iter()
+            .filter(|doc| match expr::field_value(doc, field) {
+                Some(Value::String(s)) => s == value,
+                Some(Value::Array(items)) => items.iter().any(|item| item.as_str() == Some(value)),
+                _ => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn find_with_operator(&self, field: &str, value: &str, operator: &str) -> Vec<Value> {
+        // Como en `Collection`, una expresión `regex` inválida no devuelve nada
+        Collection::scan_operator(&self.snapshot(), field, value, operator, None).unwrap_or_default()
+    }
+
+    pub fn find_expr(&self, filter: &Expr) -> Vec<Value> {
+        self.snapshot().iter().filter(|doc| filter.matches(doc)).cloned().collect()
+    }
+
+    pub fn update_field(&self, id: &str, field: &str, value: Value) -> io::Result<bool> {
+        let mut guard = self.data.write();
+        let Some(pos) = guard.iter().position(|doc| doc_id(doc) == id) else { return Ok(false); };
+        match Arc::make_mut(&mut guard)[pos].as_object_mut() {
+            Some(obj) => {
+                expr::set_field(obj, field, value)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub fn delete_by_id(&self, id: &str) -> io::Result<bool> {
+        let mut guard = self.data.write();
+        let Some(pos) = guard.iter().position(|doc| doc_id(doc) == id) else { return Ok(false); };
+        Arc::make_mut(&mut guard).remove(pos);
+        Ok(true)
+    }
+
+    /// Como `Collection::expire_now`, con la hora del `MockClock`
+    pub fn expire_now(&self) -> io::Result<usize> {
+        let Some(ttl) = self.ttl.read().clone() else { return Ok(0); };
+        let cutoff = self.clock.now_millis().saturating_sub(ttl.max_age.as_millis() as u64);
+        let mut guard = self.data.write();
+        let before = guard.len();
+        Arc::make_mut(&mut guard).retain(|doc| {
+            doc.get(&ttl.field).and_then(|v| v.as_u64()).is_none_or(|ts| ts >= cutoff)
+        });
+        Ok(before - guard.len())
+    }
+}
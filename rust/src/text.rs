@@ -0,0 +1,214 @@
+//! Analizadores para el índice de texto completo: dividen el texto en términos, los pasan a
+//! minúsculas, quitan palabras vacías, aplican filtros propios y reducen cada término a su raíz.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Idioma del stemmer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+}
+
+/// Término de un texto con su posición en bytes en el original
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub term: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Filtro de términos: devuelve el término transformado o `None` para descartarlo
+pub type TokenFilter = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+const ENGLISH_STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "no", "not", "of", "on", "or", "such", "that", "the", "their", "then", "there", "these",
+    "they", "this", "to", "was", "will", "with",
+];
+
+const SPANISH_STOP_WORDS: &[&str] = &[
+    "a", "al", "algo", "como", "con", "de", "del", "el", "ella", "ellos", "en", "entre", "es",
+    "esta", "este", "esto", "ha", "la", "las", "le", "lo", "los", "mas", "más", "me", "mi", "muy",
+    "no", "o", "para", "pero", "por", "que", "se", "si", "sin", "sobre", "su", "sus", "también",
+    "te", "tu", "un", "una", "uno", "y", "ya",
+];
+
+#[derive(Clone, Default)]
+pub struct Analyzer {
+    pub language: Option<Language>,
+    pub stop_words: HashSet<String>,
+    /// Se aplican en orden, después de quitar las palabras vacías y antes del stemmer
+    pub filters: Vec<TokenFilter>,
+}
+
+impl fmt::Debug for Analyzer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Analyzer")
+            .field("language", &self.language)
+            .field("stop_words", &self.stop_words.len())
+            .field("filters", &self.filters.len())
+            .finish()
+    }
+}
+
+impl Analyzer {
+    /// Solo divide y pasa a minúsculas
+    pub fn simple() -> Self {
+        Self::default()
+    }
+
+    pub fn english() -> Self {
+        Self::simple().language(Language::English).stop_words(ENGLISH_STOP_WORDS)
+    }
+
+    pub fn spanish() -> Self {
+        Self::simple().language(Language::Spanish).stop_words(SPANISH_STOP_WORDS)
+    }
+
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Añade palabras vacías (se comparan en minúsculas)
+    pub fn stop_words(mut self, words: &[&str]) -> Self {
+        self.stop_words.extend(words.iter().map(|w| w.to_lowercase()));
+        self
+    }
+
+    pub fn filter<F: Fn(&str) -> Option<String> + Send + Sync + 'static>(mut self, filter: F) -> Self {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    pub fn analyze(&self, text: &str) -> Vec<Token> {
+        let stemmer = self.language.map(|language| Stemmer::create(match language {
+            Language::English => Algorithm::English,
+            Language::Spanish => Algorithm::Spanish,
+        }));
+        let mut tokens = Vec::new();
+        for (start, word) in words(text) {
+            let mut term = word.to_lowercase();
+            if self.stop_words.contains(&term) {
+                continue;
+            }
+            let mut kept = true;
+            for filter in &self.filters {
+                match filter(&term) {
+                    Some(next) => term = next,
+                    None => {
+                        kept = false;
+                        break;
+                    }
+                }
+            }
+            if !kept || term.is_empty() {
+                continue;
+            }
+            if let Some(stemmer) = &stemmer {
+                term = stemmer.stem(&term).into_owned();
+            }
+            tokens.push(Token { term, start, end: start + word.len() });
+        }
+        tokens
+    }
+}
+
+/// Resultado de `Collection::search_highlighted`
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchHit {
+    pub document: Value,
+    pub score: u32,
+    /// Rangos `[start, end)` en bytes del campo indexado que coinciden con la búsqueda
+    pub matches: Vec<(usize, usize)>,
+    /// Fragmento alrededor de las coincidencias, marcadas con `pre`/`post`
+    pub snippet: String,
+}
+
+/// Cómo construir los fragmentos de `search_highlighted`
+#[derive(Clone, Debug)]
+pub struct Highlight {
+    pub pre: String,
+    pub post: String,
+    /// Caracteres de contexto antes de la primera coincidencia y tras la última
+    pub context: usize,
+}
+
+impl Default for Highlight {
+    fn default() -> Self {
+        Self { pre: "<em>".to_string(), post: "</em>".to_string(), context: 40 }
+    }
+}
+
+impl Highlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tags(mut self, pre: &str, post: &str) -> Self {
+        self.pre = pre.to_string();
+        self.post = post.to_string();
+        self
+    }
+
+    pub fn context(mut self, chars: usize) -> Self {
+        self.context = chars;
+        self
+    }
+
+    /// Fragmento de `text` con las coincidencias marcadas; `...` indica texto recortado
+    pub fn snippet(&self, text: &str, matches: &[(usize, usize)]) -> String {
+        let (first, last) = match (matches.first(), matches.last()) {
+            (Some(first), Some(last)) => (first.0, last.1),
+            _ => return String::new(),
+        };
+        let from = match self.context {
+            0 => first,
+            n => text[..first].char_indices().rev().nth(n - 1).map_or(0, |(i, _)| i),
+        };
+        let to = text[last..].char_indices().nth(self.context).map_or(text.len(), |(i, _)| last + i);
+
+        let mut out = String::new();
+        if from > 0 {
+            out.push_str("...");
+        }
+        let mut cursor = from;
+        for &(start, end) in matches {
+            out.push_str(&text[cursor..start]);
+            out.push_str(&self.pre);
+            out.push_str(&text[start..end]);
+            out.push_str(&self.post);
+            cursor = end;
+        }
+        out.push_str(&text[cursor..to]);
+        if to < text.len() {
+            out.push_str("...");
+        }
+        out
+    }
+}
+
+/// Secuencias de letras y dígitos con su posición
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = None;
+    let mut chars = text.char_indices().chain(std::iter::once((text.len(), ' ')));
+    std::iter::from_fn(move || {
+        for (i, c) in chars.by_ref() {
+            match (start, c.is_alphanumeric()) {
+                (None, true) => start = Some(i),
+                (Some(s), false) => {
+                    start = None;
+                    return Some((s, &text[s..i]));
+                }
+                _ => {}
+            }
+        }
+        None
+    })
+}
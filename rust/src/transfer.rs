@@ -0,0 +1,137 @@
+//! Movimiento de documentos entre colecciones. Antes de tocar ningún archivo se escribe un
+//! diario con los documentos; si el proceso cae a mitad, al abrir la base de datos se termina
+//! el movimiento a partir del diario, de modo que cada documento queda en una sola colección.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::cdc::ChangeOp;
+use crate::collection::doc_id;
+use crate::db::Database;
+use crate::dedupe;
+use crate::replica;
+use crate::storage::{self, StorageBackend};
+
+const JOURNAL: &str = "move.journal";
+
+#[derive(Serialize, Deserialize)]
+struct MoveJournal {
+    src: String,
+    dst: String,
+    /// Documentos tal como se escriben en disco (con los campos cifrados ya sellados)
+    lines: Vec<String>,
+}
+
+impl Database {
+    /// Quita de `src` los documentos que cumplen `filter` y los añade a `dst` con el mismo
+    /// `_id`. Mientras dura, `src` queda bloqueada para lectores y escritores, así que nadie ve
+    /// un documento en las dos colecciones o en ninguna. Tras una caída el movimiento se
+    /// completa al abrir la base de datos; con cifrado, ambas colecciones deben usar la misma
+    /// configuración. Devuelve cuántos documentos se movieron.
+    pub fn move_documents<F: Fn(&Value) -> bool>(&self, src: &str, dst: &str, filter: F) -> io::Result<usize> {
+        if self.read_only {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Database is a read-only follower"));
+        }
+        if src == dst {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Source and destination are the same collection"));
+        }
+        // Un movimiento cada vez: dos en sentidos opuestos se bloquearían entre sí
+        let _moving = self.moves.lock();
+        let src_col = self.collection(src)?;
+        let dst_col = self.collection(dst)?;
+
+        let mut guard = src_col.data_write()?;
+        let moved: Vec<Value> = guard.iter().filter(|doc| filter(doc)).cloned().collect();
+        if moved.is_empty() {
+            return Ok(0);
+        }
+        let journal = MoveJournal {
+            src: src.to_string(),
+            dst: dst.to_string(),
+            lines: moved.iter().map(|doc| src_col.encode_line(doc)).collect::<io::Result<_>>()?,
+        };
+        write_journal(self.storage.as_ref(), &journal)?;
+        if let Err(e) = dst_col.append_documents(&moved) {
+            // Nada ha cambiado todavía en `src`
+            let _ = self.storage.remove(JOURNAL);
+            return Err(e);
+        }
+
+        let ids: HashSet<&str> = moved.iter().map(doc_id).collect();
+        Arc::make_mut(&mut guard).retain(|doc| !ids.contains(doc_id(doc)));
+        for id in &ids {
+            src_col.emit(ChangeOp::Delete, id, None);
+        }
+        src_col.rebuild_indexes(&guard);
+        src_col.touch();
+        drop(guard);
+        // Si esto falla el diario se queda y el movimiento se completa en la próxima apertura
+        src_col.persist()?;
+        self.storage.remove(JOURNAL)?;
+        Ok(moved.len())
+    }
+}
+
+fn write_journal(storage: &dyn StorageBackend, journal: &MoveJournal) -> io::Result<()> {
+    storage.replace(JOURNAL, &serde_json::to_vec(journal)?)?;
+    storage.sync(JOURNAL)
+}
+
+fn line_id(line: &str) -> Option<String> {
+    serde_json::from_str::<Value>(line).ok()?.get("_id")?.as_str().map(str::to_string)
+}
+
+/// Completa un movimiento interrumpido, trabajando directamente sobre los archivos. Llamar
+/// antes de abrir ninguna colección.
+pub(crate) fn recover(storage: &dyn StorageBackend) -> io::Result<()> {
+    let journal: MoveJournal = match storage::read_all(storage, JOURNAL)? {
+        Some(bytes) => serde_json::from_slice(&bytes)?,
+        None => return Ok(()),
+    };
+    let ids: HashSet<String> = journal.lines.iter().filter_map(|line| line_id(line)).collect();
+
+    // Destino: añadir los que no llegaron a escribirse
+    let dst_name = format!("{}.col", journal.dst);
+    let present: HashSet<String> = match storage::read_all(storage, &dst_name)? {
+        Some(bytes) => bytes.lines().map_while(Result::ok).filter_map(|line| line_id(&line)).collect(),
+        None => HashSet::new(),
+    };
+    let mut missing = Vec::new();
+    for line in &journal.lines {
+        if line_id(line).is_none_or(|id| !present.contains(&id)) {
+            writeln!(missing, "{}", line)?;
+        }
+    }
+    storage.append(&dst_name, &missing)?;
+    storage.sync(&dst_name)?;
+
+    // Origen: reescribir sin los movidos
+    let src_name = format!("{}.col", journal.src);
+    if let Some(src) = storage::read_all(storage, &src_name)? {
+        // Las referencias de `dedupe` a un documento movido pasan a llevar su contenido
+        let moved: HashMap<String, Value> = journal.lines.iter()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .map(|doc| (doc_id(&doc).to_string(), doc))
+            .collect();
+        let mut kept = Vec::new();
+        for line in src.lines() {
+            let line = line?;
+            if line_id(&line).is_some_and(|id| ids.contains(&id)) {
+                continue;
+            }
+            let value = serde_json::from_str::<Value>(&line).ok();
+            let expanded = value.as_ref().and_then(|value| {
+                let target = moved.get(dedupe::reference_target(value)?)?;
+                Some(dedupe::resolve(value, target))
+            });
+            match expanded {
+                Some(doc) => writeln!(kept, "{}", doc)?,
+                None => writeln!(kept, "{}", line)?,
+            }
+        }
+        replica::replace_file(storage, &src_name, &kept)?;
+    }
+    storage.remove(JOURNAL).map(|_| ())
+}
@@ -0,0 +1,292 @@
+//! Tipos escalares propios dentro de los documentos JSON, como
+//! `{"$type": "decimal", "$value": "19.90"}`. Al escribir, `$value` se guarda en su forma
+//! canónica (`"19.9"`), y las comparaciones de expresiones, los órdenes y los índices usan el
+//! orden del tipo en lugar del de JSON. Comparado con un string o un número sin tipo, ese valor
+//! se interpreta como `$value` del mismo tipo: `{"$gt": ["$price", "10.5"]}` o
+//! `find("price", "19.90")` funcionan con precios `decimal`.
+//!
+//! Vienen incluidos `decimal` (números exactos en texto), `date` (instantes UTC en ISO-8601 o
+//! milisegundos) y `bytes` (base64); se pueden añadir otros con `register_type`.
+//!
+//! Los `bytes` (hashes, tokens, datos binarios pequeños) se guardan en base64 sin relleno, que
+//! ocupa un tercio más que los bytes. Admiten igualdad (con el texto en base64) y prefijos en
+//! hexadecimal con `find_with_operator(campo, "a1b2", "starts_with")`: un prefijo de bytes solo
+//! es un prefijo del base64 cada 3 bytes, así que se compara ya decodificado. En las variantes
+//! MessagePack de la FFI viajan como `bin`, sin pasar a texto.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde_json::{json, Value};
+use crate::compression::{base64_decode, base64_encode};
+use crate::datetime::{self, Civil};
+use crate::encryption::hex_decode;
+
+const TYPE_KEY: &str = "$type";
+const VALUE_KEY: &str = "$value";
+
+pub trait CustomType: Send + Sync {
+    /// Forma canónica de un `$value`: dos valores iguales para el tipo deben dar el mismo JSON.
+    /// Error si no es un valor del tipo.
+    fn canonical(&self, value: &Value) -> io::Result<Value>;
+
+    /// Orden entre dos `$value` canónicos
+    fn compare(&self, a: &Value, b: &Value) -> Ordering;
+}
+
+type TypeRef = Arc<dyn CustomType>;
+
+static TYPES: RwLock<Option<HashMap<String, TypeRef>>> = parking_lot::const_rwlock(None);
+
+/// Registra (o sustituye) el tipo `name` para todo el proceso. Los documentos ya guardados con
+/// ese tipo no se vuelven a escribir.
+pub fn register_type<T: CustomType + 'static>(name: &str, custom: T) -> io::Result<()> {
+    if name.is_empty() || name.contains(':') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid type name '{}'", name)));
+    }
+    TYPES.write().get_or_insert_with(HashMap::new).insert(name.to_string(), Arc::new(custom));
+    Ok(())
+}
+
+/// Tipo `name`: primero los registrados y después los incluidos
+fn custom_type(name: &str) -> Option<TypeRef> {
+    if let Some(custom) = TYPES.read().as_ref().and_then(|all| all.get(name)) {
+        return Some(custom.clone());
+    }
+    match name {
+        "decimal" => Some(Arc::new(Decimal)),
+        "date" => Some(Arc::new(Date)),
+        "bytes" => Some(Arc::new(Bytes)),
+        _ => None,
+    }
+}
+
+/// Nombres de todos los tipos, para buscar por texto en los índices
+fn type_names() -> Vec<String> {
+    let mut names: Vec<String> = ["decimal", "date", "bytes"].iter().map(|name| name.to_string()).collect();
+    if let Some(all) = TYPES.read().as_ref() {
+        names.extend(all.keys().filter(|name| !names.contains(name)).cloned().collect::<Vec<_>>());
+    }
+    names
+}
+
+fn unknown(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown type '{}'", name))
+}
+
+/// Valor con tipo en forma canónica, p. ej. `typed("decimal", json!("0.10"))`
+pub fn typed(name: &str, value: Value) -> io::Result<Value> {
+    let custom = custom_type(name).ok_or_else(|| unknown(name))?;
+    Ok(json!({ TYPE_KEY: name, VALUE_KEY: custom.canonical(&value)? }))
+}
+
+/// Valor `bytes` con `data`, p. ej. para guardar hashes o tokens
+pub fn bytes(data: &[u8]) -> Value {
+    json!({ TYPE_KEY: "bytes", VALUE_KEY: unpadded(data) })
+}
+
+/// Contenido de un valor `bytes`
+pub fn as_bytes(value: &Value) -> Option<Vec<u8>> {
+    match as_typed(value)? {
+        ("bytes", Value::String(text)) => base64_decode(text),
+        _ => None,
+    }
+}
+
+/// Si `value` es un valor `bytes` que empieza por `prefix`, en hexadecimal
+pub(crate) fn bytes_prefix(value: &Value, prefix: &str) -> bool {
+    match (as_bytes(value), hex_decode(prefix)) {
+        (Some(bytes), Ok(prefix)) => bytes.starts_with(&prefix),
+        _ => false,
+    }
+}
+
+fn unpadded(data: &[u8]) -> String {
+    let mut text = base64_encode(data);
+    text.truncate(text.trim_end_matches('=').len());
+    text
+}
+
+/// Tipo y `$value` de un valor con tipo
+pub fn as_typed(value: &Value) -> Option<(&str, &Value)> {
+    let obj = value.as_object()?;
+    if obj.len() != 2 {
+        return None;
+    }
+    Some((obj.get(TYPE_KEY)?.as_str()?, obj.get(VALUE_KEY)?))
+}
+
+/// Pasa a forma canónica todos los valores con tipo de `value`, a cualquier profundidad.
+/// Error si alguno tiene un tipo desconocido o un `$value` inválido.
+pub fn canonicalize(value: &mut Value) -> io::Result<()> {
+    if let Some((name, inner)) = as_typed(value) {
+        let canonical = typed(name, inner.clone())?;
+        *value = canonical;
+        return Ok(());
+    }
+    match value {
+        Value::Object(obj) => obj.values_mut().try_for_each(canonicalize),
+        Value::Array(items) => items.iter_mut().try_for_each(canonicalize),
+        _ => Ok(()),
+    }
+}
+
+/// Tipo y `$value` canónico; un tipo desconocido o un valor inválido no se reconocen
+fn resolve(value: &Value) -> Option<(&str, TypeRef, Value)> {
+    let (name, inner) = as_typed(value)?;
+    let custom = custom_type(name)?;
+    let canonical = custom.canonical(inner).ok()?;
+    Some((name, custom, canonical))
+}
+
+pub(crate) fn is_typed(value: &Value) -> bool {
+    as_typed(value).is_some()
+}
+
+/// Orden si alguno de los dos tiene tipo. Un valor sin tipo se interpreta con el tipo del
+/// otro; tipos distintos no son comparables.
+pub(crate) fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (resolve(a), resolve(b)) {
+        (Some((x, custom, va)), Some((y, _, vb))) => (x == y).then(|| custom.compare(&va, &vb)),
+        (Some((_, custom, va)), None) if !b.is_object() => Some(custom.compare(&va, &custom.canonical(b).ok()?)),
+        (None, Some((_, custom, vb))) if !a.is_object() => Some(custom.compare(&custom.canonical(a).ok()?, &vb)),
+        _ => None,
+    }
+}
+
+/// Si `value` tiene tipo y es igual a `text` interpretado con ese tipo
+pub(crate) fn equals_text(value: &Value, text: &str) -> bool {
+    compare(value, &Value::String(text.to_string())) == Some(Ordering::Equal)
+}
+
+/// Clave de índice de un valor con tipo
+pub(crate) fn index_key(value: &Value) -> Option<String> {
+    let (name, _, canonical) = resolve(value)?;
+    Some(key(name, &canonical))
+}
+
+/// Claves de índice de `text` interpretado con cada tipo en el que es válido
+pub(crate) fn text_keys(text: &str) -> Vec<String> {
+    let text = Value::String(text.to_string());
+    type_names().into_iter()
+        .filter_map(|name| {
+            let canonical = custom_type(&name)?.canonical(&text).ok()?;
+            Some(key(&name, &canonical))
+        })
+        .collect()
+}
+
+fn key(name: &str, canonical: &Value) -> String {
+    format!("t:{}:{}", name, canonical)
+}
+
+fn invalid(name: &str, value: &Value) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid {} value {}", name, value))
+}
+
+/// Números decimales exactos, en texto (`"-12.50"`) o como número JSON. Forma canónica: texto
+/// sin ceros sobrantes ni `+`, `"0"` para el cero.
+pub struct Decimal;
+
+/// Signo, cifras enteras sin ceros a la izquierda y decimales sin ceros a la derecha
+struct Parts<'a> {
+    negative: bool,
+    int: &'a str,
+    frac: &'a str,
+}
+
+impl<'a> Parts<'a> {
+    fn parse(text: &'a str) -> Option<Self> {
+        let (negative, digits) = match text.as_bytes().first()? {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        if int.is_empty() && frac.is_empty() || !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let int = int.trim_start_matches('0');
+        let frac = frac.trim_end_matches('0');
+        Some(Self { negative: negative && !(int.is_empty() && frac.is_empty()), int, frac })
+    }
+
+    fn magnitude(&self, other: &Self) -> Ordering {
+        self.int.len().cmp(&other.int.len())
+            .then_with(|| self.int.cmp(other.int))
+            .then_with(|| self.frac.cmp(other.frac))
+    }
+}
+
+/// Orden entre dos decimales en texto sin exponente; `None` si alguno no lo es
+pub(crate) fn compare_decimal_text(a: &str, b: &str) -> Option<Ordering> {
+    let (x, y) = (Parts::parse(a)?, Parts::parse(b)?);
+    Some(match (x.negative, y.negative) {
+        (false, false) => x.magnitude(&y),
+        (true, true) => y.magnitude(&x),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    })
+}
+
+impl CustomType for Decimal {
+    fn canonical(&self, value: &Value) -> io::Result<Value> {
+        let text = match value {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            _ => return Err(invalid("decimal", value)),
+        };
+        let parts = Parts::parse(&text).ok_or_else(|| invalid("decimal", value))?;
+        let int = if parts.int.is_empty() { "0" } else { parts.int };
+        let mut out = format!("{}{}", if parts.negative { "-" } else { "" }, int);
+        if !parts.frac.is_empty() {
+            out.push('.');
+            out.push_str(parts.frac);
+        }
+        Ok(Value::String(out))
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        match (a.as_str(), b.as_str()) {
+            (Some(x), Some(y)) => compare_decimal_text(x, y).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// Instantes en UTC, como ISO-8601 (ver `datetime::parse_iso`) o milisegundos desde epoch.
+/// Forma canónica: `YYYY-MM-DDTHH:MM:SS.mmmZ`.
+pub struct Date;
+
+impl CustomType for Date {
+    fn canonical(&self, value: &Value) -> io::Result<Value> {
+        let ms = datetime::value_to_millis(value).ok_or_else(|| invalid("date", value))?;
+        let c = Civil::from_millis(ms);
+        Ok(Value::String(format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+            c.year, c.month, c.day, c.hour, c.minute, c.second, c.millis
+        )))
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        datetime::value_to_millis(a).cmp(&datetime::value_to_millis(b))
+    }
+}
+
+/// Bytes en base64. Forma canónica: sin relleno (`=`). El orden es el de los bytes.
+pub struct Bytes;
+
+impl CustomType for Bytes {
+    fn canonical(&self, value: &Value) -> io::Result<Value> {
+        let text = value.as_str().ok_or_else(|| invalid("bytes", value))?;
+        let bytes = base64_decode(text).ok_or_else(|| invalid("bytes", value))?;
+        Ok(Value::String(unpadded(&bytes)))
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        let decode = |value: &Value| value.as_str().and_then(base64_decode);
+        decode(a).cmp(&decode(b))
+    }
+}
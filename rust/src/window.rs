@@ -0,0 +1,248 @@
+//! Funciones de ventana: totales acumulados, ranking y lag/lead sobre un orden dentro de
+//! cada grupo, para informes sobre registros ordenados en el tiempo.
+//!
+//! ```json
+//! {"partitionBy": ["region"],
+//!  "sortBy": ["date", "-amount"],
+//!  "output": {"acumulado": {"$sum": "$amount"},
+//!             "puesto": {"$rank": {}},
+//!             "anterior": {"$lag": ["$amount", 1, 0]}}}
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io;
+use serde_json::Value;
+use crate::collection::{doc_id, Collection};
+use crate::comparator::SortKey;
+use crate::expr::{compare_values, get_path, number, Expr};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WindowFn {
+    /// Suma acumulada desde el inicio del grupo; los valores no numéricos no cuentan
+    Sum(Expr),
+    /// Media acumulada de los valores numéricos vistos hasta la fila
+    Avg(Expr),
+    /// Filas del grupo hasta la actual
+    Count,
+    /// 1, 2, 3... sin tener en cuenta los empates
+    RowNumber,
+    /// Los empates comparten puesto y dejan huecos (1, 1, 3)
+    Rank,
+    /// Los empates comparten puesto sin huecos (1, 1, 2)
+    DenseRank,
+    /// Valor de la fila `offset` posiciones antes; `default` si no existe
+    Lag { expr: Expr, offset: usize, default: Value },
+    /// Valor de la fila `offset` posiciones después; `default` si no existe
+    Lead { expr: Expr, offset: usize, default: Value },
+}
+
+/// Definición de la ventana: grupos, orden dentro de cada grupo y campos calculados
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Window {
+    partition_by: Vec<String>,
+    sort_by: Vec<SortKey>,
+    outputs: Vec<(String, WindowFn)>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+impl Window {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn partition_by(mut self, field: &str) -> Self {
+        self.partition_by.push(field.to_string());
+        self
+    }
+
+    pub fn sort_by(self, field: &str, descending: bool) -> Self {
+        self.sort_by_key(SortKey::new(field, descending))
+    }
+
+    /// Como `sort_by`, con un comparador (ver `comparator`)
+    pub fn sort_by_key(mut self, key: SortKey) -> Self {
+        self.sort_by.push(key);
+        self
+    }
+
+    pub fn output(mut self, name: &str, function: WindowFn) -> Self {
+        self.outputs.push((name.to_string(), function));
+        self
+    }
+
+    /// Lee la especificación JSON del ejemplo del módulo. En `sortBy` un `-` delante del
+    /// campo ordena de mayor a menor y `:nombre` detrás usa ese comparador.
+    pub fn parse(spec: &Value) -> io::Result<Self> {
+        let obj = spec.as_object().ok_or_else(|| invalid("Window must be an object".to_string()))?;
+        let mut window = Self::new();
+
+        for field in string_list(obj.get("partitionBy"), "partitionBy")? {
+            window = window.partition_by(&field);
+        }
+        for field in string_list(obj.get("sortBy"), "sortBy")? {
+            window = window.sort_by_key(SortKey::parse(&field)?);
+        }
+        let outputs = obj.get("output").and_then(|v| v.as_object())
+            .ok_or_else(|| invalid("Window needs an 'output' object".to_string()))?;
+        for (name, spec) in outputs {
+            window = window.output(name, parse_fn(spec)?);
+        }
+        Ok(window)
+    }
+
+    /// Añade los campos calculados a `docs`. Devuelve los documentos agrupados (en el orden
+    /// en que aparece cada grupo) y ordenados dentro de cada grupo, con los empates por `_id`.
+    /// `$rank` y `$denseRank` siguen considerando empatados a los que solo difieren en `_id`.
+    pub fn apply(&self, docs: Vec<Value>) -> Vec<Value> {
+        let mut groups: Vec<Vec<Value>> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for doc in docs {
+            let key: Vec<String> = self.partition_by.iter()
+                .map(|field| get_path(&doc, field).map(|v| v.to_string()).unwrap_or_default())
+                .collect();
+            let pos = *positions.entry(key.join("\u{1f}")).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[pos].push(doc);
+        }
+
+        let mut out = Vec::new();
+        for mut group in groups {
+            // Empates por `_id` para que el orden no dependa del orden de los documentos
+            group.sort_by(|a, b| self.compare(a, b).then_with(|| doc_id(a).cmp(doc_id(b))));
+            self.fill(&mut group);
+            out.extend(group);
+        }
+        out
+    }
+
+    fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        for key in &self.sort_by {
+            let ordering = key.compare(a, b);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn fill(&self, group: &mut [Value]) {
+        for (name, function) in &self.outputs {
+            let values: Vec<Value> = match function {
+                WindowFn::Sum(expr) | WindowFn::Avg(expr) => {
+                    let (mut sum, mut count) = (0.0, 0);
+                    group.iter().map(|doc| {
+                        if let Some(n) = expr.eval(doc).as_f64() {
+                            sum += n;
+                            count += 1;
+                        }
+                        match function {
+                            WindowFn::Avg(_) if count == 0 => Value::Null,
+                            WindowFn::Avg(_) => number(sum / count as f64),
+                            _ => number(sum),
+                        }
+                    }).collect()
+                }
+                WindowFn::Count | WindowFn::RowNumber => (1..=group.len()).map(Value::from).collect(),
+                WindowFn::Rank | WindowFn::DenseRank => {
+                    let (mut rank, mut dense) = (0, 0);
+                    (0..group.len()).map(|i| {
+                        if i == 0 || self.compare(&group[i - 1], &group[i]) != Ordering::Equal {
+                            rank = i + 1;
+                            dense += 1;
+                        }
+                        Value::from(if matches!(function, WindowFn::Rank) { rank } else { dense })
+                    }).collect()
+                }
+                WindowFn::Lag { expr, offset, default } => (0..group.len())
+                    .map(|i| i.checked_sub(*offset).map_or_else(|| default.clone(), |j| expr.eval(&group[j])))
+                    .collect(),
+                WindowFn::Lead { expr, offset, default } => (0..group.len())
+                    .map(|i| group.get(i + offset).map_or_else(|| default.clone(), |doc| expr.eval(doc)))
+                    .collect(),
+            };
+            for (doc, value) in group.iter_mut().zip(values) {
+                if let Some(obj) = doc.as_object_mut() {
+                    obj.insert(name.clone(), value);
+                }
+            }
+        }
+    }
+}
+
+/// Ausentes y nulos primero; después el orden de `compare_values` y, entre tipos distintos,
+/// booleanos < números < strings < el resto
+pub(crate) fn sort_order(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    fn rank(value: Option<&Value>) -> u8 {
+        match value {
+            None | Some(Value::Null) => 0,
+            Some(Value::Bool(_)) => 1,
+            Some(Value::Number(_)) => 2,
+            Some(Value::String(_)) => 3,
+            _ => 4,
+        }
+    }
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Some(a), Some(b)) => compare_values(a, b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    })
+}
+
+fn string_list(value: Option<&Value>, name: &str) -> io::Result<Vec<String>> {
+    match value {
+        None => Ok(Vec::new()),
+        Some(Value::String(s)) => Ok(vec![s.clone()]),
+        Some(Value::Array(items)) => items.iter()
+            .map(|item| item.as_str().map(str::to_string)
+                .ok_or_else(|| invalid(format!("'{}' must contain field names", name))))
+            .collect(),
+        Some(_) => Err(invalid(format!("'{}' must be a field name or an array of them", name))),
+    }
+}
+
+fn parse_fn(spec: &Value) -> io::Result<WindowFn> {
+    let (op, arg) = spec.as_object()
+        .filter(|obj| obj.len() == 1)
+        .and_then(|obj| obj.iter().next())
+        .ok_or_else(|| invalid(format!("Invalid window function: {}", spec)))?;
+    match op.as_str() {
+        "$sum" => Ok(WindowFn::Sum(Expr::parse(arg)?)),
+        "$avg" => Ok(WindowFn::Avg(Expr::parse(arg)?)),
+        "$count" => Ok(WindowFn::Count),
+        "$rowNumber" => Ok(WindowFn::RowNumber),
+        "$rank" => Ok(WindowFn::Rank),
+        "$denseRank" => Ok(WindowFn::DenseRank),
+        "$lag" | "$lead" => {
+            // Expresión o [expresión, desplazamiento, valor por defecto]
+            let (expr, offset, default) = match arg {
+                Value::Array(items) if !items.is_empty() && items.len() <= 3 => {
+                    let offset = match items.get(1) {
+                        Some(v) => v.as_u64().ok_or_else(|| invalid(format!("{} offset must be a non-negative integer", op)))? as usize,
+                        None => 1,
+                    };
+                    (Expr::parse(&items[0])?, offset, items.get(2).cloned().unwrap_or(Value::Null))
+                }
+                other => (Expr::parse(other)?, 1, Value::Null),
+            };
+            Ok(if op == "$lag" {
+                WindowFn::Lag { expr, offset, default }
+            } else {
+                WindowFn::Lead { expr, offset, default }
+            })
+        }
+        _ => Err(invalid(format!("Unknown window function '{}'", op))),
+    }
+}
+
+impl Collection {
+    /// Documentos que cumplen `filter` con los campos calculados por la ventana
+    pub fn find_window<F: Fn(&Value) -> bool>(&self, filter: F, window: &Window) -> Vec<Value> {
+        let docs = self.snapshot().iter().filter(|doc| filter(doc)).cloned().collect();
+        window.apply(docs)
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use ruggy_db::expr::Expr;
+use ruggy_db::rules::Rule;
+use ruggy_db::{CollectionOptions, Database, ImportResult};
+use serde_json::json;
+
+fn options() -> CollectionOptions {
+    let adult = Expr::parse(&json!({"$gte": ["$age", 18]})).unwrap();
+    CollectionOptions::default().rule(Rule::new("adult", adult))
+}
+
+#[test]
+fn upsert_many_writes_nothing_when_a_document_fails() {
+    let dir = common::temp_dir("bulk-upsert");
+    {
+        let db = Database::new(&dir).unwrap();
+        let users = db.collection_with_options("users", options()).unwrap();
+        users.insert(json!({"email": "ana@x.com", "age": 30})).unwrap();
+
+        // Falla la regla al fusionar con el existente
+        let batch = vec![json!({"email": "luis@x.com", "age": 40}), json!({"email": "ana@x.com", "age": 12})];
+        let err = users.upsert_many("email", batch).unwrap_err();
+        assert!(err.to_string().starts_with("Document 1:"), "{err}");
+        // Falla un tipo propio
+        let batch = vec![json!({"email": "eva@x.com", "age": 20, "born": {"$type": "date", "$value": "ayer"}})];
+        assert!(users.upsert_many("email", batch).is_err());
+        assert_eq!(users.count(), 1);
+
+        let report = users.upsert_many("email", vec![json!({"email": "ana@x.com", "age": 31}), json!({"email": "luis@x.com", "age": 40})]).unwrap();
+        assert_eq!((report.inserted, report.updated), (1, 1));
+    }
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection_with_options("users", options()).unwrap();
+    assert_eq!(users.count(), 2);
+    assert_eq!(users.find("email", "ana@x.com")[0]["age"], json!(31));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn import_keeps_the_valid_documents() {
+    let dir = common::temp_dir("bulk-import");
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection_with_options("users", options()).unwrap();
+    let results = users.import(vec![json!({"age": 12}), json!({"age": 50}), json!("x")], None).unwrap();
+    assert!(matches!(results[0], ImportResult::Failed { .. }));
+    assert!(matches!(results[1], ImportResult::Inserted { .. }));
+    assert!(matches!(results[2], ImportResult::Failed { .. }));
+    assert_eq!(users.count(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use ruggy_db::{as_bytes, bytes, Database, SortKey};
+use serde_json::json;
+
+#[test]
+fn bytes_are_stored_as_unpadded_base64() {
+    let dir = common::temp_dir("bytes");
+    let db = Database::new(&dir).unwrap();
+    let blobs = db.collection("blobs").unwrap();
+    for data in [&[0xa1, 0xb2, 0xc3, 0xd4][..], &[0xa1, 0xb2], &[0x00, 0xff], &[0xff]] {
+        blobs.insert(json!({"hash": bytes(data)})).unwrap();
+    }
+    let file = std::fs::read_to_string(dir.join("blobs.col")).unwrap();
+    assert!(file.contains(r#""$value":"obLD1A""#), "{file}");
+    assert!(!file.contains('='));
+
+    // Igualdad con el texto en base64 (con o sin relleno), prefijos en hexadecimal
+    assert_eq!(blobs.find("hash", "obI=").len(), 1);
+    assert_eq!(blobs.find("hash", "obI").len(), 1);
+    assert_eq!(blobs.find_with_operator("hash", "a1", "starts_with").len(), 2);
+    assert_eq!(blobs.find_with_operator("hash", "a1b2c3", "starts_with").len(), 1);
+
+    // Orden de los bytes, no del texto en base64 (`/w` va antes que `AP8`)
+    let sorted = blobs.query_sorted(json!({}), &[SortKey::new("hash", false)]).unwrap();
+    let data: Vec<Vec<u8>> = sorted.iter().map(|doc| as_bytes(&doc["hash"]).unwrap()).collect();
+    assert_eq!(data, [vec![0x00, 0xff], vec![0xa1, 0xb2], vec![0xa1, 0xb2, 0xc3, 0xd4], vec![0xff]]);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,9 @@
+use std::path::PathBuf;
+
+/// Directorio vacío y propio de cada test, para que se puedan ejecutar en paralelo
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ruggy-test-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,49 @@
+mod common;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use ruggy_db::Database;
+use serde_json::json;
+
+#[test]
+fn online_compaction_keeps_concurrent_inserts() {
+    let dir = common::temp_dir("compaction-online");
+    {
+        let db = Database::new(&dir).unwrap();
+        let users = db.collection("users").unwrap();
+        for n in 0..200 {
+            users.insert(json!({"n": n, "pad": "x".repeat(64)})).unwrap();
+        }
+
+        // Se compacta una y otra vez mientras otro hilo inserta
+        let done = Arc::new(AtomicBool::new(false));
+        let compactor = {
+            let (users, done) = (users.clone(), done.clone());
+            thread::spawn(move || {
+                let mut compacted = 0;
+                while !done.load(Ordering::Relaxed) {
+                    compacted += users.compact().unwrap() as usize;
+                }
+                compacted
+            })
+        };
+        for n in 200..700 {
+            users.insert(json!({"n": n, "pad": "x".repeat(64)})).unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+        assert!(compactor.join().unwrap() > 0);
+        assert_eq!(users.count(), 700);
+    }
+
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection("users").unwrap();
+    let report = users.load_report();
+    assert_eq!(report.skipped, 0);
+    assert!(report.corrupt.is_empty());
+    assert_eq!(users.count(), 700);
+    let mut ns: Vec<i64> = users.find_all().iter().map(|doc| doc["n"].as_i64().unwrap()).collect();
+    ns.sort_unstable();
+    assert_eq!(ns, (0..700).collect::<Vec<_>>());
+    let _ = std::fs::remove_dir_all(&dir);
+}
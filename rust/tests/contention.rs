@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+use ruggy_db::contention::is_busy;
+use ruggy_db::{CollectionOptions, ContentionPolicy, Database};
+use serde_json::json;
+
+fn options() -> CollectionOptions {
+    CollectionOptions::default().contention(ContentionPolicy::new().lock_timeout(Duration::from_millis(10)))
+}
+
+#[test]
+fn raw_lines_waits_like_a_writer() {
+    let dir = common::temp_dir("contention-raw");
+    let db = Database::new(&dir).unwrap();
+    let logs = db.collection_with_options("logs", options()).unwrap();
+    logs.insert(json!({"n": 1})).unwrap();
+
+    let lines = logs.raw_lines().unwrap();
+    assert!(logs.raw_lines().is_err_and(|e| is_busy(&e)));
+    assert!(logs.insert(json!({"n": 2})).is_err_and(|e| is_busy(&e)));
+    assert_eq!(lines.count(), 1);
+    assert_eq!(logs.raw_lines().unwrap().count(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn scan_fields_waits_like_a_writer() {
+    let dir = common::temp_dir("contention-scan");
+    let db = Database::new(&dir).unwrap();
+    let logs = db.collection_with_options("logs", options()).unwrap();
+    logs.insert(json!({"n": 1, "text": "a"})).unwrap();
+
+    let lines = logs.raw_lines().unwrap();
+    assert!(logs.scan_fields(&["n"]).is_err_and(|e| is_busy(&e)));
+    drop(lines);
+    assert_eq!(logs.scan_fields(&["n"]).unwrap()[0]["n"], json!(1));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use ruggy_db::{CsvOptions, Database, ExportOptions, ImportResult};
+use serde_json::json;
+
+#[test]
+fn projection_uses_dotted_paths() {
+    let doc = json!({"_id": "1", "name": "ana", "address": {"city": "Lima", "zip": "15001"}, "a.b": 1});
+    let include = ExportOptions::new().include(&["address.city", "a.b"]);
+    assert_eq!(include.project(&doc), json!({"_id": "1", "address": {"city": "Lima"}, "a.b": 1}));
+    let exclude = ExportOptions::new().exclude(&["address.zip", "a.b"]);
+    assert_eq!(exclude.project(&doc), json!({"_id": "1", "name": "ana", "address": {"city": "Lima"}}));
+}
+
+#[test]
+fn csv_nested_columns() {
+    let dir = common::temp_dir("export-csv");
+    let db = Database::new(&dir).unwrap();
+    let people = db.collection("people").unwrap();
+    let csv = "name,address.city,address.zip\nana,Lima,15001\n";
+    let results = people.import_csv(csv.as_bytes(), &CsvOptions::new().nested(true).numbers(false), None).unwrap();
+    assert!(matches!(results[0], ImportResult::Inserted { .. }));
+    assert_eq!(people.find("name", "ana")[0]["address"], json!({"city": "Lima", "zip": "15001"}));
+
+    let conflict = "address,address.city\nx,Lima\n";
+    let results = people.import_csv(conflict.as_bytes(), &CsvOptions::new().nested(true), None).unwrap();
+    assert!(matches!(results[0], ImportResult::Failed { .. }));
+    let _ = std::fs::remove_dir_all(&dir);
+}
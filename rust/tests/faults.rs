@@ -0,0 +1,60 @@
+//! Todo en un test: los fallos inyectados son globales al proceso
+#![cfg(feature = "fault-injection")]
+
+mod common;
+
+use std::ffi::CString;
+use ruggy_db::{faults, ffi, Database};
+use serde_json::json;
+
+#[test]
+fn failed_writes_leave_the_file_readable() {
+    let dir = common::temp_dir("faults");
+    let ids: Vec<String> = {
+        let db = Database::new(&dir).unwrap();
+        let users = db.collection("users").unwrap();
+        let ids: Vec<String> = (0..3).map(|n| users.insert(json!({"n": n})).unwrap()).collect();
+        faults::only_under(&dir);
+
+        // Reescritura completa del archivo
+        faults::fail_nth_write(1);
+        assert!(users.delete_by_id(&ids[0]).is_err());
+        // Línea añadida al final
+        faults::disk_full_after(8);
+        assert!(users.insert(json!({"n": 3})).is_err());
+        assert_eq!(faults::stats().failed_writes, 2);
+        faults::reset();
+        ids
+    };
+
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection("users").unwrap();
+    let report = users.load_report();
+    assert_eq!(report.skipped, 0);
+    assert!(report.corrupt.is_empty());
+    for id in &ids {
+        assert!(users.find_by_id(id).is_some());
+    }
+    assert_eq!(users.count(), 3);
+    drop(db);
+
+    // Lo mismo desde la FFI
+    let path = CString::new(dir.to_str().unwrap()).unwrap();
+    let name = CString::new("users").unwrap();
+    let doc = CString::new(r#"{"n": 4}"#).unwrap();
+    ffi::ruggy_fault_only_under(path.as_ptr());
+    ffi::ruggy_fault_fail_nth_write(1);
+    let db = ffi::ruggy_open(path.as_ptr());
+    let col = ffi::ruggy_get_collection(db, name.as_ptr());
+    assert!(ffi::ruggy_insert(col, doc.as_ptr()).is_null());
+    ffi::ruggy_fault_reset();
+    let id = ffi::ruggy_insert(col, doc.as_ptr());
+    assert!(!id.is_null());
+    ffi::ruggy_str_free(id);
+    ffi::ruggy_col_free(col);
+    ffi::ruggy_db_free(db);
+
+    let db = Database::new(&dir).unwrap();
+    assert_eq!(db.collection("users").unwrap().count(), 4);
+    let _ = std::fs::remove_dir_all(&dir);
+}
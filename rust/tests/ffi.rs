@@ -0,0 +1,91 @@
+mod common;
+
+use std::ffi::{CStr, CString};
+use ruggy_db::ffi;
+
+#[test]
+fn opening_a_directory_twice_shares_the_database() {
+    let dir = common::temp_dir("ffi-shared");
+    let path = CString::new(dir.to_str().unwrap()).unwrap();
+    let name = CString::new("users").unwrap();
+    let doc = CString::new(r#"{"n": 1}"#).unwrap();
+
+    let (a, b) = (ffi::ruggy_open(path.as_ptr()), ffi::ruggy_open(path.as_ptr()));
+    let (col_a, col_b) = (ffi::ruggy_get_collection(a, name.as_ptr()), ffi::ruggy_get_collection(b, name.as_ptr()));
+    let id = ffi::ruggy_insert(col_a, doc.as_ptr());
+    assert!(!id.is_null());
+    assert_eq!(ffi::ruggy_count(col_b), 1);
+
+    // Cerrar uno no afecta al otro
+    ffi::ruggy_str_free(id);
+    ffi::ruggy_col_free(col_a);
+    ffi::ruggy_db_free(a);
+    let col = ffi::ruggy_get_collection(b, name.as_ptr());
+    assert_eq!(ffi::ruggy_count(col), 1);
+    ffi::ruggy_col_free(col);
+    ffi::ruggy_col_free(col_b);
+    ffi::ruggy_db_free(b);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn query_into_reports_the_size_it_needs() {
+    let dir = common::temp_dir("ffi-query-into");
+    let path = CString::new(dir.to_str().unwrap()).unwrap();
+    let name = CString::new("users").unwrap();
+    let db = ffi::ruggy_open(path.as_ptr());
+    let col = ffi::ruggy_get_collection(db, name.as_ptr());
+    for doc in [r#"{"team": "a", "n": 1}"#, r#"{"team": "b", "n": 2}"#, r#"{"team": "a", "n": 3}"#] {
+        let doc = CString::new(doc).unwrap();
+        ffi::ruggy_str_free(ffi::ruggy_insert(col, doc.as_ptr()));
+    }
+    let filter = CString::new(r#"{"team": "a"}"#).unwrap();
+
+    let (mut small, mut needed) = ([0u8; 8], 0);
+    assert_eq!(ffi::ruggy_query_into(col, filter.as_ptr(), small.as_mut_ptr(), small.len(), &mut needed), -1);
+    let mut buf = vec![0u8; needed];
+    let mut written = 0;
+    assert_eq!(ffi::ruggy_query_into(col, filter.as_ptr(), buf.as_mut_ptr(), buf.len(), &mut written), 1);
+    assert_eq!(written, needed);
+    let docs: Vec<serde_json::Value> = serde_json::from_slice(&buf).unwrap();
+    assert_eq!(docs.iter().map(|doc| doc["n"].clone()).collect::<Vec<_>>(), [1, 3]);
+
+    ffi::ruggy_col_free(col);
+    ffi::ruggy_db_free(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rule_violations_go_to_the_last_error() {
+    let dir = common::temp_dir("ffi-last-error");
+    let path = CString::new(dir.to_str().unwrap()).unwrap();
+    let name = CString::new("items").unwrap();
+    let db = ffi::ruggy_open(path.as_ptr());
+    let col = ffi::ruggy_get_collection(db, name.as_ptr());
+    let rules = CString::new(r#"{"price_positive": {"$gt": ["$price", 0]}}"#).unwrap();
+    assert_eq!(ffi::ruggy_set_rules(col, rules.as_ptr()), 1);
+    assert!(ffi::ruggy_last_error().is_null());
+    let doc = CString::new(r#"{"price": 5}"#).unwrap();
+    let id = ffi::ruggy_insert(col, doc.as_ptr());
+
+    let update = CString::new(r#"{"price": {"$subtract": ["$price", 10]}}"#).unwrap();
+    assert_eq!(ffi::ruggy_update_expr(col, id, update.as_ptr()), 0);
+    ffi::ruggy_str_free(id);
+    let (message, rule) = (ffi::ruggy_last_error(), ffi::ruggy_last_error_rule());
+    assert!(unsafe { CStr::from_ptr(message) }.to_str().unwrap().contains("price_positive"));
+    assert_eq!(unsafe { CStr::from_ptr(rule) }.to_str().unwrap(), "price_positive");
+    ffi::ruggy_str_free(message);
+    ffi::ruggy_str_free(rule);
+
+    // Las reglas mal escritas no tienen regla incumplida, solo mensaje
+    let bad = CString::new(r#"{"broken": {"$nope": 1}}"#).unwrap();
+    assert_eq!(ffi::ruggy_set_rules(col, bad.as_ptr()), 0);
+    let message = ffi::ruggy_last_error();
+    assert!(!message.is_null());
+    assert!(ffi::ruggy_last_error_rule().is_null());
+    ffi::ruggy_str_free(message);
+
+    ffi::ruggy_col_free(col);
+    ffi::ruggy_db_free(db);
+    let _ = std::fs::remove_dir_all(&dir);
+}
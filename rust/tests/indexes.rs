@@ -0,0 +1,20 @@
+mod common;
+
+use ruggy_db::Database;
+use serde_json::json;
+
+#[test]
+fn drop_index_on_an_unloaded_collection() {
+    let dir = common::temp_dir("indexes-drop");
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection("users").unwrap();
+    users.insert(json!({"email": "ana@x.com"})).unwrap();
+    users.create_index("by_email", "email").unwrap();
+    users.unload().unwrap();
+
+    assert!(users.drop_index("by_email").unwrap());
+    assert!(!users.drop_index("by_email").unwrap());
+    assert!(users.indexes().is_empty());
+    assert_eq!(users.find("email", "ana@x.com").len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,19 @@
+mod common;
+
+use ruggy_db::{Database, LayeredDatabase};
+use serde_json::json;
+
+#[test]
+fn update_field_with_a_path_on_a_base_document() {
+    let (base_dir, overlay_dir) = (common::temp_dir("layered-base"), common::temp_dir("layered-overlay"));
+    let id = Database::new(&base_dir).unwrap().collection("users").unwrap()
+        .insert(json!({"profile": {"theme": "dark", "lang": "es"}}))
+        .unwrap();
+
+    let db = LayeredDatabase::open(&base_dir, &overlay_dir).unwrap();
+    let users = db.collection("users").unwrap();
+    assert!(users.update_field(&id, "profile.theme", json!("light")).unwrap());
+    assert_eq!(users.get(&id).unwrap()["profile"], json!({"theme": "light", "lang": "es"}));
+    let _ = std::fs::remove_dir_all(&base_dir);
+    let _ = std::fs::remove_dir_all(&overlay_dir);
+}
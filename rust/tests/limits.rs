@@ -0,0 +1,25 @@
+//! Todo en un test: los límites son globales al proceso
+mod common;
+
+use ruggy_db::limits::{is_limit_exceeded, set_parse_limits};
+use ruggy_db::{Database, ParseLimits};
+use serde_json::json;
+
+#[test]
+fn update_field_checks_the_updated_document() {
+    let dir = common::temp_dir("limits");
+    let db = Database::new(&dir).unwrap();
+    let docs = db.collection("docs").unwrap();
+    let id = docs.insert(json!({"a": {"b": 1}, "text": "x"})).unwrap();
+    set_parse_limits(ParseLimits { max_depth: 3, max_bytes: 200 });
+
+    let err = docs.update_field(&id, "a.b", json!({"c": {"d": 1}})).unwrap_err();
+    assert!(is_limit_exceeded(&err), "{err}");
+    let err = docs.update_field(&id, "text", json!("x".repeat(300))).unwrap_err();
+    assert!(is_limit_exceeded(&err), "{err}");
+    assert_eq!(docs.find_by_id(&id).unwrap(), json!({"_id": id, "a": {"b": 1}, "text": "x"}));
+    assert!(docs.update_field(&id, "a.b", json!(2)).unwrap());
+
+    set_parse_limits(ParseLimits::default());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::io::ErrorKind;
+use ruggy_db::{CollectionOptions, Database};
+use serde_json::json;
+
+#[test]
+fn same_as_is_only_a_reference_with_dedupe() {
+    let dir = common::temp_dir("markers-same-as");
+    {
+        let db = Database::new(&dir).unwrap();
+        let plain = db.collection("plain").unwrap();
+        let err = plain.insert(json!({"_same_as": "a"})).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        let id = plain.insert(json!({"name": "a"})).unwrap();
+        assert_eq!(plain.update_field(&id, "_same_as", json!("b")).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(plain.upsert_many("name", vec![json!({"name": "b", "_same_as": "a"})]).is_err());
+
+        let shared = db.collection_with_options("shared", CollectionOptions::default().dedupe(true)).unwrap();
+        shared.insert(json!({"tag": "x"})).unwrap();
+        shared.insert(json!({"tag": "x"})).unwrap();
+        shared.persist().unwrap();
+    }
+    assert!(std::fs::read_to_string(dir.join("shared.col")).unwrap().contains("_same_as"));
+    // Una línea con esa forma en una colección sin dedupe es un documento más
+    std::fs::write(dir.join("plain.col"), "{\"_id\":\"1\",\"_same_as\":\"2\"}\n").unwrap();
+
+    let db = Database::new(&dir).unwrap();
+    let plain = db.collection("plain").unwrap();
+    assert_eq!(plain.find_by_id("1").unwrap()["_same_as"], json!("2"));
+    let shared = db.collection_with_options("shared", CollectionOptions::default().dedupe(true)).unwrap();
+    assert_eq!(shared.find("tag", "x").len(), 2);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn z_is_only_expanded_with_compression() {
+    let dir = common::temp_dir("markers-z");
+    let compressed = || CollectionOptions::default().compress_above(64);
+    let text = "lorem ipsum ".repeat(40);
+    {
+        let db = Database::new(&dir).unwrap();
+        let plain = db.collection("plain").unwrap();
+        assert_eq!(plain.insert(json!({"$z": "abc"})).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let id = plain.insert(json!({"name": "a"})).unwrap();
+        assert_eq!(plain.update_field(&id, "$z.x", json!(1)).unwrap_err().kind(), ErrorKind::InvalidInput);
+
+        let packed = db.collection_with_options("packed", compressed()).unwrap();
+        packed.insert(json!({"text": text})).unwrap();
+        packed.persist().unwrap();
+    }
+    assert!(std::fs::read_to_string(dir.join("packed.col")).unwrap().contains("\"$z\""));
+    std::fs::write(dir.join("plain.col"), "{\"_id\":\"1\",\"$z\":\"not base64!\"}\n").unwrap();
+
+    let db = Database::new(&dir).unwrap();
+    let plain = db.collection("plain").unwrap();
+    assert_eq!(plain.find_by_id("1").unwrap()["$z"], json!("not base64!"));
+    let packed = db.collection_with_options("packed", compressed()).unwrap();
+    assert_eq!(packed.find("text", &text).len(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,33 @@
+mod common;
+
+use ruggy_db::{CollectionOptions, Database, Normalization};
+use serde_json::json;
+
+#[test]
+fn query_filters_use_the_collection_normalization() {
+    let dir = common::temp_dir("normalization");
+    let db = Database::new(&dir).unwrap();
+    let options = CollectionOptions::default().normalization(Normalization::nfc().strip_accents(true));
+    let people = db.collection_with_options("people", options).unwrap();
+    people.insert(json!({"name": "Álvarez"})).unwrap();
+    // `e` seguida del acento combinado
+    people.insert(json!({"name": "Jose\u{301}"})).unwrap();
+
+    for filter in [
+        json!({"name": "Alvarez"}),
+        json!({"name": {"$in": ["alvarez", "Alvarez"]}}),
+        json!({"name": {"$ieq": "ALVAREZ"}}),
+        json!({"name": {"$ilike": "lvá"}}),
+        json!({"name": "José"}),
+    ] {
+        assert_eq!(people.query(filter.clone()).unwrap().len(), 1, "{filter}");
+    }
+    assert_eq!(people.query(json!({"name": {"$ne": "Alvarez"}})).unwrap().len(), 1);
+    assert_eq!(people.query(json!({"name": {"$nin": ["Alvarez", "Jose"]}})).unwrap().len(), 0);
+
+    // Lo mismo con un índice sobre el campo
+    people.create_index("by_name", "name").unwrap();
+    assert_eq!(people.query(json!({"name": "Alvarez"})).unwrap().len(), 1);
+    assert_eq!(people.count_matching(json!({"name": "Jose"})).unwrap(), 1);
+    let _ = std::fs::remove_dir_all(&dir);
+}
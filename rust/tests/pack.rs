@@ -0,0 +1,27 @@
+mod common;
+
+use std::io;
+use ruggy_db::{Database, PackBuilder};
+use serde_json::json;
+
+#[test]
+fn pack_from_dir_opens_embedded() {
+    let dir = common::temp_dir("pack");
+    let data = dir.join("data");
+    {
+        let db = Database::new(&data).unwrap();
+        let items = db.collection("items").unwrap();
+        items.insert(json!({"name": "espada", "dano": 7})).unwrap();
+        items.insert(json!({"name": "escudo", "dano": 0})).unwrap();
+    }
+    let path = dir.join("data.pack");
+    PackBuilder::from_dir(&data).unwrap().write(&path).unwrap();
+
+    let bytes: &'static [u8] = Box::leak(std::fs::read(&path).unwrap().into_boxed_slice());
+    let db = Database::open_embedded(bytes).unwrap();
+    let items = db.collection("items").unwrap();
+    assert_eq!(items.find("name", "espada").len(), 1);
+    let err = items.insert(json!({"name": "arco"})).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,29 @@
+mod common;
+
+use std::time::Duration;
+use ruggy_db::{ArchivePolicy, CollectionOptions, Database, PersistStrategy};
+use serde_json::json;
+
+#[test]
+fn expiry_and_archiving_follow_the_persist_strategy() {
+    let dir = common::temp_dir("persist-manual");
+    let db = Database::new(&dir).unwrap();
+    let options = CollectionOptions::default()
+        .persist_strategy(PersistStrategy::Manual)
+        .ttl("created", Duration::from_secs(60));
+    let events = db.collection_with_options("events", options).unwrap();
+    events.insert(json!({"created": 0, "kind": "old"})).unwrap();
+    events.insert(json!({"created": 1, "kind": "cold"})).unwrap();
+    events.persist_now().unwrap();
+    let file = || std::fs::read_to_string(dir.join("events.col")).unwrap();
+
+    assert_eq!(events.expire_now().unwrap(), 2);
+    assert!(file().contains("old"));
+    events.insert(json!({"created": u64::MAX / 2, "kind": "cold"})).unwrap();
+    assert_eq!(events.archive_where(&ArchivePolicy::matching(|doc| doc["kind"] == json!("cold"))).unwrap(), 1);
+    assert!(file().contains("old"));
+
+    events.persist_now().unwrap();
+    assert_eq!(file().trim(), "");
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,30 @@
+mod common;
+
+use ruggy_db::Database;
+use serde_json::json;
+
+fn uses(col: &ruggy_db::Collection, name: &str) -> u64 {
+    col.indexes().into_iter().find(|index| index.name == name).unwrap().uses
+}
+
+#[test]
+fn picks_the_most_selective_index_with_stats() {
+    let dir = common::temp_dir("planner");
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection("users").unwrap();
+    for i in 0..100 {
+        users.insert(json!({"country": "PE", "email": format!("u{}@x.com", i)})).unwrap();
+    }
+    users.create_index("by_country", "country").unwrap();
+    users.create_index("by_email", "email").unwrap();
+    let filter = json!({"country": "PE", "email": "u7@x.com"});
+
+    // Sin estadísticas, la primera igualdad con índice
+    assert_eq!(users.count_matching(filter.clone()).unwrap(), 1);
+    assert_eq!((uses(&users, "by_country"), uses(&users, "by_email")), (1, 0));
+
+    users.analyze();
+    assert_eq!(users.count_matching(filter).unwrap(), 1);
+    assert_eq!((uses(&users, "by_country"), uses(&users, "by_email")), (1, 1));
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::sync::Arc;
+use ruggy_db::{ArchivePolicy, CollectionOptions, Database, FieldCipher, FieldEncryption, FieldKey};
+use serde_json::json;
+
+fn key(id: &str, byte: u8) -> Arc<dyn FieldCipher> {
+    Arc::new(FieldKey::new(id, [byte; 32]))
+}
+
+fn options(key: Arc<dyn FieldCipher>) -> CollectionOptions {
+    CollectionOptions::default().encryption(FieldEncryption::new(&["ssn"], key))
+}
+
+#[test]
+fn rotate_cipher_on_an_unloaded_collection() {
+    let dir = common::temp_dir("rotation-unloaded");
+    let (old, new) = (key("k1", 1), key("k2", 2));
+    {
+        let db = Database::new(&dir).unwrap();
+        let users = db.collection_with_options("users", options(old.clone())).unwrap();
+        let id = users.insert(json!({"name": "ana", "ssn": "123"})).unwrap();
+        users.unload().unwrap();
+
+        assert!(users.rotate_cipher(old.as_ref(), new.clone()).unwrap());
+        assert_eq!(users.find_by_id(&id).unwrap()["ssn"], json!("123"));
+    }
+    let db = Database::new(&dir).unwrap();
+    let users = db.collection_with_options("users", options(new)).unwrap();
+    assert_eq!(users.find("name", "ana")[0]["ssn"], json!("123"));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn rotate_key_reencrypts_the_archives() {
+    let dir = common::temp_dir("rotation-archive");
+    let (old, new) = (key("k1", 1), key("k2", 2));
+    let cold = || ArchivePolicy::matching(|doc| doc["cold"] == json!(true));
+    {
+        let db = Database::new(&dir).unwrap();
+        for name in ["open", "closed"] {
+            let users = db.collection_with_options(name, options(old.clone())).unwrap();
+            users.insert(json!({"name": "ana", "ssn": "123", "cold": true})).unwrap();
+            assert_eq!(users.archive_where(&cold()).unwrap(), 1);
+        }
+    }
+    let db = Database::new(&dir).unwrap();
+    let open = db.collection_with_options("open", options(old.clone())).unwrap();
+    let archive = open.archive();
+    assert_eq!(db.rotate_key(old, new.clone()).unwrap(), 2);
+    // El handle ya creado lee con la clave nueva
+    assert_eq!(archive.find_all().unwrap()[0]["ssn"], json!("123"));
+
+    let db = Database::new(&dir).unwrap();
+    for name in ["open", "closed"] {
+        let users = db.collection_with_options(name, options(new.clone())).unwrap();
+        assert_eq!(users.archive().find_all().unwrap()[0]["ssn"], json!("123"));
+    }
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use ruggy_db::{LocalStorage, StorageBackend};
+
+fn read_all(storage: &LocalStorage, name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    storage.open(name, 0).unwrap().unwrap().read_to_end(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn replace_shows_the_old_or_the_new_content() {
+    let dir = common::temp_dir("storage-replace");
+    let storage = Arc::new(LocalStorage::new(&dir).unwrap());
+    let (old, new) = (vec![b'a'; 1 << 20], vec![b'b'; 1 << 20]);
+    storage.replace("users.col", &old).unwrap();
+
+    // Un lector a la vez que se sustituye el archivo nunca ve una mezcla
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let (storage, done) = (storage.clone(), done.clone());
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                let bytes = read_all(&storage, "users.col");
+                assert_eq!(bytes.len(), 1 << 20);
+                assert!(bytes.iter().all(|&b| b == bytes[0]));
+            }
+        })
+    };
+    for n in 0..20 {
+        storage.replace("users.col", if n % 2 == 0 { &new } else { &old }).unwrap();
+    }
+    done.store(true, Ordering::Relaxed);
+    reader.join().unwrap();
+    assert_eq!(read_all(&storage, "users.col"), old);
+
+    // Un temporal a medias de un corte anterior no estropea el archivo ni la siguiente sustitución
+    std::fs::write(dir.join("users.col.tmp"), b"bb").unwrap();
+    assert_eq!(read_all(&storage, "users.col"), old);
+    storage.replace("users.col", &new).unwrap();
+    assert_eq!(read_all(&storage, "users.col"), new);
+    assert!(!dir.join("users.col.tmp").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+use ruggy_db::{MockClock, MockDatabase};
+use serde_json::json;
+
+#[test]
+fn mock_ids_are_sequential() {
+    let db = MockDatabase::new();
+    let users = db.collection("users").unwrap();
+    assert_eq!(users.insert(json!({"name": "Ana"})).unwrap(), "00000000-0000-0000-0000-000000000001");
+    let id = users.insert(json!({"name": "Luis", "tags": ["admin"]})).unwrap();
+    assert_eq!(id, "00000000-0000-0000-0000-000000000002");
+    assert_eq!(users.find("tags", "admin")[0]["_id"], json!(id));
+    assert!(users.update_field(&id, "profile.theme", json!("dark")).unwrap());
+    assert_eq!(users.get(&id).unwrap()["profile"]["theme"], json!("dark"));
+}
+
+#[test]
+fn mock_clock_drives_expiry() {
+    let db = MockDatabase::with_clock(MockClock::new(10_000));
+    let sessions = db.collection("sessions").unwrap();
+    sessions.set_ttl("created", Duration::from_secs(5));
+    sessions.insert(json!({"created": 9_000})).unwrap();
+    sessions.insert(json!({"created": 4_000})).unwrap();
+    assert_eq!(sessions.expire_now().unwrap(), 1);
+    db.clock().advance(Duration::from_secs(10));
+    assert_eq!(sessions.expire_now().unwrap(), 1);
+    assert!(sessions.find_all().is_empty());
+}
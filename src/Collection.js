@@ -159,7 +159,8 @@ class RuggyCollection {
         }
 
         const fieldBuf = toCString(field);
-        const valueBuf = toCString(String(value));
+        // `in` takes the list as a JSON array
+        const valueBuf = toCString(Array.isArray(value) ? JSON.stringify(value) : String(value));
         const opBuf = toCString(operator);
 
         const resPtr = ruggy_find_op(this.#colPtr, fieldBuf, valueBuf, opBuf);
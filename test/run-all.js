@@ -22,6 +22,7 @@ async function runTests() {
 
     try {
         await testBasicOperations();
+        await testInOperator();
         await testPoolOperations();
 
         const duration = Date.now() - start;
@@ -75,6 +76,32 @@ async function testBasicOperations() {
     });
 }
 
+async function testInOperator() {
+    console.log('[Test] Running In Operator...');
+
+    await RuggyDatabase.withDatabase(TEST_DB_PATH, async (db) => {
+        await db.withCollection('in_test', async (col) => {
+            col.insert({ lang: 'rust', stars: 5, tags: ['db', 'embedded'] });
+            col.insert({ lang: 'go', stars: 4, tags: ['web'] });
+            col.insert({ lang: 'js', stars: 3 });
+
+            // Strings and numbers
+            const langs = col.findWithOperator('lang', ['rust', 'js'], 'in');
+            assert.deepStrictEqual(langs.map(d => d.lang).sort(), ['js', 'rust'], 'in should match any listed string');
+            const stars = col.findWithOperator('stars', [4, 5], 'in');
+            assert.strictEqual(stars.length, 2, 'in should match numbers');
+
+            // Array fields match when any element is listed
+            const tagged = col.findWithOperator('tags', ['embedded', 'mobile'], 'in');
+            assert.strictEqual(tagged.length, 1, 'in should match array elements');
+            assert.strictEqual(tagged[0].lang, 'rust', 'Match should be correct document');
+
+            const none = col.findWithOperator('lang', [], 'in');
+            assert.strictEqual(none.length, 0, 'Empty in list should match nothing');
+        });
+    });
+}
+
 async function testPoolOperations() {
     console.log('[Test] Running Pool Operations...');
 